@@ -0,0 +1,175 @@
+use crate::config::{AddonConfig, Config, ForwardConfig};
+use crate::forwarder::{self, ForwardState};
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::Service;
+use kube::api::Api;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const READY_TIMEOUT_SECS: u64 = 120;
+
+/// Conventional install locations of well-known addons. Entries from the
+/// config file's `addons` block take precedence over these.
+fn builtin_addons() -> HashMap<String, AddonConfig> {
+    let addon = |locations: &[(&str, &str)], local_port: u16, path: &str, probe: Option<&str>| {
+        AddonConfig {
+            locations: locations
+                .iter()
+                .map(|(ns, svc)| format!("{}/{}", ns, svc))
+                .collect(),
+            port: None,
+            local_port: Some(local_port),
+            path: Some(path.to_string()),
+            liveness_probe: probe.map(str::to_string),
+        }
+    };
+
+    HashMap::from([
+        (
+            "grafana".to_string(),
+            addon(
+                &[
+                    ("monitoring", "grafana"),
+                    ("grafana", "grafana"),
+                    ("monitoring", "kube-prometheus-stack-grafana"),
+                    ("observability", "grafana"),
+                ],
+                3000,
+                "/",
+                Some("/api/health"),
+            ),
+        ),
+        (
+            "prometheus".to_string(),
+            addon(
+                &[
+                    ("monitoring", "prometheus-server"),
+                    ("monitoring", "prometheus-operated"),
+                    ("monitoring", "prometheus-k8s"),
+                    ("monitoring", "kube-prometheus-stack-prometheus"),
+                    ("prometheus", "prometheus-server"),
+                ],
+                9090,
+                "/",
+                Some("/-/ready"),
+            ),
+        ),
+        (
+            "kubernetes-dashboard".to_string(),
+            addon(
+                &[
+                    ("kubernetes-dashboard", "kubernetes-dashboard"),
+                    ("kubernetes-dashboard", "kubernetes-dashboard-kong-proxy"),
+                    ("kube-system", "kubernetes-dashboard"),
+                ],
+                8443,
+                "/",
+                None,
+            ),
+        ),
+    ])
+}
+
+/// Locates the addon's service, forwards it and opens the browser once the
+/// forward is ready.
+pub async fn start_addon(
+    name: &str,
+    config: Option<Config>,
+    context: Option<String>,
+    verbose: u8,
+    options: forwarder::ForwardOptions,
+) -> Result<()> {
+    let mut addons = builtin_addons();
+    if let Some(custom) = config.and_then(|config| config.addons) {
+        addons.extend(custom);
+    }
+    let addon = addons.remove(name).ok_or_else(|| {
+        let mut known: Vec<_> = addons.keys().cloned().collect();
+        known.sort();
+        anyhow!("Unknown addon {}. Known addons: {}", name, known.join(", "))
+    })?;
+
+    let (namespace, service, port) = locate(&addon, context.as_deref()).await?;
+    crate::logger::log_info(format!(
+        "{} Found {} as service/{} in namespace {}",
+        "🧩", name, service, namespace
+    ));
+
+    let local_port = addon
+        .local_port
+        .filter(|port| forwarder::is_port_free(*port))
+        .map(Ok)
+        .unwrap_or_else(forwarder::find_available_port)?;
+
+    let mut forward = ForwardConfig::builder()
+        .resource(format!("service/{}:{}", service, port))
+        .local_port(local_port)
+        .namespace(namespace);
+    if let Some(path) = &addon.liveness_probe {
+        forward = forward.liveness_probe(path);
+    }
+    if let Some(context) = context {
+        forward = forward.context(context);
+    }
+    let forward = forward.build()?;
+
+    let keys = forwarder::start_from_config(
+        Config {
+            forwards: vec![forward],
+            verbose: Some(verbose),
+            protected_contexts: None,
+            addons: None,
+            profiles: None,
+            auto_port_range: None,
+            jump_host: None,
+            notifications: None,
+        },
+        options,
+    )
+    .await?;
+    // Qualified with the namespace when the same service is already forwarded
+    let key = keys.first().ok_or_else(|| anyhow!("{} was not started", name))?;
+
+    if !forwarder::wait_for_state(key, ForwardState::Active, Duration::from_secs(READY_TIMEOUT_SECS)).await {
+        return Err(anyhow!("{} did not become ready in time", name));
+    }
+
+    let url = format!(
+        "http://localhost:{}{}",
+        local_port,
+        addon.path.as_deref().unwrap_or("/")
+    );
+    crate::logger::log_success(format!("{} Opening {} at {}", "🌐", name, url));
+    crate::browser::open(&url)
+}
+
+/// Returns the namespace, service name and port of the first location of
+/// `addon` that exists in the cluster.
+async fn locate(addon: &AddonConfig, context: Option<&str>) -> Result<(String, String, u16)> {
+    let client = crate::k8s::client_for_context(context).await?;
+
+    for location in &addon.locations {
+        let Some((namespace, name)) = location.split_once('/') else {
+            continue;
+        };
+        let services: Api<Service> = Api::namespaced(client.clone(), namespace);
+        let Ok(service) = services.get(name).await else {
+            continue;
+        };
+
+        let port = match addon.port {
+            Some(port) => port,
+            None => service
+                .spec
+                .and_then(|spec| spec.ports)
+                .and_then(|ports| ports.first().map(|port| port.port as u16))
+                .ok_or_else(|| anyhow!("Service {} declares no ports", name))?,
+        };
+        return Ok((namespace.to_string(), name.to_string(), port));
+    }
+
+    Err(anyhow!(
+        "None of the usual locations exist: {}",
+        addon.locations.join(", ")
+    ))
+}
@@ -0,0 +1,245 @@
+//! A forward's `auth` and the global `--auth-basic` / `--auth-token`:
+//! credentials clients must send to the local proxy, so a proxy reachable
+//! from other machines, e.g. with `--bind 0.0.0.0`, does not hand the cluster
+//! to anyone on the network.
+//!
+//! Credentials are accepted in `Proxy-Authorization` or, e.g. from a
+//! browser, in `Authorization`. The header that carried them is removed
+//! before the request is forwarded, so upstreams still get their own
+//! `Authorization`. Requests without valid credentials are answered `401`
+//! and never reach the cluster, the cache or the mocks.
+
+use crate::config::AuthConfig;
+use anyhow::{bail, Result};
+use base64::Engine;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION, PROXY_AUTHORIZATION, WWW_AUTHENTICATE};
+use hyper::{Body, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+
+static AUTHS: LazyLock<Mutex<HashMap<String, Arc<Auth>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Credentials of `--auth-basic` / `--auth-token`, for forwards without `auth`.
+static GLOBAL: OnceLock<Arc<Auth>> = OnceLock::new();
+
+/// Accepted credentials; either one lets a request through.
+#[derive(Debug)]
+pub struct Auth {
+    /// `user:password` of basic auth
+    basic: Option<String>,
+    token: Option<String>,
+}
+
+impl Auth {
+    pub fn new(config: &AuthConfig) -> Result<Auth> {
+        let basic = match (&config.username, &config.password) {
+            (Some(username), Some(password)) => {
+                if username.is_empty() || username.contains(':') {
+                    bail!("auth username '{}' must be non-empty and without ':'", username);
+                }
+                if password.is_empty() {
+                    bail!("auth password of '{}' is empty", username);
+                }
+                Some(format!("{}:{}", username, password))
+            }
+            (None, None) => None,
+            _ => bail!("auth needs both username and password for basic auth"),
+        };
+        if config.token.as_ref().is_some_and(|token| token.trim().is_empty()) {
+            bail!("auth token is empty");
+        }
+        if basic.is_none() && config.token.is_none() {
+            bail!("auth needs a username and password, or a token");
+        }
+        Ok(Auth {
+            basic,
+            token: config.token.clone(),
+        })
+    }
+
+    fn accepts(&self, value: &HeaderValue) -> bool {
+        let Some((scheme, credentials)) = value.to_str().ok().and_then(|value| value.trim().split_once(' ')) else {
+            return false;
+        };
+        let credentials = credentials.trim();
+        if scheme.eq_ignore_ascii_case("basic") {
+            let Some(basic) = &self.basic else {
+                return false;
+            };
+            base64::engine::general_purpose::STANDARD
+                .decode(credentials)
+                .is_ok_and(|decoded| constant_time_eq(&decoded, basic.as_bytes()))
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            self.token
+                .as_ref()
+                .is_some_and(|token| constant_time_eq(credentials.as_bytes(), token.as_bytes()))
+        } else {
+            false
+        }
+    }
+
+    /// The header value kpf itself sends, e.g. for synthetic checks.
+    fn credentials(&self) -> String {
+        match (&self.token, &self.basic) {
+            (Some(token), _) => format!("Bearer {}", token),
+            (None, Some(basic)) => format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(basic)),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Compares without stopping at the first difference, so response times do
+/// not tell how much of a guess was right.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Registers the credentials of a forward, replacing any earlier ones.
+pub fn configure(key: &str, config: Option<&AuthConfig>) -> Result<()> {
+    let auth = config.map(Auth::new).transpose()?;
+    let mut registry = AUTHS.lock().unwrap();
+    match auth {
+        Some(auth) => registry.insert(key.to_string(), Arc::new(auth)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// Sets the credentials of `--auth-basic` / `--auth-token`.
+pub fn set_global(config: &AuthConfig) -> Result<()> {
+    let auth = Auth::new(config)?;
+    let _ = GLOBAL.set(Arc::new(auth));
+    Ok(())
+}
+
+fn lookup(key: &str) -> Option<Arc<Auth>> {
+    AUTHS.lock().unwrap().get(key).cloned().or_else(|| GLOBAL.get().cloned())
+}
+
+/// Whether the local proxy of a forward requires credentials.
+pub fn is_enabled(key: &str) -> bool {
+    lookup(key).is_some()
+}
+
+/// Checks the credentials of a request to a forward's local proxy and
+/// removes them from it. Returns the `401` to answer when they are missing
+/// or wrong.
+pub fn check(key: &str, headers: &mut HeaderMap) -> Option<Response<Body>> {
+    let auth = lookup(key)?;
+    for header in [PROXY_AUTHORIZATION, AUTHORIZATION] {
+        if headers.get(&header).is_some_and(|value| auth.accepts(value)) {
+            headers.remove(&header);
+            return None;
+        }
+    }
+
+    let mut response = Response::new(Body::from("Unauthorized: this kpf proxy requires credentials"));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    if auth.basic.is_some() {
+        response
+            .headers_mut()
+            .append(WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"kpf\""));
+    }
+    if auth.token.is_some() {
+        response
+            .headers_mut()
+            .append(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer realm=\"kpf\""));
+    }
+    Some(response)
+}
+
+/// `Proxy-Authorization` value for requests kpf sends to a forward's own
+/// local proxy.
+pub fn credentials(key: &str) -> Option<String> {
+    lookup(key).map(|auth| auth.credentials())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderName;
+
+    fn config(username: Option<&str>, password: Option<&str>, token: Option<&str>) -> AuthConfig {
+        AuthConfig {
+            username: username.map(String::from),
+            password: password.map(String::from),
+            token: token.map(String::from),
+        }
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials))
+    }
+
+    /// Checks a request to `key` carrying `header: value`, returning the
+    /// status it is answered with, or `None` when it goes through.
+    fn status(key: &str, header: HeaderName, value: &str) -> Option<u16> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header, HeaderValue::from_str(value).unwrap());
+        check(key, &mut headers).map(|response| response.status().as_u16())
+    }
+
+    #[test]
+    fn basic_credentials_are_checked() {
+        let key = "service/auth-basic:80";
+        configure(key, Some(&config(Some("dev"), Some("s3cret"), None))).unwrap();
+        assert_eq!(status(key, PROXY_AUTHORIZATION, &basic("dev:s3cret")), None);
+        assert_eq!(status(key, AUTHORIZATION, &basic("dev:s3cret")), None);
+        assert_eq!(status(key, PROXY_AUTHORIZATION, &basic("dev:wrong")), Some(401));
+        assert_eq!(status(key, PROXY_AUTHORIZATION, &basic("dev:s3cret2")), Some(401));
+        assert_eq!(status(key, PROXY_AUTHORIZATION, "Basic not-base64!"), Some(401));
+        assert_eq!(status(key, PROXY_AUTHORIZATION, "Bearer s3cret"), Some(401));
+    }
+
+    #[test]
+    fn bearer_tokens_are_checked() {
+        let key = "service/auth-bearer:80";
+        configure(key, Some(&config(None, None, Some("t0ken")))).unwrap();
+        assert_eq!(status(key, PROXY_AUTHORIZATION, "Bearer t0ken"), None);
+        assert_eq!(status(key, AUTHORIZATION, "bearer  t0ken "), None);
+        assert_eq!(status(key, PROXY_AUTHORIZATION, "Bearer t0ke"), Some(401));
+        assert_eq!(status(key, PROXY_AUTHORIZATION, "t0ken"), Some(401));
+        assert_eq!(status(key, PROXY_AUTHORIZATION, &basic("t0ken:t0ken")), Some(401));
+    }
+
+    #[test]
+    fn either_credential_is_accepted_when_both_are_set() {
+        let key = "service/auth-both:80";
+        configure(key, Some(&config(Some("dev"), Some("s3cret"), Some("t0ken")))).unwrap();
+        assert_eq!(status(key, PROXY_AUTHORIZATION, &basic("dev:s3cret")), None);
+        assert_eq!(status(key, PROXY_AUTHORIZATION, "Bearer t0ken"), None);
+    }
+
+    #[test]
+    fn accepted_credentials_are_removed_and_rejections_name_the_schemes() {
+        let key = "service/auth-headers:80";
+        configure(key, Some(&config(Some("dev"), Some("s3cret"), Some("t0ken")))).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer upstream"));
+        headers.insert(PROXY_AUTHORIZATION, HeaderValue::from_static("Bearer t0ken"));
+        assert!(check(key, &mut headers).is_none());
+        assert!(!headers.contains_key(PROXY_AUTHORIZATION));
+        assert_eq!(headers[AUTHORIZATION], "Bearer upstream");
+
+        let rejection = check(key, &mut HeaderMap::new()).unwrap();
+        let challenges: Vec<_> = rejection.headers().get_all(WWW_AUTHENTICATE).iter().collect();
+        assert_eq!(challenges, ["Basic realm=\"kpf\"", "Bearer realm=\"kpf\""]);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        assert!(Auth::new(&config(None, None, None)).is_err());
+        assert!(Auth::new(&config(Some("dev"), None, None)).is_err());
+        assert!(Auth::new(&config(Some("d:ev"), Some("s3cret"), None)).is_err());
+        assert!(Auth::new(&config(Some("dev"), Some(""), None)).is_err());
+        assert!(Auth::new(&config(None, None, Some(" "))).is_err());
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_values() {
+        assert!(constant_time_eq(b"t0ken", b"t0ken"));
+        assert!(!constant_time_eq(b"t0ken", b"t0kem"));
+        assert!(!constant_time_eq(b"t0ken", b"t0ken2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
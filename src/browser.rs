@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::process::{Command, Stdio};
+use std::sync::{LazyLock, Mutex};
+
+/// Forwards already opened with `open`, so reconnects do not open them again.
+static OPENED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Opens `url` in the user's default browser.
+pub fn open(url: &str) -> Result<()> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("open");
+        cmd.arg(url);
+        cmd
+    } else if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", "", url]);
+        cmd
+    } else {
+        let mut cmd = Command::new("xdg-open");
+        cmd.arg(url);
+        cmd
+    };
+
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to launch browser")?;
+
+    Ok(())
+}
+
+/// Opens a forward's `open` URL, only the first time it is asked to in this run.
+pub fn open_forward(key: &str, url: &str) {
+    if !OPENED.lock().unwrap().insert(key.to_string()) {
+        return;
+    }
+    crate::logger::log_success(format!("{} Opening {} at {}", "🌐", key, url));
+    if let Err(e) = open(url) {
+        crate::logger::log_warning(format!("{} Could not open {}: {}", "🌐", url, e));
+    }
+}
@@ -0,0 +1,191 @@
+//! A forward's `cache`: recent successful GET responses, answered again while
+//! the upstream is unavailable, e.g. during a pod restart.
+
+use crate::config::CacheConfig;
+use hyper::body::Bytes;
+use hyper::{HeaderMap, StatusCode};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Defaults for fields left out of a `cache` block.
+const DEFAULT_TTL_SECS: u64 = 300;
+const DEFAULT_MAX_ENTRIES: usize = 100;
+/// Largest response body kept; bigger responses are not cached.
+pub const MAX_CACHED_BODY: usize = 1024 * 1024;
+
+static CACHES: LazyLock<Mutex<HashMap<String, Arc<Mutex<ResponseCache>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub stored_at: Instant,
+}
+
+/// Responses of one forward by `METHOD path?query`, oldest first in `order`.
+struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: HashMap<String, CachedResponse>,
+    order: VecDeque<String>,
+}
+
+impl ResponseCache {
+    fn new(config: &CacheConfig) -> Self {
+        ResponseCache {
+            ttl: Duration::from_secs(config.ttl_secs.unwrap_or(DEFAULT_TTL_SECS)),
+            max_entries: config.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES).max(1),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn insert(&mut self, key: String, response: CachedResponse) {
+        if self.entries.insert(key.clone(), response).is_some() {
+            self.order.retain(|existing| *existing != key);
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.max_entries {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<CachedResponse> {
+        let fresh = self.entries.get(key)?.stored_at.elapsed() < self.ttl;
+        if !fresh {
+            self.entries.remove(key);
+            self.order.retain(|existing| existing != key);
+            return None;
+        }
+        self.entries.get(key).cloned()
+    }
+}
+
+/// Cache key of a request.
+pub fn cache_key(method: &hyper::Method, path_and_query: &str) -> String {
+    format!("{} {}", method, path_and_query)
+}
+
+/// Registers the cache of a forward, replacing any earlier one and its entries.
+pub fn configure(key: &str, config: Option<&CacheConfig>) {
+    let mut caches = CACHES.lock().unwrap();
+    match config {
+        Some(config) => caches.insert(key.to_string(), Arc::new(Mutex::new(ResponseCache::new(config)))),
+        None => caches.remove(key),
+    };
+}
+
+/// Whether the forward caches responses.
+pub fn enabled(resource: &str) -> bool {
+    CACHES.lock().unwrap().contains_key(resource)
+}
+
+fn cache(resource: &str) -> Option<Arc<Mutex<ResponseCache>>> {
+    CACHES.lock().unwrap().get(resource).cloned()
+}
+
+/// Keeps a response the client has read in full. Only successful GETs with a
+/// body of at most [`MAX_CACHED_BODY`] bytes are kept.
+pub fn store(resource: &str, key: String, status: StatusCode, headers: HeaderMap, body: Bytes) {
+    if !status.is_success() || body.len() > MAX_CACHED_BODY || !key.starts_with("GET ") {
+        return;
+    }
+    if let Some(cache) = cache(resource) {
+        cache.lock().unwrap().insert(
+            key,
+            CachedResponse {
+                status,
+                headers,
+                body,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+}
+
+/// A response kept for `key` within the forward's `ttl_secs`.
+pub fn lookup(resource: &str, key: &str) -> Option<CachedResponse> {
+    cache(resource)?.lock().unwrap().get(key)
+}
+
+/// Number of responses the forward keeps, expired ones included.
+pub fn len(resource: &str) -> Option<usize> {
+    cache(resource).map(|cache| cache.lock().unwrap().entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Method;
+
+    fn response(body: &'static str, age: Duration) -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+            stored_at: Instant::now().checked_sub(age).unwrap(),
+        }
+    }
+
+    fn response_cache(ttl_secs: u64, max_entries: usize) -> ResponseCache {
+        ResponseCache::new(&CacheConfig {
+            ttl_secs: Some(ttl_secs),
+            max_entries: Some(max_entries),
+        })
+    }
+
+    #[test]
+    fn expired_responses_are_dropped() {
+        let mut cache = response_cache(60, 10);
+        cache.insert("GET /fresh".to_string(), response("fresh", Duration::from_secs(59)));
+        cache.insert("GET /stale".to_string(), response("stale", Duration::from_secs(60)));
+        assert_eq!(cache.get("GET /fresh").unwrap().body, "fresh");
+        assert!(cache.get("GET /stale").is_none());
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.order, ["GET /fresh"]);
+    }
+
+    #[test]
+    fn oldest_responses_are_evicted_first() {
+        let mut cache = response_cache(60, 2);
+        cache.insert("GET /a".to_string(), response("a", Duration::ZERO));
+        cache.insert("GET /b".to_string(), response("b", Duration::ZERO));
+        // Storing again makes it the newest
+        cache.insert("GET /a".to_string(), response("a2", Duration::ZERO));
+        cache.insert("GET /c".to_string(), response("c", Duration::ZERO));
+        assert!(cache.get("GET /b").is_none());
+        assert_eq!(cache.get("GET /a").unwrap().body, "a2");
+        assert_eq!(cache.get("GET /c").unwrap().body, "c");
+        assert_eq!(cache.order, ["GET /a", "GET /c"]);
+    }
+
+    #[test]
+    fn at_least_one_response_is_kept() {
+        let mut cache = response_cache(60, 0);
+        cache.insert("GET /a".to_string(), response("a", Duration::ZERO));
+        assert!(cache.get("GET /a").is_some());
+    }
+
+    #[test]
+    fn only_small_successful_gets_are_stored() {
+        let resource = "service/cache-test:80";
+        configure(resource, Some(&CacheConfig::default()));
+        let get = cache_key(&Method::GET, "/users?page=2");
+        store(resource, get.clone(), StatusCode::OK, HeaderMap::new(), Bytes::from_static(b"[]"));
+        store(resource, cache_key(&Method::POST, "/users"), StatusCode::OK, HeaderMap::new(), Bytes::new());
+        store(resource, cache_key(&Method::GET, "/error"), StatusCode::BAD_GATEWAY, HeaderMap::new(), Bytes::new());
+        let large = Bytes::from(vec![0; MAX_CACHED_BODY + 1]);
+        store(resource, cache_key(&Method::GET, "/large"), StatusCode::OK, HeaderMap::new(), large);
+        assert_eq!(len(resource), Some(1));
+        assert_eq!(lookup(resource, &get).unwrap().body, "[]");
+
+        configure(resource, None);
+        assert!(!enabled(resource));
+        assert!(lookup(resource, &get).is_none());
+    }
+}
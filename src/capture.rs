@@ -0,0 +1,206 @@
+use hyper::{HeaderMap, Method};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// Requests kept per forward.
+const MAX_CAPTURED_PER_FORWARD: usize = 200;
+/// Bytes of each request/response body kept with a captured request.
+pub const MAX_CAPTURED_BODY: usize = 16 * 1024;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static CAPTURED_REQUESTS: LazyLock<Mutex<HashMap<String, VecDeque<CapturedRequest>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A proxied request as shown in the TUI request inspector and served by
+/// `/_internal/requests`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapturedRequest {
+    pub id: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub resource: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub latency_ms: u128,
+    /// Free-text annotation added from the inspector
+    pub note: Option<String>,
+    /// Start of the request body, capped at `MAX_CAPTURED_BODY` bytes
+    pub request_body: Option<String>,
+    /// Start of the response body, filled in once it has been streamed
+    pub response_body: Option<String>,
+    /// `curl` command replaying the request against the local proxy
+    #[serde(default)]
+    pub curl: String,
+    /// Answered from the forward's response cache
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// What is needed to replay a proxied request with `curl` against the local
+/// proxy, taken before its body streams through.
+pub struct Replay {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Replay {
+    /// `url` is the local proxy address of the request, query included.
+    pub fn new(method: &Method, url: String, headers: &HeaderMap) -> Self {
+        let headers = headers
+            .iter()
+            // curl sets these itself for the local address and the body it sends
+            .filter(|(name, _)| {
+                !matches!(
+                    name.as_str(),
+                    "host" | "content-length" | "transfer-encoding" | "connection"
+                )
+            })
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        Replay {
+            method: method.to_string(),
+            url,
+            headers,
+        }
+    }
+
+    /// The `curl` command line, with the captured request body. Truncated and
+    /// binary bodies are left out, as they cannot be sent again as captured.
+    pub fn curl(&self, body: Option<&str>) -> String {
+        let mut command = format!("curl -X {} {}", self.method, shell_quote(&self.url));
+        if self.url.starts_with("https://") {
+            command.push_str(" -k");
+        }
+        for (name, value) in &self.headers {
+            command.push_str(&format!(" -H {}", shell_quote(&format!("{}: {}", name, value))));
+        }
+        if let Some(body) = body.filter(|body| is_replayable(body)) {
+            command.push_str(&format!(" --data-raw {}", shell_quote(body)));
+        }
+        command
+    }
+}
+
+/// Whether a stored body is the whole body, not a truncated or binary summary.
+pub fn is_replayable(body: &str) -> bool {
+    !(body.starts_with("Binary data: ") || body.ends_with(" bytes total)"))
+}
+
+/// Quotes `text` as a single POSIX shell word.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+pub fn record(
+    resource: &str,
+    method: &str,
+    path: &str,
+    status: u16,
+    latency_ms: u128,
+    request_body: Option<String>,
+    curl: String,
+) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut captured = CAPTURED_REQUESTS.lock().unwrap();
+    let ring = captured.entry(resource.to_string()).or_default();
+    if ring.len() >= MAX_CAPTURED_PER_FORWARD {
+        ring.pop_front();
+    }
+    ring.push_back(CapturedRequest {
+        id,
+        timestamp: chrono::Utc::now(),
+        resource: resource.to_string(),
+        method: method.to_string(),
+        path: path.to_string(),
+        status,
+        latency_ms,
+        note: None,
+        request_body,
+        response_body: None,
+        curl,
+        cached: false,
+    });
+    id
+}
+
+/// Stores the response body of a captured request once it is complete.
+pub fn attach_response_body(resource: &str, id: u64, body: String) {
+    let mut captured = CAPTURED_REQUESTS.lock().unwrap();
+    if let Some(request) = captured
+        .get_mut(resource)
+        .and_then(|ring| ring.iter_mut().find(|request| request.id == id))
+    {
+        request.response_body = Some(body);
+    }
+}
+
+/// Marks a captured request as answered from the response cache.
+pub fn mark_cached(resource: &str, id: u64) {
+    let mut captured = CAPTURED_REQUESTS.lock().unwrap();
+    if let Some(request) = captured
+        .get_mut(resource)
+        .and_then(|ring| ring.iter_mut().find(|request| request.id == id))
+    {
+        request.cached = true;
+    }
+}
+
+/// Renders a captured body prefix as text for storage.
+pub fn body_text(captured: &[u8], total: usize) -> Option<String> {
+    if total == 0 {
+        return None;
+    }
+    let captured = &captured[..captured.len().min(MAX_CAPTURED_BODY)];
+    let mut text = match std::str::from_utf8(captured) {
+        Ok(text) => text.to_string(),
+        // A cut can split a multi-byte character; anything else is binary
+        Err(e) if e.error_len().is_none() => String::from_utf8_lossy(&captured[..e.valid_up_to()]).into_owned(),
+        Err(_) => return Some(format!("Binary data: {} bytes", total)),
+    };
+    if total > captured.len() {
+        text.push_str(&format!("… ({} bytes total)", total));
+    }
+    Some(text)
+}
+
+/// Returns the captured requests of every forward, oldest first.
+pub fn snapshot() -> Vec<CapturedRequest> {
+    let captured = CAPTURED_REQUESTS.lock().unwrap();
+    let mut requests: Vec<_> = captured.values().flatten().cloned().collect();
+    requests.sort_by_key(|request| request.id);
+    requests
+}
+
+/// Returns the captured requests of one forward, oldest first.
+pub fn snapshot_forward(resource: &str) -> Vec<CapturedRequest> {
+    CAPTURED_REQUESTS
+        .lock()
+        .unwrap()
+        .get(resource)
+        .map(|ring| ring.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Attaches a note to a captured request. An empty note removes it.
+pub fn annotate(id: u64, note: String) -> bool {
+    let mut captured = CAPTURED_REQUESTS.lock().unwrap();
+    match captured
+        .values_mut()
+        .flatten()
+        .find(|request| request.id == id)
+    {
+        Some(request) => {
+            request.note = if note.trim().is_empty() { None } else { Some(note) };
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,133 @@
+use crate::config::ChaosConfig;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Values used when a fault is toggled on from the TUI without being configured.
+const DEFAULT_LATENCY_MS: u64 = 500;
+const DEFAULT_ERROR_PERCENT: f64 = 10.0;
+const DEFAULT_DROP_PERCENT: f64 = 10.0;
+
+static CHAOS: LazyLock<Mutex<HashMap<String, ChaosState>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct ChaosState {
+    /// Faults from the config file, restored when a fault is toggled back on
+    configured: ChaosConfig,
+    active: ChaosConfig,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    Latency,
+    Errors,
+    Drops,
+}
+
+/// What to do with a single proxied request.
+pub enum ChaosAction {
+    Pass,
+    Fail,
+    Drop,
+}
+
+/// Registers the configured faults of a forward. They are active right away.
+pub fn configure(key: &str, config: ChaosConfig) {
+    CHAOS.lock().unwrap().insert(
+        key.to_string(),
+        ChaosState {
+            active: config.clone(),
+            configured: config,
+        },
+    );
+}
+
+/// Drops the faults of a forward that did not start.
+pub fn forget(key: &str) {
+    CHAOS.lock().unwrap().remove(key);
+}
+
+/// Turns a fault on or off for a forward. Returns whether it is now on.
+pub fn toggle(key: &str, fault: Fault) -> bool {
+    let mut chaos = CHAOS.lock().unwrap();
+    let state = chaos.entry(key.to_string()).or_insert_with(|| ChaosState {
+        configured: ChaosConfig::default(),
+        active: ChaosConfig::default(),
+    });
+    let (configured, active) = (&state.configured, &mut state.active);
+    match fault {
+        Fault::Latency => {
+            if active.latency_ms.take().is_none() {
+                active.latency_ms = Some(configured.latency_ms.unwrap_or(DEFAULT_LATENCY_MS));
+                active.jitter_ms = configured.jitter_ms;
+            } else {
+                active.jitter_ms = None;
+            }
+            active.latency_ms.is_some()
+        }
+        Fault::Errors => {
+            if active.error_percent.take().is_none() {
+                active.error_percent = Some(configured.error_percent.unwrap_or(DEFAULT_ERROR_PERCENT));
+            }
+            active.error_percent.is_some()
+        }
+        Fault::Drops => {
+            if active.drop_percent.take().is_none() {
+                active.drop_percent = Some(configured.drop_percent.unwrap_or(DEFAULT_DROP_PERCENT));
+            }
+            active.drop_percent.is_some()
+        }
+    }
+}
+
+/// The faults currently injected into a forward, if any.
+pub fn active(key: &str) -> Option<ChaosConfig> {
+    CHAOS
+        .lock()
+        .unwrap()
+        .get(key)
+        .map(|state| state.active.clone())
+        .filter(|active| *active != ChaosConfig::default())
+}
+
+/// Rolls the dice for one request: how long to delay it and whether to let it
+/// through, answer 503 or drop the connection.
+pub fn decide(key: &str) -> (Duration, ChaosAction) {
+    let Some(active) = active(key) else {
+        return (Duration::ZERO, ChaosAction::Pass);
+    };
+    let mut rng = rand::thread_rng();
+
+    let jitter = active.jitter_ms.map_or(0, |jitter| rng.gen_range(0..=jitter));
+    let delay = Duration::from_millis(active.latency_ms.unwrap_or(0) + jitter);
+
+    let roll = rng.gen_range(0.0..100.0);
+    let drop_percent = active.drop_percent.unwrap_or(0.0);
+    let action = if roll < drop_percent {
+        ChaosAction::Drop
+    } else if roll < drop_percent + active.error_percent.unwrap_or(0.0) {
+        ChaosAction::Fail
+    } else {
+        ChaosAction::Pass
+    };
+    (delay, action)
+}
+
+/// One-line summary for the TUI, e.g. `latency 500ms±200ms, 503 10%`.
+pub fn describe(config: &ChaosConfig) -> String {
+    let mut faults = Vec::new();
+    if let Some(latency) = config.latency_ms {
+        match config.jitter_ms {
+            Some(jitter) => faults.push(format!("latency {}ms+0-{}ms", latency, jitter)),
+            None => faults.push(format!("latency {}ms", latency)),
+        }
+    }
+    if let Some(percent) = config.error_percent {
+        faults.push(format!("503 {}%", percent));
+    }
+    if let Some(percent) = config.drop_percent {
+        faults.push(format!("drop {}%", percent));
+    }
+    faults.join(", ")
+}
@@ -0,0 +1,19 @@
+use anyhow::{Context, Result};
+use std::sync::{LazyLock, Mutex};
+
+/// On X11 and Wayland the copied text is served by the process that owns the
+/// clipboard, so it is kept open for the rest of the session.
+static CLIPBOARD: LazyLock<Mutex<Option<arboard::Clipboard>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Puts `text` on the system clipboard.
+pub fn copy(text: &str) -> Result<()> {
+    let mut clipboard = CLIPBOARD.lock().unwrap();
+    if clipboard.is_none() {
+        *clipboard = Some(arboard::Clipboard::new().context("No system clipboard available")?);
+    }
+    clipboard
+        .as_mut()
+        .unwrap()
+        .set_text(text)
+        .context("Failed to copy to the clipboard")
+}
@@ -0,0 +1,52 @@
+//! Decoding of `gzip`, `deflate` and `br` bodies for the logs and the request
+//! inspector, so compressed responses show as JSON or text instead of
+//! "Binary data". Clients always get the bytes exactly as the upstream sent
+//! them; only the copies kept for logging are decoded.
+
+use hyper::header::{HeaderMap, CONTENT_ENCODING};
+use std::io::Read;
+
+/// Most bytes a body is decoded to; larger ones are logged as binary.
+const MAX_DECODED_BODY: usize = 16 * 1024 * 1024;
+
+/// A `Content-Encoding` kpf can decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+impl Encoding {
+    /// The body's encoding, when it has a single one kpf can decode.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Encoding> {
+        let encoding = headers.get(CONTENT_ENCODING)?.to_str().ok()?.trim().to_ascii_lowercase();
+        match encoding.as_str() {
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            "br" => Some(Encoding::Brotli),
+            _ => None,
+        }
+    }
+
+    /// Decodes a complete body. `None` when it is corrupt or decodes to more
+    /// than `MAX_DECODED_BODY` bytes.
+    pub fn decode(self, body: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Encoding::Gzip => read_all(flate2::read::MultiGzDecoder::new(body)),
+            // `deflate` is meant to be zlib-wrapped, but some servers send it raw
+            Encoding::Deflate => read_all(flate2::read::ZlibDecoder::new(body))
+                .or_else(|| read_all(flate2::read::DeflateDecoder::new(body))),
+            Encoding::Brotli => read_all(brotli_decompressor::Decompressor::new(body, 4096)),
+        }
+    }
+}
+
+fn read_all(decoder: impl Read) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    decoder
+        .take(MAX_DECODED_BODY as u64 + 1)
+        .read_to_end(&mut decoded)
+        .ok()?;
+    (decoded.len() <= MAX_DECODED_BODY).then_some(decoded)
+}
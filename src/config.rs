@@ -1,27 +1,1494 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::{LazyLock, Mutex};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Profiles given with `--profile`; empty starts every forward.
+static PROFILES: LazyLock<Mutex<Vec<String>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Ports `local_port: "auto"` picks from when the config sets no `auto_port_range`.
+/// Below the ephemeral ranges of Linux, macOS and Windows.
+const DEFAULT_AUTO_PORT_RANGE: &str = "20000-29999";
+
+/// Values given with `--set`, used before environment variables for
+/// `${NAME}` placeholders in config files.
+static VARIABLES: LazyLock<Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ForwardConfig {
+    /// Empty on `release` entries
+    #[serde(default)]
     pub resource: String,
-    pub local_port: Option<u16>,
+    /// A port, or "auto" for one derived from the namespace and resource
+    pub local_port: Option<LocalPort>,
+    /// Ports to fall back to when `local_port` is taken, e.g. "8080-8099"
+    pub local_port_range: Option<String>,
     pub timeout: Option<u64>,
     pub liveness_probe: Option<String>,
     pub namespace: Option<String>,
+    /// Kubeconfig context to use instead of the current one
+    pub context: Option<String>,
+    /// Transport to forward (default: tcp)
+    pub protocol: Option<Protocol>,
+    /// Faults to inject into proxied requests, for resilience testing
+    pub chaos: Option<ChaosConfig>,
+    /// Pretty-print JSON bodies in logs (default: true). When off, bodies are
+    /// logged as one compact line capped at `body_log_limit` bytes
+    pub pretty_print: Option<bool>,
+    pub body_log_limit: Option<usize>,
+    /// Bytes of each streamed body kept for logging (default: 65536)
+    pub body_capture_limit: Option<usize>,
+    /// Requests issued periodically through the forward to monitor the target
+    pub synthetic_checks: Option<Vec<SyntheticCheck>>,
+    /// Liveness probe settings; `liveness_probe` alone gives an HTTP probe
+    pub probe: Option<ProbeConfig>,
+    /// Serve the local proxy over HTTPS (overrides `--tls-cert`/`--tls-key`)
+    pub tls: Option<TlsConfig>,
+    /// Scheme the forwarded port speaks (default: http)
+    pub upstream_scheme: Option<UpstreamScheme>,
+    /// How the cluster is reached (default: port-forward)
+    pub mode: Option<ForwardMode>,
+    /// How HTTPS upstreams are verified
+    pub upstream_tls: Option<UpstreamTlsConfig>,
+    /// Headers set on every proxied request, replacing the client's; `null`
+    /// removes a header
+    pub request_headers: Option<HashMap<String, Option<String>>>,
+    /// Headers set on every response, replacing the upstream's; `null`
+    /// removes a header
+    pub response_headers: Option<HashMap<String, Option<String>>>,
+    /// Drop the `Domain` attribute of `Set-Cookie` so cookies apply to localhost
+    pub strip_cookie_domain: Option<bool>,
+    /// Rewrites request paths before they reach the upstream
+    pub path_rewrite: Option<PathRewriteConfig>,
+    /// Rewrites text and JSON request bodies before they reach the upstream,
+    /// in order
+    pub request_body_transforms: Option<Vec<BodyTransformConfig>>,
+    /// Rewrites text and JSON response bodies before they reach the client,
+    /// in order
+    pub response_body_transforms: Option<Vec<BodyTransformConfig>>,
+    /// Retries requests the upstream fails to answer, e.g. during a pod restart
+    pub retry: Option<RetryConfig>,
+    /// Minutes without proxied requests before the port-forward is closed;
+    /// the next request reconnects it
+    pub idle_timeout: Option<u64>,
+    /// Bind the local port at once but only connect on the first request
+    pub lazy: Option<bool>,
+    /// Recent GET responses, answered again while the upstream is unavailable
+    pub cache: Option<CacheConfig>,
+    /// Caps on requests in flight to the upstream, with a queue for the rest
+    pub concurrency: Option<ConcurrencyConfig>,
+    /// Request paths left out of the request log and the requests log file
+    pub log_exclude: Option<LogExcludeConfig>,
+    /// JSON and form fields, e.g. `password`, or dotted JSON paths, e.g.
+    /// `user.token`, whose values are masked in logged and captured bodies
+    pub redact_fields: Option<Vec<String>>,
+    /// Headers masked in logged and captured requests, on top of
+    /// Authorization, Cookie, X-Api-Key and the like
+    pub redact_headers: Option<Vec<String>>,
+    /// Stream the target pod's container logs into a TUI pane (`T`)
+    pub tail_logs: Option<bool>,
+    /// Label selector, e.g. `app=api,tier=backend`, picking a ready pod on
+    /// every (re)connect; the pod name in `resource` then only names the forward
+    pub selector: Option<String>,
+    /// Helm release whose services are all forwarded, one forward per port,
+    /// instead of `resource`; the other fields apply to each of them
+    pub release: Option<String>,
+    /// SSH bastion, `[user@]host[:port]`, the context's API server is reached
+    /// through. Applies to every forward of the same context
+    pub jump_host: Option<String>,
+    /// Resources of forwards that must be ready, with their liveness probe
+    /// passing, before this one starts
+    pub depends_on: Option<Vec<String>>,
+    /// What to do when connecting keeps failing (overrides `--on-failure`)
+    pub on_failure: Option<FailurePolicy>,
+    /// Credentials clients must send to the local proxy (overrides
+    /// `--auth-basic` / `--auth-token`)
+    pub auth: Option<AuthConfig>,
+    /// `true` for permissive CORS headers on local responses, or the details
+    pub cors: Option<CorsSetting>,
+    /// Another forward, by resource, or an `http(s)://` base URL every
+    /// proxied request is also sent to; its responses are ignored
+    pub mirror_to: Option<String>,
+    /// Addresses used for host names of a `mirror_to` URL instead of DNS,
+    /// like curl's `--resolve`
+    pub mirror_resolve: Option<HashMap<String, Vec<std::net::IpAddr>>>,
+    /// How the certificate of an `https://` `mirror_to` URL is checked
+    pub mirror_tls: Option<MirrorTlsConfig>,
+    /// Sends a share of the requests, or those with given headers, to
+    /// another forward's upstream, e.g. a canary
+    pub split: Option<SplitConfig>,
+    /// `true`, or a path such as `/dashboards`, to open the forward in the
+    /// default browser once it is first ready
+    pub open: Option<OpenSetting>,
+    /// Key the forward runs under, set when it starts (see
+    /// `forwarder::forward_key`)
+    #[serde(skip)]
+    pub key: Option<String>,
+}
+
+/// What a forward does when it fails to connect or loses its connection.
+/// Without one, a forward gives up after `DEFAULT_CONNECT_ATTEMPTS` failed
+/// attempts in a row and kpf keeps running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum FailurePolicy {
+    /// Keep reconnecting, however often it fails
+    RetryForever,
+    /// Exit kpf on the first failure, including a lost connection
+    Exit,
+    /// Exit kpf after this many failed attempts in a row
+    ExitAfter(u32),
+}
+
+/// Connection attempts in a row before a forward without `on_failure` gives up.
+pub const DEFAULT_CONNECT_ATTEMPTS: u32 = 5;
+
+impl FailurePolicy {
+    /// Connection attempts in a row after which the forward stops; `None`
+    /// retries forever.
+    pub fn max_attempts(policy: Option<FailurePolicy>) -> Option<u32> {
+        match policy {
+            Some(FailurePolicy::RetryForever) => None,
+            Some(FailurePolicy::Exit) => Some(1),
+            Some(FailurePolicy::ExitAfter(attempts)) => Some(attempts),
+            None => Some(DEFAULT_CONNECT_ATTEMPTS),
+        }
+    }
+}
+
+impl std::str::FromStr for FailurePolicy {
+    type Err = anyhow::Error;
+
+    /// `retry-forever`, `exit`, or `exit-after=N` (also `exit-after N`)
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        match value {
+            "retry-forever" => return Ok(FailurePolicy::RetryForever),
+            "exit" => return Ok(FailurePolicy::Exit),
+            _ => {}
+        }
+        let attempts = value
+            .strip_prefix("exit-after")
+            .map(|rest| rest.trim_start_matches(['=', ' ']))
+            .and_then(|attempts| attempts.parse::<u32>().ok())
+            .filter(|&attempts| attempts > 0);
+        match attempts {
+            Some(attempts) => Ok(FailurePolicy::ExitAfter(attempts)),
+            None => bail!(
+                "Invalid on_failure '{}'. Expected retry-forever, exit or exit-after=N with N at least 1",
+                value
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for FailurePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailurePolicy::RetryForever => write!(f, "retry-forever"),
+            FailurePolicy::Exit => write!(f, "exit"),
+            FailurePolicy::ExitAfter(attempts) => write!(f, "exit-after={}", attempts),
+        }
+    }
+}
+
+impl TryFrom<String> for FailurePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<FailurePolicy> for String {
+    fn from(policy: FailurePolicy) -> String {
+        policy.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Attempts in total, the first one included (default: 3)
+    pub max_attempts: Option<u32>,
+    /// Wait before the first retry, doubled for every later one (default: 100)
+    pub backoff_ms: Option<u64>,
+    /// Only retry GET, HEAD, OPTIONS, TRACE, PUT and DELETE (default: true)
+    pub idempotent_only: Option<bool>,
+}
+
+/// A forward's response cache. Successful GET responses are kept and served
+/// again when the forward is down or its upstream fails.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long a response may be served again, in seconds (default: 300)
+    pub ttl_secs: Option<u64>,
+    /// Responses kept; the oldest is dropped first (default: 100)
+    pub max_entries: Option<usize>,
+}
+
+/// Caps on a forward's concurrent requests. Requests over `max_in_flight`
+/// wait for a slot; once the queue is full or the wait times out they are
+/// answered `503` with `Retry-After`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    /// Requests proxied at the same time, counted until the response is read
+    pub max_in_flight: usize,
+    /// Requests waiting for a slot (default: 100)
+    pub max_queued: Option<usize>,
+    /// Longest wait for a slot, in milliseconds (default: 10000)
+    pub queue_timeout_ms: Option<u64>,
+    /// `Retry-After` of rejected requests, in seconds (default: 1)
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Request paths a forward does not log. Slow responses and requests that
+/// could not be forwarded are logged anyway.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogExcludeConfig {
+    /// Path globs, e.g. `/healthz` or `/static/**`; `*` stays within a segment
+    pub paths: Option<Vec<String>>,
+    /// Regexes searched for in the path, e.g. `\.(js|css|png)$`
+    pub patterns: Option<Vec<String>>,
+}
+
+/// Credentials the local proxy requires: basic auth, a bearer token, or both,
+/// in which case either is accepted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Basic auth user name, together with `password`
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Token sent as `Bearer <token>`
+    pub token: Option<String>,
+}
+
+/// One step of a body transform: a regex replacement, JSON fields to set,
+/// or both, the replacement first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyTransformConfig {
+    /// Regex replaced everywhere in the body, e.g. `http://api\.default\.svc`
+    pub pattern: Option<String>,
+    /// Replacement for `pattern`, with `$1`-style groups (default: empty)
+    pub replacement: Option<String>,
+    /// JSON fields to set by dotted path, e.g. `user.role`, where `*` stands
+    /// for every array element; `null` removes the field
+    pub set_fields: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// A forward's `cors`: `true`, `false`, or the details.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CorsSetting {
+    Enabled(bool),
+    Custom(CorsConfig),
+}
+
+impl CorsSetting {
+    /// The settings to apply, `None` when CORS is off.
+    pub fn config(&self) -> Option<CorsConfig> {
+        match self {
+            CorsSetting::Enabled(true) => Some(CorsConfig::default()),
+            CorsSetting::Enabled(false) => None,
+            CorsSetting::Custom(config) => Some(config.clone()),
+        }
+    }
+}
+
+/// CORS headers the local proxy answers browsers with. Every field defaults
+/// to the permissive choice, except credentials, which only origins named in
+/// `allowed_origins` get.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the forward, e.g. `http://localhost:3000`
+    /// (default: any)
+    pub allowed_origins: Option<Vec<String>>,
+    /// Methods preflights allow (default: the one asked for)
+    pub allowed_methods: Option<Vec<String>>,
+    /// Request headers preflights allow (default: the ones asked for)
+    pub allowed_headers: Option<Vec<String>>,
+    /// Response headers scripts may read (default: all of them)
+    pub expose_headers: Option<Vec<String>>,
+    /// Let requests carry cookies and `Authorization` (default: true when
+    /// `allowed_origins` names the origins, false otherwise)
+    pub allow_credentials: Option<bool>,
+    /// Seconds browsers may cache a preflight answer (default: 600)
+    pub max_age: Option<u64>,
+}
+
+/// A forward's `open`: `true`, `false`, or the path to open.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OpenSetting {
+    Enabled(bool),
+    Path(String),
+}
+
+impl OpenSetting {
+    /// Path and query to open, `None` when the forward is not opened.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            OpenSetting::Enabled(true) => Some("/"),
+            OpenSetting::Enabled(false) => None,
+            OpenSetting::Path(path) => Some(path),
+        }
+    }
+}
+
+/// Which of a forward's requests go to another forward's upstream instead.
+/// Requests matching `headers` always do; `percent` of the rest are picked
+/// at random.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SplitConfig {
+    /// Forward of the same config, by resource, e.g. `service/api-v2:80`
+    pub to: String,
+    /// Share of requests sent to `to`, from 0 to 100 (default: 0)
+    pub percent: Option<u8>,
+    /// Headers that route a request to `to` when all are present with these
+    /// values; `*` matches any value
+    pub headers: Option<HashMap<String, String>>,
+}
+
+/// How a forward rewrites request paths. `strip_prefix` applies first, then
+/// `pattern`; the query string is left as is.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathRewriteConfig {
+    /// Leading path segments to remove, e.g. `/api` turns `/api/users` into `/users`
+    pub strip_prefix: Option<String>,
+    /// Regex whose first match in the path is replaced
+    pub pattern: Option<String>,
+    /// Replacement for `pattern`, with `$1`-style groups (default: empty)
+    pub replacement: Option<String>,
+}
+
+/// How a forward reaches its port in the cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardMode {
+    /// `kubectl port-forward` to a ready pod
+    PortForward,
+    /// HTTP requests through the API server's service (or pod) proxy, for
+    /// clusters that do not allow port-forwarding
+    ApiserverProxy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamScheme {
+    Http,
+    Https,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamTlsConfig {
+    /// PEM CA bundle that signs the upstream certificate (default: system roots,
+    /// or `--upstream-ca`)
+    pub ca: Option<std::path::PathBuf>,
+    /// Name sent as SNI and checked against the certificate (default: the
+    /// resource name, e.g. `api` for `service/api:443`)
+    pub server_name: Option<String>,
+    /// Accept any upstream certificate (default: `--upstream-tls-skip-verify`)
+    pub skip_verify: Option<bool>,
+    /// Refuse to connect when the certificate differs from the one recorded
+    /// the first time (default: only warn)
+    pub refuse_changed_cert: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MirrorTlsConfig {
+    /// PEM CA bundle that signs the mirror target's certificate (default:
+    /// system roots, or `--upstream-ca`)
+    pub ca: Option<std::path::PathBuf>,
+    /// Accept any certificate from the mirror target, which then also gets
+    /// the copies' credentials (default: `--upstream-tls-skip-verify`)
+    pub skip_verify: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented by the local proxy
+    pub cert: std::path::PathBuf,
+    /// PEM private key (PKCS#8, RSA or SEC1)
+    pub key: std::path::PathBuf,
+    /// PEM CA bundle; when set, clients must present a certificate signed by it
+    pub client_ca: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeConfig {
+    /// http (default), tcp or exec
+    #[serde(rename = "type")]
+    pub kind: Option<ProbeKind>,
+    /// HTTP path (default: `liveness_probe`)
+    pub path: Option<String>,
+    /// Seconds between probes (default: 2)
+    pub interval_secs: Option<u64>,
+    /// Seconds before a single probe counts as failed (default: the forward's `timeout`, or 1)
+    pub timeout_secs: Option<u64>,
+    /// HTTP statuses that count as success (default: [200])
+    pub success_statuses: Option<Vec<u16>>,
+    /// Command run in the target pod for exec probes; exit code 0 is success
+    pub command: Option<Vec<String>>,
+    /// Consecutive failures before the port-forward is restarted (default: 3)
+    pub failure_threshold: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeKind {
+    Http,
+    Tcp,
+    Exec,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyntheticCheck {
+    pub path: String,
+    /// Seconds between requests (default: 30)
+    pub interval_secs: Option<u64>,
+    /// HTTP method (default: GET)
+    pub method: Option<String>,
+}
+
+/// Fault injection for a forward's HTTP proxy. Every field is optional; unset
+/// faults stay off until toggled from the TUI.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosConfig {
+    /// Fixed delay added to every request, in milliseconds
+    pub latency_ms: Option<u64>,
+    /// Random extra delay of up to this many milliseconds
+    pub jitter_ms: Option<u64>,
+    /// Percentage of requests answered with 503 (0-100)
+    pub error_percent: Option<f64>,
+    /// Percentage of requests whose connection is dropped (0-100)
+    pub drop_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// A forward's `local_port`: a number, or the string "auto". Auto ports are
+/// replaced with real ones when the config is parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalPort {
+    Port(u16),
+    Auto,
+}
+
+impl Serialize for LocalPort {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LocalPort::Port(port) => serializer.serialize_u16(*port),
+            LocalPort::Auto => serializer.serialize_str("auto"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LocalPort {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LocalPortVisitor;
+
+        impl serde::de::Visitor<'_> for LocalPortVisitor {
+            type Value = LocalPort;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a port number or \"auto\"")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<LocalPort, E> {
+                u16::try_from(value)
+                    .map(LocalPort::Port)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<LocalPort, E> {
+                match value {
+                    "auto" => Ok(LocalPort::Auto),
+                    _ => Err(E::invalid_value(serde::de::Unexpected::Str(value), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(LocalPortVisitor)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub forwards: Vec<ForwardConfig>,
     pub verbose: Option<u8>,
+    /// Contexts that require `--yes-production` or an interactive confirmation
+    pub protected_contexts: Option<Vec<String>>,
+    /// Custom or overridden `addon` shortcuts, keyed by addon name
+    pub addons: Option<HashMap<String, AddonConfig>>,
+    /// Named subsets of `forwards` selectable with `--profile`. Entries are a
+    /// forward's resource, its key (`type/name:port`) or its name
+    pub profiles: Option<HashMap<String, Vec<String>>>,
+    /// Ports `local_port: "auto"` picks from, e.g. "20000-29999" (the default)
+    pub auto_port_range: Option<String>,
+    /// SSH bastion for forwards that set no `jump_host` of their own
+    pub jump_host: Option<String>,
+    /// Where to report forwards going down, failing their probe or giving up
+    pub notifications: Option<NotificationsConfig>,
+}
+
+/// Targets told about forward state changes. Any combination may be set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationsConfig {
+    /// URL a JSON description of each change is POSTed to
+    pub webhook: Option<String>,
+    /// Slack incoming webhook URL
+    pub slack_webhook: Option<String>,
+    /// Shell command run for each change, with the details in `KPF_*`
+    /// environment variables
+    pub command: Option<String>,
+    /// Changes to report (default: all)
+    pub events: Option<Vec<NotificationEvent>>,
+}
+
+/// A forward state change `notifications` can report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// A connected forward lost its port-forward
+    Down,
+    /// A forward that was down is connected again
+    Up,
+    /// The liveness probe failed `failure_threshold` times in a row
+    ProbeFailed,
+    /// Reconnecting failed too often and the forward stopped trying
+    GaveUp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddonConfig {
+    /// Candidate `namespace/service` locations, tried in order
+    pub locations: Vec<String>,
+    /// Service port to forward (default: the first declared port)
+    pub port: Option<u16>,
+    /// Preferred local port; a free one is picked if it is taken
+    pub local_port: Option<u16>,
+    /// Path opened in the browser once the forward is ready
+    pub path: Option<String>,
+    pub liveness_probe: Option<String>,
 }
 
 pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config> {
-    let file = File::open(path).context("Failed to open config file")?;
-    let reader = BufReader::new(file);
-    let config: Config = serde_json::from_reader(reader).context("Failed to parse config file")?;
+    let text = std::fs::read_to_string(path.as_ref()).context("Failed to open config file")?;
+    let mut config = parse_config(&text, path.as_ref())?;
+    config.validate()?;
+    let profiles = PROFILES.lock().unwrap().clone();
+    if !profiles.is_empty() {
+        config.select_profiles(&profiles)?;
+    }
+    Ok(config)
+}
+
+/// Sets the profiles of `--profile`, applied by [`load_config`].
+pub fn set_profiles(profiles: Vec<String>) {
+    *PROFILES.lock().unwrap() = profiles;
+}
+
+/// Parses a config file without validating its forwards, after expanding
+/// `${NAME}` placeholders. Errors name the `file:line:column` they occur at
+/// and, for misspelled field or variant names, the closest known one.
+pub fn parse_config(text: &str, path: &Path) -> Result<Config> {
+    let text = interpolate(text, path)?;
+    let mut config: Config = serde_json::from_str(&text).map_err(|e| {
+        let message = e.to_string();
+        // serde_json appends the location, which is reported up front instead
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        // The list of expected names is dropped when one is close enough
+        let message = match suggest_name(&message) {
+            Some(name) => format!(
+                "{}, did you mean `{}`?",
+                message.split_once(", expected").map_or(message.as_str(), |(unknown, _)| unknown),
+                name
+            ),
+            None => message,
+        };
+        anyhow::anyhow!("{}:{}:{}: {}", path.display(), e.line(), e.column(), message)
+    })?;
+    config
+        .assign_auto_ports()
+        .map_err(|e| anyhow::anyhow!("{}: {:#}", path.display(), e))?;
     Ok(config)
 }
+
+/// FNV-1a, which unlike std's hashers is guaranteed to stay the same across
+/// Rust versions and platforms.
+pub(crate) fn stable_hash(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Sets the values of `--set NAME=VALUE`.
+pub fn set_variables(variables: Vec<(String, String)>) {
+    *VARIABLES.lock().unwrap() = variables.into_iter().collect();
+}
+
+/// Parses a `--set` argument.
+pub fn parse_variable(argument: &str) -> Result<(String, String), String> {
+    match argument.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected NAME=VALUE, got '{}'", argument)),
+    }
+}
+
+/// Replaces `${NAME}` with the `--set` value or environment variable `NAME`,
+/// and `${NAME:-default}` with `default` when neither is set or it is empty.
+/// Values are JSON-escaped, so they can hold quotes; `$${` gives a literal `${`.
+fn interpolate(text: &str, path: &Path) -> Result<String> {
+    let variables = VARIABLES.lock().unwrap();
+    let mut interpolated = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if let Some(before) = rest[..start].strip_suffix('$') {
+            interpolated.push_str(before);
+            interpolated.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        interpolated.push_str(&rest[..start]);
+        let location = || {
+            let before = &text[..text.len() - rest.len() + start];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+            format!("{}:{}:{}", path.display(), line, column)
+        };
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("{}: unterminated ${{ placeholder", location()))?;
+        let placeholder = &rest[start + 2..start + end];
+        let (name, default) = match placeholder.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (placeholder, None),
+        };
+        let value = variables
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+            .filter(|value| default.is_none() || !value.is_empty())
+            .or_else(|| default.map(str::to_string))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}: ${{{}}} is not set; export {} or pass --set {}=<value>",
+                    location(),
+                    name,
+                    name,
+                    name
+                )
+            })?;
+        let escaped = serde_json::to_string(&value)?;
+        interpolated.push_str(&escaped[1..escaped.len() - 1]);
+        rest = &rest[start + end + 1..];
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+/// For serde's "unknown field `x`, expected one of `a`, `b`" and "unknown
+/// variant" errors, the expected name closest to the unknown one.
+fn suggest_name(message: &str) -> Option<&str> {
+    if !message.starts_with("unknown field") && !message.starts_with("unknown variant") {
+        return None;
+    }
+    let mut names = message.split('`').skip(1).step_by(2);
+    let unknown = names.next()?;
+    names
+        .map(|name| (edit_distance(unknown, name), name))
+        .filter(|(distance, name)| *distance <= (name.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance between two names.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// `line:column` of the `index`-th forward's `resource` key in the config text.
+pub fn forward_location(text: &str, index: usize) -> Option<(usize, usize)> {
+    static RESOURCE_KEY: std::sync::LazyLock<regex::Regex> =
+        std::sync::LazyLock::new(|| regex::Regex::new(r#""resource"\s*:"#).unwrap());
+    let offset = RESOURCE_KEY.find_iter(text).nth(index)?.start();
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+    Some((line, column))
+}
+
+impl ForwardConfig {
+    /// Starts a typed builder. `build` validates the forward, so mistakes show
+    /// up before anything is bound or spawned.
+    pub fn builder() -> ForwardConfigBuilder {
+        ForwardConfigBuilder::default()
+    }
+
+    /// The resource, or the release of a `release` entry, for messages.
+    pub fn describe(&self) -> String {
+        match &self.release {
+            Some(release) => format!("release {}", release),
+            None => self.resource.clone(),
+        }
+    }
+
+    /// The local port set in the config, if any. `None` until an auto port
+    /// has been assigned.
+    pub fn local_port(&self) -> Option<u16> {
+        match self.local_port {
+            Some(LocalPort::Port(port)) => Some(port),
+            Some(LocalPort::Auto) | None => None,
+        }
+    }
+
+    /// Checks the fields that would otherwise only fail once the forward starts.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(release) = &self.release {
+            if release.trim().is_empty() {
+                bail!("release is empty");
+            }
+            if !self.resource.is_empty() {
+                bail!("{} has both a resource and a release; use one of them", self.resource);
+            }
+            if self.local_port.is_some() || self.selector.is_some() {
+                bail!("release {} forwards several services and cannot have a local_port or selector", release);
+            }
+        } else if self.resource.is_empty() {
+            bail!("A forward needs a resource or a release");
+        } else if !crate::k8s::is_all_ports(&self.resource) {
+            crate::k8s::parse_resource(&self.resource)
+                .context(format!("Invalid resource '{}'", self.resource))?;
+        }
+        if let Some(range) = &self.local_port_range {
+            crate::forwarder::parse_port_range(range)?;
+        }
+        if let Some(probe) = &self.probe {
+            if probe.kind == Some(ProbeKind::Exec)
+                && probe.command.as_ref().is_none_or(|command| command.is_empty())
+            {
+                bail!("Exec probe of {} needs a command", self.resource);
+            }
+        }
+        crate::headers::HeaderRules::from_forward(self)?;
+        if let Some(rewrite) = &self.path_rewrite {
+            crate::rewrite::PathRewrite::new(rewrite)?;
+        }
+        if crate::transform::Transforms::from_forward(self)?.is_some() && self.protocol == Some(Protocol::Udp) {
+            bail!("Body transforms are not supported for UDP forwards ({})", self.resource);
+        }
+        if let Some(exclude) = &self.log_exclude {
+            crate::log_filter::LogFilter::new(exclude)?;
+        }
+        if self.redact_fields.is_some() || self.redact_headers.is_some() {
+            crate::redact::Redaction::new(
+                self.redact_headers.as_deref().unwrap_or_default(),
+                self.redact_fields.as_deref().unwrap_or_default(),
+            )
+            .context(format!("Invalid redaction for {}", self.resource))?;
+        }
+        match self.idle_timeout {
+            Some(0) => bail!("idle_timeout of {} must be at least 1 minute", self.resource),
+            Some(_) if self.protocol == Some(Protocol::Udp) => {
+                bail!("idle_timeout is not supported for UDP forwards ({})", self.resource)
+            }
+            _ => {}
+        }
+        if self.lazy == Some(true) && self.protocol == Some(Protocol::Udp) {
+            bail!("lazy is not supported for UDP forwards ({})", self.resource);
+        }
+        if let Some(concurrency) = &self.concurrency {
+            if concurrency.max_in_flight == 0 {
+                bail!("concurrency.max_in_flight of {} must be at least 1", self.resource);
+            }
+            if self.protocol == Some(Protocol::Udp) {
+                bail!("concurrency is not supported for UDP forwards ({})", self.resource);
+            }
+        }
+        if let Some(auth) = &self.auth {
+            crate::auth::Auth::new(auth).context(format!("Invalid auth for {}", self.resource))?;
+            if self.protocol == Some(Protocol::Udp) {
+                bail!("auth is not supported for UDP forwards ({})", self.resource);
+            }
+        }
+        if let Some(cors) = self.cors.as_ref().and_then(CorsSetting::config) {
+            crate::cors::Cors::new(&cors).context(format!("Invalid cors for {}", self.resource))?;
+            if self.protocol == Some(Protocol::Udp) {
+                bail!("cors is not supported for UDP forwards ({})", self.resource);
+            }
+        }
+        if let Some(mirror_to) = &self.mirror_to {
+            let key = crate::forwarder::forward_key(self).unwrap_or_else(|_| self.resource.clone());
+            crate::mirror::MirrorTarget::parse(&key, mirror_to)?;
+            if self.protocol == Some(Protocol::Udp) {
+                bail!("mirror_to is not supported for UDP forwards ({})", self.resource);
+            }
+        }
+        if let Some(split) = &self.split {
+            crate::split::Split::new(split).context(format!("Invalid split for {}", self.resource))?;
+            if self.protocol == Some(Protocol::Udp) {
+                bail!("split is not supported for UDP forwards ({})", self.resource);
+            }
+            if self.split_key()?.is_some_and(|to| self.is_named_by(&to)) {
+                bail!("{} splits to itself", self.resource);
+            }
+        }
+        if let Some(path) = self.open.as_ref().and_then(OpenSetting::path) {
+            if !path.starts_with('/') {
+                bail!("open path '{}' of {} must start with /", path, self.resource);
+            }
+            if self.protocol == Some(Protocol::Udp) {
+                bail!("open is not supported for UDP forwards ({})", self.resource);
+            }
+        }
+        if self.mode == Some(ForwardMode::ApiserverProxy) {
+            let resource_type = self.resource.split_once('/').map_or("", |(resource_type, _)| resource_type);
+            if !matches!(resource_type, "service" | "svc" | "pod" | "po" | "") {
+                bail!("apiserver-proxy mode only reaches services and pods, not {}", self.resource);
+            }
+            if self.protocol == Some(Protocol::Udp) || self.selector.is_some() {
+                bail!("{} uses apiserver-proxy mode, which supports neither UDP nor a selector", self.describe());
+            }
+            if self.probe.as_ref().is_some_and(|probe| matches!(probe.kind, Some(ProbeKind::Tcp | ProbeKind::Exec))) {
+                bail!("{} uses apiserver-proxy mode, which only supports http probes", self.describe());
+            }
+        }
+        if let Some(selector) = &self.selector {
+            if selector.trim().is_empty() {
+                bail!("selector of {} is empty", self.resource);
+            }
+            if !self.resource.starts_with("pod/") || crate::k8s::is_all_ports(&self.resource) {
+                bail!(
+                    "{} has a selector, so its resource must be pod/<name>:<port>, where <name> only names the forward",
+                    self.resource
+                );
+            }
+        }
+        if let Some(jump_host) = &self.jump_host {
+            crate::jump::validate(jump_host)?;
+        }
+        let dependencies = self.dependency_keys()?;
+        if crate::k8s::is_all_ports(&self.resource) && !dependencies.is_empty() {
+            bail!("{} forwards every port and cannot have depends_on", self.resource);
+        }
+        if dependencies.iter().any(|dependency| self.is_named_by(dependency)) {
+            bail!("{} depends on itself", self.resource);
+        }
+        Ok(())
+    }
+
+    /// `split.to` as a reference to a forward (see `is_named_by`), if the
+    /// forward splits.
+    pub fn split_key(&self) -> Result<Option<String>> {
+        self.split
+            .as_ref()
+            .map(|split| parse_reference(&split.to).context(format!("Invalid split target '{}'", split.to)))
+            .transpose()
+    }
+
+    /// `depends_on` as references to forwards (see `is_named_by`).
+    pub fn dependency_keys(&self) -> Result<Vec<String>> {
+        self.depends_on
+            .iter()
+            .flatten()
+            .map(|dependency| parse_reference(dependency).context(format!("Invalid depends_on entry '{}'", dependency)))
+            .collect()
+    }
+
+    /// Whether `reference` names this forward: its `type/name:port`, or for
+    /// `mirror_to` its resource name, optionally qualified with the namespace
+    /// and local port like the key of a duplicate forward, e.g.
+    /// `service/api:80@staging` or `service/api:80@staging:8081`. Unlike keys,
+    /// qualified references do not depend on which forward started first.
+    pub fn is_named_by(&self, reference: &str) -> bool {
+        let Ok((resource_type, resource_name, port)) = crate::k8s::parse_resource(&self.resource) else {
+            return false;
+        };
+        let reference = reference.trim();
+        let (base, qualifier) = match reference.split_once('@') {
+            Some((base, qualifier)) => (base, Some(qualifier)),
+            None => (reference, None),
+        };
+        let named = match crate::k8s::parse_resource(base) {
+            Ok(target) => target == (resource_type, resource_name, port),
+            Err(_) => base == resource_name,
+        };
+        let Some(qualifier) = qualifier.filter(|_| named) else {
+            return named;
+        };
+        let (namespace, local_port) = match qualifier.split_once(':') {
+            Some((namespace, local_port)) => (namespace, Some(local_port)),
+            None => (qualifier, None),
+        };
+        namespace == self.namespace.as_deref().unwrap_or("default")
+            && local_port.is_none_or(|local_port| local_port.parse().ok() == Some(self.local_port().unwrap_or(port)))
+    }
+}
+
+/// Checks a reference to a forward (see [`ForwardConfig::is_named_by`]) and
+/// returns it without surrounding whitespace.
+pub(crate) fn parse_reference(reference: &str) -> Result<String> {
+    let reference = reference.trim();
+    let (base, qualifier) = match reference.split_once('@') {
+        Some((base, qualifier)) => (base, Some(qualifier)),
+        None => (reference, None),
+    };
+    let (resource_type, resource_name, port) = crate::k8s::parse_resource(base)?;
+    let key = format!("{}/{}:{}", resource_type, resource_name, port);
+    let Some(qualifier) = qualifier else {
+        return Ok(key);
+    };
+    let (namespace, local_port) = match qualifier.split_once(':') {
+        Some((namespace, local_port)) => (namespace, Some(local_port)),
+        None => (qualifier, None),
+    };
+    if namespace.is_empty() || local_port.is_some_and(|local_port| local_port.parse::<u16>().is_err()) {
+        bail!("Invalid qualifier '@{}'. Expected @<namespace> or @<namespace>:<local_port>", qualifier);
+    }
+    Ok(format!("{}@{}", key, qualifier))
+}
+
+/// Builds a [`ForwardConfig`] without spelling out every optional field, e.g.
+/// `ForwardConfig::builder().resource("service/api:80").namespace("staging").build()?`.
+#[derive(Debug, Clone, Default)]
+pub struct ForwardConfigBuilder {
+    config: ForwardConfig,
+}
+
+impl ForwardConfigBuilder {
+    /// `type/name:port`, or `type/name:*` for every port of a service
+    pub fn resource(mut self, resource: impl Into<String>) -> Self {
+        self.config.resource = resource.into();
+        self
+    }
+
+    pub fn local_port(mut self, port: u16) -> Self {
+        self.config.local_port = Some(LocalPort::Port(port));
+        self
+    }
+
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.config.namespace = Some(namespace.into());
+        self
+    }
+
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.config.context = Some(context.into());
+        self
+    }
+
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.config.protocol = Some(protocol);
+        self
+    }
+
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        self.config.timeout = Some(seconds);
+        self
+    }
+
+    /// HTTP liveness probe on `path` with default settings
+    pub fn liveness_probe(mut self, path: impl Into<String>) -> Self {
+        self.config.liveness_probe = Some(path.into());
+        self
+    }
+
+    /// Label selector picking the pod to forward to
+    pub fn selector(mut self, selector: impl Into<String>) -> Self {
+        self.config.selector = Some(selector.into());
+        self
+    }
+
+    /// Open the forward at `path` in the browser once it is ready
+    pub fn open(mut self, path: impl Into<String>) -> Self {
+        self.config.open = Some(OpenSetting::Path(path.into()));
+        self
+    }
+
+    pub fn mode(mut self, mode: ForwardMode) -> Self {
+        self.config.mode = Some(mode);
+        self
+    }
+
+    pub fn upstream_scheme(mut self, scheme: UpstreamScheme) -> Self {
+        self.config.upstream_scheme = Some(scheme);
+        self
+    }
+
+    /// Forward every service of a Helm release instead of a single resource
+    pub fn release(mut self, release: impl Into<String>) -> Self {
+        self.config.release = Some(release.into());
+        self
+    }
+
+    pub fn build(self) -> Result<ForwardConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Something wrong with a config, with the index of the forward it concerns.
+#[derive(Debug)]
+pub struct ConfigProblem {
+    pub forward: Option<usize>,
+    pub error: anyhow::Error,
+}
+
+impl Config {
+    /// Fails with the first of [`Config::problems`].
+    pub fn validate(&self) -> Result<()> {
+        match self.problems().into_iter().next() {
+            Some(problem) => Err(problem.error),
+            None => Ok(()),
+        }
+    }
+
+    /// Gives every `local_port: "auto"` forward a port derived from a hash of
+    /// its namespace and resource, so everyone sharing the config gets the
+    /// same ports. A port another forward already uses moves on to the next
+    /// one in the range, in config order.
+    fn assign_auto_ports(&mut self) -> Result<()> {
+        if !self.forwards.iter().any(|forward| forward.local_port == Some(LocalPort::Auto)) {
+            return Ok(());
+        }
+        let range = self.auto_port_range.as_deref().unwrap_or(DEFAULT_AUTO_PORT_RANGE);
+        let range = crate::forwarder::parse_port_range(range)
+            .context(format!("Invalid auto_port_range '{}'", range))?;
+        let size = (*range.end() - *range.start()) as u64 + 1;
+
+        let mut taken: HashSet<u16> = self
+            .forwards
+            .iter()
+            .filter_map(|forward| match forward.local_port {
+                Some(LocalPort::Port(port)) => Some(port),
+                Some(LocalPort::Auto) => None,
+                None => crate::k8s::parse_resource(&forward.resource)
+                    .ok()
+                    .map(|(_, _, port)| port),
+            })
+            .collect();
+        for forward in &mut self.forwards {
+            if forward.local_port != Some(LocalPort::Auto) {
+                continue;
+            }
+            let seed = format!(
+                "{}/{}",
+                forward.namespace.as_deref().unwrap_or("default"),
+                forward.resource
+            );
+            let start = stable_hash(&seed) % size;
+            let port = (0..size)
+                .map(|offset| range.start() + ((start + offset) % size) as u16)
+                .find(|port| !taken.contains(port))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No port left in auto_port_range {}-{} for {}",
+                        range.start(),
+                        range.end(),
+                        forward.resource
+                    )
+                })?;
+            taken.insert(port);
+            forward.local_port = Some(LocalPort::Port(port));
+        }
+        Ok(())
+    }
+
+    /// Validates every forward, then finds forwards that would compete for
+    /// the same local port, naming both entries. Forwards with a
+    /// `local_port_range` are skipped since they can fall back to another port.
+    pub fn problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        let mut claimed: HashMap<(Protocol, u16), (usize, &str)> = HashMap::new();
+
+        for (index, forward) in self.forwards.iter().enumerate() {
+            if let Err(e) = forward.validate() {
+                problems.push(ConfigProblem {
+                    forward: Some(index),
+                    error: e.context(format!("Forward #{} ({})", index + 1, forward.describe())),
+                });
+                continue;
+            }
+            if forward.local_port_range.is_some() {
+                continue;
+            }
+            let Some(port) = forward.local_port().or_else(|| {
+                crate::k8s::parse_resource(&forward.resource)
+                    .ok()
+                    .map(|(_, _, port)| port)
+            }) else {
+                continue;
+            };
+            let protocol = forward.protocol.unwrap_or(Protocol::Tcp);
+
+            if let Some((other_index, other_resource)) =
+                claimed.insert((protocol, port), (index, &forward.resource))
+            {
+                problems.push(ConfigProblem {
+                    forward: Some(index),
+                    error: anyhow::anyhow!(
+                        "Forwards #{} ({}) and #{} ({}) both use local port {}; set a different local_port or a local_port_range on one of them",
+                        other_index + 1,
+                        other_resource,
+                        index + 1,
+                        forward.resource,
+                        port
+                    ),
+                });
+            }
+        }
+
+        if let Some(jump_host) = &self.jump_host {
+            if let Err(error) = crate::jump::validate(jump_host) {
+                problems.push(ConfigProblem { forward: None, error });
+            }
+        }
+        if let Some(notifications) = &self.notifications {
+            if let Err(error) = crate::notify::validate(notifications) {
+                problems.push(ConfigProblem { forward: None, error });
+            }
+        }
+        if problems.is_empty() {
+            if let Err(error) = self.validate_dependencies() {
+                problems.push(ConfigProblem { forward: None, error });
+            }
+        }
+        for (index, forward) in self.forwards.iter().enumerate() {
+            if let Ok(Some(to)) = forward.split_key() {
+                if !self.forwards.iter().any(|other| other.is_named_by(&to)) {
+                    problems.push(ConfigProblem {
+                        forward: Some(index),
+                        error: anyhow::anyhow!("{} splits to {}, which is not a forward of this config", forward.resource, to),
+                    });
+                }
+            }
+        }
+        // Forwards of the same resource in several namespaces or on several
+        // local ports share `type/name:port`; references to them need the
+        // namespace or local port too
+        for (index, forward) in self.forwards.iter().enumerate() {
+            let mirror = forward.mirror_to.as_deref().filter(|mirror_to| !mirror_to.contains("://"));
+            let references = forward
+                .dependency_keys()
+                .unwrap_or_default()
+                .into_iter()
+                .chain(forward.split_key().ok().flatten())
+                .chain(mirror.map(|mirror| mirror.trim().to_string()));
+            for reference in references {
+                let named: Vec<String> = self
+                    .forwards
+                    .iter()
+                    .filter(|other| other.is_named_by(&reference))
+                    .map(|other| {
+                        format!(
+                            "{}@{}:{}",
+                            crate::template::base_key(&crate::forwarder::forward_key(other).unwrap_or_default()),
+                            other.namespace.as_deref().unwrap_or("default"),
+                            other.local_port().map_or("auto".to_string(), |port| port.to_string())
+                        )
+                    })
+                    .collect();
+                if named.len() > 1 {
+                    problems.push(ConfigProblem {
+                        forward: Some(index),
+                        error: anyhow::anyhow!(
+                            "{} refers to {}, which names several forwards of this config; name one with its namespace and local port: {}",
+                            forward.resource,
+                            reference,
+                            named.join(", ")
+                        ),
+                    });
+                }
+            }
+        }
+        let mut profiles: Vec<_> = self.profiles.iter().flatten().collect();
+        profiles.sort();
+        for (name, entries) in profiles {
+            for entry in entries {
+                if let Err(error) = self.profile_entry(entry) {
+                    problems.push(ConfigProblem {
+                        forward: None,
+                        error: error.context(format!("Profile '{}'", name)),
+                    });
+                }
+            }
+        }
+        problems
+    }
+
+    /// Keeps only the forwards of the named profiles, plus the forwards they
+    /// depend on, in file order.
+    pub fn select_profiles(&mut self, names: &[String]) -> Result<()> {
+        let profiles = self.profiles.as_ref().filter(|profiles| !profiles.is_empty());
+        let Some(profiles) = profiles else {
+            bail!("--profile given, but the config defines no profiles");
+        };
+        let mut selected = Vec::new();
+        for name in names {
+            let Some(entries) = profiles.get(name) else {
+                let mut known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                known.sort();
+                bail!("Unknown profile '{}' (known: {})", name, known.join(", "));
+            };
+            for entry in entries {
+                selected.push(
+                    self.profile_entry(entry)
+                        .context(format!("Profile '{}'", name))?,
+                );
+            }
+        }
+
+        // Pull in dependencies and split targets, and theirs
+        let mut pending = selected.clone();
+        while let Some(index) = pending.pop() {
+            let forward = &self.forwards[index];
+            for dependency in forward.dependency_keys()?.into_iter().chain(forward.split_key()?) {
+                for (dependency, _) in self
+                    .forwards
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, forward)| forward.is_named_by(&dependency))
+                {
+                    if !selected.contains(&dependency) {
+                        selected.push(dependency);
+                        pending.push(dependency);
+                    }
+                }
+            }
+        }
+
+        let forwards = std::mem::take(&mut self.forwards);
+        self.forwards = forwards
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| selected.contains(index))
+            .map(|(_, forward)| forward)
+            .collect();
+        Ok(())
+    }
+
+    /// Index of the forward a profile entry names: its resource, key or name.
+    fn profile_entry(&self, entry: &str) -> Result<usize> {
+        let matches_exactly = |forward: &ForwardConfig| {
+            forward.release.as_deref() == Some(entry)
+                || forward.resource == entry
+                || crate::forwarder::forward_key(forward).is_ok_and(|key| key == entry)
+        };
+        if let Some(index) = self.forwards.iter().position(matches_exactly) {
+            return Ok(index);
+        }
+        let named: Vec<usize> = self
+            .forwards
+            .iter()
+            .enumerate()
+            .filter(|(_, forward)| crate::template::resource_name(&forward.resource) == entry)
+            .map(|(index, _)| index)
+            .collect();
+        match named.as_slice() {
+            [index] => Ok(*index),
+            [] => bail!("'{}' is not a forward of this config", entry),
+            _ => bail!("'{}' matches several forwards; use the full resource", entry),
+        }
+    }
+
+    /// Every `depends_on` entry names another forward of the file, without cycles.
+    fn validate_dependencies(&self) -> Result<()> {
+        // The forwards each forward depends on, by index
+        let mut graph: Vec<Vec<usize>> = Vec::new();
+        for forward in &self.forwards {
+            let mut dependencies = Vec::new();
+            for dependency in forward.dependency_keys()? {
+                let named: Vec<usize> = (0..self.forwards.len())
+                    .filter(|other| self.forwards[*other].is_named_by(&dependency))
+                    .collect();
+                if named.is_empty() {
+                    bail!("{} depends on {}, which is not a forward of this config", forward.resource, dependency);
+                }
+                dependencies.extend(named);
+            }
+            graph.push(dependencies);
+        }
+
+        // Depth-first search; a forward met again while still on the path closes a cycle
+        fn visit(
+            index: usize,
+            graph: &[Vec<usize>],
+            forwards: &[ForwardConfig],
+            path: &mut Vec<usize>,
+            done: &mut Vec<usize>,
+        ) -> Result<()> {
+            if done.contains(&index) {
+                return Ok(());
+            }
+            if let Some(start) = path.iter().position(|visited| *visited == index) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(index);
+                let cycle: Vec<&str> = cycle.iter().map(|index| forwards[*index].resource.as_str()).collect();
+                bail!("depends_on forms a cycle: {}", cycle.join(" → "));
+            }
+            path.push(index);
+            for dependency in &graph[index] {
+                visit(*dependency, graph, forwards, path, done)?;
+            }
+            path.pop();
+            done.push(index);
+            Ok(())
+        }
+        let mut done = Vec::new();
+        for index in 0..graph.len() {
+            visit(index, &graph, &self.forwards, &mut Vec::new(), &mut done)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(text: &str) -> Result<String> {
+        interpolate(text, Path::new("kpf.json"))
+    }
+
+    #[test]
+    fn set_values_come_before_the_environment() {
+        std::env::set_var("KPF_TEST_NAMESPACE", "from-env");
+        set_variables(vec![("KPF_TEST_NAMESPACE".to_string(), "from-set".to_string())]);
+        let expanded = expand(r#"{"namespace": "${KPF_TEST_NAMESPACE}"}"#);
+        set_variables(Vec::new());
+        assert_eq!(expanded.unwrap(), r#"{"namespace": "from-set"}"#);
+        assert_eq!(expand("${KPF_TEST_NAMESPACE}").unwrap(), "from-env");
+    }
+
+    #[test]
+    fn defaults_apply_when_unset_or_empty() {
+        std::env::set_var("KPF_TEST_EMPTY", "");
+        assert_eq!(expand("${KPF_TEST_UNSET:-8080}").unwrap(), "8080");
+        assert_eq!(expand("${KPF_TEST_EMPTY:-8080}").unwrap(), "8080");
+        assert_eq!(expand("${KPF_TEST_EMPTY}").unwrap(), "");
+        assert_eq!(expand("${KPF_TEST_UNSET:-}").unwrap(), "");
+    }
+
+    #[test]
+    fn values_are_json_escaped() {
+        std::env::set_var("KPF_TEST_QUOTED", "say \"hi\"\\");
+        assert_eq!(expand(r#""${KPF_TEST_QUOTED}""#).unwrap(), r#""say \"hi\"\\""#);
+    }
+
+    #[test]
+    fn double_dollar_is_a_literal_placeholder() {
+        assert_eq!(expand("$${KPF_TEST_UNSET} $$x").unwrap(), "${KPF_TEST_UNSET} $$x");
+    }
+
+    #[test]
+    fn errors_name_the_location() {
+        let error = expand("{\n  \"namespace\": \"${KPF_TEST_UNSET}\"\n}").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "kpf.json:2:17: ${KPF_TEST_UNSET} is not set; export KPF_TEST_UNSET or pass --set KPF_TEST_UNSET=<value>"
+        );
+        let error = expand("{\"a\": \"${KPF_TEST_UNSET\"").unwrap_err().to_string();
+        assert_eq!(error, "kpf.json:1:8: unterminated ${ placeholder");
+    }
+
+    fn config(forwards: serde_json::Value) -> Config {
+        parse_config(&serde_json::json!({ "forwards": forwards }).to_string(), Path::new("kpf.json")).unwrap()
+    }
+
+    #[test]
+    fn references_name_forwards_by_namespace_and_local_port() {
+        let forward: ForwardConfig = serde_json::from_value(serde_json::json!({
+            "resource": "service/api:80", "namespace": "prod", "local_port": 8081
+        }))
+        .unwrap();
+        assert!(forward.is_named_by("service/api:80"));
+        assert!(forward.is_named_by("api"));
+        assert!(forward.is_named_by(" service/api:80@prod"));
+        assert!(forward.is_named_by("service/api:80@prod:8081"));
+        assert!(!forward.is_named_by("service/api:80@staging"));
+        assert!(!forward.is_named_by("service/api:80@prod:8080"));
+        assert!(!forward.is_named_by("service/api:81"));
+        assert_eq!(parse_reference(" service/api:80@prod:8081").unwrap(), "service/api:80@prod:8081");
+        assert!(parse_reference("api").is_err());
+        assert!(parse_reference("service/api:80@").is_err());
+        assert!(parse_reference("service/api:80@prod:x").is_err());
+    }
+
+    #[test]
+    fn references_to_duplicate_forwards_need_a_qualifier() {
+        let duplicates = serde_json::json!([
+            { "resource": "service/api:80", "namespace": "staging", "local_port": 18120 },
+            { "resource": "service/api:80", "namespace": "prod", "local_port": 18121 }
+        ]);
+        let mut forwards = duplicates.as_array().unwrap().clone();
+        forwards.push(serde_json::json!({ "resource": "service/web:80", "local_port": 18122, "depends_on": ["service/api:80"] }));
+        let error = config(serde_json::json!(forwards)).validate().unwrap_err().to_string();
+        assert!(error.contains("service/api:80@prod:18121"), "{}", error);
+
+        let mut forwards = duplicates.as_array().unwrap().clone();
+        forwards.push(serde_json::json!({
+            "resource": "service/web:80",
+            "local_port": 18122,
+            "depends_on": ["service/api:80@prod"],
+            "split": { "to": "service/api:80@staging:18120", "percent": 10 },
+            "mirror_to": "api@prod"
+        }));
+        config(serde_json::json!(forwards)).validate().unwrap();
+    }
+
+    #[test]
+    fn qualified_references_must_name_a_forward() {
+        let config = config(serde_json::json!([
+            { "resource": "service/api:80", "namespace": "staging", "local_port": 18120 },
+            { "resource": "service/web:80", "local_port": 18122, "depends_on": ["service/api:80@prod"] }
+        ]));
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("which is not a forward of this config"), "{}", error);
+    }
+
+    #[test]
+    fn set_arguments_are_parsed() {
+        assert_eq!(parse_variable(" NS =a=b"), Ok(("NS".to_string(), "a=b".to_string())));
+        assert!(parse_variable("NS").is_err());
+        assert!(parse_variable("=value").is_err());
+    }
+}
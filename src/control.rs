@@ -0,0 +1,377 @@
+use crate::config::ForwardConfig;
+use crate::forwarder::{self, ForwardCommand};
+use crate::metrics::MetricsSnapshot;
+use crate::tui::ForwardStatus;
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use rand::distributions::{Alphanumeric, DistString};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Token of this run, sent by clients as `Authorization: Bearer <token>`.
+static TOKEN: OnceLock<String> = OnceLock::new();
+
+/// Largest request body read; a forward's config is far smaller.
+const MAX_REQUEST_BODY: usize = 64 * 1024;
+
+/// Serves the REST control API on localhost:
+///
+/// - `GET  /forwards`                   list forwards with their state
+/// - `POST /forwards`                   add a forward (body: a config `forwards` entry)
+/// - `POST /forwards/<key>/stop`        stop a forward and release its local port
+/// - `POST /forwards/<key>/start`       start a stopped forward again
+/// - `POST /forwards/<key>/restart`     reconnect a forward
+///
+/// `<key>` is the forward's `type/name:port`, as listed by `GET /forwards`;
+/// further forwards of the same resource carry their namespace, e.g.
+/// `service/api:80@staging`.
+///
+/// Every request must carry the token written to `token_path(port)`, so web
+/// pages open in a browser cannot drive the API.
+pub async fn start_control_server(port: u16) -> Result<()> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+
+    let token = TOKEN.get_or_init(|| Alphanumeric.sample_string(&mut rand::thread_rng(), 32));
+    let path = token_path(port)?;
+    write_token(&path, token)?;
+
+    crate::logger::log_info(format!(
+        "{} Control API listening on http://localhost:{} (token in {})",
+        "🎛",
+        port,
+        path.display()
+    ));
+
+    let make_svc = make_service_fn(|_conn| async {
+        Ok::<_, Infallible>(service_fn(handle_authorized_request))
+    });
+
+    Server::bind(&addr).serve(make_svc).await?;
+    Ok(())
+}
+
+/// File holding the token of the control API served on `port`.
+pub fn token_path(port: u16) -> Result<PathBuf> {
+    Ok(crate::daemon::daemon_dir()?.join(format!("control-{}.token", port)))
+}
+
+/// Token of the session serving the control API on `port`.
+pub fn read_token(port: u16) -> Result<String> {
+    let path = token_path(port)?;
+    let token = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Failed to read the control API token from {}. Is kpf running with --control-port {}?",
+            path.display(),
+            port
+        )
+    })?;
+    Ok(token.trim().to_string())
+}
+
+/// Writes the token readable by the current user only.
+fn write_token(path: &std::path::Path, token: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .context(format!("Failed to write {}", path.display()))?;
+    std::io::Write::write_all(&mut file, format!("{}\n", token).as_bytes())?;
+    Ok(())
+}
+
+/// Checks the token of a request to the TCP control API before routing it.
+async fn handle_authorized_request(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if !is_authorized(&req, TOKEN.get().map(String::as_str)) {
+        crate::logger::log_warning(format!(
+            "{} Control API: {} {} → rejected, missing or wrong token",
+            "🎛",
+            req.method(),
+            req.uri().path()
+        ));
+        return Ok(error_response(
+            StatusCode::UNAUTHORIZED,
+            "Missing or wrong token".to_string(),
+        ));
+    }
+    handle_request(req).await
+}
+
+fn is_authorized(req: &Request<Body>, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return false;
+    };
+    req.headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| crate::auth::constant_time_eq(given.trim().as_bytes(), token.as_bytes()))
+}
+
+/// Whether a request declares a JSON body. POSTs without it are refused,
+/// since browsers cannot send it cross-origin without a preflight.
+fn is_json(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+pub(crate) async fn handle_request(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+
+    let response = match (&method, path.as_str()) {
+        (&Method::POST, _) if !is_json(&req) => error_response(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "POST requests need Content-Type: application/json".to_string(),
+        ),
+        (&Method::GET, "/forwards") => list_forwards(),
+        (&Method::POST, "/forwards") => add_forward(req).await,
+        (&Method::POST, _) if path.starts_with("/forwards/") => {
+            match path["/forwards/".len()..].rsplit_once('/') {
+                Some((key, "stop")) => command_response(forwarder::send_command(key, ForwardCommand::Stop)),
+                Some((key, "restart")) => {
+                    command_response(forwarder::send_command(key, ForwardCommand::Restart))
+                }
+                Some((key, "start")) => command_response(forwarder::start_forward(key)),
+                Some((key, "pause")) => command_response(forwarder::send_command(key, ForwardCommand::Pause)),
+                Some((key, "resume")) => {
+                    command_response(forwarder::send_command(key, ForwardCommand::Resume))
+                }
+                _ => error_response(StatusCode::NOT_FOUND, "Unknown action".to_string()),
+            }
+        }
+        _ => error_response(StatusCode::NOT_FOUND, format!("No route for {} {}", method, path)),
+    };
+
+    crate::logger::log_info(format!(
+        "{} Control API: {} {} → {}",
+        "🎛",
+        method,
+        path,
+        response.status()
+    ));
+
+    Ok(response)
+}
+
+/// A forward as listed by `GET /forwards`.
+#[derive(Serialize, Deserialize)]
+pub struct ForwardView {
+    #[serde(flatten)]
+    pub status: ForwardStatus,
+    /// Seconds since the current port-forward connection was established
+    pub uptime_secs: Option<i64>,
+    /// Whether the forward serves requests: ACTIVE, or OPEN when it has no
+    /// liveness probe to pass
+    #[serde(default)]
+    pub ready: bool,
+    pub metrics: MetricsSnapshot,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ForwardList {
+    pub forwards: Vec<ForwardView>,
+}
+
+/// Every forward of this process, sorted by key.
+pub fn forward_list() -> ForwardList {
+    let mut statuses: Vec<_> = forwarder::FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect();
+    statuses.sort_by(|a, b| a.resource.cmp(&b.resource));
+
+    let now = chrono::Utc::now();
+    let forwards = statuses
+        .into_iter()
+        .map(|status| ForwardView {
+            uptime_secs: status
+                .connected_at
+                .map(|connected_at| (now - connected_at).num_seconds()),
+            ready: forwarder::is_ready(&status.resource),
+            metrics: crate::metrics::snapshot(&status.resource),
+            status,
+        })
+        .collect();
+    ForwardList { forwards }
+}
+
+fn list_forwards() -> Response<Body> {
+    json_response(StatusCode::OK, serde_json::to_value(forward_list()).unwrap_or_default())
+}
+
+async fn add_forward(req: Request<Body>) -> Response<Body> {
+    let body = match crate::http::read_limited(req.into_body(), MAX_REQUEST_BODY).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(_)) => {
+            return error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Request body over {} bytes", MAX_REQUEST_BODY),
+            )
+        }
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let forward: ForwardConfig = match serde_json::from_slice(&body) {
+        Ok(forward) => forward,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, format!("Invalid forward: {}", e)),
+    };
+
+    match forwarder::add_forwards(vec![forward]).await {
+        Ok(keys) => json_response(StatusCode::CREATED, serde_json::json!({ "added": keys })),
+        Err(e) if e.is::<forwarder::InvalidForwardError>() => {
+            error_response(StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid forward: {}", e))
+        }
+        Err(e) if e.is::<crate::k8s::ProtectedContextError>() => {
+            error_response(StatusCode::FORBIDDEN, e.to_string())
+        }
+        Err(e) => error_response(StatusCode::CONFLICT, e.to_string()),
+    }
+}
+
+/// `202` once a command is sent, `404` for an unknown forward and `409` when
+/// the forward is not in a state to take it.
+fn command_response(result: Result<()>) -> Response<Body> {
+    match result {
+        Ok(()) => json_response(StatusCode::ACCEPTED, serde_json::json!({ "ok": true })),
+        Err(e) if e.is::<forwarder::UnknownForwardError>() => error_response(StatusCode::NOT_FOUND, e.to_string()),
+        Err(e) => error_response(StatusCode::CONFLICT, e.to_string()),
+    }
+}
+
+fn error_response(status: StatusCode, message: String) -> Response<Body> {
+    json_response(status, serde_json::json!({ "error": message }))
+}
+
+fn json_response(status: StatusCode, value: serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(
+        serde_json::to_string_pretty(&value).unwrap_or_default(),
+    ));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, path: &str, content_type: Option<&str>, body: &str) -> Request<Body> {
+        let mut builder = Request::builder().method(method).uri(path);
+        if let Some(content_type) = content_type {
+            builder = builder.header(hyper::header::CONTENT_TYPE, content_type);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn status(req: Request<Body>) -> StatusCode {
+        handle_request(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn lists_forwards() {
+        let response = handle_request(request(Method::GET, "/forwards", None, "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(serde_json::from_slice::<ForwardList>(&body).is_ok());
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_are_not_found() {
+        assert_eq!(status(request(Method::GET, "/status", None, "")).await, StatusCode::NOT_FOUND);
+        assert_eq!(status(request(Method::DELETE, "/forwards", None, "")).await, StatusCode::NOT_FOUND);
+        assert_eq!(
+            status(request(Method::POST, "/forwards/service/api:80/explode", Some("application/json"), "")).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn posts_need_a_json_content_type() {
+        for content_type in [None, Some("text/plain"), Some("application/x-www-form-urlencoded")] {
+            assert_eq!(
+                status(request(Method::POST, "/forwards", content_type, "{}")).await,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            );
+        }
+        assert_eq!(
+            status(request(Method::POST, "/forwards/service/api:80/stop", None, "")).await,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_for_unknown_forwards_are_not_found() {
+        for action in ["stop", "restart", "start", "pause", "resume"] {
+            let path = format!("/forwards/service/control-test:80/{}", action);
+            assert_eq!(
+                status(request(Method::POST, &path, Some("application/json; charset=utf-8"), "")).await,
+                StatusCode::NOT_FOUND,
+                "{}",
+                action
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unparsable_forwards_are_bad_requests() {
+        assert_eq!(
+            status(request(Method::POST, "/forwards", Some("application/json"), r#"{"resourse":"x"}"#)).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(request(Method::POST, "/forwards", Some("application/json"), "not json")).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_forwards_are_unprocessable() {
+        for forward in [r#"{"resource":"api"}"#, r#"{"resource":"service/api:80","local_port_range":"9-1"}"#] {
+            assert_eq!(
+                status(request(Method::POST, "/forwards", Some("application/json"), forward)).await,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{}",
+                forward
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_refused() {
+        let body = format!(r#"{{"resource":"{}"}}"#, "a".repeat(MAX_REQUEST_BODY));
+        assert_eq!(
+            status(request(Method::POST, "/forwards", Some("application/json"), &body)).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[test]
+    fn bearer_token_is_required() {
+        let mut req = request(Method::GET, "/forwards", None, "");
+        assert!(!is_authorized(&req, Some("secret")));
+        req.headers_mut()
+            .insert(hyper::header::AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        assert!(!is_authorized(&req, Some("secret")));
+        req.headers_mut()
+            .insert(hyper::header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        assert!(is_authorized(&req, Some("secret")));
+        assert!(!is_authorized(&req, Some("secret2")));
+        assert!(!is_authorized(&req, None));
+    }
+}
@@ -0,0 +1,281 @@
+//! A forward's `cors`: lets a browser app served from another origin, e.g. a
+//! dev server on `http://localhost:3000`, call the forwarded API without
+//! changing the backend.
+//!
+//! Preflight `OPTIONS` requests from allowed origins are answered by the
+//! proxy itself, before authentication, and never reach the upstream. Every
+//! other response to an allowed origin gets CORS headers, replacing any the
+//! upstream sent, including the proxy's own `401` and `503` answers.
+//!
+//! Any page the developer opens could otherwise read the cluster's answers
+//! with their cookies, so credentials and Chrome's private network access
+//! are only granted to origins named in `allowed_origins`.
+
+use crate::config::{CorsConfig, CorsSetting};
+use anyhow::{bail, Context, Result};
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+    ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+    ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+static CORS: LazyLock<Mutex<HashMap<String, Arc<Cors>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Seconds browsers may cache a preflight answer, unless `max_age` is set.
+const DEFAULT_MAX_AGE: u64 = 600;
+
+/// A forward's CORS settings, parsed once from its config.
+#[derive(Debug)]
+pub struct Cors {
+    /// `None` allows every origin
+    origins: Option<Vec<String>>,
+    /// `None` allows whatever the preflight asks for
+    methods: Option<HeaderValue>,
+    headers: Option<HeaderValue>,
+    /// `None` exposes every response header
+    expose: Option<HeaderValue>,
+    credentials: bool,
+    max_age: HeaderValue,
+}
+
+impl Cors {
+    pub fn new(config: &CorsConfig) -> Result<Cors> {
+        let list = |values: &Option<Vec<String>>, field: &str| {
+            values
+                .as_ref()
+                .map(|values| HeaderValue::from_str(&values.join(", ")))
+                .transpose()
+                .context(format!("Invalid cors {}", field))
+        };
+        if let Some(methods) = &config.allowed_methods {
+            for method in methods {
+                Method::from_bytes(method.as_bytes()).context(format!("Invalid cors method '{}'", method))?;
+            }
+        }
+        if let Some(headers) = &config.allowed_headers {
+            for header in headers {
+                HeaderName::from_bytes(header.as_bytes()).context(format!("Invalid cors header '{}'", header))?;
+            }
+        }
+        let origins: Option<Vec<String>> = config
+            .allowed_origins
+            .as_ref()
+            .map(|origins| origins.iter().map(|origin| origin.trim_end_matches('/').to_string()).collect());
+        let named = origins
+            .as_ref()
+            .is_some_and(|origins| !origins.iter().any(|origin| origin == "*"));
+        if config.allow_credentials == Some(true) && !named {
+            bail!("cors allow_credentials needs allowed_origins naming the origins, without \"*\"");
+        }
+        Ok(Cors {
+            credentials: config.allow_credentials.unwrap_or(named),
+            origins,
+            methods: list(&config.allowed_methods, "allowed_methods")?,
+            headers: list(&config.allowed_headers, "allowed_headers")?,
+            expose: list(&config.expose_headers, "expose_headers")?,
+            max_age: config.max_age.unwrap_or(DEFAULT_MAX_AGE).into(),
+        })
+    }
+
+    fn allows(&self, origin: &HeaderValue) -> bool {
+        match (&self.origins, origin.to_str()) {
+            (None, _) => true,
+            (Some(origins), Ok(origin)) => origins.iter().any(|allowed| allowed == "*" || allowed == origin),
+            (Some(_), Err(_)) => false,
+        }
+    }
+
+    /// Whether `origin` is named in `allowed_origins`, rather than allowed
+    /// by default or `*`.
+    fn names(&self, origin: &HeaderValue) -> bool {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        self.origins
+            .as_ref()
+            .is_some_and(|origins| origins.iter().any(|allowed| allowed == origin))
+    }
+
+    /// The local answer to a preflight request from an allowed origin.
+    pub fn preflight(&self, req: &Request<Body>) -> Option<Response<Body>> {
+        let headers = req.headers();
+        let origin = headers.get(ORIGIN).filter(|origin| self.allows(origin))?;
+        let method = headers.get(ACCESS_CONTROL_REQUEST_METHOD)?;
+        if req.method() != Method::OPTIONS {
+            return None;
+        }
+
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let answer = response.headers_mut();
+        self.allow_origin(origin, answer);
+        answer.insert(ACCESS_CONTROL_ALLOW_METHODS, self.methods.clone().unwrap_or_else(|| method.clone()));
+        let requested = headers.get(ACCESS_CONTROL_REQUEST_HEADERS);
+        if let Some(allowed) = self.headers.as_ref().or(requested) {
+            answer.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed.clone());
+        }
+        // Chrome asks before a public page may call a local address
+        if headers.contains_key("access-control-request-private-network") && self.names(origin) {
+            answer.insert("access-control-allow-private-network", HeaderValue::from_static("true"));
+        }
+        answer.insert(ACCESS_CONTROL_MAX_AGE, self.max_age.clone());
+        Some(response)
+    }
+
+    /// Replaces the CORS headers of a response to a request from `origin`.
+    pub fn apply(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        if !self.allows(origin) {
+            return;
+        }
+        let upstream: Vec<HeaderName> = headers
+            .keys()
+            .filter(|name| name.as_str().starts_with("access-control-"))
+            .cloned()
+            .collect();
+        for name in upstream {
+            headers.remove(name);
+        }
+        let expose = self.expose.clone().or_else(|| {
+            let names: Vec<&str> = headers.keys().map(|name| name.as_str()).collect();
+            HeaderValue::from_str(&names.join(", ")).ok().filter(|names| !names.is_empty())
+        });
+        self.allow_origin(origin, headers);
+        if let Some(expose) = expose {
+            headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, expose);
+        }
+    }
+
+    fn allow_origin(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        // The origin is echoed rather than `*`, which browsers reject with credentials
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        headers.append(VARY, HeaderValue::from_static("Origin"));
+        if self.credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+    }
+}
+
+/// Registers the CORS settings of a forward, replacing any earlier ones.
+pub fn configure(key: &str, setting: Option<&CorsSetting>) -> Result<()> {
+    let cors = setting.and_then(CorsSetting::config).map(|config| Cors::new(&config)).transpose()?;
+    let mut registry = CORS.lock().unwrap();
+    match cors {
+        Some(cors) => registry.insert(key.to_string(), Arc::new(cors)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// CORS settings of a forward, if it has them.
+pub fn policy(key: &str) -> Option<Arc<Cors>> {
+    CORS.lock().unwrap().get(key).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cors(origins: Option<&[&str]>, credentials: Option<bool>) -> Result<Cors> {
+        Cors::new(&CorsConfig {
+            allowed_origins: origins.map(|origins| origins.iter().map(|origin| origin.to_string()).collect()),
+            allow_credentials: credentials,
+            ..Default::default()
+        })
+    }
+
+    fn preflight(origin: &str, private_network: bool) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "content-type");
+        if private_network {
+            builder = builder.header("access-control-request-private-network", "true");
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    fn applied(cors: &Cors, origin: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("1"));
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        cors.apply(&HeaderValue::from_str(origin).unwrap(), &mut headers);
+        headers
+    }
+
+    #[test]
+    fn any_origin_gets_no_credentials_or_private_network() {
+        let cors = cors(None, None).unwrap();
+        let response = cors.preflight(&preflight("https://evil.example", true)).unwrap();
+        let headers = response.headers();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://evil.example");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "PUT");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "content-type");
+        assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+        assert!(!headers.contains_key("access-control-allow-private-network"));
+
+        let headers = applied(&cors, "https://evil.example");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://evil.example");
+        assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+
+    #[test]
+    fn listed_origins_get_credentials_and_private_network() {
+        let cors = cors(Some(&["http://localhost:3000/"]), None).unwrap();
+        let response = cors.preflight(&preflight("http://localhost:3000", true)).unwrap();
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers["access-control-allow-private-network"], "true");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+
+        let headers = applied(&cors, "http://localhost:3000");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "http://localhost:3000");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], "x-request-id");
+    }
+
+    #[test]
+    fn other_origins_are_left_alone() {
+        let cors = cors(Some(&["http://localhost:3000"]), None).unwrap();
+        assert!(cors.preflight(&preflight("https://evil.example", true)).is_none());
+        let headers = applied(&cors, "https://evil.example");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+
+    #[test]
+    fn wildcard_origin_gets_no_credentials() {
+        let cors = cors(Some(&["*"]), None).unwrap();
+        let response = cors.preflight(&preflight("https://evil.example", true)).unwrap();
+        assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+        assert!(!response.headers().contains_key("access-control-allow-private-network"));
+    }
+
+    #[test]
+    fn credentials_need_listed_origins() {
+        assert!(cors(None, Some(true)).is_err());
+        assert!(cors(Some(&["*"]), Some(true)).is_err());
+        assert!(cors(Some(&["http://localhost:3000"]), Some(true)).is_ok());
+        let cors = cors(Some(&["http://localhost:3000"]), Some(false)).unwrap();
+        assert!(!applied(&cors, "http://localhost:3000").contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+
+    #[test]
+    fn only_preflights_are_answered() {
+        let cors = cors(None, None).unwrap();
+        let mut request = preflight("http://localhost:3000", false);
+        *request.method_mut() = Method::PUT;
+        assert!(cors.preflight(&request).is_none());
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .header(ORIGIN, "http://localhost:3000")
+            .body(Body::empty())
+            .unwrap();
+        assert!(cors.preflight(&request).is_none());
+    }
+}
@@ -0,0 +1,406 @@
+//! `kpf start`: forwards without the TUI, optionally detached with `--daemon`.
+//!
+//! The session serves the control API on a unix socket next to its pidfile,
+//! state file and log file in `~/.config/kpf/daemon` (a named pipe on
+//! Windows), which `kpf status`, `kpf logs` and `kpf stop` talk to.
+
+use crate::tui::LogEntry;
+use anyhow::{bail, Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Set in the environment of the detached process.
+const DETACHED_ENV: &str = "KPF_DAEMON_PROCESS";
+/// How long `start --daemon` and `stop` wait for the session to come up or go.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(15);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Recent log entries, numbered so `kpf logs --follow` can ask for newer ones.
+static LOG_BUFFER: LazyLock<Mutex<VecDeque<(u64, LogEntry)>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::new()));
+static LOG_CAPACITY: AtomicU64 = AtomicU64::new(10_000);
+static NEXT_LOG_SEQ: AtomicU64 = AtomicU64::new(1);
+static SHUTDOWN: LazyLock<tokio::sync::Notify> = LazyLock::new(tokio::sync::Notify::new);
+
+/// Keeps log entries for `GET /logs`.
+struct BufferSink;
+
+impl crate::logger::LogSink for BufferSink {
+    fn write(&self, entry: &LogEntry) {
+        let mut buffer = LOG_BUFFER.lock().unwrap();
+        if buffer.len() as u64 >= LOG_CAPACITY.load(Ordering::Relaxed) {
+            buffer.pop_front();
+        }
+        buffer.push_back((NEXT_LOG_SEQ.fetch_add(1, Ordering::Relaxed), entry.clone()));
+    }
+}
+
+/// Directory holding the pidfile, state file, socket and log of the session.
+pub fn daemon_dir() -> Result<PathBuf> {
+    // Next to the sessions directory
+    Ok(crate::session::sessions_dir()?.with_file_name("daemon"))
+}
+
+fn pid_path() -> Result<PathBuf> {
+    Ok(daemon_dir()?.join("kpf.pid"))
+}
+
+fn state_path() -> Result<PathBuf> {
+    Ok(daemon_dir()?.join("state.json"))
+}
+
+#[cfg(unix)]
+fn socket_path() -> Result<PathBuf> {
+    Ok(daemon_dir()?.join("kpf.sock"))
+}
+
+/// Named pipe of the session; one per user, like the daemon directory.
+#[cfg(windows)]
+fn pipe_name() -> String {
+    format!(r"\\.\pipe\kpf-{}", std::env::var("USERNAME").unwrap_or_default())
+}
+
+/// Where the control API is served, for the state file and the log.
+fn control_address() -> Result<String> {
+    #[cfg(unix)]
+    return Ok(socket_path()?.display().to_string());
+    #[cfg(windows)]
+    return Ok(pipe_name());
+}
+
+/// Files a session leaves behind if it dies.
+fn session_files() -> Result<Vec<PathBuf>> {
+    #[cfg(unix)]
+    let files = vec![socket_path()?, pid_path()?, state_path()?];
+    #[cfg(windows)]
+    let files = vec![pid_path()?, state_path()?];
+    Ok(files)
+}
+
+pub fn log_path() -> Result<PathBuf> {
+    Ok(daemon_dir()?.join("kpf.log"))
+}
+
+/// Whether this process is the detached one `start --daemon` spawned.
+pub fn is_detached_process() -> bool {
+    std::env::var_os(DETACHED_ENV).is_some()
+}
+
+/// Starts this command line again as a detached process and waits until its
+/// socket answers.
+pub async fn spawn() -> Result<()> {
+    if let Ok(pid) = running_pid().await {
+        bail!("A kpf session is already running (pid {}); stop it with `kpf stop`", pid);
+    }
+    let dir = daemon_dir()?;
+    std::fs::create_dir_all(&dir).context(format!("Failed to create {}", dir.display()))?;
+    let log_path = log_path()?;
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .context(format!("Failed to open {}", log_path.display()))?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(std::env::args_os().skip(1))
+        .env(DETACHED_ENV, "1")
+        .stdin(std::process::Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    let mut child =
+        crate::process::spawn_detached(&mut command).context("Failed to start the kpf daemon")?;
+
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            bail!(
+                "The kpf daemon exited during startup ({}); see {}",
+                status,
+                log_path.display()
+            );
+        }
+        if let Ok(pid) = running_pid().await {
+            println!("🚀 kpf daemon started (pid {})", pid);
+            println!("   Logs: kpf logs -f ({})", log_path.display());
+            println!("   Stop: kpf stop");
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            bail!(
+                "The kpf daemon did not answer within {}s; see {}",
+                STARTUP_TIMEOUT.as_secs(),
+                log_path.display()
+            );
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Runs a session without the TUI: logs go to stdout (or the log file when
+/// detached), the control API is served on the socket, and `start` brings up
+/// the forwards. Returns once `kpf stop`, Ctrl+C or SIGTERM ends the session.
+pub async fn run(start: impl std::future::Future<Output = ()> + Send + 'static, max_log_lines: usize) -> Result<()> {
+    if let Ok(pid) = running_pid().await {
+        bail!("A kpf session is already running (pid {}); stop it with `kpf stop`", pid);
+    }
+    let dir = daemon_dir()?;
+    std::fs::create_dir_all(&dir).context(format!("Failed to create {}", dir.display()))?;
+
+    LOG_CAPACITY.store(max_log_lines.max(1) as u64, Ordering::Relaxed);
+    crate::logger::add_sink(Box::new(BufferSink));
+    if is_detached_process() {
+        crate::logger::add_sink(Box::new(crate::logger::FileSink::open(log_path()?)?));
+    } else {
+        crate::logger::add_sink(Box::new(crate::logger::StdoutSink));
+    }
+
+    let address = control_address()?;
+    let incoming = listen().context(format!("Failed to listen on {}", address))?;
+    std::fs::write(pid_path()?, format!("{}\n", std::process::id()))?;
+    let state = serde_json::json!({
+        "pid": std::process::id(),
+        "started_at": chrono::Utc::now(),
+        "detached": is_detached_process(),
+        "socket": address,
+        "log_file": is_detached_process().then(log_path).transpose()?,
+        "args": std::env::args().skip(1).collect::<Vec<_>>(),
+    });
+    std::fs::write(state_path()?, serde_json::to_string_pretty(&state)?)?;
+
+    crate::logger::log_info(format!("{} kpf session running (pid {})", "🚀", std::process::id()));
+    crate::logger::log_info(format!("{} Control socket: {}", "🎛", address));
+
+    tokio::spawn(serve(incoming));
+    tokio::spawn(start);
+
+    // Closing the terminal must not end a detached session
+    crate::process::ignore_hangup()?;
+    let failure = tokio::select! {
+        _ = SHUTDOWN.notified() => None,
+        _ = tokio::signal::ctrl_c() => None,
+        result = crate::process::terminate_requested() => result.map(|_| None)?,
+        reason = crate::forwarder::failure_exit_requested() => Some(reason),
+    };
+
+    crate::logger::log_info(format!("{} Stopping kpf session", "🛑"));
+    for path in session_files()? {
+        let _ = std::fs::remove_file(path);
+    }
+    // Dropping the runtime kills the kubectl processes
+    match failure {
+        Some(reason) => bail!(reason),
+        None => Ok(()),
+    }
+}
+
+/// Connections to the control socket. Whatever is left at its path did not
+/// answer, so it belongs to a session that died.
+#[cfg(unix)]
+fn listen() -> Result<impl futures::Stream<Item = std::io::Result<tokio::net::UnixStream>>> {
+    let socket = socket_path()?;
+    let _ = std::fs::remove_file(&socket);
+    let listener = tokio::net::UnixListener::bind(&socket)?;
+    Ok(futures::stream::unfold(listener, |listener| async {
+        let accepted = listener.accept().await.map(|(stream, _)| stream);
+        Some((accepted, listener))
+    }))
+}
+
+/// Connections to the control pipe. Each pipe instance serves one client, so
+/// the next is created as soon as one is taken; the first fails while
+/// another session owns the name.
+#[cfg(windows)]
+fn listen(
+) -> Result<impl futures::Stream<Item = std::io::Result<tokio::net::windows::named_pipe::NamedPipeServer>>> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+    let name = pipe_name();
+    let first = ServerOptions::new().first_pipe_instance(true).create(&name)?;
+    Ok(futures::stream::unfold(Some(first), move |server| {
+        let name = name.clone();
+        async move {
+            let server = server?;
+            let connected = server.connect().await;
+            let next = ServerOptions::new().create(&name);
+            match (connected, next) {
+                (Ok(()), Ok(next)) => Some((Ok(server), Some(next))),
+                (Err(e), Ok(next)) => Some((Err(e), Some(next))),
+                (_, Err(e)) => Some((Err(e), None)),
+            }
+        }
+    }))
+}
+
+/// Connects to the running session's control socket or pipe.
+async fn connect() -> std::io::Result<impl AsyncRead + AsyncWrite + Unpin + Send + 'static> {
+    #[cfg(unix)]
+    return tokio::net::UnixStream::connect(socket_path().map_err(std::io::Error::other)?).await;
+    #[cfg(windows)]
+    loop {
+        use windows_sys::Win32::Foundation::ERROR_PIPE_BUSY;
+        match tokio::net::windows::named_pipe::ClientOptions::new().open(pipe_name()) {
+            // Every instance is taken until the server creates the next one
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) => {
+                tokio::time::sleep(Duration::from_millis(50)).await
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The control API plus `GET /logs?after=<seq>` and `POST /shutdown`.
+async fn serve<S, C>(incoming: S)
+where
+    S: futures::Stream<Item = std::io::Result<C>> + Send + 'static,
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let incoming = hyper::server::accept::from_stream(incoming);
+    let make_svc = make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(handle_request)) });
+    if let Err(e) = Server::builder(incoming).serve(make_svc).await {
+        crate::logger::log_error(format!("Control socket failed: {}", e));
+    }
+}
+
+async fn handle_request(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/logs") => {
+            let after: u64 = req
+                .uri()
+                .query()
+                .and_then(|query| {
+                    form_urlencoded::parse(query.as_bytes())
+                        .find(|(key, _)| key == "after")
+                        .and_then(|(_, value)| value.parse().ok())
+                })
+                .unwrap_or(0);
+            let entries: Vec<_> = LOG_BUFFER
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(seq, _)| *seq > after)
+                .map(|(seq, entry)| serde_json::json!({ "seq": seq, "line": crate::logger::format_entry(entry) }))
+                .collect();
+            Ok(json_response(StatusCode::OK, serde_json::json!({ "entries": entries })))
+        }
+        (&Method::POST, "/shutdown") => {
+            SHUTDOWN.notify_one();
+            Ok(json_response(StatusCode::ACCEPTED, serde_json::json!({ "ok": true })))
+        }
+        _ => crate::control::handle_request(req).await,
+    }
+}
+
+fn json_response(status: StatusCode, value: serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(value.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Sends a request to the running session and returns the response body.
+pub async fn request(method: Method, path: &str) -> Result<Vec<u8>> {
+    let stream = connect()
+        .await
+        .context("No kpf session is running; start one with `kpf start`")?;
+    let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+    tokio::spawn(connection);
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(hyper::header::HOST, "kpf")
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::empty())?;
+    let response = sender.send_request(request).await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    if !status.is_success() {
+        bail!("kpf session returned {}: {}", status, String::from_utf8_lossy(&body));
+    }
+    Ok(body.to_vec())
+}
+
+/// Pid of the running session, if its socket answers.
+async fn running_pid() -> Result<u32> {
+    request(Method::GET, "/forwards").await?;
+    let pid = std::fs::read_to_string(pid_path()?)?;
+    Ok(pid.trim().parse()?)
+}
+
+/// Asks the running session to stop and waits until it is gone.
+pub async fn stop() -> Result<()> {
+    let pid = match running_pid().await {
+        Ok(pid) => pid,
+        Err(_) => return stop_unresponsive(),
+    };
+    request(Method::POST, "/shutdown").await?;
+
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    while pid_path()?.exists() {
+        if tokio::time::Instant::now() >= deadline {
+            bail!("kpf session (pid {}) did not stop within {}s", pid, STARTUP_TIMEOUT.as_secs());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    println!("🛑 Stopped kpf session (pid {})", pid);
+    Ok(())
+}
+
+/// A pidfile without a socket that answers: terminate the process if it is
+/// still there, and clear the files it left behind.
+fn stop_unresponsive() -> Result<()> {
+    let Ok(pid) = std::fs::read_to_string(pid_path()?) else {
+        bail!("No kpf session is running");
+    };
+    let pid = pid.trim();
+    let terminated = crate::process::terminate(pid);
+    // Windows kills the process outright, so it cannot clean up after itself
+    for path in session_files()? {
+        let _ = std::fs::remove_file(path);
+    }
+    if !terminated {
+        bail!("No kpf session is running (removed the stale pidfile of pid {})", pid);
+    }
+    println!("🛑 Terminated the unresponsive kpf session (pid {})", pid);
+    Ok(())
+}
+
+/// Prints the last `tail` log lines of the running session, then, with
+/// `follow`, new ones until it stops.
+pub async fn print_logs(follow: bool, tail: usize) -> Result<()> {
+    let mut after = 0;
+    let mut first = true;
+    loop {
+        let body = match request(Method::GET, &format!("/logs?after={}", after)).await {
+            Ok(body) => body,
+            Err(_) if !first => {
+                println!("🛑 kpf session stopped");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let response: serde_json::Value = serde_json::from_slice(&body)?;
+        let entries = response["entries"].as_array().cloned().unwrap_or_default();
+        let skip = if first { entries.len().saturating_sub(tail) } else { 0 };
+        for entry in &entries[skip..] {
+            println!("{}", entry["line"].as_str().unwrap_or_default());
+        }
+        if let Some(seq) = entries.last().and_then(|entry| entry["seq"].as_u64()) {
+            after = seq;
+        }
+        if !follow {
+            return Ok(());
+        }
+        first = false;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
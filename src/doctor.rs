@@ -0,0 +1,232 @@
+//! `kpf doctor`: preflight checks run before any forward is attempted, each
+//! reported with a hint on how to fix it.
+
+use crate::config::{ForwardConfig, Protocol};
+use anyhow::{bail, Result};
+use crate::k8s::{can_i, describe_permission, Permission};
+use k8s_openapi::api::authorization::v1::SelfSubjectAccessReview;
+use kube::api::Api;
+use kube::config::Kubeconfig;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long the API server gets to answer before it counts as unreachable.
+const CLUSTER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Permissions a forward needs in its namespace, as (verb, API group,
+/// resource, subresource): validating the target, picking a ready endpoint
+/// and opening the port-forward itself.
+const REQUIRED_PERMISSIONS: &[Permission] = &[
+    ("get", "", "pods", None),
+    ("get", "", "services", None),
+    ("list", "discovery.k8s.io", "endpointslices", None),
+    ("create", "", "pods", Some("portforward")),
+];
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn pass(&mut self, message: String) {
+        println!("✅ {}", message);
+    }
+
+    fn fail(&mut self, message: String, hint: &str) {
+        self.failures += 1;
+        println!("❌ {}", message);
+        println!("   → {}", hint);
+    }
+}
+
+/// Runs every check for the forwards of `config` (or, without one, for
+/// `context` and `namespace`) and fails if any of them did.
+pub async fn run(config: Option<&Path>, context: Option<&str>, namespace: Option<&str>) -> Result<()> {
+    let forwards = match config {
+        Some(path) => crate::config::load_config(path)?.forwards,
+        None => Vec::new(),
+    };
+    let mut report = Report::default();
+
+    check_kubectl(&mut report).await;
+    let kubeconfig = check_kubeconfig(&mut report);
+
+    // Every context and namespace the forwards will talk to
+    let mut targets: Vec<(Option<String>, String)> = forwards
+        .iter()
+        .map(|forward| forward_target(forward, context, namespace))
+        .chain(
+            forwards
+                .is_empty()
+                .then(|| forward_target(&ForwardConfig::default(), context, namespace)),
+        )
+        .collect();
+    targets.sort();
+    targets.dedup();
+
+    if kubeconfig.is_some() {
+        let mut contexts: Vec<Option<String>> =
+            targets.iter().map(|(context, _)| context.clone()).collect();
+        contexts.dedup();
+        for context in contexts {
+            let namespaces: Vec<&str> = targets
+                .iter()
+                .filter(|(target, _)| *target == context)
+                .map(|(_, namespace)| namespace.as_str())
+                .collect();
+            check_cluster(&mut report, context.as_deref(), &namespaces).await;
+        }
+    }
+
+    check_local_ports(&mut report, &forwards);
+
+    if report.failures > 0 {
+        bail!("{} check(s) failed", report.failures);
+    }
+    println!("🩺 All checks passed");
+    Ok(())
+}
+
+/// Context and namespace a forward talks to, falling back to the command
+/// line's, then to the context's namespace and `default`.
+pub(crate) fn forward_target(
+    forward: &ForwardConfig,
+    context: Option<&str>,
+    namespace: Option<&str>,
+) -> (Option<String>, String) {
+    let context = forward.context.clone().or_else(|| context.map(str::to_string));
+    let namespace = forward
+        .namespace
+        .clone()
+        .or_else(|| namespace.map(str::to_string))
+        .or_else(|| crate::k8s::context_namespace(context.as_deref()))
+        .unwrap_or_else(|| "default".to_string());
+    (context, namespace)
+}
+
+async fn check_kubectl(report: &mut Report) {
+    let output = tokio::process::Command::new("kubectl")
+        .args(["version", "--client", "-o", "json"])
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            let version = serde_json::from_slice::<serde_json::Value>(&output.stdout)
+                .ok()
+                .and_then(|json| json["clientVersion"]["gitVersion"].as_str().map(str::to_string))
+                .unwrap_or_else(|| "unknown version".to_string());
+            report.pass(format!("kubectl found ({})", version));
+        }
+        Ok(output) => report.fail(
+            format!(
+                "kubectl version failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            "Check that the kubectl on your PATH runs on its own",
+        ),
+        Err(e) => report.fail(
+            format!("kubectl could not be run: {}", e),
+            "Install kubectl and make sure it is on your PATH (https://kubernetes.io/docs/tasks/tools/)",
+        ),
+    }
+}
+
+fn check_kubeconfig(report: &mut Report) -> Option<Kubeconfig> {
+    match Kubeconfig::read() {
+        Ok(kubeconfig) => {
+            match &kubeconfig.current_context {
+                Some(context) => report.pass(format!("kubeconfig loaded (current context {})", context)),
+                None => report.pass("kubeconfig loaded (no current context)".to_string()),
+            }
+            Some(kubeconfig)
+        }
+        Err(e) => {
+            report.fail(
+                format!("kubeconfig could not be read: {}", e),
+                "Set KUBECONFIG or create ~/.kube/config, e.g. with your cloud provider's get-credentials command",
+            );
+            None
+        }
+    }
+}
+
+/// Reachability and permissions for one context.
+async fn check_cluster(report: &mut Report, context: Option<&str>, namespaces: &[&str]) {
+    let name = crate::k8s::effective_context(context).unwrap_or_else(|| "current context".to_string());
+    let client = match crate::k8s::client_for_context(context).await {
+        Ok(client) => client,
+        Err(e) => {
+            report.fail(
+                format!("{}: {:#}", name, e),
+                "Check that the context exists in your kubeconfig (kubectl config get-contexts)",
+            );
+            return;
+        }
+    };
+
+    match tokio::time::timeout(CLUSTER_TIMEOUT, client.apiserver_version()).await {
+        Ok(Ok(version)) => report.pass(format!("{}: cluster reachable (server {})", name, version.git_version)),
+        Ok(Err(e)) => {
+            report.fail(
+                format!("{}: cluster unreachable: {}", name, e),
+                "Check your network or VPN, and refresh expired credentials",
+            );
+            return;
+        }
+        Err(_) => {
+            report.fail(
+                format!("{}: cluster did not answer within {}s", name, CLUSTER_TIMEOUT.as_secs()),
+                "Check your network or VPN; the API server may be behind a bastion",
+            );
+            return;
+        }
+    }
+
+    let reviews: Api<SelfSubjectAccessReview> = Api::all(client);
+    for namespace in namespaces {
+        for (verb, group, resource, subresource) in REQUIRED_PERMISSIONS {
+            let permission = describe_permission(verb, resource, *subresource);
+            match can_i(&reviews, namespace, verb, group, resource, *subresource).await {
+                Ok(true) => report.pass(format!("{}: allowed to {} in {}", name, permission, namespace)),
+                Ok(false) => report.fail(
+                    format!("{}: not allowed to {} in {}", name, permission, namespace),
+                    "Ask a cluster admin for a Role granting it, or check the namespace",
+                ),
+                Err(e) => report.fail(
+                    format!("{}: could not check {} in {}: {:#}", name, permission, namespace, e),
+                    "Check that your credentials may create selfsubjectaccessreviews",
+                ),
+            }
+        }
+    }
+}
+
+/// Local ports of the config's forwards. Ports picked at start (`*` forwards,
+/// `local_port_range`) are not checked.
+fn check_local_ports(report: &mut Report, forwards: &[ForwardConfig]) {
+    for forward in forwards {
+        if forward.local_port_range.is_some() {
+            continue;
+        }
+        let Some(port) = forward.local_port().or_else(|| {
+            crate::k8s::parse_resource(&forward.resource)
+                .ok()
+                .map(|(_, _, port)| port)
+        }) else {
+            continue;
+        };
+        let free = match forward.protocol {
+            Some(Protocol::Udp) => std::net::UdpSocket::bind(("127.0.0.1", port)).is_ok(),
+            _ => crate::forwarder::is_port_free(port),
+        };
+        if free {
+            report.pass(format!("local port {} is free for {}", port, forward.resource));
+        } else {
+            report.fail(
+                format!("local port {} for {} is already in use", port, forward.resource),
+                "Stop whatever listens on it, change local_port, or pass --auto-port",
+            );
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! `--write-env FILE`: keeps a dotenv file with the address of every active
+//! forward, e.g. `API_HOST=localhost` and `API_PORT=8080`, so docker-compose
+//! (`env_file`) or a local app can pick up the current local ports instead of
+//! hardcoding them.
+//!
+//! The file is rewritten whenever a forward becomes usable, stops being usable
+//! or changes its local port. It is replaced atomically, so readers never see
+//! a half-written file.
+
+use crate::forwarder::ForwardState;
+use crate::tui::ForwardStatus;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often the forwards are checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Writes the env file now and keeps it up to date in the background.
+pub fn start(path: PathBuf) -> Result<()> {
+    let mut last = contents(&[]);
+    write(&path, &last).context(format!("Failed to write env file {}", path.display()))?;
+
+    tokio::spawn(async move {
+        let mut failed = false;
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let mut forwards: Vec<ForwardStatus> = crate::forwarder::FORWARD_STATUSES
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect();
+            forwards.sort_by(|a, b| a.resource.cmp(&b.resource));
+            let current = contents(&forwards);
+            if current == last {
+                continue;
+            }
+            match write(&path, &current) {
+                Ok(()) => {
+                    last = current;
+                    failed = false;
+                }
+                // Retried on the next check; logged once until it works again
+                Err(e) if !failed => {
+                    crate::logger::log_warning(format!("{} Failed to write env file {}: {}", "📝", path.display(), e));
+                    failed = true;
+                }
+                Err(_) => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+/// `<NAME>_HOST` and `<NAME>_PORT` of every forward that accepts connections.
+/// Names come from all forwards, so they do not change as forwards come and go.
+fn contents(forwards: &[ForwardStatus]) -> String {
+    let host = host();
+    let mut contents = String::from("# Written by kpf (--write-env); rewritten when forwards change\n");
+    for (forward, name) in forwards.iter().zip(crate::template::variable_names(forwards)) {
+        // Idle forwards reconnect on the next connection, so they count as usable
+        if matches!(forward.state, ForwardState::Open | ForwardState::Active | ForwardState::Idle) {
+            contents.push_str(&format!("{}_HOST={}\n", name, host));
+            contents.push_str(&format!("{}_PORT={}\n", name, forward.local_port));
+        }
+    }
+    contents
+}
+
+/// `localhost`, unless `--bind` names a specific address to reach the proxies on.
+fn host() -> String {
+    match crate::http::bind_addresses().first() {
+        Some(address) if !address.is_loopback() && !address.is_unspecified() => address.to_string(),
+        _ => "localhost".to_string(),
+    }
+}
+
+/// Writes a temporary file next to `path` and renames it over `path`.
+fn write(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)
+}
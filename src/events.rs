@@ -0,0 +1,192 @@
+//! Kubernetes Warning events about a forward's target, logged as warnings so
+//! the cluster-side reason for a dropping forward (`BackOff` of a crash
+//! looping container, `FailedScheduling`, `Unhealthy`, ...) shows up next to
+//! the reconnects it causes.
+//!
+//! Each forward watches the Warning events of its namespace and keeps those
+//! about its current target pod, the other pods of that pod's ReplicaSet, or
+//! the forwarded service. `BackOff` events are completed with the reason the
+//! container last terminated, e.g. `OOMKilled`, which Kubernetes reports on
+//! the pod rather than as an event.
+
+use crate::forwarder::FORWARD_STATUSES;
+use futures::{StreamExt, TryStreamExt};
+use k8s_openapi::api::core::v1::{Event, Pod};
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::Api;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::task::JoinHandle;
+
+/// Logged events remembered to skip repeats; the cluster expires events
+/// after an hour anyway.
+const MAX_SEEN_EVENTS: usize = 1000;
+
+/// Set by `--no-events`.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Stops forwards started from now on from watching events (`--no-events`).
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// What a forward's events are matched against.
+#[derive(Clone)]
+pub struct EventTarget {
+    /// The forward's key, e.g. `service/api:80`
+    pub key: String,
+    pub resource_type: String,
+    pub resource_name: String,
+    pub namespace: String,
+    pub context: Option<String>,
+}
+
+/// Starts logging the Warning events about a forward's target, unless events
+/// are disabled or responses come from recordings. Abort the task when the
+/// forward stops.
+pub fn spawn(target: EventTarget) -> Option<JoinHandle<()>> {
+    if DISABLED.load(Ordering::Relaxed) || crate::mock::is_mocking() {
+        return None;
+    }
+    Some(tokio::spawn(watch(target)))
+}
+
+async fn watch(target: EventTarget) {
+    let client = match crate::k8s::client_for_context(target.context.as_deref()).await {
+        Ok(client) => client,
+        Err(e) => {
+            crate::logger::log_warning(format!(
+                "{} Not watching Kubernetes events for {}: {}",
+                "📣", target.key, e
+            ));
+            return;
+        }
+    };
+    let events: Api<Event> = Api::namespaced(client.clone(), &target.namespace);
+    let pods: Api<Pod> = Api::namespaced(client, &target.namespace);
+
+    // The watch starts with a listing of past events; only newer ones are logged
+    let started = chrono::Utc::now() - chrono::Duration::seconds(5);
+    // Event counts already logged, by event uid
+    let mut seen: HashMap<String, i32> = HashMap::new();
+    let mut failing = false;
+    let mut stream = watcher(events, watcher::Config::default().fields("type=Warning"))
+        .default_backoff()
+        .applied_objects()
+        .boxed();
+    loop {
+        let event = match stream.try_next().await {
+            Ok(Some(event)) => event,
+            Ok(None) => return,
+            Err(e) => {
+                // The watcher retries with a backoff; say so only once
+                if !std::mem::replace(&mut failing, true) {
+                    crate::logger::log_warning(format!(
+                        "{} Cannot watch Kubernetes events in {} for {}: {}",
+                        "📣", target.namespace, target.key, e
+                    ));
+                }
+                continue;
+            }
+        };
+        failing = false;
+
+        if last_seen(&event).is_some_and(|at| at < started) || !concerns(&event, &target) {
+            continue;
+        }
+        let count = event.count.unwrap_or(1);
+        let uid = event.metadata.uid.clone().unwrap_or_default();
+        if seen.len() >= MAX_SEEN_EVENTS {
+            seen.clear();
+        }
+        if seen.insert(uid, count).is_some_and(|logged| logged >= count) {
+            continue;
+        }
+        crate::logger::log_warning(describe(&event, &pods, &target.key).await);
+    }
+}
+
+/// When the event last occurred.
+fn last_seen(event: &Event) -> Option<chrono::DateTime<chrono::Utc>> {
+    event
+        .last_timestamp
+        .as_ref()
+        .map(|time| time.0)
+        .or_else(|| event.event_time.as_ref().map(|time| time.0))
+        .or_else(|| event.metadata.creation_timestamp.as_ref().map(|time| time.0))
+}
+
+/// Whether an event is about the forward's target pod, a pod of the same
+/// ReplicaSet, or the forwarded service.
+fn concerns(event: &Event, target: &EventTarget) -> bool {
+    let object = &event.involved_object;
+    let name = object.name.as_deref().unwrap_or_default();
+    match object.kind.as_deref() {
+        Some("Pod") => {
+            if target.resource_type == "pod" && name == target.resource_name {
+                return true;
+            }
+            let current = FORWARD_STATUSES
+                .lock()
+                .unwrap()
+                .get(&target.key)
+                .and_then(|status| status.target_pod.clone());
+            let Some(current) = current else {
+                return false;
+            };
+            // Pods of a ReplicaSet are named `<replicaset>-<suffix>`
+            name == current
+                || current.rsplit_once('-').is_some_and(|(replicaset, _)| {
+                    name.strip_prefix(replicaset)
+                        .and_then(|rest| rest.strip_prefix('-'))
+                        .is_some_and(|suffix| !suffix.contains('-'))
+                })
+        }
+        Some("Service") => target.resource_type != "pod" && name == target.resource_name,
+        _ => false,
+    }
+}
+
+/// e.g. `📣 service/api:80: Pod api-7d9f8-x2kqp BackOff (x4): Back-off
+/// restarting failed container api (last terminated: OOMKilled, exit code 137)`
+async fn describe(event: &Event, pods: &Api<Pod>, key: &str) -> String {
+    let object = &event.involved_object;
+    let reason = event.reason.clone().unwrap_or_else(|| "Warning".to_string());
+    let mut line = format!(
+        "{} {}: {} {} {}",
+        "📣",
+        key,
+        object.kind.as_deref().unwrap_or_default(),
+        object.name.as_deref().unwrap_or_default(),
+        reason
+    );
+    if let Some(count) = event.count.filter(|&count| count > 1) {
+        line.push_str(&format!(" (x{})", count));
+    }
+    if let Some(message) = &event.message {
+        line.push_str(&format!(": {}", message.trim()));
+    }
+    if reason == "BackOff" && object.kind.as_deref() == Some("Pod") {
+        if let Some(terminated) = last_termination(pods, object.name.as_deref().unwrap_or_default()).await {
+            line.push_str(&format!(" (last terminated: {})", terminated));
+        }
+    }
+    line
+}
+
+/// Why a container of the pod last terminated, e.g. `OOMKilled, exit code 137`.
+async fn last_termination(pods: &Api<Pod>, name: &str) -> Option<String> {
+    let pod = pods.get(name).await.ok()?;
+    pod.status?
+        .container_statuses?
+        .into_iter()
+        .filter_map(|status| status.last_state?.terminated)
+        .max_by_key(|terminated| terminated.finished_at.clone().map(|time| time.0))
+        .map(|terminated| {
+            format!(
+                "{}, exit code {}",
+                terminated.reason.unwrap_or_else(|| "Error".to_string()),
+                terminated.exit_code
+            )
+        })
+}
@@ -0,0 +1,241 @@
+//! `kpf find`: fuzzy search over the pods and services of every namespace,
+//! listed with the ports they declare. In a terminal, a result can be picked
+//! to start forwarding it right away.
+
+use anyhow::{bail, Context, Result};
+use k8s_openapi::api::core::v1::{Pod, Service};
+use kube::api::{Api, ListParams};
+use std::io::{IsTerminal, Write};
+
+/// Most results listed.
+const MAX_RESULTS: usize = 20;
+
+/// A pod or service matching the query.
+struct Found {
+    kind: &'static str,
+    namespace: String,
+    name: String,
+    /// Declared ports, as (port, name, protocol)
+    ports: Vec<(u16, Option<String>, String)>,
+    score: i64,
+}
+
+impl Found {
+    fn describe_ports(&self) -> String {
+        if self.ports.is_empty() {
+            return "-".to_string();
+        }
+        self.ports
+            .iter()
+            .map(|(port, name, protocol)| match name {
+                Some(name) => format!("{}/{} ({})", port, protocol, name),
+                None => format!("{}/{}", port, protocol),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn tcp_ports(&self) -> Vec<u16> {
+        self.ports
+            .iter()
+            .filter(|(_, _, protocol)| protocol == "TCP")
+            .map(|(port, _, _)| *port)
+            .collect()
+    }
+}
+
+/// Lists the pods and services matching `query` in every namespace, or only
+/// in `namespace`. Returns the resource and namespace the user picked to
+/// forward, if any.
+pub async fn run(
+    query: &str,
+    context: Option<&str>,
+    namespace: Option<&str>,
+) -> Result<Option<(String, String)>> {
+    let client = crate::k8s::client_for_context(context).await?;
+    let (services, pods): (Api<Service>, Api<Pod>) = match namespace {
+        Some(namespace) => (
+            Api::namespaced(client.clone(), namespace),
+            Api::namespaced(client, namespace),
+        ),
+        None => (Api::all(client.clone()), Api::all(client)),
+    };
+    let services = services
+        .list(&ListParams::default())
+        .await
+        .context("Failed to list services (pass -n to search a single namespace)")?;
+    let pods = pods
+        .list(&ListParams::default())
+        .await
+        .context("Failed to list pods (pass -n to search a single namespace)")?;
+
+    let mut found: Vec<Found> = Vec::new();
+    for service in services {
+        let ports = service
+            .spec
+            .and_then(|spec| spec.ports)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|port| (port.port as u16, port.name, port.protocol.unwrap_or_else(|| "TCP".to_string())))
+            .collect();
+        found.extend(matching(query, "service", service.metadata.namespace, service.metadata.name, ports));
+    }
+    for pod in pods {
+        let finished = pod
+            .status
+            .as_ref()
+            .and_then(|status| status.phase.as_deref())
+            .is_some_and(|phase| phase == "Succeeded" || phase == "Failed");
+        if finished {
+            continue;
+        }
+        let ports = pod
+            .spec
+            .map(|spec| spec.containers)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|container| container.ports.unwrap_or_default())
+            .map(|port| {
+                let protocol = port.protocol.unwrap_or_else(|| "TCP".to_string());
+                (port.container_port as u16, port.name, protocol)
+            })
+            .collect();
+        found.extend(matching(query, "pod", pod.metadata.namespace, pod.metadata.name, ports));
+    }
+
+    if found.is_empty() {
+        bail!("No pods or services match '{}'", query);
+    }
+    // Best match first; services before the pods behind them
+    found.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| b.kind.cmp(a.kind))
+            .then_with(|| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)))
+    });
+    let total = found.len();
+    found.truncate(MAX_RESULTS);
+    print_results(&found, total);
+
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    pick(&found)
+}
+
+fn matching(
+    query: &str,
+    kind: &'static str,
+    namespace: Option<String>,
+    name: Option<String>,
+    ports: Vec<(u16, Option<String>, String)>,
+) -> Option<Found> {
+    let (namespace, name) = (namespace?, name?);
+    let score = fuzzy_score(query, &format!("{}/{}", namespace, name))?;
+    Some(Found {
+        kind,
+        namespace,
+        name,
+        ports,
+        score,
+    })
+}
+
+/// Scores `candidate` when every character of `query` appears in it in order,
+/// ignoring case. Runs of consecutive characters, matches at the start of a
+/// word and a whole-substring match score higher; longer candidates lower.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let query = query.to_lowercase();
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = position + candidate[position..].iter().position(|&c| c == wanted)?;
+        score += 10;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 15;
+        }
+        if found == 0 || matches!(candidate[found - 1], '-' | '_' | '.' | '/') {
+            score += 10;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    if candidate.iter().collect::<String>().contains(query.trim()) {
+        score += 50;
+    }
+    Some(score - candidate.len() as i64)
+}
+
+fn print_results(found: &[Found], total: usize) {
+    let number_width = found.len().to_string().len();
+    let kind_width = found.iter().map(|f| f.kind.len()).max().unwrap_or(0);
+    let namespace_width = found.iter().map(|f| f.namespace.len()).max().unwrap_or(0).max(9);
+    let name_width = found.iter().map(|f| f.name.len()).max().unwrap_or(0).max(4);
+    println!(
+        "{:>number_width$}  {:kind_width$}  {:namespace_width$}  {:name_width$}  PORTS",
+        "#", "KIND", "NAMESPACE", "NAME"
+    );
+    for (index, result) in found.iter().enumerate() {
+        println!(
+            "{:>number_width$}  {:kind_width$}  {:namespace_width$}  {:name_width$}  {}",
+            index + 1,
+            result.kind,
+            result.namespace,
+            result.name,
+            result.describe_ports()
+        );
+    }
+    if total > found.len() {
+        println!("… and {} more; refine the query to narrow them down", total - found.len());
+    }
+}
+
+/// Asks which result to forward, and on which port when it declares several.
+fn pick(found: &[Found]) -> Result<Option<(String, String)>> {
+    let answer = prompt(&format!("Forward which result? [1-{}, Enter to quit] ", found.len()))?;
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    let result = answer
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| found.get(number.checked_sub(1)?))
+        .context(format!("'{}' is not a result number", answer))?;
+
+    let ports = result.tcp_ports();
+    let port = match ports.as_slice() {
+        [port] => port.to_string(),
+        [] => {
+            let answer = prompt(&format!("{} declares no TCP ports. Port to forward: ", result.name))?;
+            answer.parse::<u16>().context(format!("'{}' is not a port", answer))?;
+            answer
+        }
+        [first, ..] => {
+            let listed: Vec<String> = ports.iter().map(u16::to_string).collect();
+            let all = if result.kind == "service" { ", * for all" } else { "" };
+            let answer = prompt(&format!("Port? [{}; Enter for {}{}] ", listed.join(", "), first, all))?;
+            match answer.as_str() {
+                "" => first.to_string(),
+                "*" if result.kind == "service" => answer,
+                _ => {
+                    answer.parse::<u16>().context(format!("'{}' is not a port", answer))?;
+                    answer
+                }
+            }
+        }
+    };
+
+    let resource = format!("{}/{}:{}", result.kind, result.name, port);
+    println!("▶ kpf {} -n {}", resource, result.namespace);
+    Ok(Some((resource, result.namespace.clone())))
+}
+
+fn prompt(question: &str) -> Result<String> {
+    print!("{}", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
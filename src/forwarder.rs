@@ -1,22 +1,32 @@
-use crate::config::Config;
+use crate::config::{Config, FailurePolicy, ForwardConfig, ForwardMode, LocalPort, OpenSetting, Protocol, SplitConfig, UpstreamScheme};
 use crate::http::start_http_server;
-use crate::k8s::{create_port_forward, parse_resource};
-use anyhow::{Context, Result};
-use futures::future::join_all;
-use std::sync::{Arc, Mutex};
+use crate::k8s::{create_port_forward, expand_all_ports, is_all_ports, parse_resource};
+use anyhow::{anyhow, Context, Result};
+use futures::FutureExt;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
-const MAX_RETRY_ATTEMPTS: u32 = 5;
 const RETRY_DELAY_MS: u64 = 1000;
+const MAX_RECONNECT_HISTORY: usize = 20;
+/// How often a forward with an `idle_timeout` checks for activity.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum ForwardState {
+    #[default]
     Initializing,
     Open,
     Active,
     Unavailable,
+    /// Disconnected on request; the local port stays bound and answers 503
+    Paused,
+    /// Disconnected after `idle_timeout`; the next request reconnects it
+    Idle,
+    Stopped,
 }
 
 impl std::fmt::Display for ForwardState {
@@ -26,18 +36,106 @@ impl std::fmt::Display for ForwardState {
             ForwardState::Open => "OPEN",
             ForwardState::Active => "ACTIVE",
             ForwardState::Unavailable => "UNAVAILABLE",
+            ForwardState::Paused => "PAUSED",
+            ForwardState::Idle => "IDLE",
+            ForwardState::Stopped => "STOPPED",
         };
         write!(f, "{}", s)
     }
 }
 
+impl ForwardState {
+    pub const ALL: [ForwardState; 7] = [
+        ForwardState::Initializing,
+        ForwardState::Open,
+        ForwardState::Active,
+        ForwardState::Unavailable,
+        ForwardState::Paused,
+        ForwardState::Idle,
+        ForwardState::Stopped,
+    ];
+
+    /// The state with its status table icon, e.g. `🚀 ACTIVE`.
+    pub fn label(&self) -> String {
+        let icon = match self {
+            ForwardState::Initializing => "🔄",
+            ForwardState::Open => "☕",
+            ForwardState::Active => "🚀",
+            ForwardState::Unavailable => "🚫",
+            ForwardState::Paused => "⏸",
+            ForwardState::Idle => "💤",
+            ForwardState::Stopped => "⏹",
+        };
+        format!("{} {}", icon, self)
+    }
+
+    /// Whether a forward in this state may move to `next`. A stopped forward
+    /// only leaves STOPPED by starting again, so late probe results or a
+    /// closing port-forward cannot bring it back.
+    pub fn can_become(&self, next: &ForwardState) -> bool {
+        *self != ForwardState::Stopped || *next == ForwardState::Initializing
+    }
+}
+
+/// A change of a forward's state.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct StateTransition {
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub from: ForwardState,
+    pub to: ForwardState,
+}
+
+/// Published by the retry loop while it waits between connection attempts.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RetryState {
+    /// The attempt about to be made, starting at 2 for the first retry
+    pub attempt: u32,
+    /// `None` when the forward retries forever
+    pub max_attempts: Option<u32>,
+    pub next_attempt_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl RetryState {
+    /// e.g. `retrying in 4s (attempt 3/5)`
+    pub fn describe(&self) -> String {
+        let remaining = (self.next_attempt_at - chrono::Utc::now()).num_milliseconds().max(0);
+        match self.max_attempts {
+            Some(max_attempts) => format!(
+                "retrying in {}s (attempt {}/{})",
+                (remaining + 999) / 1000,
+                self.attempt,
+                max_attempts
+            ),
+            None => format!("retrying in {}s (attempt {})", (remaining + 999) / 1000, self.attempt),
+        }
+    }
+}
+
+/// A forward's target moving to another pod, e.g. during a rollout.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PodChange {
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub from: String,
+    pub to: String,
+}
+
 use std::net::TcpListener;
 
 use std::collections::HashMap;
 use std::sync::LazyLock;
 pub static FORWARD_STATUSES: LazyLock<Mutex<HashMap<String, crate::tui::ForwardStatus>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
 
-fn find_available_port() -> Result<u16> {
+/// Whether `port` is free on every address the local proxies listen on.
+pub fn is_port_free(port: u16) -> bool {
+    crate::http::bind_addresses()
+        .into_iter()
+        .all(|address| match TcpListener::bind((address, port)) {
+            Ok(_) => true,
+            Err(e) => crate::http::bind_error_is_ignorable(address, &e),
+        })
+}
+
+pub fn find_available_port() -> Result<u16> {
     // Bind to port 0 to get an available port from the OS
     let listener = TcpListener::bind("127.0.0.1:0").context("Failed to bind to random port")?;
     let port = listener
@@ -48,23 +146,314 @@ fn find_available_port() -> Result<u16> {
     Ok(port)
 }
 
-pub async fn start_single(
+/// Returns `requested` if it is free. Otherwise, with a `local_port_range`
+/// (e.g. `8080-8099`) the first free port of the range is used, and with
+/// `--auto-port` the next free port above `requested`. The status table is
+/// updated with the port actually chosen.
+fn pick_local_port(key: &str, requested: u16, range: Option<&str>, auto_port: bool) -> Result<u16> {
+    let range = range.map(parse_port_range).transpose()?;
+    let in_range = range.as_ref().is_none_or(|range| range.contains(&requested));
+    if in_range && is_port_free(requested) {
+        return Ok(requested);
+    }
+
+    let candidates = match range {
+        Some(range) => range,
+        None if auto_port => requested.saturating_add(1)..=u16::MAX,
+        None => {
+            return Err(anyhow!(
+                "Local port {} for {} is already in use (pass --auto-port or set local_port_range to pick another)",
+                requested,
+                key
+            ))
+        }
+    };
+    let port = candidates
+        .clone()
+        .find(|port| is_port_free(*port))
+        .ok_or_else(|| anyhow!("No free local port in {:?} for {}", candidates, key))?;
+
+    crate::logger::log_warning(format!(
+        "{} Local port {} is unavailable, using {} for {}",
+        "🔀", requested, port, key
+    ));
+    if let Some(entry) = FORWARD_STATUSES.lock().unwrap().get_mut(key) {
+        entry.local_port = port;
+    }
+    Ok(port)
+}
+
+/// Parses `start-end` into an inclusive port range.
+pub(crate) fn parse_port_range(range: &str) -> Result<std::ops::RangeInclusive<u16>> {
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| anyhow!("Invalid local_port_range '{}', expected start-end", range))?;
+    let start: u16 = start.trim().parse().context(format!("Invalid port in range '{}'", range))?;
+    let end: u16 = end.trim().parse().context(format!("Invalid port in range '{}'", range))?;
+    if start > end {
+        return Err(anyhow!("Invalid local_port_range '{}': start is above end", range));
+    }
+    Ok(start..=end)
+}
+
+/// Commands accepted by a running forward, e.g. from the control API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForwardCommand {
+    Restart,
+    /// Drop the cluster connection but keep the local port
+    Pause,
+    Resume,
+    Stop,
+    /// Disconnect until the next request, after `idle_timeout`
+    Idle,
+}
+
+/// Options shared by every forward, set once from the command line.
+#[derive(Clone, Debug, Default)]
+pub struct ForwardOptions {
+    pub show_liveness: bool,
+    pub requests_log_file: Option<std::path::PathBuf>,
+    pub requests_log_verbosity: u8,
+    /// Move to the next free local port when the requested one is taken
+    pub auto_port: bool,
+    /// Serve every local proxy over HTTPS unless the forward sets its own `tls`
+    pub tls: Option<crate::config::TlsConfig>,
+    /// CA bundle for HTTPS upstreams that do not set their own
+    pub upstream_ca: Option<std::path::PathBuf>,
+    /// Accept any certificate from HTTPS upstreams that do not set `skip_verify`
+    pub upstream_tls_skip_verify: bool,
+    /// Failure policy of forwards that do not set their own `on_failure`
+    pub on_failure: Option<FailurePolicy>,
+}
+
+static FORWARD_OPTIONS: OnceLock<ForwardOptions> = OnceLock::new();
+
+/// Why kpf exits, once a forward's `on_failure` policy asked it to.
+static FAILURE_EXIT: OnceLock<String> = OnceLock::new();
+static FAILURE_EXIT_REQUESTED: LazyLock<tokio::sync::Notify> = LazyLock::new(tokio::sync::Notify::new);
+
+/// Sets the options of every forward started from now on; the first call wins.
+pub fn set_forward_options(options: ForwardOptions) {
+    let _ = FORWARD_OPTIONS.set(options);
+}
+
+pub(crate) fn forward_options() -> ForwardOptions {
+    FORWARD_OPTIONS.get().cloned().unwrap_or_default()
+}
+
+/// A forward that was never added, or was removed since.
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown forward: {0}")]
+pub struct UnknownForwardError(pub String);
+
+/// A forward that cannot be added as given: an invalid config, or a release
+/// or `*` port whose services cannot be listed.
+#[derive(Debug, thiserror::Error)]
+#[error("{0:#}")]
+pub struct InvalidForwardError(pub anyhow::Error);
+
+struct ForwardHandle {
+    forward: ForwardConfig,
+    commands: mpsc::UnboundedSender<ForwardCommand>,
+    task: JoinHandle<()>,
+}
+
+static FORWARD_HANDLES: LazyLock<Mutex<HashMap<String, ForwardHandle>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Everything the port-forward loop needs to (re)connect a single forward.
+#[derive(Clone)]
+struct ForwardTarget {
+    key: String,
     resource_type: String,
     resource_name: String,
     resource_port: u16,
     namespace: String,
+    context: Option<String>,
+    /// Label selector picking the pod instead of `resource_name`
+    selector: Option<String>,
     local_port: u16,
-    _verbose: u8,
-    timeout: Option<u64>,
-    liveness_probe: Option<String>,
-    show_liveness: bool,
-    requests_log_file: Option<std::path::PathBuf>,
-    requests_log_verbosity: u8,
+    internal_port: u16,
+    probe: Option<crate::probe::Probe>,
+    upstream: crate::http::Upstream,
+    protected: bool,
+    on_failure: Option<FailurePolicy>,
+    /// Opened in the browser the first time the forward is ready
+    open_url: Option<String>,
+}
+
+/// Builds the client that reaches the forwarded port, over TLS when the
+/// forward sets `upstream_scheme: https`, or through the API server in
+/// apiserver-proxy mode.
+fn upstream(
+    forward: &ForwardConfig,
+    resource_type: &str,
+    resource_name: &str,
+    resource_port: u16,
+    options: &ForwardOptions,
+) -> Result<crate::http::Upstream> {
+    if forward.mode == Some(ForwardMode::ApiserverProxy) {
+        // The API server makes the TLS connection to an https port itself
+        return Ok(crate::http::Upstream::api_server_proxy(crate::k8s::api_server_proxy_path(
+            resource_type,
+            resource_name,
+            resource_port,
+            forward.namespace.as_deref().unwrap_or("default"),
+            forward.upstream_scheme == Some(UpstreamScheme::Https),
+        )));
+    }
+    if forward.upstream_scheme != Some(UpstreamScheme::Https) {
+        return Ok(crate::http::Upstream::default());
+    }
+
+    let tls = forward.upstream_tls.clone().unwrap_or_default();
+    let ca = tls.ca.or_else(|| options.upstream_ca.clone());
+    let skip_verify = tls.skip_verify.unwrap_or(options.upstream_tls_skip_verify);
+    let pin = crate::tls::CertificatePin {
+        key: crate::tls::pin_key(
+            crate::k8s::effective_context(forward.context.as_deref()).as_deref(),
+            forward.namespace.as_deref().unwrap_or("default"),
+            resource_type,
+            resource_name,
+            resource_port,
+        ),
+        refuse_changed: tls.refuse_changed_cert.unwrap_or(false),
+    };
+    let config = crate::tls::client_config(ca.as_deref(), skip_verify, Some(pin))?;
+    let server_name = tls.server_name.unwrap_or_else(|| resource_name.to_string());
+    Ok(crate::http::Upstream::https(config, server_name))
+}
+
+/// A forward's key, `type/name:port`, or the key it runs under once started.
+pub fn forward_key(forward: &ForwardConfig) -> Result<String> {
+    if let Some(key) = &forward.key {
+        return Ok(key.clone());
+    }
+    let (resource_type, resource_name, resource_port) = parse_resource(&forward.resource)
+        .context(format!("Failed to parse resource: {}", forward.resource))?;
+    Ok(format!("{}/{}:{}", resource_type, resource_name, resource_port))
+}
+
+/// Whether a forward is ready to serve: ACTIVE, or OPEN for UDP forwards,
+/// which have no liveness probe to pass.
+pub(crate) fn is_ready(key: &str) -> bool {
+    let probed = FORWARD_HANDLES
+        .lock()
+        .unwrap()
+        .get(key)
+        .map(|handle| crate::probe::Probe::from_forward(&handle.forward).is_some());
+    let state = FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .get(key)
+        .map(|status| status.state.clone());
+    match state {
+        Some(ForwardState::Active) => true,
+        Some(ForwardState::Open) => probed == Some(false),
+        _ => false,
+    }
+}
+
+/// Holds a forward back until everything in its `depends_on` is ready.
+/// Returns false if the forward was stopped while waiting.
+async fn wait_for_dependencies(
+    key: &str,
+    forward: &ForwardConfig,
+    commands: &mut mpsc::UnboundedReceiver<ForwardCommand>,
+) -> Result<bool> {
+    let dependencies = {
+        let handles = FORWARD_HANDLES.lock().unwrap();
+        forward
+            .dependency_keys()?
+            .iter()
+            .map(|dependency| resolve_reference(&handles, dependency, key))
+            .collect::<Result<Vec<String>>>()?
+    };
+    let mut logged = false;
+    loop {
+        let waiting_for: Vec<String> = dependencies
+            .iter()
+            // Idle dependencies connect as soon as they are used
+            .filter(|dependency| !is_ready(dependency) && !crate::idle::is_idle(dependency))
+            .cloned()
+            .collect();
+        if let Some(entry) = FORWARD_STATUSES.lock().unwrap().get_mut(key) {
+            entry.waiting_for = waiting_for.clone();
+        }
+        if waiting_for.is_empty() {
+            if logged {
+                crate::logger::log_info(format!("{} Dependencies of {} are ready", "▶", key));
+            }
+            return Ok(true);
+        }
+        if !logged {
+            crate::logger::log_info(format!(
+                "{} {} waits for {}",
+                "⏳",
+                key,
+                waiting_for.join(", ")
+            ));
+            logged = true;
+        }
+
+        tokio::select! {
+            _ = sleep(Duration::from_millis(250)) => {}
+            command = commands.recv() => {
+                if matches!(command, Some(ForwardCommand::Stop) | None) {
+                    if let Some(entry) = FORWARD_STATUSES.lock().unwrap().get_mut(key) {
+                        entry.waiting_for.clear();
+                    }
+                    set_state(key, ForwardState::Stopped);
+                    return Ok(false);
+                }
+            }
+        }
+    }
+}
+
+pub async fn start_single(
+    forward: ForwardConfig,
+    mut commands: mpsc::UnboundedReceiver<ForwardCommand>,
 ) -> Result<()> {
-    let (tx, mut rx) = mpsc::channel::<bool>(10);
+    if forward.depends_on.is_some()
+        && !wait_for_dependencies(&forward_key(&forward)?, &forward, &mut commands).await?
+    {
+        return Ok(());
+    }
+    if forward.protocol == Some(Protocol::Udp) {
+        if crate::mock::is_mocking() {
+            crate::logger::log_warning(format!(
+                "{} UDP forward {} is not started in mock mode",
+                "🎭", forward.resource
+            ));
+            return Ok(());
+        }
+        return crate::udp::start_udp_forward(forward, commands).await;
+    }
+
+    let options = forward_options();
+    let (resource_type, resource_name, resource_port) = parse_resource(&forward.resource)
+        .context(format!("Failed to parse resource: {}", forward.resource))?;
+    let key = forward_key(&forward)?;
+    let local_port = pick_local_port(
+        &key,
+        forward.local_port().unwrap_or(resource_port),
+        forward.local_port_range.as_deref(),
+        options.auto_port,
+    )?;
+    let tls = forward
+        .tls
+        .as_ref()
+        .or(options.tls.as_ref())
+        .map(crate::tls::server_config)
+        .transpose()
+        .context(format!("Failed to load TLS settings for {}", key))?;
+    let upstream = upstream(&forward, &resource_type, &resource_name, resource_port, &options)
+        .context(format!("Failed to set up upstream TLS for {}", key))?;
     let port_forward_status = Arc::new(Mutex::new(false));
     let child_handle = std::sync::Arc::new(tokio::sync::Mutex::new(None));
     let port_forward_status_clone = port_forward_status.clone();
+    let protected = crate::k8s::is_protected_context(forward.context.as_deref());
 
     // Find an available port for the internal port-forward
     let internal_port = find_available_port()?;
@@ -74,229 +463,630 @@ pub async fn start_single(
     ));
 
     // Start HTTP server on the user-specified port
-    let resource_prefix = format!("{}/{}:{}", resource_type, resource_name, resource_port);
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let resource_prefix = key.clone();
+    let serves_tls = tls.is_some();
+    let http_upstream = upstream.clone();
     let http_handle = tokio::spawn(async move {
         start_http_server(
             local_port,
             internal_port,
             port_forward_status_clone,
-            show_liveness,
+            options.show_liveness,
             resource_prefix,
-            requests_log_file.clone(),
-            requests_log_verbosity,
+            options.requests_log_file.clone(),
+            options.requests_log_verbosity,
+            tls,
+            http_upstream,
+            shutdown_rx,
         )
         .await
     });
 
-    // Start port-forward manager
-    let k8s_handle = tokio::spawn(async move {
-        let mut attempt = 0;
+    let synthetic_checks = forward.synthetic_checks.clone();
+    if serves_tls && synthetic_checks.is_some() {
+        crate::logger::log_warning(format!(
+            "{} Synthetic checks are not supported on HTTPS forwards; skipping them for {}",
+            "🩺", key
+        ));
+    }
+    let synthetic_handle = synthetic_checks.filter(|_| !serves_tls).map(|checks| {
+        tokio::spawn(crate::synthetic::run_checks(key.clone(), local_port, checks))
+    });
+
+    let namespace = forward.namespace.clone().unwrap_or_else(|| "default".to_string());
+    let events_handle = crate::events::spawn(crate::events::EventTarget {
+        key: key.clone(),
+        resource_type: resource_type.clone(),
+        resource_name: resource_name.clone(),
+        namespace: namespace.clone(),
+        context: forward.context.clone(),
+    });
+    let pod_logs_handle = (forward.tail_logs == Some(true) && !crate::mock::is_mocking())
+        .then(|| crate::pod_logs::spawn(key.clone(), namespace.clone(), forward.context.clone()));
+
+    let idle_timeout = forward.idle_timeout.map(|minutes| Duration::from_secs(minutes * 60));
+    let mock = crate::mock::is_mocking();
+    let lazy = forward.lazy.unwrap_or(false) || mock;
+    let probe = crate::probe::Probe::from_forward(&forward);
+    let open_url = forward.open.as_ref().and_then(OpenSetting::path).map(|path| {
+        format!("{}://localhost:{}{}", if serves_tls { "https" } else { "http" }, local_port, path)
+    });
+    let target = ForwardTarget {
+        key: key.clone(),
+        resource_type,
+        resource_name,
+        resource_port,
+        namespace,
+        context: forward.context,
+        selector: forward.selector,
+        local_port,
+        internal_port,
+        probe,
+        upstream,
+        protected,
+        on_failure: forward.on_failure.or(forward_options().on_failure),
+        open_url,
+    };
+
+    // Start port-forward manager and supervise it until stopped or it gives up.
+    // Lazy forwards start out idle, so their first request connects them.
+    // Mock mode only connects for requests without a recording, and only with
+    // --mock-passthrough.
+    let mut k8s_handle = if lazy {
+        if mock {
+            crate::logger::log_info(format!("{} {} serves recorded responses", "🎭", key));
+        } else {
+            crate::logger::log_info(format!("{} {} connects on its first request", "💤", key));
+        }
+        set_state(&key, ForwardState::Idle);
+        // Opening the page is the request that connects the forward
+        if let Some(url) = &target.open_url {
+            crate::browser::open_forward(&key, url);
+        }
+        tokio::spawn(async {})
+    } else {
+        tokio::spawn(port_forward_loop(
+            target.clone(),
+            port_forward_status.clone(),
+            child_handle.clone(),
+        ))
+    };
+
+    // While paused or idle the port-forward task has finished and must not be polled
+    let mut paused = lazy;
+    let mut idle = lazy;
+    let mut idle_check = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    idle_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    crate::idle::touch(&key);
+    loop {
+        let command = tokio::select! {
+            _ = &mut k8s_handle, if !paused => break,
+            command = commands.recv() => command,
+            _ = idle_check.tick(), if !paused => match idle_timeout {
+                Some(timeout) if crate::idle::idle_for(&key) >= timeout => Some(ForwardCommand::Idle),
+                _ => continue,
+            },
+        };
+        // Pausing an idle forward keeps requests from waking it
+        if command == Some(ForwardCommand::Pause) && paused && !idle {
+            continue;
+        }
+        if command == Some(ForwardCommand::Resume) && !paused {
+            continue;
+        }
+        if !paused {
+            k8s_handle.abort();
+            let _ = (&mut k8s_handle).await;
+            if let Some(mut child) = child_handle.lock().await.take() {
+                let _ = child.kill().await;
+            }
+            *port_forward_status.lock().unwrap() = false;
+        }
+
+        match command {
+            Some(ForwardCommand::Pause) => {
+                crate::logger::log_warning(format!("{} Paused {}", "⏸", key));
+                set_state(&key, ForwardState::Paused);
+                set_retry(&key, None);
+                paused = true;
+                idle = false;
+            }
+            Some(ForwardCommand::Idle) => {
+                crate::logger::log_info(format!(
+                    "{} {} idle for {} min; disconnected until the next request",
+                    "💤",
+                    key,
+                    idle_timeout.unwrap_or_default().as_secs() / 60
+                ));
+                set_state(&key, ForwardState::Idle);
+                set_retry(&key, None);
+                paused = true;
+                idle = true;
+            }
+            Some(command @ (ForwardCommand::Restart | ForwardCommand::Resume)) => {
+                if command == ForwardCommand::Restart {
+                    crate::logger::log_warning(format!("{} Restarting {}", "🔁", key));
+                    mark_restart(&key);
+                } else if idle {
+                    crate::logger::log_info(format!("{} Connecting idle {}", "💤", key));
+                } else {
+                    crate::logger::log_info(format!("{} Resuming {}", "▶", key));
+                }
+                set_state(&key, ForwardState::Initializing);
+                paused = false;
+                idle = false;
+                crate::idle::touch(&key);
+                k8s_handle = tokio::spawn(port_forward_loop(
+                    target.clone(),
+                    port_forward_status.clone(),
+                    child_handle.clone(),
+                ));
+            }
+            Some(ForwardCommand::Stop) | None => break,
+        }
+    }
+
+    crate::logger::log_warning(format!("{} Shutting down {}...", "🛑", key));
+    set_state(&key, ForwardState::Stopped);
+    set_retry(&key, None);
+    if let Some(synthetic_handle) = synthetic_handle {
+        synthetic_handle.abort();
+    }
+    if let Some(events_handle) = events_handle {
+        events_handle.abort();
+    }
+    if let Some(pod_logs_handle) = pod_logs_handle {
+        pod_logs_handle.abort();
+    }
+
+    // Stop the HTTP server so the local port is released
+    let _ = shutdown_tx.send(());
+    let _ = http_handle.await;
 
+    Ok(())
+}
+
+/// Waits until the forward registered under `key` reaches `state`. Returns
+/// false if it does not within `timeout`.
+pub async fn wait_for_state(key: &str, state: ForwardState, timeout: Duration) -> bool {
+    let reached = async {
         loop {
-            attempt += 1;
-            if attempt > 1 {
-                sleep(Duration::from_millis(RETRY_DELAY_MS)).await;
+            let current = FORWARD_STATUSES
+                .lock()
+                .unwrap()
+                .get(key)
+                .map(|status| status.state.clone());
+            if current.as_ref() == Some(&state) {
+                return;
             }
+            sleep(Duration::from_millis(250)).await;
+        }
+    };
+    tokio::time::timeout(timeout, reached).await.is_ok()
+}
+
+pub(crate) fn set_state(key: &str, state: ForwardState) {
+    let mut statuses = FORWARD_STATUSES.lock().unwrap();
+    if let Some(entry) = statuses.get_mut(key) {
+        transition(entry, state);
+    }
+}
 
-            match create_port_forward(
+/// Moves a forward to `state`, recording the change in its history. Returns
+/// false when it already was in `state` or may not move there.
+pub(crate) fn transition(entry: &mut crate::tui::ForwardStatus, state: ForwardState) -> bool {
+    if entry.state == state || !entry.state.can_become(&state) {
+        return false;
+    }
+    let from = std::mem::replace(&mut entry.state, state.clone());
+    entry.transitions.push(StateTransition {
+        at: chrono::Utc::now(),
+        from,
+        to: state,
+    });
+    if entry.transitions.len() > MAX_RECONNECT_HISTORY {
+        entry.transitions.remove(0);
+    }
+    crate::metrics::record_state_transition(&entry.resource);
+    true
+}
+
+/// Counts a requested restart; the status table shows it until the forward
+/// connects again.
+pub(crate) fn mark_restart(key: &str) {
+    if let Some(entry) = FORWARD_STATUSES.lock().unwrap().get_mut(key) {
+        entry.restarts += 1;
+        entry.restarting = true;
+        entry.retry = None;
+    }
+}
+
+/// Records that the forward is connected to `pod`. Returns the message to
+/// log: the end of a requested restart, or the target moving to another pod
+/// on its own, e.g. because a rollout replaced the previous one.
+pub(crate) fn land_on_pod(
+    entry: &mut crate::tui::ForwardStatus,
+    pod: &crate::k8s::TargetPod,
+) -> Option<(crate::tui::LogLevel, String)> {
+    let restarting = std::mem::take(&mut entry.restarting);
+    let previous = entry.target_pod.replace(pod.name.clone());
+    entry.target_node = pod.node.clone();
+    let moved = previous.filter(|previous| *previous != pod.name);
+    if let Some(from) = &moved {
+        entry.pod_changes.push(PodChange {
+            at: chrono::Utc::now(),
+            from: from.clone(),
+            to: pod.name.clone(),
+        });
+        if entry.pod_changes.len() > MAX_RECONNECT_HISTORY {
+            entry.pod_changes.remove(0);
+        }
+    }
+
+    use crate::tui::LogLevel;
+    match (restarting, moved) {
+        (true, Some(from)) => Some((
+            LogLevel::Success,
+            format!(
+                "{} Restarted {} (restart #{}): pod {} → {}",
+                "🔁", entry.resource, entry.restarts, from, pod.name
+            ),
+        )),
+        (true, None) => Some((
+            LogLevel::Success,
+            format!(
+                "{} Restarted {} (restart #{}) on pod {}",
+                "🔁", entry.resource, entry.restarts, pod.name
+            ),
+        )),
+        (false, Some(from)) => Some((
+            LogLevel::Warning,
+            format!("{} {} moved to another pod: {} → {}", "🔀", entry.resource, from, pod.name),
+        )),
+        (false, None) => None,
+    }
+}
+
+fn set_retry(key: &str, retry: Option<RetryState>) {
+    if let Some(entry) = FORWARD_STATUSES.lock().unwrap().get_mut(key) {
+        entry.retry = retry;
+    }
+}
+
+async fn port_forward_loop(
+    target: ForwardTarget,
+    port_forward_status: Arc<Mutex<bool>>,
+    child_handle: Arc<tokio::sync::Mutex<Option<tokio::process::Child>>>,
+) {
+    let ForwardTarget {
+        key,
+        resource_type,
+        resource_name,
+        resource_port,
+        namespace,
+        context,
+        selector,
+        local_port,
+        internal_port,
+        probe,
+        upstream,
+        protected,
+        on_failure,
+        open_url,
+    } = target;
+    let mut attempt = 0;
+    let mut reconnecting = false;
+
+    loop {
+        attempt += 1;
+        if attempt > 1 {
+            set_retry(
+                &key,
+                Some(RetryState {
+                    attempt,
+                    max_attempts: FailurePolicy::max_attempts(on_failure),
+                    next_attempt_at: chrono::Utc::now()
+                        + chrono::Duration::milliseconds(RETRY_DELAY_MS as i64),
+                }),
+            );
+            sleep(Duration::from_millis(RETRY_DELAY_MS)).await;
+        }
+        if reconnecting {
+            crate::reconnect::wait_turn(&key).await;
+        }
+        reconnecting = true;
+
+        // Services and selectors are forwarded to a ready pod picked here,
+        // re-resolved on every reconnect, rather than by kubectl, which may
+        // pick a terminating one. Through the API server proxy, the API
+        // server picks the pod.
+        let permissions = crate::k8s::required_permissions(&resource_type, upstream.api_server_proxy_path().is_some());
+        let connected = if let Err(e) = crate::k8s::check_access(&permissions, &namespace, context.as_deref()).await {
+            Err(e)
+        } else if let Some(path) = upstream.api_server_proxy_path() {
+            crate::logger::log_info(format!("{} {} → API server proxy {}", "🎯", key, path));
+            crate::k8s::create_api_server_proxy(
                 &resource_type,
                 &resource_name,
-                resource_port,
+                path,
                 internal_port,
                 &namespace,
+                context.as_deref(),
                 child_handle.clone(),
             )
             .await
+            .map(|pf| (pf.boxed(), None))
+        } else {
+            match crate::k8s::resolve_container_port(
+                &resource_type,
+                &resource_name,
+                resource_port,
+                "tcp",
+                selector.as_deref(),
+                &namespace,
+                context.as_deref(),
+            )
+            .await
             {
-                Ok(pf) => {
-                    {
-                        let mut status = port_forward_status.lock().unwrap();
-                        *status = true;
+                Ok((pod, container_port)) => {
+                    if resource_type != "pod" || selector.is_some() {
                         crate::logger::log_info(format!(
-                            "{} Port-forward status set to ACTIVE (PID: {})",
-                            "🔄",
-                            std::process::id()
+                            "{} {} → pod {} port {}",
+                            "🎯", key, pod.name, container_port
                         ));
-                        {
-                            use crate::tui::ForwardStatus;
-                            let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                            statuses.insert(format!("{}/{}", resource_type, resource_name), ForwardStatus {
-                                resource: format!("{}/{}", resource_type, resource_name),
-                                local_port,
-                                state: ForwardState::Open,
-                                last_probe: None,
-                            });
-                        }
                     }
+                    create_port_forward(
+                        "pod",
+                        &pod.name,
+                        container_port,
+                        internal_port,
+                        &namespace,
+                        context.as_deref(),
+                        child_handle.clone(),
+                    )
+                    .await
+                    .map(|pf| (pf.boxed(), Some(pod)))
+                }
+                Err(e) => Err(e),
+            }
+        };
 
+        match connected {
+            Ok((pf, target_pod)) => {
+                let exec_target = target_pod.as_ref().map_or_else(|| resource_name.clone(), |pod| pod.name.clone());
+                {
+                    let mut status = port_forward_status.lock().unwrap();
+                    *status = true;
                     crate::logger::log_info(format!(
-                        "{} HTTP proxy listening on port {} and forwarding to internal port {}",
-                        "🔄", local_port, internal_port
+                        "{} Port-forward status set to ACTIVE (PID: {})",
+                        "🔄",
+                        std::process::id()
                     ));
+                    let landed = {
+                        use crate::tui::ForwardStatus;
+                        let mut statuses = FORWARD_STATUSES.lock().unwrap();
+                        let entry = statuses.entry(key.clone()).or_insert_with(|| ForwardStatus {
+                            resource: key.clone(),
+                            namespace: namespace.clone(),
+                            local_port,
+                            protected,
+                            context: crate::k8s::effective_context(context.as_deref()).unwrap_or_default(),
+                            ..Default::default()
+                        });
+                        let now = chrono::Utc::now();
+                        if entry.connected_at.is_some() {
+                            crate::metrics::record_reconnect(&entry.resource);
+                            entry.reconnects.push(now);
+                            if entry.reconnects.len() > MAX_RECONNECT_HISTORY {
+                                entry.reconnects.remove(0);
+                            }
+                        }
+                        entry.connected_at = Some(now);
+                        entry.retry = None;
+                        // Without a probe to pass, listening is all there is to wait for
+                        let connected = if probe.is_some() { ForwardState::Open } else { ForwardState::Active };
+                        transition(entry, connected);
+                        target_pod.as_ref().and_then(|pod| land_on_pod(entry, pod))
+                    };
+                    if let Some((level, message)) = landed {
+                        crate::logger::log(level, message);
+                    }
+                }
+
+                crate::logger::log_info(format!(
+                    "{} HTTP proxy listening on port {} and forwarding to internal port {}",
+                    "🔄", local_port, internal_port
+                ));
 
+                // Dropping the port-forward future kills kubectl, so a failed
+                // probe can restart the connection without waiting for it to exit
+                let mut pf = Box::pin(pf);
+                let probe_target = crate::probe::ProbeTarget {
+                    internal_port,
+                    namespace: namespace.clone(),
+                    context: context.clone(),
+                    exec_target,
+                    upstream: upstream.clone(),
+                };
+                if let Some(probe) = &probe {
                     crate::logger::log_info(format!(
                         "{} Port-forward active, waiting for first successful probe...",
                         "🔄"
                     ));
-                    if let Some(probe_path) = liveness_probe.clone() {
-                        use hyper::{Body, Client, Request, StatusCode};
-                        let client = Client::new();
-                        let per_request_timeout = std::time::Duration::from_secs(timeout.unwrap_or(1));
-                        let overall_probe_timeout = std::time::Duration::from_secs(10);
-                        let probe_success = match tokio::time::timeout(overall_probe_timeout, async {
-                            let mut probe_fail_count = 0;
-                            loop {
-                                sleep(Duration::from_secs(2)).await;
-                                let url = format!("http://127.0.0.1:{}{}", internal_port, probe_path);
-                                let req = Request::get(url)
-                                    .header("x-internal-probe", "true")
-                                    .body(Body::empty())
-                                    .unwrap();
-                                let res = tokio::time::timeout(per_request_timeout, client.request(req)).await;
-                                match res {
-                                    Ok(Ok(response)) => {
-                                        if response.status() == StatusCode::OK {
-                                            crate::logger::log_info("Successful probe received.".to_string());
-                                            {
-                                                let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                                                let key = format!("{}/{}", resource_type, resource_name);
-                                                statuses.entry(key).and_modify(|entry| {
-                                                    entry.last_probe = Some(chrono::Utc::now().to_rfc3339());
-                                                    entry.state = ForwardState::Active;
-                                                });
-                                            }
-                                            return true;
-                                        } else if response.status() == StatusCode::SERVICE_UNAVAILABLE {
-                                            crate::logger::log_warning("Received 503 from probe. Marking resource as UNAVAILABLE.".to_string());
-                                            {
-                                                let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                                                let key = format!("{}/{}", resource_type, resource_name);
-                                                statuses.entry(key).and_modify(|entry| {
-                                                    entry.state = ForwardState::Unavailable;
-                                                });
-                                            }
-                                            return false;
-                                        } else {
-                                            probe_fail_count += 1;
-                                            crate::logger::log_warning(format!("Probe returned non-OK status: {}", response.status()));
-                                            {
-                                                let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                                                let key = format!("{}/{}", resource_type, resource_name);
-                                                statuses.entry(key).and_modify(|entry| {
-                                                    entry.state = ForwardState::Unavailable;
-                                                });
-                                            }
-                                        }
-                                    },
-                                    _ => {
-                                        probe_fail_count += 1;
-                                        crate::logger::log_warning("Probe failed or timed out.".to_string());
-                                        {
-                                            let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                                            let key = format!("{}/{}", resource_type, resource_name);
-                                            statuses.entry(key).and_modify(|entry| {
-                                                entry.state = ForwardState::Unavailable;
-                                            });
-                                        }
-                                    }
-                                }
-                                if probe_fail_count > 2 {
-                                    {
-                                        let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                                        let key = format!("{}/{}", resource_type, resource_name);
-                                        statuses.entry(key).and_modify(|entry| {
-                                            entry.state = ForwardState::Unavailable;
-                                        });
-                                    }
-                                    crate::logger::log_error("Probe failed more than 2 times. Restarting port-forward.".to_string());
-                                    break;
-                                }
-                            }
-                            false
-                        }).await {
-                            Ok(success) => success,
-                            Err(_) => {
-                                {
-                                    let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                                    let key = format!("{}/{}", resource_type, resource_name);
-                                    statuses.entry(key).and_modify(|entry| {
-                                        entry.state = ForwardState::Unavailable;
-                                    });
-                                }
-                                crate::logger::log_error("Probe overall timeout reached. Restarting port-forward.".to_string());
-                                false
-                            }
-                        };
-                        if !probe_success {
-                            let _ = pf.await;
-                            continue;
+                    if !probe.wait_until_live(&key, &probe_target).await {
+                        drop(pf);
+                        *port_forward_status.lock().unwrap() = false;
+                        if on_failure == Some(FailurePolicy::Exit) {
+                            exit_on_failure(format!("{} never passed its liveness probe", key));
+                            break;
                         }
+                        continue;
                     }
-                    crate::logger::log_success(format!(
-                        "{} Port-forward ready to accept connections",
-                        "✅"
-                    ));
-                    let result = pf.await;
+                }
+                crate::logger::log_success(format!(
+                    "{} Port-forward ready to accept connections",
+                    "✅"
+                ));
+                match &target_pod {
+                    Some(pod) => crate::notify::up(&key, format!("connected to pod {}", pod.name)),
+                    None => crate::notify::up(&key, "connected through the API server proxy".to_string()),
+                }
+                crate::output::forward_ready(&key, local_port, target_pod.as_ref().map(|pod| pod.name.as_str()));
+                if let Some(url) = &open_url {
+                    crate::browser::open_forward(&key, url);
+                }
+                let result = match &probe {
+                    Some(probe) => tokio::select! {
+                        result = &mut pf => result,
+                        _ = probe.monitor(&key, &probe_target) => {
+                            drop(pf);
+                            Err(anyhow!("liveness probe failed {} times in a row", probe.failure_threshold))
+                        }
+                    },
+                    None => pf.await,
+                };
 
+                {
+                    let mut status = port_forward_status.lock().unwrap();
+                    *status = false;
+                    crate::logger::log_warning(format!(
+                        "{} Port-forward status set to INACTIVE (PID: {})",
+                        "🔄",
+                        std::process::id()
+                    ));
                     {
-                        let mut status = port_forward_status.lock().unwrap();
-                        *status = false;
-                        crate::logger::log_warning(format!(
-                            "{} Port-forward status set to INACTIVE (PID: {})",
-                            "🔄",
-                            std::process::id()
-                        ));
-                        {
-                            let mut statuses = FORWARD_STATUSES.lock().unwrap();
-                            let key = format!("{}/{}", resource_type, resource_name);
-                            statuses.entry(key).and_modify(|entry| entry.state = ForwardState::Unavailable);
-                        }
+                        let mut statuses = FORWARD_STATUSES.lock().unwrap();
+                        statuses.entry(key.clone()).and_modify(|entry| {
+                            transition(entry, ForwardState::Unavailable);
+                        });
                     }
+                }
 
-                    if let Err(e) = result {
+                let exits = on_failure == Some(FailurePolicy::Exit);
+                let error = match result {
+                    Err(e) => {
                         crate::logger::log_error(format!("Port-forward failed: {}", e));
+                        e.to_string()
                     }
-
-                    // Reset attempt counter on successful connection
-                    attempt = 0;
+                    Ok(()) => "the port-forward closed".to_string(),
+                };
+                crate::notify::down(&key, error.clone());
+                crate::output::forward_failed(&key, &error, !exits);
+                if exits {
+                    exit_on_failure(format!("{} lost its connection: {}", key, error));
+                    break;
                 }
-                Err(e) => {
-                    crate::logger::log_error(format!("Failed to create port-forward: {}", e));
 
-                    if attempt >= MAX_RETRY_ATTEMPTS {
-                        crate::logger::log_error(format!(
-                            "Max retry attempts ({}) reached, giving up",
-                            MAX_RETRY_ATTEMPTS
-                        ));
-                        break;
+                // Reset attempt counter on successful connection
+                attempt = 0;
+            }
+            Err(e) => {
+                crate::logger::log_error(format!("Failed to create port-forward: {}", e));
+                let max_attempts = FailurePolicy::max_attempts(on_failure);
+                let retrying = max_attempts.is_none_or(|max_attempts| attempt < max_attempts);
+                crate::output::forward_failed(&key, &e.to_string(), retrying);
+
+                if !retrying {
+                    set_retry(&key, None);
+                    crate::logger::log_error(format!("Max retry attempts ({}) reached, giving up", attempt));
+                    crate::notify::gave_up(
+                        &key,
+                        format!("{} connection attempts failed, the last with: {}", attempt, e),
+                    );
+                    if on_failure.is_some() {
+                        exit_on_failure(format!("{} failed to connect {} times in a row: {}", key, attempt, e));
                     }
+                    break;
                 }
             }
         }
+    }
+}
 
-        // Signal HTTP server to shut down
-        let _ = tx.send(true).await;
-    });
+/// Ends kpf because of a forward's `on_failure` policy. The first reason wins.
+fn exit_on_failure(reason: String) {
+    crate::logger::log_error(format!("{} Exiting: {}", "🛑", reason));
+    if FAILURE_EXIT.set(reason).is_ok() {
+        FAILURE_EXIT_REQUESTED.notify_one();
+    }
+}
+
+/// Why kpf should exit, once an `on_failure` policy asked it to.
+pub fn failure_exit() -> Option<String> {
+    FAILURE_EXIT.get().cloned()
+}
 
-    // Wait for shutdown signal
-    if (rx.recv().await).is_some() {
-        crate::logger::log_warning(format!("{} Shutting down...", "🛑"));
+/// Resolves once an `on_failure` policy asks kpf to exit, with the reason.
+pub async fn failure_exit_requested() -> String {
+    loop {
+        if let Some(reason) = failure_exit() {
+            return reason;
+        }
+        FAILURE_EXIT_REQUESTED.notified().await;
     }
+}
 
-    // Wait for tasks to complete
-    let _ = tokio::join!(http_handle, k8s_handle);
+/// Replaces every `service/name:*` forward with one forward per service port,
+/// and every `release` forward with one per port of the release's services.
+/// Expanded forwards get a local port automatically: the service port itself
+/// when it is free, otherwise one picked by the OS.
+async fn expand_forwards(forwards: Vec<ForwardConfig>) -> Result<Vec<ForwardConfig>> {
+    let mut taken: Vec<u16> = forwards.iter().filter_map(|f| f.local_port()).collect();
+    let mut expanded = Vec::new();
 
-    Ok(())
+    for forward in forwards {
+        let ns = forward.namespace.clone().unwrap_or_else(|| "default".to_string());
+        let resources = if let Some(release) = &forward.release {
+            let resources = crate::k8s::release_services(release, &ns, forward.context.as_deref()).await?;
+            crate::logger::log_info(format!(
+                "{} Helm release {} has {} service ports in {}",
+                "⎈",
+                release,
+                resources.len(),
+                ns
+            ));
+            resources
+        } else if is_all_ports(&forward.resource) {
+            expand_all_ports(&forward.resource, &ns, forward.context.as_deref())
+                .await
+                .context(format!("Failed to expand ports of {}", forward.resource))?
+        } else {
+            expanded.push(forward);
+            continue;
+        };
+
+        for resource in resources {
+            let (_, _, resource_port) = parse_resource(&resource)?;
+            let local_port = if !taken.contains(&resource_port) && is_port_free(resource_port) {
+                resource_port
+            } else {
+                find_available_port()?
+            };
+            taken.push(local_port);
+
+            crate::logger::log_info(format!(
+                "{} Assigned local port {} to {}",
+                "🔌", local_port, resource
+            ));
+
+            expanded.push(ForwardConfig {
+                resource,
+                local_port: Some(LocalPort::Port(local_port)),
+                release: None,
+                ..forward.clone()
+            });
+        }
+    }
+
+    Ok(expanded)
 }
 
-pub async fn start_from_config(
-    config: Config,
-    show_liveness: bool,
-    requests_log_file: Option<std::path::PathBuf>,
-    requests_log_verbosity: u8,
-) -> Result<()> {
-    let verbose = config.verbose.unwrap_or(1);
-    let mut handles = Vec::new();
+/// Starts every forward of `config` in the background and returns once they are
+/// spawned, with the keys they are registered under. The forwards keep running
+/// until stopped through their handles.
+pub async fn start_from_config(config: Config, options: ForwardOptions) -> Result<Vec<String>> {
+    set_forward_options(options);
 
     crate::logger::log_info(format!(
         "{} Starting {} port-forwards from config",
@@ -304,59 +1094,463 @@ pub async fn start_from_config(
         config.forwards.len()
     ));
 
-    {
-        use crate::tui::ForwardStatus;
-        let mut statuses = FORWARD_STATUSES.lock().unwrap();
-        for forward in &config.forwards {
-            let (resource_type, resource_name, resource_port) = crate::k8s::parse_resource(&forward.resource)
-                .expect(&format!("Failed to parse resource: {}", forward.resource));
-            let local_port = forward.local_port.unwrap_or(resource_port);
-            statuses.insert(
-                format!("{}/{}", resource_type, resource_name),
-                ForwardStatus {
-                    resource: format!("{}/{}", resource_type, resource_name),
-                    local_port,
-                    state: ForwardState::Initializing,
-                    last_probe: None,
-                },
-            );
+    if let Some(notifications) = &config.notifications {
+        crate::notify::start(notifications)?;
+    }
+
+    let mut forwards = config.forwards;
+    for forward in &mut forwards {
+        if forward.jump_host.is_none() {
+            forward.jump_host = config.jump_host.clone();
         }
     }
-    let requests_log_file_arc = std::sync::Arc::new(requests_log_file.clone());
+    add_forwards(forwards).await
+}
 
-    for forward in config.forwards {
-        let requests_log_file_clone = requests_log_file_arc.clone();
-        let (resource_type, resource_name, resource_port) = parse_resource(&forward.resource)
-            .context(format!("Failed to parse resource: {}", forward.resource))?;
+/// Expands and spawns `forwards`, returning the keys they are registered under.
+pub async fn add_forwards(forwards: Vec<ForwardConfig>) -> Result<Vec<String>> {
+    let forwards: Vec<ForwardConfig> = forwards.into_iter().map(resolve_namespace).collect();
+    for forward in &forwards {
+        forward.validate().map_err(InvalidForwardError)?;
+        crate::k8s::check_protected_context(forward.context.as_deref())?;
+    }
+    // Set before expanding, which already lists ports through the API server
+    for forward in &forwards {
+        if let Some(jump_host) = &forward.jump_host {
+            crate::jump::configure(forward.context.as_deref(), jump_host);
+        }
+    }
+    let forwards = dependency_order(expand_forwards(forwards).await.map_err(InvalidForwardError)?);
+    let mut keys = Vec::new();
 
-        let ns = forward.namespace.unwrap_or_else(|| "default".to_string());
-        let local_port = forward.local_port.unwrap_or(resource_port);
+    for forward in forwards {
+        keys.push(spawn_forward(forward)?);
+    }
 
-        let handle = tokio::spawn(async move {
-            if let Err(e) = start_single(
-                resource_type,
-                resource_name,
-                resource_port,
-                ns,
-                local_port,
-                verbose,
-                forward.timeout,
-                forward.liveness_probe,
-                show_liveness,
-                (*requests_log_file_clone).clone(),
-                requests_log_verbosity,
-            )
-            .await
-            {
-                crate::logger::log_error(format!("Forward failed: {}", e));
-            }
-        });
+    Ok(keys)
+}
+
+/// Sorts forwards so each comes after the ones it depends on, keeping the
+/// given order otherwise. Cycles are rejected when the config is loaded.
+fn dependency_order(mut pending: Vec<ForwardConfig>) -> Vec<ForwardConfig> {
+    let mut ordered: Vec<ForwardConfig> = Vec::new();
+    while !pending.is_empty() {
+        let next = pending
+            .iter()
+            .position(|forward| {
+                forward.dependency_keys().unwrap_or_default().iter().all(|dependency| {
+                    !pending
+                        .iter()
+                        .any(|other| !std::ptr::eq(other, forward) && other.is_named_by(dependency))
+                })
+            })
+            .unwrap_or(0);
+        ordered.push(pending.remove(next));
+    }
+    ordered
+}
+
+/// Fills in the namespace of a forward that does not set one, the way kubectl
+/// does: the namespace of its kubeconfig context, else `default`.
+fn resolve_namespace(mut forward: ForwardConfig) -> ForwardConfig {
+    if forward.namespace.is_none() {
+        let (namespace, source) = match crate::k8s::context_namespace(forward.context.as_deref()) {
+            Some(namespace) => (namespace, "from the kubeconfig context"),
+            None => ("default".to_string(), "(no namespace set on the kubeconfig context)"),
+        };
+        crate::logger::log_info(format!(
+            "{} Using namespace {} for {} {}",
+            "📁", namespace, forward.resource, source
+        ));
+        forward.namespace = Some(namespace);
+    }
+    forward
+}
 
-        handles.push(handle);
+/// Key a forward starts under: its `type/name:port`, unless another forward
+/// already has that key for another namespace or local port. The key is then
+/// qualified with the namespace, and the local port when that is not enough,
+/// e.g. `service/api:80@staging` or `service/api:80@staging:8081`.
+fn unique_key(handles: &HashMap<String, ForwardHandle>, forward: &ForwardConfig) -> Result<String> {
+    let (resource_type, resource_name, resource_port) = parse_resource(&forward.resource)?;
+    let key = format!("{}/{}:{}", resource_type, resource_name, resource_port);
+    let namespace = forward.namespace.as_deref().unwrap_or("default");
+    let local_port = forward.local_port().unwrap_or(resource_port);
+    // A forward started before tries its earlier key first
+    let candidates = forward.key.iter().cloned().chain([
+        key.clone(),
+        format!("{}@{}", key, namespace),
+        format!("{}@{}:{}", key, namespace, local_port),
+    ]);
+    for candidate in candidates {
+        let Some(handle) = handles.get(&candidate) else {
+            return Ok(candidate);
+        };
+        let same = handle.forward.namespace.as_deref().unwrap_or("default") == namespace
+            && handle.forward.local_port().unwrap_or(resource_port) == local_port;
+        if same && !handle.task.is_finished() {
+            return Err(anyhow!("Forward {} is already running", candidate));
+        }
+        if same {
+            return Ok(candidate);
+        }
+    }
+    Err(anyhow!("Forward {} is already running", key))
+}
+
+/// Key of the forward, other than `own_key`, that `reference` in its
+/// `depends_on`, `split` or `mirror_to` names (see `ForwardConfig::is_named_by`).
+fn resolve_reference(handles: &HashMap<String, ForwardHandle>, reference: &str, own_key: &str) -> Result<String> {
+    let mut named = handles
+        .iter()
+        .filter(|(key, handle)| key.as_str() != own_key && handle.forward.is_named_by(reference))
+        .map(|(key, _)| key);
+    match (named.next(), named.next()) {
+        (Some(key), None) => Ok(key.clone()),
+        (None, _) => Err(anyhow!("{} refers to {}, which is not a known forward", own_key, reference)),
+        (Some(_), Some(_)) => Err(anyhow!(
+            "{} refers to {}, which names forwards in several namespaces or on several local ports; \
+             qualify it like {}@<namespace> or {}@<namespace>:<local_port>",
+            own_key,
+            reference,
+            crate::template::base_key(reference),
+            crate::template::base_key(reference)
+        )),
+    }
+}
+
+/// Key of the forward a `mirror_to` of the forward `key` names.
+pub fn resolve_mirror_target(key: &str, reference: &str) -> Result<String> {
+    resolve_reference(&FORWARD_HANDLES.lock().unwrap(), reference, key)
+}
+
+/// Registers the per-request settings of a forward under `key`. Stops at the
+/// first invalid one, leaving the earlier ones for `forget_configuration`.
+fn configure_forward(key: &str, forward: &ForwardConfig, split: Option<&SplitConfig>) -> Result<()> {
+    crate::http::set_body_format(
+        key,
+        crate::http::BodyFormat {
+            pretty: forward.pretty_print.unwrap_or(true),
+            limit: forward.body_log_limit.unwrap_or(crate::http::DEFAULT_BODY_LOG_LIMIT),
+            capture_limit: forward
+                .body_capture_limit
+                .unwrap_or(crate::http::DEFAULT_BODY_CAPTURE_LIMIT),
+        },
+    );
+    if let Some(chaos) = &forward.chaos {
+        crate::chaos::configure(key, chaos.clone());
+    }
+    crate::headers::configure(key, forward)?;
+    crate::rewrite::configure(key, forward.path_rewrite.as_ref())?;
+    crate::transform::configure(key, forward)?;
+    crate::log_filter::configure(key, forward.log_exclude.as_ref())?;
+    crate::redact::configure(
+        key,
+        forward.redact_headers.as_deref(),
+        forward.redact_fields.as_deref(),
+    )?;
+    crate::retry::configure(key, forward.retry.as_ref());
+    crate::cache::configure(key, forward.cache.as_ref());
+    crate::limit::configure(key, forward.concurrency.as_ref());
+    crate::auth::configure(key, forward.auth.as_ref())?;
+    crate::cors::configure(key, forward.cors.as_ref())?;
+    crate::mirror::configure(
+        key,
+        forward.mirror_to.as_deref(),
+        forward.mirror_resolve.as_ref(),
+        forward.mirror_tls.as_ref(),
+    )?;
+    crate::split::configure(key, split)?;
+    Ok(())
+}
+
+/// Drops every per-request setting registered under `key`, so a forward that
+/// failed to configure leaves nothing behind for a later one with its key.
+fn forget_configuration(key: &str) {
+    crate::http::forget(key);
+    crate::chaos::forget(key);
+    crate::headers::forget(key);
+    crate::transform::forget(key);
+    let _ = crate::rewrite::configure(key, None);
+    let _ = crate::log_filter::configure(key, None);
+    let _ = crate::redact::configure(key, None, None);
+    crate::retry::configure(key, None);
+    crate::cache::configure(key, None);
+    crate::limit::configure(key, None);
+    let _ = crate::auth::configure(key, None);
+    let _ = crate::cors::configure(key, None);
+    let _ = crate::mirror::configure(key, None, None, None);
+    let _ = crate::split::configure(key, None);
+}
+
+fn spawn_forward(mut forward: ForwardConfig) -> Result<String> {
+    use crate::tui::ForwardStatus;
+
+    let (_, _, resource_port) = parse_resource(&forward.resource)?;
+    let mut handles = FORWARD_HANDLES.lock().unwrap();
+    let key = unique_key(&handles, &forward)?;
+    if forward.key.is_none() && key != forward_key(&forward)? {
+        crate::logger::log_info(format!(
+            "{} {} is already forwarded from another namespace or local port; this one is {}",
+            "🔑", forward.resource, key
+        ));
+    }
+    forward.key = Some(key.clone());
+    for dependency in forward.dependency_keys()? {
+        resolve_reference(&handles, &dependency, &key)?;
+    }
+    // Split-off requests go to the target's key, whatever the reference
+    let mut split = forward.split.clone();
+    if let Some(split) = &mut split {
+        split.to = resolve_reference(&handles, &split.to, &key)?;
+    }
+
+    if let Err(e) = configure_forward(&key, &forward, split.as_ref()) {
+        forget_configuration(&key);
+        return Err(e);
+    }
+    if forward.protocol != Some(Protocol::Udp) && !crate::auth::is_enabled(&key) {
+        if let Some(address) = crate::http::bind_addresses().into_iter().find(|address| !address.is_loopback()) {
+            crate::logger::log_warning(format!(
+                "{} {} listens on {} without auth; anyone reaching it reaches the cluster",
+                "🔓", key, address
+            ));
+        }
+    }
+
+    // Listed only once configured, so a failure above leaves no row behind
+    let status = ForwardStatus {
+        resource: key.clone(),
+        local_port: forward.local_port().unwrap_or(resource_port),
+        state: ForwardState::Initializing,
+        protected: crate::k8s::is_protected_context(forward.context.as_deref()),
+        tls: forward.tls.is_some() || forward_options().tls.is_some(),
+        namespace: forward.namespace.clone().unwrap_or_else(|| "default".to_string()),
+        context: crate::k8s::effective_context(forward.context.as_deref()).unwrap_or_default(),
+        ..Default::default()
+    };
+    crate::output::forward_started(&key, &status.namespace, &status.context, status.local_port);
+    FORWARD_STATUSES.lock().unwrap().insert(key.clone(), status);
+
+    let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+    let task_forward = forward.clone();
+    let task = tokio::spawn(async move {
+        if let Err(e) = start_single(task_forward, commands_rx).await {
+            crate::logger::log_error(format!("Forward failed: {}", e));
+        }
+    });
+
+    handles.insert(
+        key.clone(),
+        ForwardHandle {
+            forward,
+            commands: commands_tx,
+            task,
+        },
+    );
+
+    Ok(key)
+}
+
+/// Configuration of every forward started in this run, running or stopped,
+/// ordered by key.
+pub fn forwards() -> Vec<ForwardConfig> {
+    let handles = FORWARD_HANDLES.lock().unwrap();
+    let mut keys: Vec<&String> = handles.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .map(|key| handles[key].forward.clone())
+        .collect()
+}
+
+/// Sends a command to a running forward.
+pub fn send_command(key: &str, command: ForwardCommand) -> Result<()> {
+    let handles = FORWARD_HANDLES.lock().unwrap();
+    let handle = handles
+        .get(key)
+        .ok_or_else(|| UnknownForwardError(key.to_string()))?;
+    if handle.task.is_finished() {
+        return Err(anyhow!("Forward {} is not running", key));
     }
+    handle
+        .commands
+        .send(command)
+        .map_err(|_| anyhow!("Forward {} is not running", key))
+}
 
-    // Wait for all forwards to complete
-    join_all(handles).await;
+/// Stops a forward and forgets it: its row leaves the status table and it
+/// can no longer be started again.
+pub fn remove_forward(key: &str) -> Result<()> {
+    let handle = FORWARD_HANDLES
+        .lock()
+        .unwrap()
+        .remove(key)
+        .ok_or_else(|| UnknownForwardError(key.to_string()))?;
+    let _ = handle.commands.send(ForwardCommand::Stop);
+    FORWARD_STATUSES.lock().unwrap().remove(key);
 
+    // A reconnect finishing during shutdown can bring the row back
+    let key = key.to_string();
+    tokio::spawn(async move {
+        let _ = handle.task.await;
+        FORWARD_STATUSES.lock().unwrap().remove(&key);
+        crate::pod_logs::forget(&key);
+    });
     Ok(())
 }
+
+/// Stops every forward and waits, up to `timeout`, until their port-forwards
+/// are torn down and their local ports released.
+pub async fn stop_all(timeout: Duration) {
+    let handles: Vec<ForwardHandle> = FORWARD_HANDLES.lock().unwrap().drain().map(|(_, handle)| handle).collect();
+    for handle in &handles {
+        let _ = handle.commands.send(ForwardCommand::Stop);
+    }
+    let tasks = handles.into_iter().map(|handle| handle.task);
+    let _ = tokio::time::timeout(timeout, futures::future::join_all(tasks)).await;
+}
+
+/// Starts a previously stopped forward again with its original configuration.
+pub fn start_forward(key: &str) -> Result<()> {
+    let forward = {
+        let handles = FORWARD_HANDLES.lock().unwrap();
+        let handle = handles
+            .get(key)
+            .ok_or_else(|| UnknownForwardError(key.to_string()))?;
+        if !handle.task.is_finished() {
+            return Err(anyhow!("Forward {} is already running", key));
+        }
+        handle.forward.clone()
+    };
+    spawn_forward(forward).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forward(namespace: &str, local_port: u16) -> ForwardConfig {
+        ForwardConfig::builder()
+            .resource("service/api:80")
+            .namespace(namespace)
+            .local_port(local_port)
+            .build()
+            .unwrap()
+    }
+
+    /// A handle whose task is still running, or has finished when `running`
+    /// is false.
+    async fn handle(forward: ForwardConfig, running: bool) -> ForwardHandle {
+        let task = match running {
+            true => tokio::spawn(std::future::pending()),
+            false => tokio::spawn(async {}),
+        };
+        while !running && !task.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        ForwardHandle { forward, commands: mpsc::unbounded_channel().0, task }
+    }
+
+    #[tokio::test]
+    async fn first_forward_keeps_the_plain_key() {
+        let handles = HashMap::new();
+        assert_eq!(unique_key(&handles, &forward("staging", 8080)).unwrap(), "service/api:80");
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_qualified() {
+        let mut handles = HashMap::new();
+        handles.insert("service/api:80".to_string(), handle(forward("staging", 8080), true).await);
+        assert_eq!(unique_key(&handles, &forward("prod", 8081)).unwrap(), "service/api:80@prod");
+
+        handles.insert("service/api:80@prod".to_string(), handle(forward("staging", 8082), true).await);
+        assert_eq!(
+            unique_key(&handles, &forward("prod", 8081)).unwrap(),
+            "service/api:80@prod:8081"
+        );
+    }
+
+    #[tokio::test]
+    async fn running_forward_is_not_started_twice() {
+        let mut handles = HashMap::new();
+        handles.insert("service/api:80".to_string(), handle(forward("staging", 8080), true).await);
+        let error = unique_key(&handles, &forward("staging", 8080)).unwrap_err();
+        assert_eq!(error.to_string(), "Forward service/api:80 is already running");
+    }
+
+    #[tokio::test]
+    async fn finished_forward_key_is_reused() {
+        let mut handles = HashMap::new();
+        handles.insert("service/api:80".to_string(), handle(forward("staging", 8080), true).await);
+        handles.insert("service/api:80@prod".to_string(), handle(forward("prod", 8081), false).await);
+        assert_eq!(unique_key(&handles, &forward("prod", 8081)).unwrap(), "service/api:80@prod");
+    }
+
+    #[tokio::test]
+    async fn restarted_forward_tries_its_earlier_key_first() {
+        let mut handles = HashMap::new();
+        handles.insert("service/api:80".to_string(), handle(forward("staging", 8080), false).await);
+        let mut restarted = forward("prod", 8081);
+        restarted.key = Some("service/api:80@prod".to_string());
+        assert_eq!(unique_key(&handles, &restarted).unwrap(), "service/api:80@prod");
+    }
+
+    #[tokio::test]
+    async fn references_resolve_to_the_forward_they_name() {
+        let mut handles = HashMap::new();
+        handles.insert("service/api:80".to_string(), handle(forward("staging", 8080), true).await);
+        assert_eq!(resolve_reference(&handles, "service/api:80", "service/web:80").unwrap(), "service/api:80");
+        assert!(resolve_reference(&handles, "service/api:80", "service/api:80").is_err());
+        assert!(resolve_reference(&handles, "service/other:80", "service/web:80").is_err());
+
+        handles.insert("service/api:80@prod".to_string(), handle(forward("prod", 8081), true).await);
+        let error = resolve_reference(&handles, "service/api:80", "service/web:80").unwrap_err();
+        assert!(error.to_string().contains("qualify it like service/api:80@<namespace>"), "{}", error);
+        assert_eq!(
+            resolve_reference(&handles, "service/api:80@staging", "service/web:80").unwrap(),
+            "service/api:80"
+        );
+        assert_eq!(
+            resolve_reference(&handles, "service/api:80@prod:8081", "service/web:80").unwrap(),
+            "service/api:80@prod"
+        );
+        assert!(resolve_reference(&handles, "service/api:80@prod:9999", "service/web:80").is_err());
+    }
+
+    #[test]
+    fn dependencies_start_first() {
+        let mut web = forward("staging", 8090);
+        web.resource = "service/web:80".to_string();
+        web.depends_on = Some(vec!["service/api:80@prod".to_string()]);
+        let ordered = dependency_order(vec![web, forward("staging", 8080), forward("prod", 8081)]);
+        let order: Vec<(&str, Option<&str>)> = ordered
+            .iter()
+            .map(|forward| (forward.resource.as_str(), forward.namespace.as_deref()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("service/api:80", Some("staging")),
+                ("service/api:80", Some("prod")),
+                ("service/web:80", Some("staging"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn forward_that_fails_to_configure_leaves_no_settings() {
+        let mut broken = forward("staging", 8095);
+        broken.resource = "service/broken:80".to_string();
+        broken.request_headers = Some(HashMap::from([("x-env".to_string(), Some("dev".to_string()))]));
+        broken.retry = Some(Default::default());
+        broken.cors = Some(serde_json::from_str(r#"{"allow_credentials": true}"#).unwrap());
+        assert!(spawn_forward(broken).is_err());
+
+        let key = "service/broken:80";
+        assert!(crate::headers::rules(key).is_none());
+        assert!(crate::retry::policy(key).is_none());
+        assert!(crate::cors::policy(key).is_none());
+        assert!(!FORWARD_STATUSES.lock().unwrap().contains_key(key));
+        assert!(!FORWARD_HANDLES.lock().unwrap().contains_key(key));
+    }
+}
@@ -0,0 +1,63 @@
+use hyper::HeaderMap;
+
+/// Whether a request carries a gRPC call (`application/grpc`, `application/grpc+proto`, ...).
+pub fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/grpc"))
+}
+
+/// The outcome of a gRPC call, read from `grpc-status` / `grpc-message`.
+#[derive(Clone, Debug)]
+pub struct GrpcStatus {
+    pub code: u32,
+    pub message: Option<String>,
+}
+
+impl GrpcStatus {
+    /// Reads the status from response trailers, or from the headers of a
+    /// trailers-only response.
+    pub fn from_headers(headers: &HeaderMap) -> Option<GrpcStatus> {
+        let code = headers.get("grpc-status")?.to_str().ok()?.trim().parse().ok()?;
+        let message = headers
+            .get("grpc-message")
+            .and_then(|value| value.to_str().ok())
+            .filter(|message| !message.is_empty())
+            .map(str::to_string);
+        Some(GrpcStatus { code, message })
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.code == 0
+    }
+
+    /// `NOT_FOUND (5): no such user`
+    pub fn describe(&self) -> String {
+        let name = match self.code {
+            0 => "OK",
+            1 => "CANCELLED",
+            2 => "UNKNOWN",
+            3 => "INVALID_ARGUMENT",
+            4 => "DEADLINE_EXCEEDED",
+            5 => "NOT_FOUND",
+            6 => "ALREADY_EXISTS",
+            7 => "PERMISSION_DENIED",
+            8 => "RESOURCE_EXHAUSTED",
+            9 => "FAILED_PRECONDITION",
+            10 => "ABORTED",
+            11 => "OUT_OF_RANGE",
+            12 => "UNIMPLEMENTED",
+            13 => "INTERNAL",
+            14 => "UNAVAILABLE",
+            15 => "DATA_LOSS",
+            16 => "UNAUTHENTICATED",
+            _ => "UNKNOWN_CODE",
+        };
+        match &self.message {
+            Some(message) if !self.is_ok() => format!("{} ({}): {}", name, self.code, message),
+            _ if !self.is_ok() => format!("{} ({})", name, self.code),
+            _ => name.to_string(),
+        }
+    }
+}
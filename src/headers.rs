@@ -0,0 +1,111 @@
+use crate::config::ForwardConfig;
+use anyhow::{Context, Result};
+use hyper::header::{HeaderName, HeaderValue, SET_COOKIE};
+use hyper::HeaderMap;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+static HEADER_RULES: LazyLock<Mutex<HashMap<String, Arc<HeaderRules>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The `Domain` attribute of a `Set-Cookie` value, with its leading separator.
+static COOKIE_DOMAIN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i);\s*domain\s*=[^;]*").unwrap());
+
+/// A forward's header rewriting, parsed once from its config.
+#[derive(Debug, Default)]
+pub struct HeaderRules {
+    /// Header to set, replacing any existing values, or to remove when `None`
+    request: Vec<(HeaderName, Option<HeaderValue>)>,
+    response: Vec<(HeaderName, Option<HeaderValue>)>,
+    strip_cookie_domain: bool,
+}
+
+impl HeaderRules {
+    /// `None` when the forward rewrites no headers.
+    pub fn from_forward(forward: &ForwardConfig) -> Result<Option<HeaderRules>> {
+        let rules = HeaderRules {
+            request: parse(forward.request_headers.as_ref(), "request_headers")?,
+            response: parse(forward.response_headers.as_ref(), "response_headers")?,
+            strip_cookie_domain: forward.strip_cookie_domain.unwrap_or(false),
+        };
+        let empty =
+            rules.request.is_empty() && rules.response.is_empty() && !rules.strip_cookie_domain;
+        Ok((!empty).then_some(rules))
+    }
+
+    pub fn apply_request(&self, headers: &mut HeaderMap) {
+        apply(&self.request, headers);
+    }
+
+    pub fn apply_response(&self, headers: &mut HeaderMap) {
+        apply(&self.response, headers);
+        if self.strip_cookie_domain {
+            let cookies: Vec<HeaderValue> = headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .map(|cookie| match cookie.to_str() {
+                    Ok(text) => HeaderValue::from_str(&COOKIE_DOMAIN.replace_all(text, ""))
+                        .unwrap_or_else(|_| cookie.clone()),
+                    Err(_) => cookie.clone(),
+                })
+                .collect();
+            headers.remove(SET_COOKIE);
+            for cookie in cookies {
+                headers.append(SET_COOKIE, cookie);
+            }
+        }
+    }
+}
+
+fn parse(
+    headers: Option<&HashMap<String, Option<String>>>,
+    field: &str,
+) -> Result<Vec<(HeaderName, Option<HeaderValue>)>> {
+    let mut rules = Vec::new();
+    for (name, value) in headers.into_iter().flatten() {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .context(format!("Invalid header name '{}' in {}", name, field))?;
+        let value = value
+            .as_deref()
+            .map(HeaderValue::from_str)
+            .transpose()
+            .context(format!("Invalid value for header '{}' in {}", name, field))?;
+        rules.push((header, value));
+    }
+    Ok(rules)
+}
+
+fn apply(rules: &[(HeaderName, Option<HeaderValue>)], headers: &mut HeaderMap) {
+    for (name, value) in rules {
+        match value {
+            Some(value) => {
+                headers.insert(name.clone(), value.clone());
+            }
+            None => {
+                headers.remove(name);
+            }
+        }
+    }
+}
+
+/// Registers the header rules of a forward, replacing any earlier ones.
+pub fn configure(key: &str, forward: &ForwardConfig) -> Result<()> {
+    let mut registry = HEADER_RULES.lock().unwrap();
+    match HeaderRules::from_forward(forward)? {
+        Some(rules) => registry.insert(key.to_string(), Arc::new(rules)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// Drops the header rules of a forward that did not start.
+pub fn forget(key: &str) {
+    HEADER_RULES.lock().unwrap().remove(key);
+}
+
+/// Header rules of a forward, if it has any.
+pub fn rules(key: &str) -> Option<Arc<HeaderRules>> {
+    HEADER_RULES.lock().unwrap().get(key).cloned()
+}
@@ -1,113 +1,1209 @@
+use crate::chaos::ChaosAction;
 use anyhow::Result;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Client, Request, Response, Server, StatusCode};
+use hyper::{Body, Client, HeaderMap, Request, Response, Server, StatusCode};
 use std::convert::Infallible;
-use std::net::SocketAddr;
-use std::sync::atomic::{AtomicU8, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use hyper::body::{Bytes, HttpBody};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
 static HTTP_VERBOSE: AtomicU8 = AtomicU8::new(0);
+/// How long requests wait for a reconnecting forward (`--hold-requests`); 0 answers 503 at once
+static HOLD_REQUESTS_SECS: AtomicU64 = AtomicU64::new(0);
+/// Responses slower than this are logged as slow (`--slow-threshold-ms`); 0 turns it off
+static SLOW_THRESHOLD_MS: AtomicU64 = AtomicU64::new(0);
+/// Addresses the local proxies listen on (`--bind`). `None` means loopback
+/// over both IPv4 and IPv6, where IPv6 is skipped on hosts without it.
+static BIND_ADDRESSES: LazyLock<Mutex<Option<Vec<IpAddr>>>> = LazyLock::new(|| Mutex::new(None));
+/// Most body bytes shown in a log entry (`--max-body-log-bytes`)
+static MAX_BODY_LOG_BYTES: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_BODY_LOG_BYTES);
 
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Body type of proxied requests and responses. Unlike `Body::wrap_stream`
+/// it carries trailers, which gRPC uses for its status.
+pub type ProxyBody = http_body::combinators::UnsyncBoxBody<Bytes, hyper::Error>;
+
+/// Default cap on logged body size when pretty-printing is off.
+pub const DEFAULT_BODY_LOG_LIMIT: usize = 2048;
+/// Default number of body bytes kept for logging while the body streams through.
+pub const DEFAULT_BODY_CAPTURE_LIMIT: usize = 64 * 1024;
+/// Where the proxy reaches the port-forwards kubectl listens on.
+const UPSTREAM_HOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+/// Default for `--max-body-log-bytes`.
+pub const DEFAULT_MAX_BODY_LOG_BYTES: usize = 4096;
+
+/// How the proxy and HTTP probes reach a forward's port-forward.
+#[derive(Clone)]
+pub enum Upstream {
+    Http {
+        http1: Client<hyper::client::HttpConnector, ProxyBody>,
+        /// HTTP/2 with prior knowledge (h2c), for requests that arrived over HTTP/2
+        http2: Client<hyper::client::HttpConnector, ProxyBody>,
+    },
+    /// Negotiates HTTP/2 or HTTP/1.1 with the upstream via ALPN
+    Https(Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>, ProxyBody>),
+    /// HTTP/1.1 to `kubectl proxy`, under the API server's proxy path of the
+    /// forwarded port
+    ApiServerProxy {
+        client: Client<hyper::client::HttpConnector, ProxyBody>,
+        path: Arc<str>,
+    },
+}
+
+impl Default for Upstream {
+    fn default() -> Self {
+        Upstream::Http {
+            http1: Client::builder().build_http(),
+            http2: Client::builder().http2_only(true).build_http(),
+        }
+    }
+}
+
+impl Upstream {
+    /// Connects over TLS, sending `server_name` as SNI and verifying the
+    /// certificate against it rather than against 127.0.0.1.
+    pub fn https(config: tokio_rustls::rustls::ClientConfig, server_name: String) -> Self {
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(config)
+            .https_only()
+            .with_server_name(server_name)
+            .enable_http1()
+            .enable_http2()
+            .build();
+        Upstream::Https(Client::builder().build(connector))
+    }
+
+    /// Reaches the forwarded port through the API server's proxy at `path`,
+    /// with `kubectl proxy` listening on the internal port.
+    pub fn api_server_proxy(path: String) -> Self {
+        Upstream::ApiServerProxy {
+            client: Client::builder().build_http(),
+            path: path.into(),
+        }
+    }
+
+    /// The API server proxy path the forward is reached under, in
+    /// apiserver-proxy mode.
+    pub fn api_server_proxy_path(&self) -> Option<&str> {
+        match self {
+            Upstream::ApiServerProxy { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// URL of `path_and_query` on the internal port-forward port.
+    pub fn url(&self, port: u16, path_and_query: &str) -> String {
+        let (scheme, path) = match self {
+            Upstream::Http { .. } => ("http", ""),
+            Upstream::Https(_) => ("https", ""),
+            Upstream::ApiServerProxy { path, .. } => ("http", &**path),
+        };
+        format!("{}://{}{}{}", scheme, SocketAddr::from((UPSTREAM_HOST, port)), path, path_and_query)
+    }
+
+    /// Sends `req` over HTTP/2 if it arrived over HTTP/2 (plain upstreams),
+    /// or over whatever protocol was negotiated (HTTPS upstreams).
+    pub async fn request<B>(&self, req: Request<B>) -> Result<Response<Body>, hyper::Error>
+    where
+        B: HttpBody<Data = Bytes, Error = hyper::Error> + Send + 'static,
+    {
+        let http2 = req.version() == hyper::Version::HTTP_2;
+        let mut req = req.map(HttpBody::boxed_unsync);
+        // The pooled connection decides the protocol on the wire
+        *req.version_mut() = hyper::Version::HTTP_11;
+        match self {
+            Upstream::Http { http2: client, .. } if http2 => client.request(req).await,
+            Upstream::Http { http1: client, .. } => client.request(req).await,
+            Upstream::Https(client) => client.request(req).await,
+            Upstream::ApiServerProxy { client, .. } => client.request(req).await,
+        }
+    }
+}
+
+/// Where each running proxy reaches its port-forward, so requests another
+/// forward splits off can be sent there.
+static FORWARD_UPSTREAMS: LazyLock<Mutex<HashMap<String, ForwardUpstream>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone)]
+struct ForwardUpstream {
+    port: u16,
+    status: Arc<Mutex<bool>>,
+    upstream: Upstream,
+}
+
+/// Per-forward settings the proxy consults on every request, so they can be
+/// changed at runtime from the TUI.
+static PROXY_SETTINGS: LazyLock<Mutex<HashMap<String, ProxySettings>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Copy, Default)]
+struct ProxySettings {
+    body: BodyFormat,
+    /// Overrides the global verbosity for this forward
+    verbose: Option<u8>,
+}
+
+/// How a forward's request/response bodies are rendered in logs.
+#[derive(Clone, Copy)]
+pub struct BodyFormat {
+    /// Pretty-print JSON over several lines; otherwise log one compact line
+    pub pretty: bool,
+    /// Maximum logged bytes in compact mode
+    pub limit: usize,
+    /// Maximum bytes of a streamed body kept for logging
+    pub capture_limit: usize,
+}
+
+impl Default for BodyFormat {
+    fn default() -> Self {
+        Self {
+            pretty: true,
+            limit: DEFAULT_BODY_LOG_LIMIT,
+            capture_limit: DEFAULT_BODY_CAPTURE_LIMIT,
+        }
+    }
+}
+
+pub fn set_body_format(resource: &str, format: BodyFormat) {
+    PROXY_SETTINGS
+        .lock()
+        .unwrap()
+        .entry(resource.to_string())
+        .or_default()
+        .body = format;
+}
+
+/// Switches a forward between pretty and compact bodies. Returns whether
+/// pretty-printing is now on.
+pub fn toggle_pretty_print(resource: &str) -> bool {
+    let mut settings = PROXY_SETTINGS.lock().unwrap();
+    let format = &mut settings.entry(resource.to_string()).or_default().body;
+    format.pretty = !format.pretty;
+    format.pretty
+}
+
+/// Sets or, with `None`, clears a forward's verbosity override.
+pub fn set_forward_verbose(resource: &str, level: Option<u8>) {
+    PROXY_SETTINGS
+        .lock()
+        .unwrap()
+        .entry(resource.to_string())
+        .or_default()
+        .verbose = level;
+}
+
+/// Drops the settings of a forward that did not start.
+pub fn forget(resource: &str) {
+    PROXY_SETTINGS.lock().unwrap().remove(resource);
+}
+
+/// The forward's verbosity override, if it has one.
+pub fn forward_verbose(resource: &str) -> Option<u8> {
+    proxy_settings(resource).verbose
+}
+
+fn proxy_settings(resource: &str) -> ProxySettings {
+    PROXY_SETTINGS.lock().unwrap().get(resource).copied().unwrap_or_default()
+}
+
+/// Verbosity of a request to `path`. Excluded paths are logged as at
+/// verbosity 0: only slow or failed requests.
+fn request_verbose(resource: &str, settings: &ProxySettings, path: &str) -> u8 {
+    if crate::log_filter::is_excluded(resource, path) {
+        0
+    } else {
+        settings
+            .verbose
+            .unwrap_or_else(|| HTTP_VERBOSE.load(Ordering::Relaxed))
+    }
+}
+
+/// Renders a body as a single line: minified JSON or escaped text, cut off at
+/// `limit` bytes.
+fn compact_body(bytes: &[u8], limit: usize) -> String {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return format!("Binary data: {} bytes", bytes.len());
+    };
+    let line = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(json) => json.to_string(),
+        Err(_) => escape_newlines(text),
+    };
+    if line.len() <= limit {
+        return line;
+    }
+    let end = floor_char_boundary(&line, limit);
+    format!("{}{}", &line[..end], truncation_marker(line.len() - end))
+}
+
+/// Pretty-prints JSON; other text is logged as is.
+fn pretty_body(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(json) => serde_json::to_string_pretty(&json).unwrap_or_else(|_| text.to_string()),
+            Err(_) => text.to_string(),
+        },
+        Err(_) => format!("Binary data: {} bytes", bytes.len()),
+    }
+}
+
+/// Renders a captured body prefix for the log. Bodies larger than
+/// `--max-body-log-bytes` (or the forward's `body_log_limit` in compact mode)
+/// are cut off as raw text instead of being parsed, with a marker giving the
+/// number of bytes left out.
+pub(crate) fn describe_body(captured: &[u8], total: usize, format: BodyFormat, compact: bool) -> String {
+    let mut max = MAX_BODY_LOG_BYTES.load(Ordering::Relaxed);
+    if compact {
+        max = max.min(format.limit);
+    }
+    if total <= max && captured.len() == total {
+        return if compact {
+            compact_body(captured, format.limit)
+        } else {
+            pretty_body(captured)
+        };
+    }
+
+    let prefix = &captured[..captured.len().min(max)];
+    let text = match std::str::from_utf8(prefix) {
+        Ok(text) => text,
+        // The cut may split a multi-byte character
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&prefix[..e.valid_up_to()]).unwrap(),
+        Err(_) => return format!("Binary data: {} bytes", total),
+    };
+    let shown = if compact { escape_newlines(text) } else { text.to_string() };
+    format!("{}{}", shown, truncation_marker(total - text.len()))
+}
+
+/// A body as it may be logged, with redacted fields masked and the total
+/// size adjusted to match.
+fn loggable_body<'a>(resource: &str, captured: &'a [u8], total: usize) -> (std::borrow::Cow<'a, [u8]>, usize) {
+    let redacted = crate::redact::body(resource, captured);
+    let total = total - captured.len() + redacted.len();
+    (redacted, total)
+}
+
+/// A captured body decoded for the logs when it is compressed and was
+/// captured whole, with its decoded size; as it is otherwise.
+fn decoded_body(
+    encoding: Option<crate::compression::Encoding>,
+    captured: Vec<u8>,
+    total: usize,
+) -> (Vec<u8>, usize) {
+    match encoding.filter(|_| captured.len() == total).and_then(|encoding| encoding.decode(&captured)) {
+        Some(decoded) => {
+            let total = decoded.len();
+            (decoded, total)
+        }
+        None => (captured, total),
+    }
+}
+
+/// The request body kept for the requests log file, on a single line.
+fn describe_request_payload(payload: Option<&(Vec<u8>, usize)>, format: BodyFormat) -> String {
+    match payload {
+        Some((captured, total)) if *total > 0 => describe_body(captured, *total, format, true),
+        _ => "N/A".to_string(),
+    }
+}
+
+fn truncation_marker(omitted: usize) -> String {
+    format!("…truncated {} bytes", omitted)
+}
+
+fn escape_newlines(text: &str) -> String {
+    text.replace('\r', "\\r").replace('\n', "\\n")
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Streams `body` through unchanged, trailers included, while copying its
+/// first `limit` bytes. `on_complete` gets the copy, the total size and the
+/// trailers once the body is finished, or dropped early because the peer
+/// went away.
+fn tee_body<F>(body: Body, limit: usize, on_complete: F) -> TeeBody<F>
+where
+    F: FnOnce(Vec<u8>, usize, Option<HeaderMap>) + Send + Unpin + 'static,
+{
+    TeeBody {
+        inner: body,
+        captured: Vec::new(),
+        total: 0,
+        limit,
+        on_complete: Some(on_complete),
+    }
+}
+
+struct TeeBody<F: FnOnce(Vec<u8>, usize, Option<HeaderMap>)> {
+    inner: Body,
+    captured: Vec<u8>,
+    total: usize,
+    limit: usize,
+    on_complete: Option<F>,
+}
+
+impl<F: FnOnce(Vec<u8>, usize, Option<HeaderMap>)> TeeBody<F> {
+    fn complete(&mut self, trailers: Option<HeaderMap>) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(std::mem::take(&mut self.captured), self.total, trailers);
+        }
+    }
+}
+
+impl<F: FnOnce(Vec<u8>, usize, Option<HeaderMap>) + Unpin> HttpBody for TeeBody<F> {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, hyper::Error>>> {
+        let this = &mut *self;
+        let polled = Pin::new(&mut this.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &polled {
+            this.total += chunk.len();
+            let room = this.limit.saturating_sub(this.captured.len());
+            this.captured.extend_from_slice(&chunk[..room.min(chunk.len())]);
+        }
+        polled
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, hyper::Error>> {
+        let this = &mut *self;
+        let polled = Pin::new(&mut this.inner).poll_trailers(cx);
+        if let Poll::Ready(Ok(trailers)) = &polled {
+            this.complete(trailers.clone());
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// HTTP/1.1 connections never ask for trailers, so the body usually completes here
+impl<F: FnOnce(Vec<u8>, usize, Option<HeaderMap>)> Drop for TeeBody<F> {
+    fn drop(&mut self) {
+        self.complete(None);
+    }
+}
+
+/// What is written to the requests log file and the TUI after a response.
+struct ResponseLog {
+    timestamp: String,
+    resource: String,
+    method: String,
+    path: String,
+    status: StatusCode,
+    /// Set for gRPC calls once the trailers have arrived
+    grpc_status: Option<crate::grpc::GrpcStatus>,
+    elapsed_ms: u128,
+    verbose: u8,
+    requests_log_file: Option<std::path::PathBuf>,
+    requests_log_verbosity: u8,
+    /// The captured request body and its total size, kept for the requests
+    /// log file at verbosity 3
+    request_payload: Option<(Vec<u8>, usize)>,
+}
+
+impl ResponseLog {
+    /// The gRPC status for gRPC calls, the HTTP status otherwise.
+    fn outcome(&self) -> String {
+        match &self.grpc_status {
+            Some(grpc_status) => grpc_status.describe(),
+            None => self.status.to_string(),
+        }
+    }
+
+    /// `payload` is the captured response body, its total size and format.
+    fn write(&self, payload: Option<(&[u8], usize, BodyFormat)>) {
+        let payload = payload.map(|(captured, total, format)| {
+            let (captured, total) = loggable_body(&self.resource, captured, total);
+            (captured, total, format)
+        });
+        if self.verbose > 0 {
+            if let Some(ref log_path) = self.requests_log_file {
+                let log_line = if self.requests_log_verbosity >= 3 {
+                    // The file always gets single-line payloads
+                    let file_payload = payload
+                        .as_ref()
+                        .map(|(captured, total, format)| describe_body(captured, *total, *format, true));
+                    let format = payload.as_ref().map_or_else(BodyFormat::default, |(_, _, format)| *format);
+                    format!(
+                        "{} {} - {} {} → {} ({}) [Request: {}] [Payload: {}]\n",
+                        self.timestamp,
+                        self.resource,
+                        self.method,
+                        self.path,
+                        self.outcome(),
+                        self.elapsed_ms,
+                        describe_request_payload(self.request_payload.as_ref(), format),
+                        file_payload.as_deref().unwrap_or("N/A")
+                    )
+                } else {
+                    format!(
+                        "{} {} - {} {} → {} ({})\n",
+                        self.timestamp,
+                        self.resource,
+                        self.method,
+                        self.path,
+                        self.outcome(),
+                        self.elapsed_ms
+                    )
+                };
+                crate::requests_log::append(log_path, log_line);
+            }
+        }
+
+        // Log the payload in the TUI at verbose level 3
+        if self.verbose >= 3 {
+            if let Some((captured, total, format)) = payload {
+                let indented = describe_body(&captured, total, format, !format.pretty)
+                    .lines()
+                    .map(|line| format!("    {}", line))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                crate::logger::log_info(format!("{} Response payload:\n{}", "📄", indented));
+            }
+        }
+    }
+}
+
+/// Logs a finished gRPC call with its gRPC status, which only arrives with
+/// the end of the response.
+fn log_grpc_call(log: &ResponseLog) {
+    let slow = is_slow(log.elapsed_ms);
+    if log.verbose == 0 && !slow {
+        return;
+    }
+    let line = format!(
+        "{} - gRPC {} → {} ({}ms)",
+        log.resource,
+        log.path,
+        log.grpc_status
+            .as_ref()
+            .map_or_else(|| format!("{}, no grpc-status", log.status), |status| status.describe()),
+        log.elapsed_ms
+    );
+    match &log.grpc_status {
+        Some(status) if status.is_ok() && slow => {
+            crate::logger::log_slow(format!("{} {}, slow", "🐢", line))
+        }
+        Some(status) if status.is_ok() => crate::logger::log_success(format!("{} {}", "✓", line)),
+        _ => crate::logger::log_warning(format!("{} {}", "✗", line)),
+    }
+}
+
+/// Whether a response took longer than `--slow-threshold-ms`.
+fn is_slow(elapsed_ms: u128) -> bool {
+    let threshold = SLOW_THRESHOLD_MS.load(Ordering::Relaxed);
+    threshold > 0 && elapsed_ms > threshold as u128
+}
+
+fn is_paused(resource: &str) -> bool {
+    crate::forwarder::FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .get(resource)
+        .is_some_and(|status| status.state == crate::forwarder::ForwardState::Paused)
+}
+
+/// Parks a request while its forward reconnects, for up to `--hold-requests`
+/// seconds. Returns whether the forward came back. Paused forwards are not
+/// waited for.
+async fn hold_request(port_forward_status: &Arc<Mutex<bool>>, resource: &str) -> bool {
+    let hold = Duration::from_secs(HOLD_REQUESTS_SECS.load(Ordering::Relaxed));
+    let deadline = Instant::now() + hold;
+    while Instant::now() < deadline && !is_paused(resource) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if *port_forward_status.lock().unwrap() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Copies the method, URI, version and headers of `head` onto `body`, so the
+/// same upstream request can be sent more than once.
+fn with_body<B>(head: &Request<()>, body: B) -> Request<B> {
+    let mut request = Request::new(body);
+    *request.method_mut() = head.method().clone();
+    *request.uri_mut() = head.uri().clone();
+    *request.version_mut() = head.version();
+    *request.headers_mut() = head.headers().clone();
+    request
+}
+
+fn boxed(response: Response<Body>) -> Response<ProxyBody> {
+    response.map(HttpBody::boxed_unsync)
+}
+
+/// Largest body read whole to apply a forward's transforms; larger ones pass
+/// through untransformed.
+const MAX_TRANSFORM_BODY: usize = 4 * 1024 * 1024;
+
+/// Reads `body` whole when it is at most `limit` bytes. A larger body comes
+/// back unread as far as the caller is concerned: the chunks read so far,
+/// followed by the rest.
+pub(crate) async fn read_limited(mut body: Body, limit: usize) -> Result<Result<Bytes, Body>, hyper::Error> {
+    if body.size_hint().lower() > limit as u64 {
+        return Ok(Err(body));
+    }
+    let mut chunks = Vec::new();
+    let mut size = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        size += chunk.len();
+        chunks.push(chunk);
+        if size > limit {
+            let read = futures::stream::iter(chunks.into_iter().map(Ok::<_, hyper::Error>));
+            return Ok(Err(Body::wrap_stream(futures::StreamExt::chain(read, body))));
+        }
+    }
+    Ok(Ok(chunks.concat().into()))
+}
+
+/// 502 for a request whose body, or whose upstream's response body, could
+/// not be read.
+fn body_error(resource: &str, e: &hyper::Error) -> Response<ProxyBody> {
+    let message = format!("Failed to read the body: {}", e);
+    crate::logger::log_error(format!("{} {}: {}", "✗", resource, message));
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = StatusCode::BAD_GATEWAY;
+    boxed(response)
+}
+
+/// Feeds a finished request into the request inspector and the metrics registry.
+/// Returns the capture id, used to attach the response body later.
+fn record_request(
+    resource: &str,
+    method: &str,
+    path: &str,
+    status: u16,
+    latency_ms: u128,
+    request_body: Option<String>,
+    curl: String,
+) -> u64 {
+    crate::metrics::record_request(resource, status, latency_ms as u64);
+    crate::output::request(resource, method, path, status, latency_ms);
+    crate::capture::record(resource, method, path, status, latency_ms, request_body, curl)
+}
+
+/// Answers a request from the `--mock` recordings. Without one it is answered
+/// with 404, or `None` to pass it through to the cluster with `--mock-passthrough`.
+fn serve_mocked(
+    resource: &str,
+    request_key: &str,
+    method: &hyper::Method,
+    path: &str,
+    verbose: u8,
+    start: Instant,
+    replay: &crate::capture::Replay,
+) -> Option<Response<Body>> {
+    let (response, recorded) = match crate::mock::response(resource, request_key) {
+        Some(response) => (response, true),
+        None if crate::mock::passthrough() => return None,
+        None => {
+            let mut response = Response::new(Body::from(format!(
+                "Not Found: no recording of {}",
+                request_key
+            )));
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            (response, false)
+        }
+    };
+    let status = response.status();
+    record_request(
+        resource,
+        method.as_str(),
+        path,
+        status.as_u16(),
+        start.elapsed().as_millis(),
+        None,
+        replay.curl(None),
+    );
+    if verbose > 0 {
+        let message = format!(
+            "{} {} - {} {} → {} ({})",
+            "🎭",
+            resource,
+            method,
+            path,
+            status.as_str(),
+            if recorded { "recorded" } else { "no recording" }
+        );
+        match recorded {
+            true => crate::logger::log_success(message),
+            false => crate::logger::log_warning(message),
+        }
+    }
+    Some(response)
+}
+
+/// Answers a GET from the forward's `cache` while the upstream cannot, e.g.
+/// during a pod restart. `None` when no fresh response is kept for it.
+fn serve_cached(
+    resource: &str,
+    cache_key: &str,
+    path: &str,
+    reason: &str,
+    start: Instant,
+    replay: &crate::capture::Replay,
+) -> Option<Response<ProxyBody>> {
+    let cached = crate::cache::lookup(resource, cache_key)?;
+    let age = cached.stored_at.elapsed().as_secs();
+    let status = cached.status;
+    let mut response = Response::new(Body::from(cached.body));
+    *response.status_mut() = status;
+    *response.headers_mut() = cached.headers;
+    response.headers_mut().insert(hyper::header::AGE, age.into());
+    response
+        .headers_mut()
+        .insert("x-kpf-cache", hyper::header::HeaderValue::from_static("hit"));
+
+    let capture_id = record_request(
+        resource,
+        "GET",
+        path,
+        status.as_u16(),
+        start.elapsed().as_millis(),
+        None,
+        replay.curl(None),
+    );
+    crate::capture::mark_cached(resource, capture_id);
+    crate::metrics::record_cache_hit(resource);
+    crate::logger::log_warning(format!(
+        "{} {} - GET {} → {} (from cache, {}s old; {})",
+        "💾",
+        resource,
+        path,
+        status.as_str(),
+        age,
+        reason
+    ));
+    Some(boxed(response))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn proxy_request(
-    req: Request<Body>,
+    mut req: Request<Body>,
     target_port: u16,
     port_forward_status: Arc<Mutex<bool>>,
     _show_liveness: bool,
     resource: String,
     requests_log_file: Option<std::path::PathBuf>,
     requests_log_verbosity: u8,
-) -> Result<Response<Body>, hyper::Error> {
-    let verbose = HTTP_VERBOSE.load(Ordering::Relaxed);
-    let start = Instant::now();
+    upstream: Upstream,
+    local_base: Arc<str>,
+) -> Result<Response<ProxyBody>, BoxError> {
+    let settings = proxy_settings(&resource);
     let method = req.method().clone();
     let path = req.uri().path().to_string();
+    let verbose = request_verbose(&resource, &settings, &path);
+    let body_format = settings.body;
+    let start = Instant::now();
+    let started_at = std::time::SystemTime::now();
+    let grpc = crate::grpc::is_grpc(req.headers());
+
+    // Without valid credentials nothing is answered, not even the internal endpoints
+    if let Some(response) = crate::auth::check(&resource, req.headers_mut()) {
+        record_request(
+            &resource,
+            method.as_str(),
+            &path,
+            StatusCode::UNAUTHORIZED.as_u16(),
+            start.elapsed().as_millis(),
+            None,
+            String::new(),
+        );
+        if verbose > 0 {
+            crate::logger::log_warning(format!(
+                "{} {} {} → {} (missing or wrong credentials)",
+                "🔒",
+                method.as_str(),
+                path,
+                "401 Unauthorized"
+            ));
+        }
+        return Ok(boxed(response));
+    }
 
     // Check for internal endpoints
     if path == "/_internal/status" {
-        return handle_internal_status(port_forward_status, verbose).await;
+        return Ok(boxed(handle_internal_status(port_forward_status, verbose, &resource).await?));
+    }
+    if path == "/_internal/healthz" {
+        return Ok(boxed(handle_internal_healthz(&port_forward_status, &resource)));
+    }
+    if path == "/_internal/requests" {
+        return Ok(boxed(handle_internal_requests(req.uri().query(), &resource)));
+    }
+
+    // A split forward sends some requests to another forward's port-forward
+    let split = crate::split::policy(&resource);
+    let split_to = split.as_ref().and_then(|split| split.route(req.headers())).map(str::to_string);
+    let (target_port, port_forward_status, upstream) = match &split_to {
+        None => (target_port, port_forward_status, upstream),
+        Some(to) => {
+            let Some(other) = FORWARD_UPSTREAMS.lock().unwrap().get(to).cloned() else {
+                let mut response = Response::new(Body::from(format!(
+                    "Service Unavailable: split target {} is not running",
+                    to
+                )));
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                record_request(
+                    &resource,
+                    method.as_str(),
+                    &path,
+                    StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                    start.elapsed().as_millis(),
+                    None,
+                    String::new(),
+                );
+                if verbose > 0 {
+                    crate::logger::log_error(format!(
+                        "{} {} - {} {} → {} ({} is not running)",
+                        "🔀",
+                        resource,
+                        method.as_str(),
+                        path,
+                        "503 Service Unavailable",
+                        to
+                    ));
+                }
+                return Ok(boxed(response));
+            };
+            if verbose >= 2 {
+                crate::logger::log_info(format!(
+                    "{} {} - {} {} goes to {}",
+                    "🔀",
+                    resource,
+                    method.as_str(),
+                    path,
+                    to
+                ));
+            }
+            (other.port, other.status, other.upstream)
+        }
+    };
+    // The forward whose port-forward serves the request
+    let upstream_key = split_to.clone().unwrap_or_else(|| resource.clone());
+
+    let replay = crate::capture::Replay::new(
+        &method,
+        format!(
+            "{}{}",
+            local_base,
+            req.uri().path_and_query().map(|x| x.as_str()).unwrap_or("")
+        ),
+        &crate::redact::headers(&resource, req.headers()),
+    );
+    let path_and_query = req.uri().path_and_query().map(|x| x.as_str()).unwrap_or("");
+    let request_key = crate::cache::cache_key(&method, path_and_query);
+    let cache_key = (method == hyper::Method::GET && split_to.is_none() && crate::cache::enabled(&resource))
+        .then(|| request_key.clone());
+
+    // In mock mode recorded requests never reach the cluster
+    if crate::mock::is_mocking() {
+        if let Some(response) = serve_mocked(&resource, &request_key, &method, &path, verbose, start, &replay) {
+            return Ok(boxed(response));
+        }
     }
 
+    // Synthetic checks neither keep a forward from going idle nor wake it
+    let synthetic = req.headers().contains_key("x-kpf-synthetic");
+    let activity = (!synthetic).then(|| crate::idle::begin_request(&upstream_key));
+
     // Check if port-forward is active
     let is_active = {
         let status = port_forward_status.lock().unwrap();
         *status
     };
 
+    let is_active = is_active
+        || (!synthetic && crate::idle::wake(&upstream_key, &port_forward_status).await)
+        || hold_request(&port_forward_status, &upstream_key).await;
+
     if !is_active {
-        let mut response = Response::new(Body::from(
-            "Service Unavailable: Port-forward is not active",
-        ));
+        let cached = cache_key.as_deref().and_then(|key| {
+            serve_cached(&resource, key, &path, "port-forward not active", start, &replay)
+        });
+        if let Some(response) = cached {
+            return Ok(response);
+        }
+        let paused = is_paused(&upstream_key);
+        let mut response = Response::new(Body::from(if paused {
+            "Service Unavailable: Port-forward is paused"
+        } else {
+            "Service Unavailable: Port-forward is not active"
+        }));
         *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        record_request(
+            &resource,
+            method.as_str(),
+            &path,
+            StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+            start.elapsed().as_millis(),
+            None,
+            replay.curl(None),
+        );
 
         // If verbose > 0, log error response for inactive port-forward
         if verbose > 0 {
             crate::logger::log_error(format!(
-                "{} {} {} → {} ({})",
+                "{} {} {} → {} ({}ms)",
                 "✗",
                 method.as_str(),
                 path,
                 "503 Service Unavailable",
-                format!("{}ms", start.elapsed().as_millis())
+                start.elapsed().as_millis()
             ));
         }
 
-        return Ok(response);
+        return Ok(boxed(response));
+    }
+
+    // Over the concurrency limit, requests wait for a slot or are turned away
+    let permit = match crate::limit::acquire(&resource).await {
+        Ok(permit) => permit,
+        Err(saturated) => {
+            let response = saturated.response();
+            record_request(
+                &resource,
+                method.as_str(),
+                &path,
+                StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                start.elapsed().as_millis(),
+                None,
+                replay.curl(None),
+            );
+            if verbose > 0 {
+                crate::logger::log_warning(format!(
+                    "{} {} - {} {} → {} ({}ms)",
+                    "🚦",
+                    resource,
+                    method.as_str(),
+                    path,
+                    "503 too many requests in flight",
+                    start.elapsed().as_millis()
+                ));
+            }
+            return Ok(boxed(response));
+        }
+    };
+
+    // Inject chaos-mode faults before the request reaches the upstream
+    let (delay, action) = crate::chaos::decide(&resource);
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    match action {
+        ChaosAction::Pass => {}
+        ChaosAction::Fail => {
+            let mut response = Response::new(Body::from(
+                "Service Unavailable: injected by chaos mode",
+            ));
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            record_request(
+                &resource,
+                method.as_str(),
+                &path,
+                StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                start.elapsed().as_millis(),
+                None,
+                replay.curl(None),
+            );
+            if verbose > 0 {
+                crate::logger::log_warning(format!(
+                    "{} {} {} → {} ({}ms)",
+                    "🐒",
+                    method.as_str(),
+                    path,
+                    "503 injected",
+                    start.elapsed().as_millis()
+                ));
+            }
+            return Ok(boxed(response));
+        }
+        ChaosAction::Drop => {
+            if verbose > 0 {
+                crate::logger::log_warning(format!(
+                    "{} {} {} → connection dropped",
+                    "🐒",
+                    method.as_str(),
+                    path
+                ));
+            }
+            // Failing the service makes hyper close the connection without a response
+            return Err("connection dropped by chaos mode".into());
+        }
     }
 
     // Create a new request with the target URL (using the internal port)
-    let target_uri = format!(
-        "http://127.0.0.1:{}{}",
-        target_port,
-        req.uri().path_and_query().map(|x| x.as_str()).unwrap_or("")
-    );
+    let target_uri = match crate::rewrite::path_rewrite(&resource) {
+        Some(rewrite) => upstream.url(target_port, &rewrite.apply(path_and_query)),
+        None => upstream.url(target_port, path_and_query),
+    };
+
+    // Transformed bodies are read whole; their length changes with them
+    let transforms = crate::transform::rules(&resource);
+    if let Some(transforms) = transforms.as_ref().filter(|transforms| transforms.request.applies(req.headers())) {
+        let (mut parts, body) = req.into_parts();
+        req = match read_limited(body, MAX_TRANSFORM_BODY).await {
+            Ok(Ok(body)) => {
+                let body = transforms.request.apply(body);
+                parts.headers.remove(hyper::header::TRANSFER_ENCODING);
+                parts.headers.insert(hyper::header::CONTENT_LENGTH, body.len().into());
+                Request::from_parts(parts, Body::from(body))
+            }
+            Ok(Err(body)) => {
+                if verbose > 0 {
+                    crate::logger::log_warning(format!(
+                        "{} {} - {} {}: request body over {} bytes, not transformed",
+                        "✂", resource, method, path, MAX_TRANSFORM_BODY
+                    ));
+                }
+                Request::from_parts(parts, body)
+            }
+            Err(e) => {
+                record_request(&resource, method.as_str(), &path, 502, start.elapsed().as_millis(), None, replay.curl(None));
+                return Ok(body_error(&resource, &e));
+            }
+        };
+    }
 
     let mut target_req = Request::builder()
         .method(req.method().clone())
+        .version(req.version())
         .uri(target_uri);
 
     // Copy headers
     for (name, value) in req.headers() {
-        if name != "host" {
+        if name != "host" && name != "traceparent" {
             // Skip the host header
             target_req = target_req.header(name, value);
         }
     }
+    // The upstream's spans become children of the proxy's client span
+    let trace = crate::otel::TraceContext::from_headers(req.headers());
+    target_req = target_req.header("traceparent", trace.traceparent());
+    let header_rules = crate::headers::rules(&resource);
+    if let (Some(rules), Some(headers)) = (&header_rules, target_req.headers_mut()) {
+        rules.apply_request(headers);
+    }
+    // Compressed responses could not be transformed
+    if let (Some(transforms), Some(headers)) = (&transforms, target_req.headers_mut()) {
+        if !transforms.response.is_empty() {
+            headers.remove(hyper::header::ACCEPT_ENCODING);
+        }
+    }
 
-    // Handle the request body
-    let (req_body_content, req_body_for_logging) = if verbose >= 2 {
-        // If we need to log the body, we need to read it fully
-        let bytes = hyper::body::to_bytes(req.into_body())
-            .await
-            .unwrap_or_default();
-        let bytes_clone = bytes.clone();
-
-        // Try to parse as JSON for pretty printing
-        let body_for_logging = if let Ok(json_str) = String::from_utf8(bytes_clone.to_vec()) {
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&json_str) {
-                Some(serde_json::to_string_pretty(&json_value).unwrap_or(json_str))
-            } else {
-                Some(format!("Binary data: {} bytes", bytes_clone.len()))
-            }
-        } else {
-            Some(format!("Binary data: {} bytes", bytes_clone.len()))
+    // Stream the request body through, keeping a prefix for the capture ring
+    // and the log. It has normally been sent by the time the response arrives.
+    let mut capture_limit = body_format.capture_limit.max(crate::capture::MAX_CAPTURED_BODY);
+    if cache_key.is_some() {
+        capture_limit = capture_limit.max(crate::cache::MAX_CACHED_BODY);
+    }
+    let record_key = crate::mock::is_recording().then_some(request_key);
+    if record_key.is_some() {
+        capture_limit = capture_limit.max(crate::mock::MAX_RECORDED_BODY);
+    }
+    // The mirror gets the request as the upstream does, once its body is complete
+    let mirror = crate::mirror::mirror(&resource, req.headers()).map(|mirror| {
+        let mut headers = target_req.headers_ref().cloned().unwrap_or_default();
+        headers.remove("traceparent");
+        let request = crate::mirror::MirroredRequest {
+            key: resource.clone(),
+            method: method.clone(),
+            path_and_query: target_req
+                .uri_ref()
+                .and_then(|uri| uri.path_and_query())
+                .map_or_else(|| "/".to_string(), |path| path.to_string()),
+            headers,
+            verbose,
         };
+        (mirror, request, req.body().size_hint().exact())
+    });
+    if mirror.is_some() {
+        capture_limit = capture_limit.max(crate::mirror::MAX_MIRRORED_BODY);
+    }
+    let request_body = Arc::new(Mutex::new(None));
+    let request_body_slot = request_body.clone();
+    let log_request_body = verbose >= 2 && method != hyper::Method::GET;
+    // The requests log file gets request bodies along with response payloads
+    let keep_request_payload = requests_log_file.is_some() && requests_log_verbosity >= 3;
+    let request_payload = Arc::new(Mutex::new(None));
+    let request_payload_slot = request_payload.clone();
+    let request_resource = resource.clone();
+    let request_encoding = crate::compression::Encoding::from_headers(req.headers());
+    let on_request_body = move |captured: Vec<u8>, total: usize, _| {
+        crate::metrics::record_request_bytes(&request_resource, total);
+        // Bodies too large to keep, or cut short, are not mirrored
+        if let Some((mirror, request, length)) = mirror {
+            let complete = length.is_none_or(|length| length as usize == total);
+            if complete && captured.len() == total && total <= crate::mirror::MAX_MIRRORED_BODY {
+                mirror.send(request, Bytes::copy_from_slice(&captured));
+            }
+        }
+        let (captured, total) = decoded_body(request_encoding, captured, total);
+        let (loggable, total) = loggable_body(&request_resource, &captured, total);
+        *request_body_slot.lock().unwrap() = crate::capture::body_text(&loggable, total);
+        if (log_request_body || keep_request_payload) && total > 0 {
+            if log_request_body {
+                crate::logger::log_info(format!(
+                    "{} Request body:\n{}",
+                    "📄",
+                    describe_body(&loggable, total, body_format, !body_format.pretty)
+                ));
+            }
+            if keep_request_payload {
+                *request_payload_slot.lock().unwrap() = Some((loggable.into_owned(), total));
+            }
+        }
+    };
 
-        (Body::from(bytes), body_for_logging)
-    } else {
-        // If we don't need to log, just pass the body through
-        (req.into_body(), None)
+    // Forward the request. Requests that may be retried are buffered first so
+    // they can be sent again; the size hint is exact only for a body with a
+    // Content-Length (or none), so others are never buffered.
+    let retry = crate::retry::policy(&resource)
+        .filter(|policy| policy.allows(&method, req.body().size_hint().exact()));
+    let head = target_req.body(()).unwrap();
+
+    let upstream_started_at = std::time::SystemTime::now();
+    let result = match retry {
+        None => {
+            let body = tee_body(req.into_body(), capture_limit, on_request_body);
+            upstream.request(with_body(&head, body)).await
+        }
+        Some(policy) => {
+            let bytes = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    record_request(&resource, method.as_str(), &path, 502, start.elapsed().as_millis(), None, replay.curl(None));
+                    return Ok(body_error(&resource, &e));
+                }
+            };
+            let body = tee_body(Body::from(bytes.clone()), capture_limit, on_request_body);
+            let mut result = upstream.request(with_body(&head, body)).await;
+            let mut attempt = 1;
+            while attempt < policy.max_attempts {
+                let Err(e) = &result else {
+                    break;
+                };
+                let backoff = policy.backoff(attempt);
+                if verbose > 0 {
+                    crate::logger::log_warning(format!(
+                        "{} {} - {} {} failed ({}), retrying in {}ms ({}/{})",
+                        "🔁",
+                        resource,
+                        method,
+                        path,
+                        e,
+                        backoff.as_millis(),
+                        attempt + 1,
+                        policy.max_attempts
+                    ));
+                }
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                result = upstream.request(with_body(&head, Body::from(bytes.clone()))).await;
+            }
+            result
+        }
     };
+    trace.export(&crate::otel::RequestSpans {
+        resource: resource.clone(),
+        method: method.clone(),
+        path: path.clone(),
+        status: result.as_ref().ok().map(|response| response.status().as_u16()),
+        started_at,
+        upstream_started_at,
+        finished_at: std::time::SystemTime::now(),
+    });
 
-    // Forward the request
-    let client = Client::new();
-    let target_req = target_req.body(req_body_content).unwrap();
+    // Upstream failures fall back to a cached response
+    if let Some(key) = &cache_key {
+        let unavailable = match &result {
+            Ok(response) => matches!(
+                response.status(),
+                StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+            )
+            .then(|| format!("upstream answered {}", response.status().as_u16())),
+            Err(_) => Some("upstream unreachable".to_string()),
+        };
+        if let Some(response) = unavailable
+            .and_then(|reason| serve_cached(&resource, key, &path, &reason, start, &replay))
+        {
+            return Ok(response);
+        }
+    }
 
-    match client.request(target_req).await {
+    match result {
         Ok(response) => {
-            let status = response.status();
+            // Transformed bodies are read first, so a failed read is recorded as the 502 the client gets
+            let (mut parts, body) = response.into_parts();
+            // Errors without a body come as a trailers-only response
+            let header_grpc_status = crate::grpc::GrpcStatus::from_headers(&parts.headers);
+            if let Some(rules) = &header_rules {
+                rules.apply_response(&mut parts.headers);
+            }
+            if split.is_some() {
+                if let Ok(answered_by) = hyper::header::HeaderValue::from_str(&upstream_key) {
+                    parts.headers.insert(crate::split::UPSTREAM_HEADER, answered_by);
+                }
+            }
+            let transforms = transforms.filter(|transforms| {
+                method != hyper::Method::HEAD && transforms.response.applies(&parts.headers)
+            });
+            let body = match transforms {
+                Some(transforms) => match read_limited(body, MAX_TRANSFORM_BODY).await {
+                    Ok(Ok(body)) => {
+                        let body = transforms.response.apply(body);
+                        parts.headers.remove(hyper::header::TRANSFER_ENCODING);
+                        parts.headers.insert(hyper::header::CONTENT_LENGTH, body.len().into());
+                        Body::from(body)
+                    }
+                    Ok(Err(body)) => {
+                        if verbose > 0 {
+                            crate::logger::log_warning(format!(
+                                "{} {}: response body over {} bytes, not transformed",
+                                "✂", resource, MAX_TRANSFORM_BODY
+                            ));
+                        }
+                        body
+                    }
+                    Err(e) => {
+                        let curl = replay.curl(request_body.lock().unwrap().as_deref());
+                        record_request(&resource, method.as_str(), &path, 502, start.elapsed().as_millis(), None, curl);
+                        return Ok(body_error(&resource, &e));
+                    }
+                },
+                None => body,
+            };
+            let status = parts.status;
             let elapsed = start.elapsed();
             // Always log successful requests regardless of verbosity level
             let colored_method = match method {
+                // gRPC calls are all POSTs; the path already names the method
+                _ if grpc => "gRPC",
                 hyper::Method::GET => "GET",
                 hyper::Method::POST => "POST",
                 hyper::Method::PUT => "PUT",
@@ -122,112 +1218,96 @@ async fn proxy_request(
             };
             let ms = elapsed.as_millis();
             let duration_colored = format!("{}ms", ms);
+            let request_body = request_body.lock().unwrap().take();
+            let curl = replay.curl(request_body.as_deref());
+            let capture_id = record_request(
+                &resource,
+                colored_method,
+                &path,
+                status.as_u16(),
+                ms,
+                request_body,
+                curl.clone(),
+            );
 
-            // Always log to the TUI logger
-            if verbose > 0 {
+            // Always log to the TUI logger. gRPC calls are logged once their
+            // status arrives in the trailers.
+            if is_slow(ms) && !grpc {
+                crate::logger::log_slow(format!(
+                    "{} {} - {} {} → {} ({}, slow)",
+                    "🐢", resource, colored_method, path, status_colored, duration_colored
+                ));
+            } else if verbose > 0 && !grpc {
                 crate::logger::log_success(format!(
                     "{} {} - {} {} → {} ({})",
                     "✓", resource, colored_method, path, status_colored, duration_colored
                 ));
             }
-            let (response, opt_resp_body) = if verbose >= 3
-                || (requests_log_file.is_some() && requests_log_verbosity >= 3)
-            {
-                let (parts, body) = response.into_parts();
-                let bytes = hyper::body::to_bytes(body).await.unwrap_or_default();
-                let body_clone = bytes.clone();
-                let content_type_json = parts
-                    .headers
-                    .get("content-type")
-                    .and_then(|ct| ct.to_str().ok())
-                    .map(|ct| ct.contains("application/json"))
-                    .unwrap_or(false);
-                let computed_resp_body = if let Ok(json_str) = String::from_utf8(bytes.to_vec()) {
-                    if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&json_str) {
-                        if verbose >= 3 {
-                            serde_json::to_string_pretty(&json_value).unwrap_or(json_str)
-                        } else if content_type_json {
-                            if requests_log_file.is_some() {
-                                serde_json::to_string(&json_value).unwrap_or(json_str)
-                            } else {
-                                serde_json::to_string_pretty(&json_value).unwrap_or(json_str)
-                            }
-                        } else {
-                            json_str
-                        }
-                    } else {
-                        format!("Binary data: {} bytes", body_clone.len())
-                    }
-                } else {
-                    format!("Binary data: {} bytes", body_clone.len())
-                };
-                (
-                    Response::from_parts(parts, Body::from(body_clone)),
-                    Some(computed_resp_body),
-                )
-            } else {
-                (response, None)
+            if verbose >= 2 {
+                crate::logger::log_info(format!("{} {}", "📋", curl));
+            }
+
+            let mut log = ResponseLog {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                resource: resource.clone(),
+                method: colored_method.to_string(),
+                path,
+                status,
+                grpc_status: None,
+                elapsed_ms: ms,
+                verbose,
+                requests_log_file,
+                requests_log_verbosity,
+                request_payload: request_payload.lock().unwrap().take(),
             };
-            if verbose > 0 {
-                if let Some(ref log_path) = requests_log_file {
-                    use std::fs::OpenOptions;
-                    use std::io::Write;
-                    let timestamp = chrono::Utc::now().to_rfc3339();
-                    let log_line = if requests_log_verbosity >= 3 {
-                        format!(
-                            "{} {} - {} {} → {} ({}) [Payload: {}]\n",
-                            timestamp,
-                            resource,
-                            method.as_str(),
-                            path,
-                            status.to_string(),
-                            elapsed.as_millis(),
-                            opt_resp_body.as_deref().unwrap_or("N/A")
-                        )
-                    } else {
-                        format!(
-                            "{} {} - {} {} → {} ({})\n",
-                            timestamp,
-                            resource,
-                            method.as_str(),
-                            path,
-                            status.to_string(),
-                            elapsed.as_millis()
-                        )
-                    };
-                    if let Ok(mut file) = OpenOptions::new().append(true).create(true).open(log_path) {
-                        let _ = file.write_all(log_line.as_bytes());
-                    } else {
-                        crate::logger::log_error(format!(
-                            "Failed to write to log file: {}",
-                            log_path.display()
-                        ));
+            let log_payload =
+                verbose >= 3 || (log.requests_log_file.is_some() && requests_log_verbosity >= 3);
+            if !log_payload && !grpc {
+                log.write(None);
+            }
+            // The body is captured, and its payload logged, once the client has read it
+            // Event streams may never end: they are neither cached nor recorded,
+            // and do not hold a concurrency slot once connected
+            let event_stream = crate::sse::is_event_stream(&parts.headers);
+            let permit = (!event_stream).then_some(permit);
+            let kept_headers = ((cache_key.is_some() || record_key.is_some()) && !event_stream)
+                .then(|| parts.headers.clone());
+            let encoding = crate::compression::Encoding::from_headers(&parts.headers);
+            let body = tee_body(body, capture_limit, move |captured, total, trailers| {
+                // The forward stays busy until the response has been read
+                drop(activity);
+                drop(permit);
+                if let Some(mut headers) = kept_headers.filter(|_| total == captured.len()) {
+                    // The body is replayed whole, not in chunks
+                    headers.remove(hyper::header::TRANSFER_ENCODING);
+                    if let Some(key) = &record_key {
+                        crate::mock::record(&resource, key, status, &headers, &captured);
+                    }
+                    if let Some(key) = cache_key.filter(|_| status.is_success()) {
+                        let body = Bytes::copy_from_slice(&captured);
+                        crate::cache::store(&resource, key, status, headers, body);
                     }
                 }
-            }
-            // Log request body if available and not a GET request
-            if verbose > 0 && req_body_for_logging.is_some() && method != hyper::Method::GET {
-                crate::logger::log_info(format!(
-                    "{} Request body:\n{}",
-                    "📄",
-                    req_body_for_logging.unwrap()
-                ));
-            }
-            // Log the JSON payload if verbose level is 3 and a JSON payload is available
-            if verbose >= 3 {
-                if let Some(resp_body_str) = opt_resp_body {
-                    let indented_json = resp_body_str
-                        .lines()
-                        .map(|line| format!("    {}", line))
-                        .collect::<Vec<_>>()
-                        .join("\n");
-                    crate::logger::log_info(format!(
-                        "{} Response payload:\n{}",
-                        "📄", indented_json
-                    ));
+                crate::metrics::record_response_bytes(&resource, total);
+                let (captured, total) = decoded_body(encoding, captured, total);
+                let (loggable, loggable_total) = loggable_body(&resource, &captured, total);
+                if let Some(text) = crate::capture::body_text(&loggable, loggable_total) {
+                    crate::capture::attach_response_body(&resource, capture_id, text);
                 }
-            }
-            Ok(response)
+                if grpc {
+                    log.grpc_status = trailers
+                        .as_ref()
+                        .and_then(crate::grpc::GrpcStatus::from_headers)
+                        .or(header_grpc_status);
+                    log_grpc_call(&log);
+                }
+                if log_payload {
+                    log.write(Some((&captured, total, body_format)));
+                } else if grpc {
+                    log.write(None);
+                }
+            });
+            Ok(Response::from_parts(parts, body.boxed_unsync()))
         }
         Err(e) => {
             let error_msg = format!("Failed to forward request: {}", e);
@@ -235,20 +1315,28 @@ async fn proxy_request(
 
             let mut response = Response::new(Body::from(error_msg));
             *response.status_mut() = StatusCode::BAD_GATEWAY;
+            record_request(
+                &resource,
+                method.as_str(),
+                &path,
+                StatusCode::BAD_GATEWAY.as_u16(),
+                start.elapsed().as_millis(),
+                None,
+                replay.curl(request_body.lock().unwrap().as_deref()),
+            );
 
             if let Some(ref log_path) = requests_log_file {
-                use std::fs::OpenOptions;
-                use std::io::Write;
                 let timestamp = chrono::Utc::now().to_rfc3339();
                 let log_line = if requests_log_verbosity >= 3 {
                     format!(
-                        "{} {} - {} {} → {} ({}) [Error Payload]\n",
+                        "{} {} - {} {} → {} ({}) [Request: {}] [Error Payload]\n",
                         timestamp,
                         resource,
                         method,
                         path,
                         "502 Bad Gateway",
-                        start.elapsed().as_millis()
+                        start.elapsed().as_millis(),
+                        describe_request_payload(request_payload.lock().unwrap().as_ref(), body_format)
                     )
                 } else {
                     format!(
@@ -261,14 +1349,7 @@ async fn proxy_request(
                         start.elapsed().as_millis()
                     )
                 };
-                if let Ok(mut file) = OpenOptions::new().append(true).create(true).open(log_path) {
-                    let _ = file.write_all(log_line.as_bytes());
-                } else {
-                    crate::logger::log_error(format!(
-                        "Failed to write to log file: {}",
-                        log_path.display()
-                    ));
-                }
+                crate::requests_log::append(log_path, log_line);
             }
             // Always log error responses regardless of verbosity level
             let colored_method = match method {
@@ -281,17 +1362,17 @@ async fn proxy_request(
             // Always log to the TUI logger
             if verbose > 0 {
                 crate::logger::log_error(format!(
-                    "{} {} - {} {} → {} ({})",
+                    "{} {} - {} {} → {} ({}ms)",
                     "✗",
                     resource,
                     colored_method,
                     path,
                     "502 Bad Gateway",
-                    format!("{}ms", start.elapsed().as_millis())
+                    start.elapsed().as_millis()
                 ));
             }
 
-            Ok(response)
+            Ok(boxed(response))
         }
     }
 }
@@ -299,19 +1380,52 @@ async fn proxy_request(
 async fn handle_internal_status(
     port_forward_status: Arc<Mutex<bool>>,
     verbose: u8,
+    resource: &str,
 ) -> Result<Response<Body>, hyper::Error> {
     // Get current status
     let is_active = {
         let status = port_forward_status.lock().unwrap();
         *status
     };
+    let forward = crate::forwarder::FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .get(resource)
+        .cloned()
+        .unwrap_or_default();
+    let metrics = crate::metrics::snapshot(resource);
+    let now = chrono::Utc::now();
 
     // Create status response with health details
     let status_info = serde_json::json!({
         "health": {
             "active": is_active,
-            "last_ping": chrono::Utc::now().to_rfc3339(),
-            "latency": "unknown"
+            "ready": is_active && crate::forwarder::is_ready(resource),
+            "state": forward.state,
+            "state_since": forward.transitions.last().map(|change| change.at.to_rfc3339()),
+            "state_transitions": metrics.state_transitions,
+            "last_probe": forward.last_probe,
+            "probe_latency_ms": metrics.probe_latency_ms,
+            "last_success": metrics.last_success_at.map(|at| at.to_rfc3339()),
+            "seconds_since_last_success": metrics
+                .last_success_at
+                .map(|at| (now - at).num_milliseconds() as f64 / 1000.0),
+            "connected_for_secs": forward.connected_at.map(|at| (now - at).num_seconds()),
+            "reconnects": metrics.reconnects
+        },
+        "cache": {
+            "entries": crate::cache::len(resource),
+            "hits": metrics.cache_hits
+        },
+        "traffic": {
+            "requests": metrics.total_requests,
+            "error_responses": metrics.error_responses,
+            "requests_per_sec": metrics.requests_per_sec,
+            "request_bytes": metrics.request_bytes,
+            "response_bytes": metrics.response_bytes,
+            "latency_p50_ms": metrics.latency_p50_ms,
+            "latency_p95_ms": metrics.latency_p95_ms,
+            "latency_p99_ms": metrics.latency_p99_ms
         },
         "status": {
             "verbose_level": verbose,
@@ -320,17 +1434,19 @@ async fn handle_internal_status(
         "version": env!("CARGO_PKG_VERSION"),
         "debug_info": {
             "process_id": std::process::id(),
-            "system_time": format!("{:?}", std::time::SystemTime::now()),
-            "uptime": format!("{:?}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap()),
+            "system_time": now.to_rfc3339(),
+            "uptime_secs": crate::metrics::process_uptime().as_secs(),
             "memory_usage": format!("{} MB", std::process::Command::new("ps")
-                .args(&["-o", "rss=", "-p", &std::process::id().to_string()])
+                .args(["-o", "rss=", "-p", &std::process::id().to_string()])
                 .output()
                 .map(|output| String::from_utf8_lossy(&output.stdout).trim().parse::<u64>().unwrap_or(0) / 1024)
                 .unwrap_or(0))
         },
         "help": {
             "endpoints": {
-                "/_internal/status": "Shows port-forward status and health (last ping, latency, active)",
+                "/_internal/status": "Shows port-forward status, health and traffic counters",
+                "/_internal/healthz": "Prometheus metrics; 200 when the forward is ready, 503 otherwise",
+                "/_internal/requests": "Recent proxied requests with capped bodies (?forward=<type/name:port>&limit=<n>)",
                 "/<any-path>": "Proxied to the target service"
             }
         }
@@ -355,6 +1471,130 @@ async fn handle_internal_status(
     Ok(response)
 }
 
+/// The forward's health in the Prometheus text format, answered with 200
+/// when the forward is ready and 503 otherwise so plain HTTP checks work too.
+fn handle_internal_healthz(port_forward_status: &Arc<Mutex<bool>>, resource: &str) -> Response<Body> {
+    let ready = *port_forward_status.lock().unwrap() && crate::forwarder::is_ready(resource);
+    let state = crate::forwarder::FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .get(resource)
+        .map(|status| status.state.clone())
+        .unwrap_or_default();
+    let metrics = crate::metrics::snapshot(resource);
+    let labels = format!("forward=\"{}\"", resource.replace('\\', "\\\\").replace('"', "\\\""));
+
+    let mut body = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: Option<f64>| {
+        if let Some(value) = value {
+            body.push_str(&format!(
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name}{{{labels}}} {value}\n"
+            ));
+        }
+    };
+    metric("kpf_up", "gauge", "Whether the forward is ready to serve requests.", Some(if ready { 1.0 } else { 0.0 }));
+    metric(
+        "kpf_probe_latency_seconds",
+        "gauge",
+        "Duration of the last successful liveness probe.",
+        metrics.probe_latency_ms.map(|ms| ms as f64 / 1000.0),
+    );
+    metric(
+        "kpf_seconds_since_last_success",
+        "gauge",
+        "Time since the last proxied request answered without a 5xx.",
+        metrics
+            .last_success_at
+            .map(|at| (chrono::Utc::now() - at).num_milliseconds() as f64 / 1000.0),
+    );
+    metric("kpf_reconnects_total", "counter", "Reconnects of the port-forward.", Some(metrics.reconnects as f64));
+    metric(
+        "kpf_state_transitions_total",
+        "counter",
+        "Changes of the forward's state.",
+        Some(metrics.state_transitions as f64),
+    );
+    metric(
+        "kpf_cache_hits_total",
+        "counter",
+        "Requests answered from the response cache.",
+        Some(metrics.cache_hits as f64),
+    );
+    metric("kpf_requests_total", "counter", "Proxied requests.", Some(metrics.total_requests as f64));
+    metric(
+        "kpf_error_responses_total",
+        "counter",
+        "Proxied requests answered with a 5xx.",
+        Some(metrics.error_responses as f64),
+    );
+    metric(
+        "kpf_request_bytes_total",
+        "counter",
+        "Request body bytes received from clients.",
+        Some(metrics.request_bytes as f64),
+    );
+    metric(
+        "kpf_response_bytes_total",
+        "counter",
+        "Response body bytes sent to clients.",
+        Some(metrics.response_bytes as f64),
+    );
+
+    // One series per state, 1 for the current one
+    body.push_str("# HELP kpf_state Whether the forward is in the state.\n# TYPE kpf_state gauge\n");
+    for candidate in crate::forwarder::ForwardState::ALL {
+        body.push_str(&format!(
+            "kpf_state{{{},state=\"{}\"}} {}\n",
+            labels,
+            candidate,
+            u8::from(candidate == state)
+        ));
+    }
+
+    let mut response = Response::new(Body::from(body));
+    if !ready {
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    response
+}
+
+/// Lists the captured requests of this forward, or of the one named by
+/// `forward`, newest first.
+fn handle_internal_requests(query: Option<&str>, resource: &str) -> Response<Body> {
+    let mut forward = resource.to_string();
+    let mut limit = usize::MAX;
+    for (name, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        match name.as_ref() {
+            "forward" => forward = value.into_owned(),
+            "limit" => limit = value.parse().unwrap_or(limit),
+            _ => {}
+        }
+    }
+
+    let requests: Vec<_> = crate::capture::snapshot_forward(&forward)
+        .into_iter()
+        .rev()
+        .take(limit)
+        .collect();
+    let body = serde_json::to_string_pretty(&serde_json::json!({
+        "forward": forward,
+        "requests": requests,
+    }))
+    .unwrap_or_default();
+
+    let mut response = Response::new(Body::from(body));
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn start_http_server(
     local_port: u16,
     target_port: u16,
@@ -363,18 +1603,46 @@ pub async fn start_http_server(
     resource: String,
     requests_log_file: Option<std::path::PathBuf>,
     requests_log_verbosity: u8,
-) -> Result<(), hyper::Error> {
+    tls: Option<Arc<tokio_rustls::rustls::ServerConfig>>,
+    upstream: Upstream,
+    shutdown: tokio::sync::oneshot::Receiver<()>,
+) -> Result<()> {
     let verbose = HTTP_VERBOSE.load(Ordering::Relaxed);
-    let addr = SocketAddr::from(([127, 0, 0, 1], local_port));
+    let listeners = bind_listeners(local_port).await?;
+    let addresses: Vec<String> = listeners
+        .iter()
+        .filter_map(|listener| listener.local_addr().ok())
+        .map(|addr| addr.to_string())
+        .collect();
 
     crate::logger::log_info(format!(
-        "{} HTTP proxy server listening on {}",
+        "{} HTTP proxy server listening on {}://localhost:{} ({})",
         "🌐",
-        format!("http://localhost:{}", local_port)
+        if tls.is_some() { "https" } else { "http" },
+        local_port,
+        addresses.join(", ")
     ));
     crate::logger::log_info(format!("{} Verbosity level set to {}", "🔍", verbose));
 
+    FORWARD_UPSTREAMS.lock().unwrap().insert(
+        resource.clone(),
+        ForwardUpstream {
+            port: target_port,
+            status: port_forward_status.clone(),
+            upstream: upstream.clone(),
+        },
+    );
     let port_forward_status_clone = port_forward_status.clone();
+    let resource_for_accept = resource.clone();
+    let local_base: Arc<str> = match tls {
+        Some(_) => local_url(local_port, "").replacen("http://", "https://", 1),
+        None => local_url(local_port, ""),
+    }
+    .into();
+
+    // Tells open event streams to end once the server shuts down, since the
+    // graceful shutdown waits for every response to finish
+    let (closing_tx, closing) = tokio::sync::watch::channel(false);
 
     let make_svc = make_service_fn(move |_conn| {
         let port_forward_status = port_forward_status_clone.clone();
@@ -383,10 +1651,21 @@ pub async fn start_http_server(
         let resource = resource.clone();
         let requests_log_file = requests_log_file.clone();
         let requests_log_verbosity = requests_log_verbosity;
+        let upstream = upstream.clone();
+        let local_base = local_base.clone();
+        let closing = closing.clone();
 
         async move {
             Ok::<_, Infallible>(service_fn(move |req| {
-                proxy_request(
+                // Preflights are answered here, before authentication; the
+                // proxied request only runs once awaited
+                let cors = crate::cors::policy(&resource);
+                let preflight = cors.as_ref().and_then(|cors| cors.preflight(&req));
+                let origin = req.headers().get(hyper::header::ORIGIN).cloned();
+                let path = req.uri().path().to_string();
+                let mut closing = closing.clone();
+                let resource_for_stream = resource.clone();
+                let response = proxy_request(
                     req,
                     target,
                     port_forward_status.clone(),
@@ -394,15 +1673,177 @@ pub async fn start_http_server(
                     resource.clone(),
                     requests_log_file.clone(),
                     requests_log_verbosity,
-                )
+                    upstream.clone(),
+                    local_base.clone(),
+                );
+                async move {
+                    if let Some(preflight) = preflight {
+                        return Ok::<_, BoxError>(boxed(preflight));
+                    }
+                    let mut response = response.await?;
+                    if let (Some(cors), Some(origin)) = (cors, origin) {
+                        cors.apply(&origin, response.headers_mut());
+                    }
+                    if crate::sse::is_event_stream(response.headers()) {
+                        let settings = proxy_settings(&resource_for_stream);
+                        let log = crate::sse::EventLog {
+                            verbose: request_verbose(&resource_for_stream, &settings, &path),
+                            format: settings.body,
+                            resource: resource_for_stream,
+                            path,
+                        };
+                        let closed = async move {
+                            let _ = closing.wait_for(|closing| *closing).await;
+                        };
+                        response = response.map(|body| crate::sse::EventStream::new(body, closed, log).boxed_unsync());
+                    }
+                    Ok(response)
+                }
             }))
         }
     });
 
-    let server = Server::bind(&addr).serve(make_svc);
+    let incoming = crate::tls::incoming(listeners, tls, resource_for_accept.clone());
+    let server = Server::builder(hyper::server::accept::from_stream(incoming))
+        .serve(make_svc)
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.await;
+            let _ = closing_tx.send(true);
+        });
+
+    let result = server.await;
+    FORWARD_UPSTREAMS.lock().unwrap().remove(&resource_for_accept);
+    result?;
+    Ok(())
+}
+/// Sets the addresses the local proxies listen on; empty restores the
+/// dual-stack loopback default.
+pub fn set_bind_addresses(addresses: Vec<IpAddr>) {
+    *BIND_ADDRESSES.lock().unwrap() = Some(addresses).filter(|addresses| !addresses.is_empty());
+}
+
+/// Addresses the local proxies listen on.
+pub fn bind_addresses() -> Vec<IpAddr> {
+    BIND_ADDRESSES
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| vec![IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)])
+}
+
+/// Whether failing to bind `address` may be ignored: only the default IPv6
+/// loopback on a host without IPv6. A port taken on it is still a conflict.
+pub fn bind_error_is_ignorable(address: IpAddr, error: &std::io::Error) -> bool {
+    BIND_ADDRESSES.lock().unwrap().is_none()
+        && address.is_ipv6()
+        && error.kind() != std::io::ErrorKind::AddrInUse
+}
+
+/// URL of the local proxy on `port`, on the first address it listens on.
+pub fn local_url(port: u16, path_and_query: &str) -> String {
+    let address = bind_addresses()[0];
+    let address = if address.is_unspecified() {
+        match address {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        }
+    } else {
+        address
+    };
+    format!("http://{}{}", SocketAddr::from((address, port)), path_and_query)
+}
+
+async fn bind_listeners(port: u16) -> Result<Vec<tokio::net::TcpListener>> {
+    let mut listeners = Vec::new();
+    for address in bind_addresses() {
+        match tokio::net::TcpListener::bind((address, port)).await {
+            Ok(listener) => listeners.push(listener),
+            Err(e) if bind_error_is_ignorable(address, &e) => {}
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("Failed to listen on {}", SocketAddr::from((address, port)))))
+            }
+        }
+    }
+    Ok(listeners)
+}
+
+/// Sets the latency above which responses are logged as slow; 0 turns it off.
+pub fn set_slow_threshold(ms: u64) {
+    SLOW_THRESHOLD_MS.store(ms, Ordering::Relaxed);
+}
 
-    server.await
+/// The `--slow-threshold-ms` latency, if set.
+pub fn slow_threshold() -> Option<u64> {
+    Some(SLOW_THRESHOLD_MS.load(Ordering::Relaxed)).filter(|ms| *ms > 0)
 }
+
+/// Sets the most body bytes shown in a log entry.
+pub fn set_max_body_log_bytes(bytes: usize) {
+    MAX_BODY_LOG_BYTES.store(bytes, Ordering::Relaxed);
+}
+
+/// Sets how long requests wait for a reconnecting forward before a 503.
+pub fn set_hold_requests(seconds: u64) {
+    HOLD_REQUESTS_SECS.store(seconds, Ordering::Relaxed);
+}
+
 pub fn set_verbose(new_level: u8) {
     HTTP_VERBOSE.store(new_level, Ordering::Relaxed);
 }
+
+/// The global verbosity level.
+pub fn verbose() -> u8 {
+    HTTP_VERBOSE.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunked(chunks: &[&'static str]) -> Body {
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            chunks.iter().map(|chunk| Ok(Bytes::from_static(chunk.as_bytes()))).collect();
+        Body::wrap_stream(futures::stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn small_bodies_are_read_whole() {
+        let read = read_limited(chunked(&["ab", "cd"]), 4).await.unwrap();
+        assert_eq!(read.unwrap(), Bytes::from_static(b"abcd"));
+    }
+
+    #[tokio::test]
+    async fn bodies_with_a_large_size_pass_through_unread() {
+        let read = read_limited(Body::from("abcdef"), 4).await.unwrap();
+        let body = read.unwrap_err();
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), Bytes::from_static(b"abcdef"));
+    }
+
+    #[tokio::test]
+    async fn large_streams_pass_through_whole() {
+        let read = read_limited(chunked(&["ab", "cd", "ef", "gh"]), 5).await.unwrap();
+        let body = read.unwrap_err();
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), Bytes::from_static(b"abcdefgh"));
+    }
+
+    #[tokio::test]
+    async fn read_errors_are_reported() {
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            vec![Ok(Bytes::from_static(b"ab")), Err(std::io::Error::other("reset"))];
+        let body = Body::wrap_stream(futures::stream::iter(chunks));
+        assert!(read_limited(body, MAX_TRANSFORM_BODY).await.is_err());
+    }
+
+    #[test]
+    fn body_errors_are_bad_gateway() {
+        // hyper::Error cannot be built directly; a closed channel gives one
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let error = runtime.block_on(async {
+            let (sender, body) = Body::channel();
+            sender.abort();
+            hyper::body::to_bytes(body).await.unwrap_err()
+        });
+        assert_eq!(body_error("service/api:80", &error).status(), StatusCode::BAD_GATEWAY);
+    }
+}
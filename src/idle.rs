@@ -0,0 +1,116 @@
+//! `idle_timeout`: a forward closes its port-forward after a stretch without
+//! proxied requests and reconnects when the next one comes in. `lazy`
+//! forwards start out idle.
+
+use crate::forwarder::{ForwardCommand, ForwardState, FORWARD_STATUSES};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long requests wait for an idle forward to reconnect before a 503.
+const WAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+static ACTIVITY: LazyLock<Mutex<HashMap<String, Activity>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct Activity {
+    /// When the forward connected, or a request last started or finished
+    last: Instant,
+    /// Requests whose response has not been read to the end yet
+    in_flight: usize,
+    /// Set by the request that woke the forward up
+    woken_at: Option<Instant>,
+}
+
+impl Activity {
+    fn new() -> Activity {
+        Activity {
+            last: Instant::now(),
+            in_flight: 0,
+            woken_at: None,
+        }
+    }
+}
+
+/// Keeps a forward busy while a request, or its response body, is in flight.
+pub struct RequestGuard {
+    key: String,
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        if let Some(activity) = ACTIVITY.lock().unwrap().get_mut(&self.key) {
+            activity.in_flight = activity.in_flight.saturating_sub(1);
+            activity.last = Instant::now();
+        }
+    }
+}
+
+/// Counts a proxied request as activity until the guard is dropped.
+pub fn begin_request(key: &str) -> RequestGuard {
+    let mut registry = ACTIVITY.lock().unwrap();
+    let activity = registry.entry(key.to_string()).or_insert_with(Activity::new);
+    activity.in_flight += 1;
+    activity.last = Instant::now();
+    RequestGuard {
+        key: key.to_string(),
+    }
+}
+
+/// Restarts the idle clock, e.g. when the forward (re)connects.
+pub fn touch(key: &str) {
+    ACTIVITY
+        .lock()
+        .unwrap()
+        .entry(key.to_string())
+        .or_insert_with(Activity::new)
+        .last = Instant::now();
+}
+
+/// How long the forward has gone without requests; zero while one is in flight.
+pub fn idle_for(key: &str) -> Duration {
+    match ACTIVITY.lock().unwrap().get(key) {
+        Some(activity) if activity.in_flight == 0 => activity.last.elapsed(),
+        _ => Duration::ZERO,
+    }
+}
+
+/// Whether the forward is disconnected until its next request, after
+/// `idle_timeout` or because it is `lazy`.
+pub(crate) fn is_idle(key: &str) -> bool {
+    FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .get(key)
+        .is_some_and(|status| status.state == ForwardState::Idle)
+}
+
+/// Reconnects an idle forward for an incoming request and waits until it is
+/// connected. Requests arriving while it wakes up wait as well. False when
+/// the forward is not idle, or did not come back within [`WAKE_TIMEOUT`].
+pub async fn wake(key: &str, connected: &Arc<Mutex<bool>>) -> bool {
+    let idle = is_idle(key);
+    let woken_at = {
+        let mut registry = ACTIVITY.lock().unwrap();
+        let activity = registry.entry(key.to_string()).or_insert_with(Activity::new);
+        let waking = activity.woken_at.is_some_and(|at| at.elapsed() < WAKE_TIMEOUT);
+        if idle && !waking {
+            if crate::forwarder::send_command(key, ForwardCommand::Resume).is_err() {
+                return false;
+            }
+            activity.woken_at = Some(Instant::now());
+        }
+        match activity.woken_at {
+            Some(at) if idle || waking => at,
+            _ => return false,
+        }
+    };
+
+    while woken_at.elapsed() < WAKE_TIMEOUT {
+        if *connected.lock().unwrap() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
@@ -0,0 +1,171 @@
+//! `jump_host`: reaching a cluster's API server through an SSH bastion.
+//!
+//! The first request to a context with a jump host opens an `ssh -L` tunnel
+//! from a local port to the context's API server. The Kubernetes client and
+//! every kubectl process for that context (port-forwards, exec probes, UDP
+//! relays) then connect through it, still verifying the API server's own TLS
+//! name. The tunnel is reopened when ssh exits and dies with kpf.
+
+use anyhow::{bail, Context, Result};
+use kube::config::KubeConfigOptions;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+/// How long ssh gets to connect and start listening.
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Jump hosts, by effective context (empty for no kubeconfig context).
+static JUMP_HOSTS: LazyLock<Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Open tunnels, by effective context. Held across the ssh startup so that
+/// concurrent forwards share a single tunnel.
+static TUNNELS: LazyLock<tokio::sync::Mutex<HashMap<String, Tunnel>>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(HashMap::new()));
+
+struct Tunnel {
+    api: ApiTunnel,
+    jump_host: String,
+    child: Child,
+}
+
+/// The local end of a tunnel to an API server.
+#[derive(Clone)]
+pub struct ApiTunnel {
+    /// Where the API server is reached, e.g. `https://127.0.0.1:41234`
+    pub server: String,
+    /// The name the API server's certificate is checked against
+    pub tls_server_name: String,
+}
+
+/// Sets the jump host of `context`: `[user@]host[:port]`, or any `Host`
+/// alias from `~/.ssh/config`.
+pub fn configure(context: Option<&str>, jump_host: &str) {
+    let name = crate::k8s::effective_context(context).unwrap_or_default();
+    JUMP_HOSTS.lock().unwrap().insert(name, jump_host.to_string());
+}
+
+/// Checks a `jump_host` value.
+pub fn validate(jump_host: &str) -> Result<()> {
+    let host = jump_host.rsplit_once('@').map_or(jump_host, |(_, host)| host);
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => {
+            port.parse::<u16>()
+                .ok()
+                .filter(|&port| port > 0)
+                .context(format!("Invalid port in jump host '{}'", jump_host))?;
+            host
+        }
+        _ => host,
+    };
+    if host.is_empty() || host.starts_with('-') || host.chars().any(char::is_whitespace) {
+        bail!("Invalid jump host '{}'. Expected [user@]host[:port]", jump_host);
+    }
+    Ok(())
+}
+
+/// The tunnel to the API server of `context`, opened on first use, or `None`
+/// when the context has no jump host.
+pub async fn api_tunnel(context: Option<&str>) -> Result<Option<ApiTunnel>> {
+    let name = crate::k8s::effective_context(context).unwrap_or_default();
+    let Some(jump_host) = JUMP_HOSTS.lock().unwrap().get(&name).cloned() else {
+        return Ok(None);
+    };
+
+    let mut tunnels = TUNNELS.lock().await;
+    if let Some(tunnel) = tunnels.get_mut(&name) {
+        if tunnel.jump_host == jump_host && matches!(tunnel.child.try_wait(), Ok(None)) {
+            return Ok(Some(tunnel.api.clone()));
+        }
+        crate::logger::log_warning(format!(
+            "{} SSH tunnel through {} closed, reopening it",
+            "🪜", tunnel.jump_host
+        ));
+        tunnels.remove(&name);
+    }
+
+    let tunnel = open(context, &jump_host).await?;
+    let api = tunnel.api.clone();
+    tunnels.insert(name, tunnel);
+    Ok(Some(api))
+}
+
+async fn open(context: Option<&str>, jump_host: &str) -> Result<Tunnel> {
+    let options = KubeConfigOptions {
+        context: context.map(str::to_string),
+        ..Default::default()
+    };
+    let config = kube::Config::from_kubeconfig(&options)
+        .await
+        .context("Failed to load kubeconfig")?;
+    let url = &config.cluster_url;
+    let host = url.host().context(format!("API server URL {} has no host", url))?;
+    let scheme = url.scheme_str().unwrap_or("https");
+    let port = url
+        .port_u16()
+        .unwrap_or(if scheme == "http" { 80 } else { 443 });
+    let local_port = crate::forwarder::find_available_port()?;
+
+    let mut child = Command::new("ssh")
+        .arg("-N")
+        .args(["-o", "BatchMode=yes"])
+        .args(["-o", "ExitOnForwardFailure=yes"])
+        .args(["-o", "ServerAliveInterval=30"])
+        .arg("-L")
+        .arg(format!("127.0.0.1:{}:{}:{}", local_port, host, port))
+        .arg(ssh_destination(jump_host))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start ssh for the jump host")?;
+    crate::process::adopt_child(&child);
+
+    let deadline = tokio::time::Instant::now() + TUNNEL_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            let mut stderr = String::new();
+            if let Some(mut err) = child.stderr.take() {
+                use tokio::io::AsyncReadExt;
+                let _ = err.read_to_string(&mut stderr).await;
+            }
+            bail!("ssh to jump host {} exited with {}: {}", jump_host, status, stderr.trim());
+        }
+        if tokio::net::TcpStream::connect(("127.0.0.1", local_port)).await.is_ok() {
+            break;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            bail!("Timed out opening an SSH tunnel through {}", jump_host);
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    crate::logger::log_info(format!(
+        "{} Reaching the API server {}:{} through {} (localhost:{})",
+        "🪜", host, port, jump_host, local_port
+    ));
+    Ok(Tunnel {
+        api: ApiTunnel {
+            server: format!("{}://127.0.0.1:{}", scheme, local_port),
+            tls_server_name: config
+                .tls_server_name
+                .clone()
+                .unwrap_or_else(|| host.trim_matches(|c| c == '[' || c == ']').to_string()),
+        },
+        jump_host: jump_host.to_string(),
+        child,
+    })
+}
+
+/// `host:port` is not an ssh destination; `ssh://` URLs take the port inline.
+fn ssh_destination(jump_host: &str) -> String {
+    let host = jump_host.rsplit_once('@').map_or(jump_host, |(_, host)| host);
+    match host.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => format!("ssh://{}", jump_host),
+        _ => jump_host.to_string(),
+    }
+}
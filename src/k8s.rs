@@ -1,9 +1,139 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
 use k8s_openapi::api::core::v1::{Pod, Service};
-use kube::{api::Api, Client};
+use k8s_openapi::api::discovery::v1::EndpointSlice;
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::{
+    api::{Api, ListParams, PostParams},
+    Client,
+};
+use std::collections::HashSet;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::Duration;
 use tokio::process::Command;
 
+/// Longest kubectl may take to start listening once spawned.
+const KUBECTL_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+static PROTECTED_CONTEXTS: OnceLock<Vec<String>> = OnceLock::new();
+
+pub fn set_protected_contexts(contexts: Vec<String>) {
+    let _ = PROTECTED_CONTEXTS.set(contexts);
+}
+
+/// Set by `--yes-production`: every protected context may be forwarded to.
+static PRODUCTION_ALLOWED: AtomicBool = AtomicBool::new(false);
+/// Protected contexts the user confirmed forwarding to in this run.
+static CONFIRMED_CONTEXTS: LazyLock<Mutex<HashSet<String>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// A forward to a protected context that was not confirmed.
+#[derive(Debug, thiserror::Error)]
+#[error("Refusing to forward to protected context {0} without --yes-production")]
+pub struct ProtectedContextError(pub String);
+
+pub fn allow_protected_contexts() {
+    PRODUCTION_ALLOWED.store(true, Ordering::Relaxed);
+}
+
+pub fn confirm_protected_context(context: &str) {
+    CONFIRMED_CONTEXTS.lock().unwrap().insert(context.to_string());
+}
+
+/// Fails for a protected context unless the run was started with
+/// `--yes-production` or the user confirmed it. Every way of adding a forward
+/// goes through this check.
+pub fn check_protected_context(context: Option<&str>) -> Result<(), ProtectedContextError> {
+    if !is_protected_context(context) || PRODUCTION_ALLOWED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let context = effective_context(context).unwrap_or_default();
+    if CONFIRMED_CONTEXTS.lock().unwrap().contains(&context) {
+        return Ok(());
+    }
+    Err(ProtectedContextError(context))
+}
+
+pub fn current_context() -> Option<String> {
+    Kubeconfig::read().ok().and_then(|config| config.current_context)
+}
+
+/// The context a forward actually talks to: its explicit one, or the
+/// kubeconfig's current context when none is given.
+pub fn effective_context(context: Option<&str>) -> Option<String> {
+    context.map(str::to_string).or_else(current_context)
+}
+
+/// Namespace set on a kubeconfig context (the current one when `context` is
+/// `None`), the same one kubectl falls back to without `--namespace`.
+pub fn context_namespace(context: Option<&str>) -> Option<String> {
+    let kubeconfig = Kubeconfig::read().ok()?;
+    let name = context
+        .map(str::to_string)
+        .or_else(|| kubeconfig.current_context.clone())?;
+    kubeconfig
+        .contexts
+        .into_iter()
+        .find(|named| named.name == name)?
+        .context?
+        .namespace
+}
+
+pub fn is_protected_context(context: Option<&str>) -> bool {
+    let protected = PROTECTED_CONTEXTS.get().map(Vec::as_slice).unwrap_or_default();
+    effective_context(context).is_some_and(|ctx| protected.contains(&ctx))
+}
+
+pub async fn client_for_context(context: Option<&str>) -> Result<Client> {
+    if let Some(tunnel) = crate::jump::api_tunnel(context).await? {
+        let options = KubeConfigOptions {
+            context: context.map(str::to_string),
+            ..Default::default()
+        };
+        let mut config = kube::Config::from_kubeconfig(&options)
+            .await
+            .context("Failed to load kubeconfig")?;
+        config.cluster_url = tunnel.server.parse().context("Invalid tunnel URL")?;
+        config.tls_server_name = Some(tunnel.tls_server_name);
+        return Client::try_from(config).context("Failed to create Kubernetes client");
+    }
+    match context {
+        None => Client::try_default()
+            .await
+            .context("Failed to create Kubernetes client"),
+        Some(context) => {
+            let options = KubeConfigOptions {
+                context: Some(context.to_string()),
+                ..Default::default()
+            };
+            let config = kube::Config::from_kubeconfig(&options)
+                .await
+                .context(format!("Failed to load kubeconfig context {}", context))?;
+            Client::try_from(config).context("Failed to create Kubernetes client")
+        }
+    }
+}
+
+/// A `kubectl` command for `context`, pointed at its jump host tunnel if it
+/// has one.
+pub async fn kubectl(context: Option<&str>) -> Result<Command> {
+    let mut cmd = Command::new("kubectl");
+    if let Some(context) = context {
+        cmd.arg("--context").arg(context);
+    }
+    if let Some(tunnel) = crate::jump::api_tunnel(context).await? {
+        cmd.arg("--server")
+            .arg(tunnel.server)
+            .arg("--tls-server-name")
+            .arg(tunnel.tls_server_name);
+    }
+    Ok(cmd)
+}
+
 pub fn parse_resource(resource_str: &str) -> Result<(String, String, u16)> {
     // Format: type/name:port
     let parts: Vec<&str> = resource_str.split(':').collect();
@@ -23,14 +153,407 @@ pub fn parse_resource(resource_str: &str) -> Result<(String, String, u16)> {
     Ok((resource_type, resource_name, port))
 }
 
+/// Returns true for `type/name:*`, which asks for every port of the resource.
+pub fn is_all_ports(resource_str: &str) -> bool {
+    resource_str.ends_with(":*")
+}
+
+/// Expands `service/name:*` into one `service/name:port` entry per port declared
+/// on the Service spec. Any other resource string is returned unchanged.
+pub async fn expand_all_ports(
+    resource_str: &str,
+    namespace: &str,
+    context: Option<&str>,
+) -> Result<Vec<String>> {
+    if !is_all_ports(resource_str) {
+        return Ok(vec![resource_str.to_string()]);
+    }
+
+    let target = resource_str.trim_end_matches(":*");
+    let (resource_type, resource_name) = target
+        .split_once('/')
+        .ok_or_else(|| anyhow!("Invalid resource format. Expected type/name:*"))?;
+    if resource_type != "service" && resource_type != "svc" {
+        return Err(anyhow!(
+            "Forwarding all ports is only supported for services, got: {}",
+            resource_type
+        ));
+    }
+
+    let client = client_for_context(context).await?;
+    let services: Api<Service> = Api::namespaced(client, namespace);
+    let service = services
+        .get(resource_name)
+        .await
+        .context("Service not found")?;
+
+    let ports: Vec<String> = service
+        .spec
+        .and_then(|spec| spec.ports)
+        .unwrap_or_default()
+        .iter()
+        .filter(|port| port.protocol.as_deref().unwrap_or("TCP") == "TCP")
+        .map(|port| format!("{}/{}:{}", resource_type, resource_name, port.port))
+        .collect();
+
+    if ports.is_empty() {
+        return Err(anyhow!("Service {} declares no TCP ports", resource_name));
+    }
+
+    Ok(ports)
+}
+
+/// One `service/name:port` entry per TCP port of every service of a Helm
+/// release, found by the `app.kubernetes.io/instance` label, or the `release`
+/// label older charts set.
+pub async fn release_services(release: &str, namespace: &str, context: Option<&str>) -> Result<Vec<String>> {
+    let client = client_for_context(context).await?;
+    let services: Api<Service> = Api::namespaced(client, namespace);
+    let mut found = Vec::new();
+    for label in ["app.kubernetes.io/instance", "release"] {
+        found = services
+            .list(&ListParams::default().labels(&format!("{}={}", label, release)))
+            .await
+            .context(format!("Failed to list the services of release {}", release))?
+            .items;
+        if !found.is_empty() {
+            break;
+        }
+    }
+
+    let mut resources: Vec<String> = found
+        .into_iter()
+        .flat_map(|service| {
+            let name = service.metadata.name.unwrap_or_default();
+            service
+                .spec
+                .and_then(|spec| spec.ports)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|port| port.protocol.as_deref().unwrap_or("TCP") == "TCP")
+                .map(move |port| format!("service/{}:{}", name, port.port))
+        })
+        .collect();
+    if resources.is_empty() {
+        return Err(anyhow!("No services of Helm release {} in {}", release, namespace));
+    }
+    resources.sort();
+    Ok(resources)
+}
+
+/// Whether `resource_str` names its port, unlike `svc/foo`.
+pub fn has_port(resource_str: &str) -> bool {
+    resource_str.contains(':')
+}
+
+/// Ports declared by a `type/name` given without one, as (port, name): the
+/// TCP ports of a service, or the container ports of a pod.
+pub async fn declared_ports(
+    resource: &str,
+    namespace: &str,
+    context: Option<&str>,
+) -> Result<Vec<(u16, Option<String>)>> {
+    let (resource_type, resource_name) = resource
+        .split_once('/')
+        .ok_or_else(|| anyhow!("Invalid resource format. Expected type/name[:port]"))?;
+    let client = client_for_context(context).await?;
+    match resource_type {
+        "service" | "svc" => {
+            let services: Api<Service> = Api::namespaced(client, namespace);
+            let service = services
+                .get(resource_name)
+                .await
+                .context(format!("Service {} not found in {}", resource_name, namespace))?;
+            Ok(service
+                .spec
+                .and_then(|spec| spec.ports)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|port| port.protocol.as_deref().unwrap_or("TCP") == "TCP")
+                .map(|port| (port.port as u16, port.name))
+                .collect())
+        }
+        "pod" | "po" => {
+            let pods: Api<Pod> = Api::namespaced(client, namespace);
+            let pod = pods
+                .get(resource_name)
+                .await
+                .context(format!("Pod {} not found in {}", resource_name, namespace))?;
+            Ok(pod
+                .spec
+                .map(|spec| spec.containers)
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|container| container.ports.unwrap_or_default())
+                .filter(|port| port.protocol.as_deref().unwrap_or("TCP") == "TCP")
+                .map(|port| (port.container_port as u16, port.name))
+                .collect())
+        }
+        other => Err(anyhow!(
+            "Cannot look up the ports of a {}; give one as {}:<port>",
+            other,
+            resource
+        )),
+    }
+}
+
+/// Lists ports as `80 (http), 9090`.
+pub fn describe_ports(ports: &[(u16, Option<String>)]) -> String {
+    ports
+        .iter()
+        .map(|(port, name)| match name {
+            Some(name) => format!("{} ({})", port, name),
+            None => port.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The concrete pod behind a forward and the node it runs on.
+#[derive(Clone, Debug)]
+pub struct TargetPod {
+    pub name: String,
+    pub node: Option<String>,
+}
+
+/// Returns the pod a forward lands on: the pod itself, or for a service the
+/// first running, ready and not terminating pod matched by its selector.
+pub async fn resolve_target_pod(
+    resource_type: &str,
+    resource_name: &str,
+    namespace: &str,
+    context: Option<&str>,
+) -> Result<TargetPod> {
+    let client = client_for_context(context).await?;
+    let pods: Api<Pod> = Api::namespaced(client.clone(), namespace);
+
+    if resource_type == "pod" {
+        let pod = pods.get(resource_name).await.context("Pod not found")?;
+        return Ok(TargetPod {
+            name: resource_name.to_string(),
+            node: pod.spec.and_then(|spec| spec.node_name),
+        });
+    }
+
+    let services: Api<Service> = Api::namespaced(client.clone(), namespace);
+    let service = services
+        .get(resource_name)
+        .await
+        .context("Service not found")?;
+    let selector = service
+        .spec
+        .and_then(|spec| spec.selector)
+        .filter(|selector| !selector.is_empty())
+        .ok_or_else(|| anyhow!("Service {} has no selector", resource_name))?
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    ready_pod(&pods, &selector)
+        .await?
+        .ok_or_else(|| anyhow!("No ready pod found for service {}", resource_name))
+}
+
+/// The first running, ready and not terminating pod matching `selector`.
+async fn ready_pod(pods: &Api<Pod>, selector: &str) -> Result<Option<TargetPod>> {
+    let pod_list = pods
+        .list(&ListParams::default().labels(selector))
+        .await
+        .context("Failed to list pods")?;
+
+    Ok(pod_list.items.into_iter().find(is_pod_ready).and_then(|pod| {
+        Some(TargetPod {
+            name: pod.metadata.name?,
+            node: pod.spec.and_then(|spec| spec.node_name),
+        })
+    }))
+}
+
+/// A ready pod matching a forward's `selector`.
+pub async fn resolve_selector(selector: &str, namespace: &str, context: Option<&str>) -> Result<TargetPod> {
+    let client = client_for_context(context).await?;
+    let pods: Api<Pod> = Api::namespaced(client, namespace);
+    ready_pod(&pods, selector)
+        .await?
+        .ok_or_else(|| anyhow!("No ready pod matches {} in {}", selector, namespace))
+}
+
+/// Resolves the pod behind a forward together with the container port that
+/// `resource_port` maps to. For services a ready endpoint is picked from the
+/// EndpointSlices, so terminating pods are skipped. Without access to
+/// EndpointSlices, the pods matching the selector are used and the port's
+/// `targetPort` is followed, including named ports.
+pub async fn resolve_container_port(
+    resource_type: &str,
+    resource_name: &str,
+    resource_port: u16,
+    protocol: &str,
+    selector: Option<&str>,
+    namespace: &str,
+    context: Option<&str>,
+) -> Result<(TargetPod, u16)> {
+    if let Some(selector) = selector {
+        let target = resolve_selector(selector, namespace, context).await?;
+        return Ok((target, resource_port));
+    }
+    if resource_type == "pod" {
+        let target = resolve_target_pod(resource_type, resource_name, namespace, context).await?;
+        return Ok((target, resource_port));
+    }
+
+    match resolve_ready_endpoint(resource_name, resource_port, protocol, namespace, context).await {
+        Err(e) if e.downcast_ref::<kube::Error>().is_some() => {
+            resolve_selected_pod_port(resource_name, resource_port, protocol, namespace, context).await
+        }
+        resolved => resolved,
+    }
+}
+
+/// The service port `resource_port`, matched on protocol too.
+async fn service_port(
+    client: Client,
+    resource_name: &str,
+    resource_port: u16,
+    protocol: &str,
+    namespace: &str,
+) -> Result<k8s_openapi::api::core::v1::ServicePort> {
+    let services: Api<Service> = Api::namespaced(client, namespace);
+    let service = services
+        .get(resource_name)
+        .await
+        .context("Service not found")?;
+    service
+        .spec
+        .and_then(|spec| spec.ports)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|port| {
+            port.port == resource_port as i32
+                && port.protocol.as_deref().unwrap_or("TCP").eq_ignore_ascii_case(protocol)
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Service {} has no {} port {}",
+                resource_name,
+                protocol.to_uppercase(),
+                resource_port
+            )
+        })
+}
+
+/// Picks a ready, non-terminating pod endpoint of a service from its
+/// EndpointSlices, with the pod port the service port maps to. Each slice
+/// carries its own resolved ports, so pods mid-rollout with a different
+/// `targetPort` get the right one.
+pub async fn resolve_ready_endpoint(
+    resource_name: &str,
+    resource_port: u16,
+    protocol: &str,
+    namespace: &str,
+    context: Option<&str>,
+) -> Result<(TargetPod, u16)> {
+    let client = client_for_context(context).await?;
+    let service_port =
+        service_port(client.clone(), resource_name, resource_port, protocol, namespace).await?;
+
+    let slices: Api<EndpointSlice> = Api::namespaced(client, namespace);
+    let slices = slices
+        .list(&ListParams::default().labels(&format!("kubernetes.io/service-name={}", resource_name)))
+        .await
+        .context("Failed to list EndpointSlices")?;
+
+    for slice in slices.items {
+        let Some(port) = slice.ports.unwrap_or_default().into_iter().find(|port| {
+            port.name == service_port.name
+                && port.protocol.as_deref().unwrap_or("TCP").eq_ignore_ascii_case(protocol)
+        }) else {
+            continue;
+        };
+        let Some(port) = port.port else {
+            continue;
+        };
+        let ready = slice.endpoints.into_iter().find_map(|endpoint| {
+            let conditions = endpoint.conditions.unwrap_or_default();
+            let usable = conditions.ready == Some(true) && conditions.terminating != Some(true);
+            let pod = endpoint.target_ref.filter(|target| target.kind.as_deref() == Some("Pod"))?;
+            usable.then(|| TargetPod {
+                name: pod.name.unwrap_or_default(),
+                node: endpoint.node_name,
+            })
+        });
+        if let Some(pod) = ready.filter(|pod| !pod.name.is_empty()) {
+            return Ok((pod, port as u16));
+        }
+    }
+
+    Err(anyhow!(
+        "No ready endpoint for service {} port {}",
+        resource_name,
+        resource_port
+    ))
+}
+
+/// Fallback of `resolve_container_port` for services when EndpointSlices
+/// cannot be read: a ready pod matched by the selector.
+async fn resolve_selected_pod_port(
+    resource_name: &str,
+    resource_port: u16,
+    protocol: &str,
+    namespace: &str,
+    context: Option<&str>,
+) -> Result<(TargetPod, u16)> {
+    use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+
+    let target = resolve_target_pod("service", resource_name, namespace, context).await?;
+    let client = client_for_context(context).await?;
+    let service_port =
+        service_port(client.clone(), resource_name, resource_port, protocol, namespace).await?;
+
+    let container_port = match service_port.target_port {
+        None => resource_port,
+        Some(IntOrString::Int(port)) => port as u16,
+        Some(IntOrString::String(name)) => {
+            let pods: Api<Pod> = Api::namespaced(client, namespace);
+            let pod = pods.get(&target.name).await.context("Pod not found")?;
+            pod.spec
+                .map(|spec| spec.containers)
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|container| container.ports.clone().unwrap_or_default())
+                .find(|port| port.name.as_deref() == Some(name.as_str()))
+                .map(|port| port.container_port as u16)
+                .ok_or_else(|| anyhow!("Pod {} has no port named {}", target.name, name))?
+        }
+    };
+
+    Ok((target, container_port))
+}
+
+fn is_pod_ready(pod: &Pod) -> bool {
+    let Some(status) = &pod.status else {
+        return false;
+    };
+    // A pod being deleted keeps its Ready condition for a while
+    pod.metadata.deletion_timestamp.is_none()
+        && status.phase.as_deref() == Some("Running")
+        && status
+            .conditions
+            .as_ref()
+            .is_some_and(|conditions| {
+                conditions
+                    .iter()
+                    .any(|c| c.type_ == "Ready" && c.status == "True")
+            })
+}
+
 pub async fn validate_resource(
     resource_type: &str,
     resource_name: &str,
     namespace: &str,
+    context: Option<&str>,
 ) -> Result<()> {
-    let client = Client::try_default()
-        .await
-        .context("Failed to create Kubernetes client")?;
+    let client = client_for_context(context).await?;
 
     match resource_type {
         "pod" => {
@@ -50,39 +573,256 @@ pub async fn validate_resource(
     Ok(())
 }
 
+/// A permission as (verb, API group, resource, subresource).
+pub type Permission = (&'static str, &'static str, &'static str, Option<&'static str>);
+
+/// Permissions a forward to a `resource_type` target needs in its namespace:
+/// reading the target and opening a port-forward to its pod, or, through the
+/// API server proxy, using the target's proxy subresource.
+pub fn required_permissions(resource_type: &str, api_server_proxy: bool) -> Vec<Permission> {
+    let resource = match resource_type {
+        "service" | "svc" => "services",
+        _ => "pods",
+    };
+    if api_server_proxy {
+        return vec![("get", "", resource, None), ("get", "", resource, Some("proxy"))];
+    }
+    let mut permissions = vec![("get", "", "pods", None)];
+    if resource == "services" {
+        permissions.push(("get", "", "services", None));
+    }
+    permissions.push(("create", "", "pods", Some("portforward")));
+    permissions
+}
+
+/// `verb resource/subresource`, as `kubectl auth can-i` takes it.
+pub fn describe_permission(verb: &str, resource: &str, subresource: Option<&str>) -> String {
+    match subresource {
+        Some(subresource) => format!("{} {}/{}", verb, resource, subresource),
+        None => format!("{} {}", verb, resource),
+    }
+}
+
+pub async fn can_i(
+    reviews: &Api<SelfSubjectAccessReview>,
+    namespace: &str,
+    verb: &str,
+    group: &str,
+    resource: &str,
+    subresource: Option<&str>,
+) -> Result<bool> {
+    let review = SelfSubjectAccessReview {
+        spec: SelfSubjectAccessReviewSpec {
+            resource_attributes: Some(ResourceAttributes {
+                namespace: Some(namespace.to_string()),
+                verb: Some(verb.to_string()),
+                group: Some(group.to_string()),
+                resource: Some(resource.to_string()),
+                subresource: subresource.map(str::to_string),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let review = reviews
+        .create(&PostParams::default(), &review)
+        .await
+        .context("SelfSubjectAccessReview failed")?;
+    Ok(review.status.is_some_and(|status| status.allowed))
+}
+
+/// Fails, naming what is missing, when RBAC denies any of `permissions` in
+/// `namespace`. When the API server does not answer the review, e.g. because
+/// reviews are not allowed either, nothing is checked and kubectl reports
+/// whatever goes wrong.
+pub async fn check_access(permissions: &[Permission], namespace: &str, context: Option<&str>) -> Result<()> {
+    let Ok(client) = client_for_context(context).await else {
+        return Ok(());
+    };
+    let reviews: Api<SelfSubjectAccessReview> = Api::all(client);
+    let mut missing = Vec::new();
+    for (verb, group, resource, subresource) in permissions {
+        if let Ok(false) = can_i(&reviews, namespace, verb, group, resource, *subresource).await {
+            missing.push(describe_permission(verb, resource, *subresource));
+        }
+    }
+    if let Some(first) = missing.first() {
+        let context_arg = context.map(|context| format!(" --context {}", context)).unwrap_or_default();
+        bail!(
+            "missing RBAC: {} in namespace {}. Ask a cluster admin for a Role granting it (check with kubectl auth can-i {} -n {}{})",
+            missing.join(", "),
+            namespace,
+            first,
+            namespace,
+            context_arg
+        );
+    }
+    Ok(())
+}
+
 pub async fn create_port_forward(
     resource_type: &str,
     resource_name: &str,
     resource_port: u16,
     local_port: u16,
     namespace: &str,
+    context: Option<&str>,
     child_handle: std::sync::Arc<tokio::sync::Mutex<Option<tokio::process::Child>>>,
 ) -> Result<impl futures::Future<Output = Result<()>>> {
     // Validate that the resource exists
-    if let Err(e) = validate_resource(resource_type, resource_name, namespace).await {
+    if let Err(e) = validate_resource(resource_type, resource_name, namespace, context).await {
         crate::logger::log_error(format!("Resource validation failed: {}", e));
         return Err(e);
     }
 
     // Use kubectl port-forward command
-    let mut cmd = Command::new("kubectl");
+    let mut cmd = kubectl(context).await?;
     cmd.arg("port-forward")
         .arg("-n")
         .arg(namespace)
         .arg(format!("{}/{}", resource_type, resource_name))
-        .arg(format!("{}:{}", local_port, resource_port))
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+        .arg(format!("{}:{}", local_port, resource_port));
+    let target = format!("{}/{}", resource_type, resource_name);
+    supervise_kubectl(cmd, "port-forward", target, "Forwarding from", child_handle).await
+}
+
+/// Starts `kubectl proxy` on `local_port` for a forward in apiserver-proxy
+/// mode. Only `path`, the forward's proxy path, is served, so other local
+/// processes cannot use it to reach the rest of the API.
+pub async fn create_api_server_proxy(
+    resource_type: &str,
+    resource_name: &str,
+    path: &str,
+    local_port: u16,
+    namespace: &str,
+    context: Option<&str>,
+    child_handle: std::sync::Arc<tokio::sync::Mutex<Option<tokio::process::Child>>>,
+) -> Result<impl futures::Future<Output = Result<()>>> {
+    if let Err(e) = validate_resource(resource_type, resource_name, namespace, context).await {
+        crate::logger::log_error(format!("Resource validation failed: {}", e));
+        return Err(e);
+    }
+
+    let mut cmd = kubectl(context).await?;
+    cmd.arg("proxy")
+        .arg("--address=127.0.0.1")
+        .arg(format!("--port={}", local_port))
+        .arg(format!("--accept-paths=^{}(/.*)?$", regex::escape(path)));
+    let target = format!("{}/{}", resource_type, resource_name);
+    supervise_kubectl(cmd, "proxy", target, "Starting to serve on", child_handle).await
+}
 
-    let child = cmd
+/// Path of a forward's port under the API server's proxy: the service (or
+/// pod) proxy subresource, with the scheme the port speaks.
+pub fn api_server_proxy_path(
+    resource_type: &str,
+    resource_name: &str,
+    resource_port: u16,
+    namespace: &str,
+    https: bool,
+) -> String {
+    let kind = match resource_type {
+        "pod" | "po" => "pods",
+        _ => "services",
+    };
+    let scheme = if https { "https:" } else { "" };
+    format!(
+        "/api/v1/namespaces/{}/{}/{}{}:{}/proxy",
+        namespace, kind, scheme, resource_name, resource_port
+    )
+}
+
+/// The klog prefix (`E1016 12:00:00.000000   4242 portforward.go:413] `)
+/// kubectl's stderr lines may carry; the first letter is the severity.
+static KLOG_PREFIX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[IWEF]\d{4} [\d:.]+\s+\d+ [^\]]+\] ").unwrap());
+
+/// kubectl's message on a stderr line, without the klog prefix it may carry.
+fn kubectl_message(line: &str) -> &str {
+    let line = line.trim();
+    KLOG_PREFIX.find(line).map_or(line, |prefix| &line[prefix.end()..])
+}
+
+/// Whether a kubectl stderr line reports a problem, rather than progress
+/// like `Handling connection for 8080`: klog lines by their severity, plain
+/// lines by their wording.
+fn is_kubectl_error(line: &str) -> bool {
+    let line = line.trim();
+    if KLOG_PREFIX.is_match(line) {
+        return !line.starts_with('I');
+    }
+    let line = line.to_lowercase();
+    ["error", "unable", "fail", "forbidden", "denied", "refused", "timeout", "timed out", "lost connection", "not found"]
+        .iter()
+        .any(|word| line.contains(word))
+}
+
+/// Spawns a long-running kubectl `command` and waits until it prints
+/// `ready_line`, so it is listening when this returns. The returned future
+/// ends with kubectl, failing with its last error when it exits
+/// unsuccessfully. stderr is logged line by line as kubectl writes it.
+async fn supervise_kubectl(
+    mut cmd: Command,
+    command: &'static str,
+    target: String,
+    ready_line: &'static str,
+    child_handle: std::sync::Arc<tokio::sync::Mutex<Option<tokio::process::Child>>>,
+) -> Result<impl futures::Future<Output = Result<()>>> {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+    let mut child = cmd
         .spawn()
-        .context("Failed to start kubectl port-forward")?;
+        .context(format!("Failed to start kubectl {}", command))?;
+    crate::process::adopt_child(&child);
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
     {
         let mut handle = child_handle.lock().await;
         *handle = Some(child);
     }
 
-    Ok(async move {
+    // stdout is read to the end, or kubectl blocks once the pipe is full of
+    // "Handling connection for" lines
+    let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+    if let Some(stdout) = stdout {
+        tokio::spawn(async move {
+            let mut ready_tx = Some(ready_tx);
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.starts_with(ready_line) {
+                    if let Some(ready_tx) = ready_tx.take() {
+                        let _ = ready_tx.send(());
+                    }
+                }
+            }
+        });
+    }
+    let last_error = std::sync::Arc::new(std::sync::Mutex::new(None::<String>));
+    let stderr_done = stderr.map(|stderr| {
+        let last_error = last_error.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let raw = line;
+                let line = kubectl_message(&raw);
+                if line.is_empty() {
+                    continue;
+                }
+                let message = format!("{} kubectl {} {}: {}", "🔌", command, target, line);
+                if !is_kubectl_error(&raw) {
+                    crate::logger::log_info(message);
+                    continue;
+                }
+                crate::logger::log_warning(message);
+                *last_error.lock().unwrap() = Some(line.to_string());
+            }
+        })
+    });
+
+    let started = child_handle.clone();
+    let exited = async move {
         let child_opt = {
             let mut handle = child_handle.lock().await;
             handle.take()
@@ -94,16 +834,36 @@ pub async fn create_port_forward(
                 .context("Failed to wait for kubectl process")?;
 
             if !status.success() {
-                let mut stderr = String::new();
-                if let Some(mut err) = child.stderr.take() {
-                    use tokio::io::AsyncReadExt;
-                    let _ = err.read_to_string(&mut stderr).await;
+                if let Some(stderr_done) = stderr_done {
+                    let _ = stderr_done.await;
                 }
-
-                return Err(anyhow!("kubectl port-forward failed: {}", stderr));
+                let error = last_error.lock().unwrap().take();
+                return Err(anyhow!(
+                    "kubectl {} failed: {}",
+                    command,
+                    error.unwrap_or_else(|| status.to_string())
+                ));
             }
         }
 
         Ok(())
-    })
+    };
+
+    // The ready line never comes when kubectl exits first, which closes stdout
+    match tokio::time::timeout(KUBECTL_READY_TIMEOUT, ready_rx).await {
+        Ok(Ok(())) => Ok(exited),
+        Ok(Err(_)) => match exited.await {
+            Err(e) => Err(e),
+            Ok(()) => Err(anyhow!("kubectl {} exited before it started listening", command)),
+        },
+        Err(_) => {
+            // Dropping the child kills it
+            drop(started.lock().await.take());
+            Err(anyhow!(
+                "kubectl {} did not start listening within {}s",
+                command,
+                KUBECTL_READY_TIMEOUT.as_secs()
+            ))
+        }
+    }
 }
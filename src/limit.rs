@@ -0,0 +1,225 @@
+//! A forward's `concurrency` limit and the global `--max-in-flight`: caps on
+//! requests in flight, so a burst of clients neither exhausts the local
+//! machine nor floods the single pod behind a port-forward.
+
+use crate::config::ConcurrencyConfig;
+use hyper::header::{HeaderValue, RETRY_AFTER};
+use hyper::{Body, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Defaults for fields left out of a `concurrency` block.
+const DEFAULT_MAX_QUEUED: usize = 100;
+const DEFAULT_QUEUE_TIMEOUT_MS: u64 = 10_000;
+const DEFAULT_RETRY_AFTER_SECS: u64 = 1;
+
+static LIMITERS: LazyLock<Mutex<HashMap<String, Arc<Limiter>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The limit across all forwards (`--max-in-flight`).
+static GLOBAL: OnceLock<Arc<Limiter>> = OnceLock::new();
+
+/// Caps the requests in flight, letting a bounded number of others wait for
+/// a slot.
+struct Limiter {
+    slots: Arc<Semaphore>,
+    max_in_flight: usize,
+    max_queued: usize,
+    queued: AtomicUsize,
+    queue_timeout: Duration,
+    retry_after: Duration,
+}
+
+impl From<&ConcurrencyConfig> for Limiter {
+    fn from(config: &ConcurrencyConfig) -> Self {
+        let max_in_flight = config.max_in_flight.max(1);
+        Limiter {
+            slots: Arc::new(Semaphore::new(max_in_flight)),
+            max_in_flight,
+            max_queued: config.max_queued.unwrap_or(DEFAULT_MAX_QUEUED),
+            queued: AtomicUsize::new(0),
+            queue_timeout: Duration::from_millis(config.queue_timeout_ms.unwrap_or(DEFAULT_QUEUE_TIMEOUT_MS)),
+            retry_after: Duration::from_secs(config.retry_after_secs.unwrap_or(DEFAULT_RETRY_AFTER_SECS)),
+        }
+    }
+}
+
+impl Limiter {
+    async fn acquire(&self) -> Result<OwnedSemaphorePermit, Saturated> {
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+        let saturated = Saturated {
+            max_in_flight: self.max_in_flight,
+            retry_after: self.retry_after,
+        };
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst);
+        let _leave = QueueSlot(&self.queued);
+        if queued >= self.max_queued {
+            return Err(saturated);
+        }
+        match tokio::time::timeout(self.queue_timeout, self.slots.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => Err(saturated),
+        }
+    }
+
+    fn usage(&self) -> Usage {
+        Usage {
+            in_flight: self.max_in_flight - self.slots.available_permits(),
+            max_in_flight: self.max_in_flight,
+            queued: self.queued.load(Ordering::SeqCst).min(self.max_queued),
+        }
+    }
+}
+
+/// Leaves the queue when dropped, whether the wait ended or was cancelled.
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A request's slot in the forward's and the global limit, held until its
+/// response has been read.
+pub struct Permit {
+    _forward: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+/// Why a request was turned away: the limit was reached and the queue was
+/// full or the wait timed out.
+pub struct Saturated {
+    pub max_in_flight: usize,
+    /// Sent as `Retry-After`
+    pub retry_after: Duration,
+}
+
+impl Saturated {
+    /// The `503` a turned away request is answered with.
+    pub fn response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(format!(
+            "Service Unavailable: more than {} requests in flight",
+            self.max_in_flight
+        )));
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(self.retry_after.as_secs().max(1)));
+        response
+    }
+}
+
+/// Requests in flight and waiting under a limit.
+pub struct Usage {
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+    pub queued: usize,
+}
+
+/// Registers the concurrency limit of a forward, replacing any earlier one.
+pub fn configure(key: &str, config: Option<&ConcurrencyConfig>) {
+    let mut limiters = LIMITERS.lock().unwrap();
+    match config {
+        Some(config) => limiters.insert(key.to_string(), Arc::new(Limiter::from(config))),
+        None => limiters.remove(key),
+    };
+}
+
+/// Sets the limit shared by all forwards.
+pub fn set_global(config: &ConcurrencyConfig) {
+    let _ = GLOBAL.set(Arc::new(Limiter::from(config)));
+}
+
+/// Takes a slot for a request to `key`, waiting in the queue when the
+/// forward's or the global limit is reached.
+pub async fn acquire(key: &str) -> Result<Permit, Saturated> {
+    let forward = LIMITERS.lock().unwrap().get(key).cloned();
+    let forward = match forward {
+        Some(limiter) => Some(limiter.acquire().await?),
+        None => None,
+    };
+    let global = match GLOBAL.get() {
+        Some(limiter) => Some(limiter.acquire().await?),
+        None => None,
+    };
+    Ok(Permit {
+        _forward: forward,
+        _global: global,
+    })
+}
+
+/// Usage of a forward's own limit, if it has one.
+pub fn usage(key: &str) -> Option<Usage> {
+    LIMITERS.lock().unwrap().get(key).map(|limiter| limiter.usage())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_in_flight: usize, max_queued: usize, queue_timeout_ms: u64) -> Arc<Limiter> {
+        Arc::new(Limiter::from(&ConcurrencyConfig {
+            max_in_flight,
+            max_queued: Some(max_queued),
+            queue_timeout_ms: Some(queue_timeout_ms),
+            retry_after_secs: Some(3),
+        }))
+    }
+
+    fn assert_turned_away(saturated: Saturated) {
+        assert_eq!(saturated.max_in_flight, 1);
+        let response = saturated.response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "3");
+    }
+
+    #[tokio::test]
+    async fn full_queue_turns_requests_away() {
+        let limiter = limiter(1, 1, 10_000);
+        let in_flight = limiter.acquire().await.ok().unwrap();
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.is_ok() }
+        });
+        while limiter.usage().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_turned_away(limiter.acquire().await.err().unwrap());
+        assert_eq!(limiter.usage().queued, 1);
+
+        drop(in_flight);
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn queued_requests_time_out() {
+        let limiter = limiter(1, 10, 20);
+        let _in_flight = limiter.acquire().await.ok().unwrap();
+        assert_turned_away(limiter.acquire().await.err().unwrap());
+        assert_eq!(limiter.usage().queued, 0);
+        assert_eq!(limiter.usage().in_flight, 1);
+    }
+
+    #[tokio::test]
+    async fn released_slots_are_reused() {
+        let limiter = limiter(1, 0, 20);
+        drop(limiter.acquire().await.ok().unwrap());
+        let _in_flight = limiter.acquire().await.ok().unwrap();
+        assert_turned_away(limiter.acquire().await.err().unwrap());
+    }
+
+    #[test]
+    fn retry_after_is_at_least_a_second() {
+        let saturated = Saturated {
+            max_in_flight: 1,
+            retry_after: Duration::ZERO,
+        };
+        assert_eq!(saturated.response().headers()[RETRY_AFTER], "1");
+    }
+}
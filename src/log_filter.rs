@@ -0,0 +1,163 @@
+//! A forward's `log_exclude` and the global `--log-exclude`: request paths
+//! left out of the TUI request log and the requests log file, e.g. health
+//! checks and static assets. Slow responses and requests that could not be
+//! forwarded are still logged, as at verbosity 0.
+
+use crate::config::LogExcludeConfig;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+
+static LOG_FILTERS: LazyLock<Mutex<HashMap<String, Arc<LogFilter>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Globs of `--log-exclude`, applied to every forward.
+static GLOBAL: OnceLock<LogFilter> = OnceLock::new();
+
+/// Compiled `paths` globs and `patterns` regexes; a path matching any of
+/// them is excluded.
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    matchers: Vec<Regex>,
+}
+
+impl LogFilter {
+    pub fn new(config: &LogExcludeConfig) -> Result<LogFilter> {
+        let mut matchers = Vec::new();
+        for glob in config.paths.iter().flatten() {
+            matchers.push(glob_regex(glob)?);
+        }
+        for pattern in config.patterns.iter().flatten() {
+            matchers.push(
+                Regex::new(pattern).context(format!("Invalid log_exclude pattern '{}'", pattern))?,
+            );
+        }
+        Ok(LogFilter { matchers })
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.matchers.iter().any(|matcher| matcher.is_match(path))
+    }
+}
+
+/// Turns a path glob into an anchored regex: `*` matches within a segment,
+/// `**` across segments and `?` a single character.
+fn glob_regex(glob: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).context(format!("Invalid log_exclude path '{}'", glob))
+}
+
+/// Registers the log exclusions of a forward, replacing any earlier ones.
+pub fn configure(key: &str, config: Option<&LogExcludeConfig>) -> Result<()> {
+    let filter = config.map(LogFilter::new).transpose()?;
+    let mut registry = LOG_FILTERS.lock().unwrap();
+    match filter {
+        Some(filter) => registry.insert(key.to_string(), Arc::new(filter)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// Sets the globs of `--log-exclude`.
+pub fn set_global(globs: &[String]) -> Result<()> {
+    let filter = LogFilter::new(&LogExcludeConfig {
+        paths: Some(globs.to_vec()),
+        patterns: None,
+    })?;
+    let _ = GLOBAL.set(filter);
+    Ok(())
+}
+
+/// Whether requests to `path` on a forward are left out of the request logs.
+pub fn is_excluded(key: &str, path: &str) -> bool {
+    GLOBAL.get().is_some_and(|filter| filter.matches(path))
+        || LOG_FILTERS
+            .lock()
+            .unwrap()
+            .get(key)
+            .is_some_and(|filter| filter.matches(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(paths: &[&str], patterns: &[&str]) -> LogFilter {
+        LogFilter::new(&LogExcludeConfig {
+            paths: Some(paths.iter().map(|path| path.to_string()).collect()),
+            patterns: Some(patterns.iter().map(|pattern| pattern.to_string()).collect()),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn single_star_stays_within_a_segment() {
+        let filter = filter(&["/static/*"], &[]);
+        assert!(filter.matches("/static/app.js"));
+        assert!(!filter.matches("/static/js/app.js"));
+        assert!(!filter.matches("/api/static/app.js"));
+    }
+
+    #[test]
+    fn double_star_crosses_segments() {
+        let filter = filter(&["/static/**"], &[]);
+        assert!(filter.matches("/static/js/app.js"));
+        assert!(!filter.matches("/api/users"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        let filter = filter(&["/v?/health"], &[]);
+        assert!(filter.matches("/v1/health"));
+        assert!(!filter.matches("/v10/health"));
+        assert!(!filter.matches("/v//health"));
+    }
+
+    #[test]
+    fn globs_are_anchored_and_literal() {
+        let filter = filter(&["/health.json"], &[]);
+        assert!(filter.matches("/health.json"));
+        assert!(!filter.matches("/healthzjson"));
+        assert!(!filter.matches("/health.json/x"));
+    }
+
+    #[test]
+    fn patterns_are_unanchored_regexes() {
+        let filter = filter(&[], &[r"\.(png|css)$"]);
+        assert!(filter.matches("/assets/logo.png"));
+        assert!(!filter.matches("/assets/logo.png.txt"));
+    }
+
+    #[test]
+    fn invalid_pattern_is_rejected() {
+        let config = LogExcludeConfig { paths: None, patterns: Some(vec!["(".to_string()]) };
+        assert!(LogFilter::new(&config).is_err());
+    }
+
+    #[test]
+    fn excluded_per_forward() {
+        configure("service/log-filter-test:80", Some(&LogExcludeConfig {
+            paths: Some(vec!["/healthz".to_string()]),
+            patterns: None,
+        }))
+        .unwrap();
+        assert!(is_excluded("service/log-filter-test:80", "/healthz"));
+        assert!(!is_excluded("service/other:80", "/healthz"));
+        configure("service/log-filter-test:80", None).unwrap();
+        assert!(!is_excluded("service/log-filter-test:80", "/healthz"));
+    }
+}
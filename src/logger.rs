@@ -1,81 +1,182 @@
-use std::sync::{mpsc, Mutex, OnceLock};
+use crate::tui::{LogEntry, LogLevel};
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{mpsc, Mutex, OnceLock, RwLock};
 use tracing_subscriber::{fmt, EnvFilter};
 
-static LOG_SENDER: OnceLock<Mutex<Option<mpsc::Sender<crate::tui::LogEntry>>>> = OnceLock::new();
+/// A destination for log entries. Sinks are shared between the TUI thread and
+/// the tokio workers, so implementations must be thread-safe.
+pub trait LogSink: Send + Sync {
+    fn write(&self, entry: &LogEntry);
+}
+
+/// Sends entries to the TUI log panel.
+pub struct TuiSink {
+    sender: mpsc::Sender<LogEntry>,
+}
+
+impl LogSink for TuiSink {
+    fn write(&self, entry: &LogEntry) {
+        if let Err(e) = self.sender.send(entry.clone()) {
+            eprintln!("Failed to send log to TUI: {}", e);
+        }
+    }
+}
+
+/// Prints entries with an emoji prefix; errors go to stderr. With `--output
+/// json` entries are printed as `log` events instead. This is the fallback
+/// when no other sink is registered.
+pub struct StdoutSink;
+
+impl LogSink for StdoutSink {
+    fn write(&self, entry: &LogEntry) {
+        if crate::output::is_json() {
+            crate::output::log(entry);
+            return;
+        }
+        match entry.level {
+            LogLevel::Error => eprintln!("{}", prefixed(entry)),
+            _ => println!("{}", prefixed(entry)),
+        }
+    }
+}
+
+/// Prints entries like `StdoutSink`, but all of them to stderr, leaving
+/// stdout to the command of `kpf run`.
+pub struct StderrSink;
+
+impl LogSink for StderrSink {
+    fn write(&self, entry: &LogEntry) {
+        eprintln!("{}", prefixed(entry));
+    }
+}
+
+/// The message with an emoji for its level.
+fn prefixed(entry: &LogEntry) -> String {
+    let emoji = match entry.level {
+        LogLevel::Info => "ℹ️",
+        LogLevel::Success => "✅",
+        LogLevel::Warning | LogLevel::Slow => "⚠️",
+        LogLevel::Error => "❌",
+    };
+    format!("{} {}", emoji, entry.message)
+}
+
+/// Appends entries with timestamp and level to a file.
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path.as_ref())
+            .context(format!("Failed to open log file {}", path.as_ref().display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&self, entry: &LogEntry) {
+        let mut file = self.file.lock().unwrap();
+        let _ = writeln!(file, "{}", format_entry(entry));
+    }
+}
 
-fn log_sender() -> &'static Mutex<Option<mpsc::Sender<crate::tui::LogEntry>>> {
-    LOG_SENDER.get_or_init(|| Mutex::new(None))
+/// One log line as written to files: timestamp, padded level and message.
+pub fn format_entry(entry: &LogEntry) -> String {
+    format!(
+        "{} {:<7} {}",
+        entry.timestamp.to_rfc3339(),
+        entry.level.as_str(),
+        entry.message
+    )
+}
+
+/// Writes `entries` to a new file at `path`, replacing any existing one.
+pub fn write_entries<'a, P: AsRef<Path>>(
+    path: P,
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+) -> Result<()> {
+    let mut file = File::create(path.as_ref())
+        .context(format!("Failed to create {}", path.as_ref().display()))?;
+    for entry in entries {
+        writeln!(file, "{}", format_entry(entry))?;
+    }
+    Ok(())
+}
+
+static SINKS: OnceLock<RwLock<Vec<Box<dyn LogSink>>>> = OnceLock::new();
+
+fn sinks() -> &'static RwLock<Vec<Box<dyn LogSink>>> {
+    SINKS.get_or_init(|| RwLock::new(Vec::new()))
 }
 
 pub fn init(_verbose: u8) {
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error"));
 
-    let is_tui_mode = log_sender().lock().unwrap().is_some();
+    let is_tui_mode = !sinks().read().unwrap().is_empty();
 
     if !is_tui_mode {
+        // Keeps JSON output on stdout parseable
         fmt::fmt()
             .with_env_filter(env_filter)
             .with_target(false)
+            .with_writer(std::io::stderr)
             .init();
     }
 }
 
-pub fn set_log_sender(sender: mpsc::Sender<crate::tui::LogEntry>) {
-    *log_sender().lock().unwrap() = Some(sender);
+pub fn add_sink(sink: Box<dyn LogSink>) {
+    sinks().write().unwrap().push(sink);
 }
 
-pub fn log_info(message: String) {
-    if let Some(sender) = log_sender().lock().unwrap().clone() {
-        if let Err(e) = sender.send(crate::tui::LogEntry {
-            timestamp: chrono::Utc::now(),
-            message: message.clone(),
-            level: crate::tui::LogLevel::Info,
-        }) {
-            eprintln!("Failed to send log to TUI: {}", e);
-        }
-    } else {
-        println!("{} {}", "ℹ️", message);
+pub fn set_log_sender(sender: mpsc::Sender<LogEntry>) {
+    add_sink(Box::new(TuiSink { sender }));
+}
+
+pub fn log(level: LogLevel, message: String) {
+    write(&LogEntry {
+        timestamp: chrono::Utc::now(),
+        message,
+        level,
+    });
+}
+
+/// Passes an entry to the sinks as it is, keeping its timestamp.
+pub fn write(entry: &LogEntry) {
+    let sinks = sinks().read().unwrap();
+    if sinks.is_empty() {
+        StdoutSink.write(entry);
+    }
+    for sink in sinks.iter() {
+        sink.write(entry);
     }
 }
 
+pub fn log_info(message: String) {
+    log(LogLevel::Info, message);
+}
+
 pub fn log_success(message: String) {
-    if let Some(sender) = log_sender().lock().unwrap().clone() {
-        if let Err(e) = sender.send(crate::tui::LogEntry {
-            timestamp: chrono::Utc::now(),
-            message: message.clone(),
-            level: crate::tui::LogLevel::Success,
-        }) {
-            eprintln!("Failed to send log to TUI: {}", e);
-        }
-    } else {
-        println!("{} {}", "✅", message);
-    }
+    log(LogLevel::Success, message);
 }
 
 pub fn log_warning(message: String) {
-    if let Some(sender) = log_sender().lock().unwrap().clone() {
-        if let Err(e) = sender.send(crate::tui::LogEntry {
-            timestamp: chrono::Utc::now(),
-            message: message.clone(),
-            level: crate::tui::LogLevel::Warning,
-        }) {
-            eprintln!("Failed to send log to TUI: {}", e);
-        }
-    } else {
-        println!("{} {}", "⚠️", message);
-    }
+    log(LogLevel::Warning, message);
+}
+
+/// Warns about a response slower than `--slow-threshold-ms`.
+pub fn log_slow(message: String) {
+    log(LogLevel::Slow, message);
 }
 
 pub fn log_error(message: String) {
-    if let Some(sender) = log_sender().lock().unwrap().clone() {
-        if let Err(e) = sender.send(crate::tui::LogEntry {
-            timestamp: chrono::Utc::now(),
-            message: message.clone(),
-            level: crate::tui::LogLevel::Error,
-        }) {
-            eprintln!("Failed to send log to TUI: {}", e);
-        }
-    } else {
-        eprintln!("{} {}", "❌", message);
-    }
+    log(LogLevel::Error, message);
 }
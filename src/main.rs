@@ -1,14 +1,63 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use std::path::PathBuf;
 use std::time::Duration;
 
+mod addon;
+mod auth;
+mod browser;
+mod cache;
+mod capture;
+mod chaos;
+mod clipboard;
+mod compression;
 mod config;
+mod control;
+mod cors;
+mod daemon;
+mod doctor;
+mod envfile;
+mod events;
+mod find;
 mod forwarder;
+mod grpc;
+mod headers;
 mod http;
+mod idle;
+mod jump;
 mod k8s;
+mod limit;
+mod log_filter;
 mod logger;
+mod metrics;
+mod mirror;
+mod mock;
+mod notify;
+mod otel;
+mod output;
+mod plugin;
+mod pod_logs;
+mod probe;
+mod process;
+mod reconnect;
+mod redact;
+mod replay;
+mod requests_log;
+mod retry;
+mod rewrite;
+mod run;
+mod session;
+mod socks;
+mod split;
+mod sse;
+mod status;
+mod synthetic;
+mod template;
+mod tls;
+mod transform;
 mod tui;
+mod udp;
+mod validate;
 
 #[derive(Parser, Debug, Clone)]
 #[command(
@@ -18,21 +67,60 @@ mod tui;
     author
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Kubernetes resource to port-forward (format: type/name:port)
-    #[arg(help = "Example: pod/my-pod:8080 or service/my-service:80", group = "input")]
+    #[arg(help = "Example: pod/my-pod:8080, service/my-service:80 or service/my-service:*", group = "input")]
     resource: Option<String>,
 
+    /// Forward every port declared on the service, assigning local ports automatically
+    #[arg(long, default_value_t = false)]
+    all_ports: bool,
+
+    /// Label selector picking a ready pod, e.g. app=api,tier=backend; the resource's pod name then only names the forward
+    #[arg(long)]
+    selector: Option<String>,
+
     /// Local port to listen on. Only used when specifying a single resource.
     #[arg(long, short)]
     local_port: Option<u16>,
 
+    /// Transport to forward. UDP is relayed through `kubectl exec` and needs socat in the target container
+    #[arg(long, value_enum, default_value_t = config::Protocol::Tcp)]
+    protocol: config::Protocol,
+
     /// Path to JSON config file with multiple port-forwards
     #[arg(long, short, group = "input")]
     config: Option<PathBuf>,
 
-    /// Kubernetes namespace (default: default)
-    #[arg(long, default_value = "default")]
-    namespace: String,
+    /// Start the forwards of a session saved with --save-session
+    #[arg(long, group = "input")]
+    session: Option<String>,
+
+    /// Save the forwards of this run, including ones added later, as a named session on exit
+    #[arg(long)]
+    save_session: Option<String>,
+
+    /// Kubernetes namespace (default: the kubeconfig context's namespace, or `default` when it sets none)
+    #[arg(long, short = 'n')]
+    namespace: Option<String>,
+
+    /// Kubeconfig context to use (default: current context)
+    #[arg(long, global = true)]
+    context: Option<String>,
+
+    /// Reach the API server through this SSH bastion ([user@]host[:port])
+    #[arg(long, global = true)]
+    jump_host: Option<String>,
+
+    /// Mark a context as protected; can be repeated
+    #[arg(long = "protected-context")]
+    protected_contexts: Vec<String>,
+
+    /// Skip the confirmation prompt for protected contexts
+    #[arg(long, default_value_t = false)]
+    yes_production: bool,
 
     /// Verbosity level (0-3)
     #[arg(long, short, default_value = "1")]
@@ -43,6 +131,9 @@ struct Args {
     /// Liveness probe HTTP endpoint path (e.g., /ping)
     #[arg(long)]
     liveness_probe: Option<String>,
+    /// Open the forwarded resource in the default browser, at PATH (default: /), once it is ready
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true, default_missing_value = "/")]
+    open: Option<String>,
     /// Show liveness probe logs (disabled by default)
     #[arg(long, default_value_t = false)]
     show_liveness: bool,
@@ -52,21 +143,750 @@ struct Args {
     /// Verbosity level for requests log file (0-3)
     #[arg(long, default_value = "1")]
     requests_log_verbosity: u8,
+    /// Rotate the requests log file before it grows past this size (e.g. 100M)
+    #[arg(long, value_parser = requests_log::parse_size)]
+    requests_log_max_size: Option<u64>,
+    /// Rotated requests log files to keep
+    #[arg(long, default_value = "5")]
+    requests_log_max_files: usize,
+    /// Rotate the requests log file every day
+    #[arg(long, default_value_t = false)]
+    requests_log_rotate_daily: bool,
+    /// Hold requests for up to this many seconds while a forward reconnects instead of answering 503
+    #[arg(long, default_value = "0")]
+    hold_requests: u64,
+    /// Leave requests to paths matching this glob (e.g. /healthz, /static/**) out of the request logs; can be repeated
+    #[arg(long = "log-exclude", value_name = "GLOB")]
+    log_excludes: Vec<String>,
+    /// Mask the value of this JSON or form field, or dotted JSON path (e.g. user.token), in logged and captured bodies; can be repeated
+    #[arg(long = "redact-field", value_name = "NAME")]
+    redact_fields: Vec<String>,
+    /// Mask this header in logged and captured requests, on top of Authorization, Cookie, X-Api-Key and the like; can be repeated
+    #[arg(long = "redact-header", value_name = "NAME")]
+    redact_headers: Vec<String>,
+    /// Do not log the Kubernetes Warning events (BackOff, FailedScheduling, ...) of forwarded pods and services
+    #[arg(long)]
+    no_events: bool,
+    /// Show a desktop notification when a forward goes down, fails its liveness probe or gives up reconnecting
+    #[arg(long)]
+    notify: bool,
+    /// Log and capture headers and bodies as they are, without masking anything
+    #[arg(long, conflicts_with_all = ["redact_fields", "redact_headers"])]
+    no_redact: bool,
+    /// Require basic auth with these credentials on every local proxy without its own auth
+    #[arg(long, value_name = "USER:PASSWORD")]
+    auth_basic: Option<String>,
+    /// Require this bearer token on every local proxy without its own auth
+    #[arg(long, value_name = "TOKEN")]
+    auth_token: Option<String>,
+    /// Most requests proxied at once across all forwards; more wait in a queue, then get 503 with Retry-After
+    #[arg(long)]
+    max_in_flight: Option<usize>,
+    /// Requests waiting for a slot under --max-in-flight before new ones get 503 (default: 100)
+    #[arg(long, requires = "max_in_flight")]
+    max_queued: Option<usize>,
+    /// Log responses slower than this many milliseconds as warnings and highlight them in the TUI
+    #[arg(long)]
+    slow_threshold_ms: Option<u64>,
+    /// Most bytes of a request or response body shown in logs; longer bodies are cut off with a marker
+    #[arg(long, default_value = "4096")]
+    max_body_log_bytes: usize,
+    /// Address the local proxies listen on; can be repeated (default: 127.0.0.1 and ::1)
+    #[arg(long = "bind")]
+    bind_addresses: Vec<std::net::IpAddr>,
+    /// Start only the forwards of this config profile; can be repeated
+    #[arg(long = "profile", global = true)]
+    profiles: Vec<String>,
+    /// Value for `${NAME}` placeholders in the config file, used before the environment; can be repeated
+    #[arg(long = "set", value_name = "NAME=VALUE", value_parser = config::parse_variable, global = true)]
+    variables: Vec<(String, String)>,
+    /// Use the next free local port when the requested one is already in use
+    #[arg(long, default_value_t = false)]
+    auto_port: bool,
+    /// Serve the local proxies over HTTPS with this PEM certificate chain
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key for --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Require client certificates signed by this PEM CA bundle (needs --tls-cert)
+    #[arg(long, requires = "tls_cert")]
+    tls_client_ca: Option<PathBuf>,
+    /// Scheme the forwarded port speaks in single-resource mode; https makes the proxy connect over TLS
+    #[arg(long, value_enum)]
+    upstream_scheme: Option<config::UpstreamScheme>,
+    /// How the single resource is reached: port-forward, or apiserver-proxy through the API server's service proxy
+    #[arg(long, value_enum)]
+    mode: Option<config::ForwardMode>,
+    /// PEM CA bundle for verifying HTTPS upstreams (default: system roots)
+    #[arg(long)]
+    upstream_ca: Option<PathBuf>,
+    /// Accept any certificate from HTTPS upstreams
+    #[arg(long, default_value_t = false)]
+    upstream_tls_skip_verify: bool,
+    /// Port for the REST control API (disabled by default)
+    #[arg(long)]
+    control_port: Option<u16>,
+    /// What forwards do when connecting keeps failing: retry-forever, exit, or exit-after=N failed attempts in a row (default: give up after 5 attempts and keep running)
+    #[arg(long, value_name = "POLICY")]
+    on_failure: Option<config::FailurePolicy>,
+    /// Log entries kept in the TUI; older ones are dropped
+    #[arg(long, default_value = "10000")]
+    max_log_lines: usize,
+    /// Print NDJSON events (forward_started, forward_ready, request, forward_failed, log) instead of the TUI or log lines; the default when stdout is not a terminal
+    #[arg(long, value_enum)]
+    output: Option<output::OutputFormat>,
+    /// Append all log messages, with timestamps and levels, to this file
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+    /// Record log entries and forward status changes with timestamps to this file, for `replay`
+    #[arg(long, value_name = "FILE")]
+    record_session: Option<PathBuf>,
+    /// Keep a dotenv file with <NAME>_HOST and <NAME>_PORT of every active forward
+    #[arg(long, value_name = "FILE")]
+    write_env: Option<PathBuf>,
+    /// Write every proxied response to this directory, for --mock
+    #[arg(long, value_name = "DIR")]
+    record: Option<PathBuf>,
+    /// Serve the responses recorded with --record from this directory instead of connecting to the cluster
+    #[arg(long, value_name = "DIR")]
+    mock: Option<PathBuf>,
+    /// In mock mode, forward requests without a recording to the cluster instead of answering 404
+    #[arg(long, requires = "mock")]
+    mock_passthrough: bool,
+    /// Export proxy and upstream spans to this OTLP/HTTP collector (e.g. http://localhost:4318)
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Forward a well-known addon (grafana, prometheus, kubernetes-dashboard) and open it in the browser
+    Addon {
+        /// Addon name
+        name: String,
+        /// Config file whose `addons` block adds or overrides addon locations
+        #[arg(long, short)]
+        config: Option<PathBuf>,
+    },
+    /// Check kubectl, the kubeconfig, cluster access, permissions and local ports before forwarding
+    Doctor {
+        /// Config file whose forwards are checked
+        #[arg(long, short)]
+        config: Option<PathBuf>,
+        /// Namespace whose permissions are checked (default: the context's)
+        #[arg(long, short = 'n')]
+        namespace: Option<String>,
+    },
+    /// Check a config file for unknown fields, invalid resources, local port clashes and missing namespaces
+    Validate {
+        /// Config file to check
+        #[arg(long, short)]
+        config: PathBuf,
+        /// Skip the checks that need the cluster
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Fuzzy-search pods and services across namespaces, list their ports and offer to forward one
+    Find {
+        /// Part of the name, e.g. `payapi` finds payments/api-gateway
+        query: String,
+        /// Search only this namespace
+        #[arg(long, short = 'n')]
+        namespace: Option<String>,
+    },
+    /// Forward every port of every service of a Helm release
+    Helm {
+        /// Release name, matched against the services' app.kubernetes.io/instance (or release) label
+        release: String,
+        /// Namespace the release is installed in
+        #[arg(long, short = 'n')]
+        namespace: Option<String>,
+    },
+    /// Serve a SOCKS5 / HTTP CONNECT proxy that reaches <service>.<namespace>.svc hosts through on-demand port-forwards
+    Proxy {
+        /// Local port of the proxy
+        #[arg(long, default_value = "1080")]
+        port: u16,
+    },
+    /// Run forwards without the TUI, controllable with `status`, `logs` and `stop`
+    Start {
+        /// Kubernetes resource to port-forward (format: type/name:port)
+        resource: Option<String>,
+        /// Path to JSON config file with multiple port-forwards
+        #[arg(long, short, conflicts_with = "resource")]
+        config: Option<PathBuf>,
+        /// Detach into the background, so the forwards outlive the terminal
+        #[arg(long)]
+        daemon: bool,
+        /// With --daemon, return only once every forward is connected and past its first liveness probe
+        #[arg(long, requires = "daemon")]
+        wait_ready: bool,
+        /// Seconds --wait-ready waits before failing
+        #[arg(long, default_value = "60", requires = "wait_ready")]
+        wait_timeout: u64,
+    },
+    /// Start forwards, wait until they are ready, run a command with their ports in KPF_<NAME>_PORT / KPF_<NAME>_URL, then stop them
+    Run {
+        /// Kubernetes resource to port-forward (format: type/name:port)
+        resource: Option<String>,
+        /// Path to JSON config file with multiple port-forwards
+        #[arg(long, short, conflicts_with = "resource")]
+        config: Option<PathBuf>,
+        /// Seconds to wait for the forwards before failing
+        #[arg(long, default_value = "60")]
+        ready_timeout: u64,
+        /// Command to run, after `--`; `{{port "name"}}` and `{{url "name"}}` in its arguments are expanded
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Stop the session started with `start`
+    Stop,
+    /// Print the logs of the session started with `start`
+    Logs {
+        /// Keep printing new log lines until the session stops
+        #[arg(long, short)]
+        follow: bool,
+        /// Lines of earlier logs to print
+        #[arg(long, default_value = "100")]
+        tail: usize,
+    },
+    /// Wait until every forward of a running session is connected and past its first liveness probe; exits non-zero on timeout
+    Wait {
+        /// Seconds to wait before failing
+        #[arg(long, default_value = "60")]
+        timeout: u64,
+        /// Control API port of the running session (default: the session started with `start`)
+        #[arg(long)]
+        control_port: Option<u16>,
+    },
+    /// Print the forwards of a running session: the one started with `start`, or one running with --control-port
+    Status {
+        /// Control API port of the running session (default: the session started with `start`)
+        #[arg(long)]
+        control_port: Option<u16>,
+        /// Output format
+        #[arg(long, short, value_enum, default_value_t = status::OutputFormat::Table)]
+        output: status::OutputFormat,
+    },
+    /// Play a session saved with --record-session back in the TUI
+    Replay {
+        /// Recording to play
+        file: PathBuf,
+        /// How many times faster than recorded to play, e.g. 10
+        #[arg(long, default_value = "1")]
+        speed: f64,
+    },
+    /// Expand `{{port "name"}}` / `{{url "name"}}` placeholders against a running session
+    Render {
+        /// Text to render, e.g. 'psql -p {{port "db"}}'
+        template: String,
+        /// Control API port of the running session (default: the session started with `start`)
+        #[arg(long)]
+        control_port: Option<u16>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = parse_args();
+    metrics::mark_started();
+    config::set_variables(args.variables.clone());
+    config::set_profiles(args.profiles.clone());
+    if wants_json_output(&args) {
+        output::set_json();
+    }
     
     // Initialize logger with verbosity level
     logger::init(args.verbose);
     http::set_verbose(args.verbose);
+    http::set_hold_requests(args.hold_requests);
+    http::set_max_body_log_bytes(args.max_body_log_bytes);
+    http::set_bind_addresses(args.bind_addresses.clone());
+    http::set_slow_threshold(args.slow_threshold_ms.unwrap_or(0));
+    if !args.log_excludes.is_empty() {
+        log_filter::set_global(&args.log_excludes)?;
+    }
+    if args.no_events {
+        events::disable();
+    }
+    if args.notify {
+        notify::enable_desktop();
+    }
+    if args.no_redact {
+        redact::disable();
+    } else {
+        redact::set_global(&args.redact_headers, &args.redact_fields)?;
+    }
+    if args.auth_basic.is_some() || args.auth_token.is_some() {
+        let (username, password) = match &args.auth_basic {
+            Some(basic) => match basic.split_once(':') {
+                Some((username, password)) => (Some(username.to_string()), Some(password.to_string())),
+                None => anyhow::bail!("Invalid --auth-basic '{}'. Expected USER:PASSWORD", basic),
+            },
+            None => (None, None),
+        };
+        auth::set_global(&config::AuthConfig {
+            username,
+            password,
+            token: args.auth_token.clone(),
+        })?;
+    }
+    if let Some(max_in_flight) = args.max_in_flight {
+        limit::set_global(&config::ConcurrencyConfig {
+            max_in_flight,
+            max_queued: args.max_queued,
+            ..Default::default()
+        });
+    }
+    if let Some(jump_host) = &args.jump_host {
+        jump::validate(jump_host)?;
+        jump::configure(args.context.as_deref(), jump_host);
+    }
+
+    if let Some(Command::Status { control_port, output }) = &args.command {
+        return status::print_status(*control_port, *output).await;
+    }
+
+    if let Some(Command::Wait { timeout, control_port }) = &args.command {
+        return status::wait_ready(*control_port, Duration::from_secs(*timeout)).await;
+    }
+
+    if let Some(Command::Stop) = &args.command {
+        return daemon::stop().await;
+    }
+
+    if let Some(Command::Logs { follow, tail }) = &args.command {
+        return daemon::print_logs(*follow, *tail).await;
+    }
+
+    if let Some(Command::Doctor { config, namespace }) = &args.command {
+        return doctor::run(config.as_deref(), args.context.as_deref(), namespace.as_deref()).await;
+    }
+
+    if let Some(Command::Validate { config, offline }) = &args.command {
+        return validate::run(config, args.context.as_deref(), *offline).await;
+    }
+
+    if let Some(Command::Replay { file, speed }) = &args.command {
+        return replay::run(file.clone(), *speed, args.max_log_lines).await;
+    }
+
+    if let Some(Command::Render { template, control_port }) = &args.command {
+        let forwards = status::fetch_forwards(*control_port).await?;
+        let statuses: Vec<_> = forwards.forwards.into_iter().map(|view| view.status).collect();
+        println!("{}", template::render(template, &statuses)?);
+        return Ok(());
+    }
+
+    if let Some(Command::Run { resource, config, .. }) = &args.command {
+        args.resource = resource.clone().or(args.resource);
+        args.config = config.clone().or(args.config);
+    }
+
+    if let Some(Command::Start { resource, config, .. }) = &args.command {
+        args.resource = resource.clone().or(args.resource);
+        args.config = config.clone().or(args.config);
+        // The detached process cannot prompt; the user confirmed before it was spawned
+        if daemon::is_detached_process() {
+            args.yes_production = true;
+        }
+    }
+
+    // A saved session is a config file kept under ~/.config/kpf/sessions
+    if let Some(name) = &args.session {
+        args.config = Some(session::existing_path(name)?);
+    }
+    if let Some(name) = &args.save_session {
+        session::path(name)?;
+    }
+
+    // A result picked from `find` is forwarded like a resource given on the command line
+    if let Some(Command::Find { query, namespace }) = &args.command {
+        let Some((resource, namespace)) =
+            find::run(query, args.context.as_deref(), namespace.as_deref()).await?
+        else {
+            return Ok(());
+        };
+        args.resource = Some(resource);
+        args.namespace = Some(namespace);
+        args.command = None;
+    }
+
+    // Without the TUI to ask in, a resource given without a port must declare exactly one
+    if let (Some(Command::Start { .. } | Command::Run { .. }), Some(resource)) = (&args.command, &args.resource) {
+        if !k8s::has_port(resource) && !args.all_ports {
+            args.resource = Some(single_declared_port(resource, &args).await?);
+        }
+    }
+
+    confirm_protected_contexts(&args)?;
+
+    if let Some(Command::Proxy { port }) = &args.command {
+        return socks::run(*port, args.context.clone()).await;
+    }
+
+    if let Some(Command::Start { daemon: true, wait_ready, wait_timeout, .. }) = &args.command {
+        if !daemon::is_detached_process() {
+            daemon::spawn().await?;
+            if *wait_ready {
+                status::wait_ready(None, Duration::from_secs(*wait_timeout)).await?;
+            }
+            return Ok(());
+        }
+    }
+
+    // Keeps stdout to the command of `run`
+    if let Some(Command::Run { .. }) = &args.command {
+        logger::add_sink(Box::new(logger::StderrSink));
+    }
+    if let Some(log_file) = &args.log_file {
+        logger::add_sink(Box::new(logger::FileSink::open(log_file)?));
+    }
+    if let Some(path) = &args.record_session {
+        replay::start_recording(path)?;
+    }
+    if let Some(path) = &args.write_env {
+        envfile::start(path.clone())?;
+    }
+    requests_log::configure(requests_log::RotationPolicy {
+        max_bytes: args.requests_log_max_size,
+        max_files: args.requests_log_max_files,
+        daily: args.requests_log_rotate_daily,
+    });
+    if let Some(endpoint) = &args.otlp_endpoint {
+        otel::start_exporter(endpoint)?;
+    }
+    if let Some(dir) = &args.record {
+        mock::set_record_dir(dir.clone())?;
+    }
+    if let Some(dir) = &args.mock {
+        mock::set_mock(dir.clone(), args.mock_passthrough)?;
+    }
+
+    if let Some(Command::Run { command, ready_timeout, .. }) = &args.command {
+        start_forwards(args.clone(), forward_options(&args)).await;
+        let code = run::run(command, Duration::from_secs(*ready_timeout)).await?;
+        std::process::exit(code);
+    }
+
+    if let Some(Command::Start { .. }) = &args.command {
+        let options = forward_options(&args);
+        daemon::run(start_forwards(args.clone(), options), args.max_log_lines).await?;
+        if let Some(name) = &args.save_session {
+            let path = session::save(name, args.verbose)?;
+            logger::log_info(format!("{} Saved session {} to {}", "💾", name, path.display()));
+        }
+        return Ok(());
+    }
     
     run_tui_mode(args).await
 }
 
+/// Parses the command line. As a kubectl plugin, usage is shown as
+/// `kubectl kpf` and the context and namespace default to kubectl's.
+fn parse_args() -> Args {
+    if !plugin::is_plugin() {
+        return Args::parse();
+    }
+
+    let matches = Args::command().bin_name(plugin::PLUGIN_BIN_NAME).get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    args.context = args.context.or_else(plugin::context);
+    args.namespace = args
+        .namespace
+        .or_else(|| plugin::namespace(args.context.as_deref()));
+    args
+}
+
+/// Whether forwards report as NDJSON: with `--output json`, or when a run
+/// that would show the TUI or log lines has no terminal to print to.
+fn wants_json_output(args: &Args) -> bool {
+    use std::io::IsTerminal;
+
+    let forwarding = matches!(
+        args.command,
+        None | Some(Command::Start { daemon: false, .. }) | Some(Command::Addon { .. }) | Some(Command::Helm { .. })
+    );
+    match args.output {
+        Some(format) => format == output::OutputFormat::Json,
+        None => forwarding && !std::io::stdout().is_terminal() && !daemon::is_detached_process(),
+    }
+}
+
+/// Refuses to start forwards against protected contexts unless the user passed
+/// `--yes-production` or confirms interactively.
+fn confirm_protected_contexts(args: &Args) -> Result<()> {
+    use std::io::{IsTerminal, Write};
+
+    let mut protected = args.protected_contexts.clone();
+    let mut contexts = vec![args.context.clone()];
+    if let Some(config_path) = &args.config {
+        let config = config::load_config(config_path)?;
+        protected.extend(config.protected_contexts.unwrap_or_default());
+        contexts = config
+            .forwards
+            .into_iter()
+            .map(|forward| forward.context.or_else(|| args.context.clone()))
+            .collect();
+    }
+    k8s::set_protected_contexts(protected);
+
+    let mut hits: Vec<String> = contexts
+        .iter()
+        .filter(|context| k8s::is_protected_context(context.as_deref()))
+        .filter_map(|context| k8s::effective_context(context.as_deref()))
+        .collect();
+    hits.sort();
+    hits.dedup();
+
+    if args.yes_production {
+        k8s::allow_protected_contexts();
+    }
+    if hits.is_empty() || args.yes_production {
+        return Ok(());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Refusing to forward to protected context(s) {} without --yes-production",
+            hits.join(", ")
+        );
+    }
+
+    print!(
+        "⚠️  About to forward to protected context(s): {}. Continue? [y/N] ",
+        hits.join(", ")
+    );
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        anyhow::bail!("Aborted by user");
+    }
+    for context in &hits {
+        k8s::confirm_protected_context(context);
+    }
+
+    Ok(())
+}
+
+/// `resource` with the only port it declares, or an error listing its ports.
+async fn single_declared_port(resource: &str, args: &Args) -> Result<String> {
+    let namespace = args
+        .namespace
+        .clone()
+        .or_else(|| k8s::context_namespace(args.context.as_deref()))
+        .unwrap_or_else(|| "default".to_string());
+    let ports = k8s::declared_ports(resource, &namespace, args.context.as_deref()).await?;
+    match ports.as_slice() {
+        [(port, _)] => Ok(format!("{}:{}", resource, port)),
+        [] => anyhow::bail!("{} declares no TCP ports; give one as {}:<port>", resource, resource),
+        _ => anyhow::bail!(
+            "{} declares several ports: {}; pick one as {}:<port>",
+            resource,
+            k8s::describe_ports(&ports),
+            resource
+        ),
+    }
+}
+
+/// Options shared by every forward, taken from the command line.
+fn forward_options(args: &Args) -> forwarder::ForwardOptions {
+    forwarder::ForwardOptions {
+        show_liveness: args.show_liveness,
+        requests_log_file: args.requests_log_file.clone(),
+        requests_log_verbosity: args.requests_log_verbosity,
+        auto_port: args.auto_port,
+        tls: args.tls_cert.clone().zip(args.tls_key.clone()).map(|(cert, key)| {
+            config::TlsConfig {
+                cert,
+                key,
+                client_ca: args.tls_client_ca.clone(),
+            }
+        }),
+        upstream_ca: args.upstream_ca.clone(),
+        upstream_tls_skip_verify: args.upstream_tls_skip_verify,
+        on_failure: args.on_failure,
+    }
+}
+
+/// Starts the control API and the forwards the command line asks for.
+async fn start_forwards(args: Args, options: forwarder::ForwardOptions) {
+    if let Some(control_port) = args.control_port {
+        tokio::spawn(async move {
+            if let Err(e) = control::start_control_server(control_port).await {
+                logger::log_error(format!("Control API failed: {}", e));
+            }
+        });
+    }
+    
+    // Start the port forwarding based on args
+    if let Some(Command::Addon { name, config }) = args.command {
+        let result = match config.map(config::load_config).transpose() {
+            Ok(config) => {
+                addon::start_addon(&name, config, args.context, args.verbose, options).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            logger::log_error(format!("Addon {} failed: {}", name, e));
+        }
+    } else if let Some(Command::Helm { release, namespace }) = args.command {
+        // A release runs through the config code path as a one-entry config
+        let mut forward = config::ForwardConfig::builder().release(release.clone());
+        if let Some(timeout) = args.timeout {
+            forward = forward.timeout(timeout);
+        }
+        if let Some(path) = args.liveness_probe {
+            forward = forward.liveness_probe(path);
+        }
+        if let Some(namespace) = namespace.or(args.namespace) {
+            forward = forward.namespace(namespace);
+        }
+        if let Some(context) = args.context {
+            forward = forward.context(context);
+        }
+
+        logger::log_info(format!("📡 Forwarding Helm release {} via HTTP proxy", release));
+
+        if let Err(e) = start_single(forward.build(), args.verbose, options).await {
+            logger::log_error(format!("Error starting port-forwards: {}", e));
+        }
+    } else if let Some(config_path) = args.config {
+        // Load config file and start multiple port-forwards
+        match config::load_config(config_path) {
+            Ok(mut config) => {
+                config.verbose = Some(args.verbose);
+                config.jump_host = args.jump_host.clone().or(config.jump_host);
+                for forward in &mut config.forwards {
+                    if forward.context.is_none() {
+                        forward.context = args.context.clone();
+                    }
+                }
+                
+                logger::log_info(match args.profiles.is_empty() {
+                    true => format!("📋 Starting {} port-forwards from config", config.forwards.len()),
+                    false => format!(
+                        "📋 Starting {} port-forwards from config (profile {})",
+                        config.forwards.len(),
+                        args.profiles.join(", ")
+                    ),
+                });
+                
+                if let Err(e) = forwarder::start_from_config(config, options).await {
+                    logger::log_error(format!("Error starting port-forwards: {}", e));
+                }
+            }
+            Err(e) => {
+                logger::log_error(format!("Failed to load config: {}", e));
+            }
+        }
+    } else if let Some(resource_str) = args.resource.clone().filter(|resource| !k8s::has_port(resource) && !args.all_ports) {
+        // Without a port the TUI asks for one, unless the resource declares a single one
+        forwarder::set_forward_options(options);
+        tui::resolve_port(tui::PortPrompt {
+            resource: resource_str,
+            namespace: args.namespace,
+            context: args.context,
+            local_port: args.local_port,
+            ..Default::default()
+        })
+        .await;
+    } else if let Some(resource_str) = args.resource {
+        // A single resource runs through the config code path as a one-entry config
+        let resource = if args.all_ports || k8s::is_all_ports(&resource_str) {
+            let target = resource_str.split(':').next().unwrap_or_default();
+            format!("{}:*", target)
+        } else {
+            resource_str
+        };
+        let mut forward = config::ForwardConfig::builder()
+            .resource(resource.clone())
+            .protocol(args.protocol);
+        if let Some(port) = args.local_port {
+            forward = forward.local_port(port);
+        }
+        if let Some(timeout) = args.timeout {
+            forward = forward.timeout(timeout);
+        }
+        if let Some(path) = args.liveness_probe {
+            forward = forward.liveness_probe(path);
+        }
+        if let Some(path) = args.open {
+            forward = forward.open(path);
+        }
+        if let Some(namespace) = args.namespace {
+            forward = forward.namespace(namespace);
+        }
+        if let Some(context) = args.context {
+            forward = forward.context(context);
+        }
+        if let Some(scheme) = args.upstream_scheme {
+            forward = forward.upstream_scheme(scheme);
+        }
+        if let Some(mode) = args.mode {
+            forward = forward.mode(mode);
+        }
+        if let Some(selector) = args.selector {
+            forward = forward.selector(selector);
+        }
+
+        logger::log_info(format!("📡 Forwarding {} via HTTP proxy", resource));
+
+        if let Err(e) = start_single(forward.build(), args.verbose, options).await {
+            logger::log_error(format!("Error starting port-forward: {}", e));
+        }
+    } else {
+        logger::log_error("No resource or config specified".to_string());
+    }
+}
+
+/// Starts a forward given on the command line as a one-entry config.
+async fn start_single(
+    forward: Result<config::ForwardConfig>,
+    verbose: u8,
+    options: forwarder::ForwardOptions,
+) -> Result<()> {
+    let config = config::Config {
+        forwards: vec![forward?],
+        verbose: Some(verbose),
+        protected_contexts: None,
+        addons: None,
+        profiles: None,
+        auto_port_range: None,
+        jump_host: None,
+        notifications: None,
+    };
+    forwarder::start_from_config(config, options).await?;
+    Ok(())
+}
+
+/// Runs the forwards without the TUI, printing JSON events, until interrupted.
+async fn run_headless(args: Args) -> Result<()> {
+    let options = forward_options(&args);
+    tokio::spawn(start_forwards(args.clone(), options));
+    let failure = tokio::select! {
+        result = tokio::signal::ctrl_c() => result.map(|_| None)?,
+        result = process::terminate_requested() => result.map(|_| None)?,
+        reason = forwarder::failure_exit_requested() => Some(reason),
+    };
+
+    if let Some(name) = &args.save_session {
+        let path = session::save(name, args.verbose)?;
+        logger::log_info(format!("{} Saved session {} to {}", "💾", name, path.display()));
+    }
+    match failure {
+        Some(reason) => anyhow::bail!(reason),
+        None => Ok(()),
+    }
+}
 
 async fn run_tui_mode(args: Args) -> Result<()> {
+    if output::is_json() {
+        return run_headless(args).await;
+    }
+
     // Set up the terminal
     let mut terminal = tui::setup_terminal()?;
     
@@ -77,10 +897,11 @@ async fn run_tui_mode(args: Args) -> Result<()> {
     logger::set_log_sender(log_sender.clone());
     
     // Create the app state
-    let mut app = tui::App::new(log_receiver);
+    let mut app = tui::App::new(log_receiver, args.max_log_lines);
     
     // Spawn a thread to handle the port forwarding
     let args_clone = args.clone();
+    let options = forward_options(&args);
     let log_sender_clone = log_sender.clone();
     let _port_forward_handle = tokio::spawn(async move {
         // Log startup information
@@ -95,89 +916,8 @@ async fn run_tui_mode(args: Args) -> Result<()> {
             message: format!("🔊 Verbosity level: {}", args_clone.verbose),
             level: tui::LogLevel::Info,
         }).unwrap();
-        
-        // Start the port forwarding based on args
-        if let Some(config_path) = args_clone.config {
-            // Load config file and start multiple port-forwards
-            match config::load_config(config_path) {
-                Ok(mut config) => {
-                    config.verbose = Some(args_clone.verbose);
-                    
-                    log_sender_clone.send(tui::LogEntry {
-                        timestamp: chrono::Utc::now(),
-                        message: format!("📋 Starting {} port-forwards from config", config.forwards.len()),
-                        level: tui::LogLevel::Info,
-                    }).unwrap();
-                    
-                    if let Err(e) = forwarder::start_from_config(
-                        config, 
-                        args_clone.show_liveness, 
-                        args_clone.requests_log_file, 
-                        args_clone.requests_log_verbosity
-                    ).await {
-                        log_sender_clone.send(tui::LogEntry {
-                            timestamp: chrono::Utc::now(),
-                            message: format!("❌ Error starting port-forwards: {}", e),
-                            level: tui::LogLevel::Error,
-                        }).unwrap();
-                    }
-                }
-                Err(e) => {
-                    log_sender_clone.send(tui::LogEntry {
-                        timestamp: chrono::Utc::now(),
-                        message: format!("❌ Failed to load config: {}", e),
-                        level: tui::LogLevel::Error,
-                    }).unwrap();
-                }
-            }
-        } else if let Some(resource_str) = args_clone.resource {
-            // Parse resource string and start single port-forward
-            match k8s::parse_resource(&resource_str) {
-                Ok((resource_type, resource_name, resource_port)) => {
-                    let local_port = args_clone.local_port.unwrap_or(resource_port);
-                    
-                    log_sender_clone.send(tui::LogEntry {
-                        timestamp: chrono::Utc::now(),
-                        message: format!("📡 Forwarding {}/{} port {} via HTTP proxy on port {}", 
-                            resource_type, resource_name, resource_port, local_port),
-                        level: tui::LogLevel::Info,
-                    }).unwrap();
-                    
-                    if let Err(e) = forwarder::start_single(
-                        resource_type,
-                        resource_name,
-                        resource_port,
-                        args_clone.namespace,
-                        local_port,
-                        args_clone.verbose,
-                        args_clone.timeout,
-                        args_clone.liveness_probe,
-                        args_clone.show_liveness,
-                        args_clone.requests_log_file,
-                        args_clone.requests_log_verbosity,
-                    ).await {
-                        log_sender_clone.send(tui::LogEntry {
-                            timestamp: chrono::Utc::now(),
-                            message: format!("❌ Error starting port-forward: {}", e),
-                            level: tui::LogLevel::Error,
-                        }).unwrap();
-                    }
-                }
-                Err(e) => {
-                    log_sender_clone.send(tui::LogEntry {
-                        timestamp: chrono::Utc::now(),
-                        message: format!("❌ Failed to parse resource: {}", e),
-                        level: tui::LogLevel::Error,
-                    }).unwrap();
-                }
-            }
-        } else {
-            log_sender_clone.send(tui::LogEntry {
-                timestamp: chrono::Utc::now(),
-                message: "❌ No resource or config specified".to_string(),
-                level: tui::LogLevel::Error,
-            }).unwrap();
-        }
+
+        start_forwards(args_clone, options).await;
     });
     
     // Run the app
@@ -191,6 +931,14 @@ async fn run_tui_mode(args: Args) -> Result<()> {
     if let Err(err) = res {
         logger::log_error(format!("TUI error: {}", err));
     }
-    
+
+    if let Some(name) = &args.save_session {
+        let path = session::save(name, args.verbose)?;
+        println!("💾 Saved session {} to {}", name, path.display());
+    }
+
+    if let Some(reason) = forwarder::failure_exit() {
+        anyhow::bail!(reason);
+    }
     Ok(())
 }
@@ -0,0 +1,259 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Window over which the request rate is computed.
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+/// Number of recent responses the latency percentiles are computed over.
+const LATENCY_WINDOW: usize = 1000;
+/// Seconds of per-second traffic kept for the status table sparklines and
+/// the TUI metrics view.
+pub const TRAFFIC_HISTORY_SECS: u64 = 120;
+/// Upper bounds of the latency histogram buckets, in milliseconds; slower
+/// responses fall in a last, open bucket.
+pub const LATENCY_BUCKETS_MS: [u64; 9] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500];
+
+static METRICS: LazyLock<Mutex<HashMap<String, ForwardMetrics>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static PROCESS_STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+#[derive(Default)]
+struct ForwardMetrics {
+    total_requests: u64,
+    error_responses: u64,
+    recent: VecDeque<Instant>,
+    latencies_ms: VecDeque<u64>,
+    request_bytes: u64,
+    response_bytes: u64,
+    last_success: Option<chrono::DateTime<chrono::Utc>>,
+    reconnects: u64,
+    state_transitions: u64,
+    cache_hits: u64,
+    probe_latency_ms: Option<u64>,
+    /// Requests and body bytes per second of process uptime, oldest first
+    traffic: VecDeque<TrafficSample>,
+}
+
+/// Traffic of a forward during one second.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrafficSample {
+    second: u64,
+    pub requests: u64,
+    pub bytes: u64,
+    /// Responses by status class: 1xx and 2xx, 3xx, 4xx, 5xx
+    pub statuses: [u64; 4],
+}
+
+impl ForwardMetrics {
+    /// The sample of the current second, dropping ones past the history.
+    fn current_sample(&mut self) -> &mut TrafficSample {
+        let second = PROCESS_STARTED.elapsed().as_secs();
+        if self.traffic.back().is_none_or(|sample| sample.second != second) {
+            self.traffic.push_back(TrafficSample {
+                second,
+                ..Default::default()
+            });
+        }
+        while self
+            .traffic
+            .front()
+            .is_some_and(|sample| sample.second + TRAFFIC_HISTORY_SECS < second)
+        {
+            self.traffic.pop_front();
+        }
+        self.traffic.back_mut().unwrap()
+    }
+}
+
+/// Point-in-time view of a forward's request counters.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct MetricsSnapshot {
+    pub total_requests: u64,
+    pub error_responses: u64,
+    pub requests_per_sec: f64,
+    /// Request and response body bytes per second
+    pub bytes_per_sec: f64,
+    /// Latency percentiles over the last responses; `None` before the first one
+    pub latency_p50_ms: Option<u64>,
+    pub latency_p95_ms: Option<u64>,
+    pub latency_p99_ms: Option<u64>,
+    /// Body bytes received from clients and sent back to them
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    /// Last proxied request answered without a 5xx
+    pub last_success_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Reconnects of the port-forward since it started
+    pub reconnects: u64,
+    /// Changes of the forward's state since it started
+    #[serde(default)]
+    pub state_transitions: u64,
+    /// Requests answered from the response cache
+    pub cache_hits: u64,
+    /// Duration of the last successful liveness probe
+    pub probe_latency_ms: Option<u64>,
+}
+
+impl MetricsSnapshot {
+    /// Share of responses with a 5xx status, between 0 and 1.
+    pub fn error_rate(&self) -> f64 {
+        if self.total_requests == 0 {
+            0.0
+        } else {
+            self.error_responses as f64 / self.total_requests as f64
+        }
+    }
+}
+
+pub fn record_request(resource: &str, status: u16, latency_ms: u64) {
+    let now = Instant::now();
+    let mut metrics = METRICS.lock().unwrap();
+    let entry = metrics.entry(resource.to_string()).or_default();
+    entry.total_requests += 1;
+    if status >= 500 {
+        entry.error_responses += 1;
+    } else {
+        entry.last_success = Some(chrono::Utc::now());
+    }
+    entry.recent.push_back(now);
+    let sample = entry.current_sample();
+    sample.requests += 1;
+    sample.statuses[match status {
+        ..=299 => 0,
+        300..=399 => 1,
+        400..=499 => 2,
+        _ => 3,
+    }] += 1;
+    if entry.latencies_ms.len() == LATENCY_WINDOW {
+        entry.latencies_ms.pop_front();
+    }
+    entry.latencies_ms.push_back(latency_ms);
+    while entry
+        .recent
+        .front()
+        .is_some_and(|t| now.duration_since(*t) > RATE_WINDOW)
+    {
+        entry.recent.pop_front();
+    }
+}
+
+pub fn record_request_bytes(resource: &str, bytes: usize) {
+    let mut metrics = METRICS.lock().unwrap();
+    let entry = metrics.entry(resource.to_string()).or_default();
+    entry.request_bytes += bytes as u64;
+    entry.current_sample().bytes += bytes as u64;
+}
+
+pub fn record_response_bytes(resource: &str, bytes: usize) {
+    let mut metrics = METRICS.lock().unwrap();
+    let entry = metrics.entry(resource.to_string()).or_default();
+    entry.response_bytes += bytes as u64;
+    entry.current_sample().bytes += bytes as u64;
+}
+
+pub fn record_reconnect(resource: &str) {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.entry(resource.to_string()).or_default().reconnects += 1;
+}
+
+pub fn record_state_transition(resource: &str) {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.entry(resource.to_string()).or_default().state_transitions += 1;
+}
+
+pub fn record_cache_hit(resource: &str) {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.entry(resource.to_string()).or_default().cache_hits += 1;
+}
+
+pub fn record_probe_latency(resource: &str, latency: Duration) {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.entry(resource.to_string()).or_default().probe_latency_ms = Some(latency.as_millis() as u64);
+}
+
+/// Starts the process uptime clock; called once at startup.
+pub fn mark_started() {
+    LazyLock::force(&PROCESS_STARTED);
+}
+
+pub fn process_uptime() -> Duration {
+    PROCESS_STARTED.elapsed()
+}
+
+/// Traffic of the last `seconds` complete seconds, oldest first, with
+/// zeroes for idle seconds.
+pub fn traffic_history(resource: &str, seconds: u64) -> Vec<TrafficSample> {
+    let now = PROCESS_STARTED.elapsed().as_secs();
+    let metrics = METRICS.lock().unwrap();
+    let samples = metrics.get(resource).map(|entry| &entry.traffic);
+    (now.saturating_sub(seconds)..now)
+        .map(|second| {
+            samples
+                .and_then(|samples| samples.iter().find(|sample| sample.second == second))
+                .copied()
+                .unwrap_or(TrafficSample {
+                    second,
+                    ..Default::default()
+                })
+        })
+        .collect()
+}
+
+/// Counts of the last responses per bucket of `LATENCY_BUCKETS_MS`, the
+/// open bucket last.
+pub fn latency_histogram(resource: &str) -> [u64; LATENCY_BUCKETS_MS.len() + 1] {
+    let mut histogram = [0; LATENCY_BUCKETS_MS.len() + 1];
+    if let Some(entry) = METRICS.lock().unwrap().get(resource) {
+        for latency in &entry.latencies_ms {
+            histogram[LATENCY_BUCKETS_MS.partition_point(|bound| bound < latency)] += 1;
+        }
+    }
+    histogram
+}
+
+pub fn snapshot(resource: &str) -> MetricsSnapshot {
+    let now = Instant::now();
+    let metrics = METRICS.lock().unwrap();
+    let Some(entry) = metrics.get(resource) else {
+        return MetricsSnapshot::default();
+    };
+    let in_window = entry
+        .recent
+        .iter()
+        .filter(|t| now.duration_since(**t) <= RATE_WINDOW)
+        .count();
+    let window_start = PROCESS_STARTED
+        .elapsed()
+        .as_secs()
+        .saturating_sub(RATE_WINDOW.as_secs());
+    let bytes_in_window: u64 = entry
+        .traffic
+        .iter()
+        .filter(|sample| sample.second >= window_start)
+        .map(|sample| sample.bytes)
+        .sum();
+    let mut latencies: Vec<u64> = entry.latencies_ms.iter().copied().collect();
+    latencies.sort_unstable();
+    MetricsSnapshot {
+        total_requests: entry.total_requests,
+        error_responses: entry.error_responses,
+        requests_per_sec: in_window as f64 / RATE_WINDOW.as_secs_f64(),
+        bytes_per_sec: bytes_in_window as f64 / RATE_WINDOW.as_secs_f64(),
+        latency_p50_ms: percentile(&latencies, 50),
+        latency_p95_ms: percentile(&latencies, 95),
+        latency_p99_ms: percentile(&latencies, 99),
+        request_bytes: entry.request_bytes,
+        response_bytes: entry.response_bytes,
+        last_success_at: entry.last_success,
+        reconnects: entry.reconnects,
+        state_transitions: entry.state_transitions,
+        cache_hits: entry.cache_hits,
+        probe_latency_ms: entry.probe_latency_ms,
+    }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[u64], percent: usize) -> Option<u64> {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
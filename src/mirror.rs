@@ -0,0 +1,420 @@
+//! A forward's `mirror_to`: a copy of every request the proxy forwards is
+//! also sent to a second target, another forward of the session or a URL,
+//! e.g. to compare a new version of a service with the current one under
+//! real traffic.
+//!
+//! Mirroring never delays or changes the client's request: copies are sent
+//! in the background once the request body is complete, and their responses
+//! are read and dropped. Copies carry `x-kpf-mirror`, and requests carrying
+//! it are not mirrored again.
+//!
+//! Host names of URL targets are resolved on the runtime with the system's
+//! DNS settings, without blocking a thread in getaddrinfo, and cached for up
+//! to `DNS_CACHE_TTL`, unless the forward's `mirror_resolve` names their
+//! addresses; IPv6 and IPv4 addresses are tried happy-eyeballs style.
+
+use crate::config::MirrorTlsConfig;
+use anyhow::{bail, Context, Result};
+use hickory_resolver::config::LookupIpStrategy;
+use hickory_resolver::TokioAsyncResolver;
+use hyper::body::Bytes;
+use hyper::client::connect::dns::Name;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::{Body, Client, Method, Request};
+use hyper_rustls::HttpsConnector;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Marks mirrored copies, naming the forward they were copied from.
+pub const MIRROR_HEADER: &str = "x-kpf-mirror";
+
+/// Largest request body that is mirrored; requests with larger bodies are not.
+pub const MAX_MIRRORED_BODY: usize = 1024 * 1024;
+
+/// Longest a mirrored request may take before it is given up on.
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Mirrored requests of a forward in flight at once; more are dropped, so a
+/// slow target cannot pile them up.
+const MAX_IN_FLIGHT: usize = 100;
+
+/// Longest a resolved mirror host is used before it is looked up again,
+/// however long its records live.
+const DNS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Head start of the first address family before the other one is tried too.
+const HAPPY_EYEBALLS_TIMEOUT: Duration = Duration::from_millis(300);
+
+static MIRRORS: LazyLock<Mutex<HashMap<String, Arc<Mirror>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Resolves and caches mirror hosts, or why the system's DNS settings could
+/// not be read.
+static DNS: LazyLock<Result<TokioAsyncResolver, String>> = LazyLock::new(|| {
+    let (config, mut options) = hickory_resolver::system_conf::read_system_conf()
+        .map_err(|e| format!("Cannot read the system's DNS settings: {}", e))?;
+    // Both families, for happy eyeballs to choose from
+    options.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+    options.positive_max_ttl = Some(DNS_CACHE_TTL);
+    Ok(TokioAsyncResolver::tokio(config, options))
+});
+
+type MirrorClient = Client<HttpsConnector<HttpConnector<Resolver>>>;
+
+/// Where a forward's requests are mirrored to.
+#[derive(Debug)]
+pub enum MirrorTarget {
+    /// Another forward of this session, by key or resource name
+    Forward(String),
+    /// Base URL the request path is appended to
+    Url(String),
+}
+
+impl MirrorTarget {
+    pub fn parse(key: &str, mirror_to: &str) -> Result<MirrorTarget> {
+        let mirror_to = mirror_to.trim();
+        if mirror_to.is_empty() {
+            bail!("mirror_to of {} is empty", key);
+        }
+        if !mirror_to.contains("://") {
+            if mirror_to == key || mirror_to == crate::template::resource_name(key) {
+                bail!("{} cannot mirror to itself", key);
+            }
+            return Ok(MirrorTarget::Forward(mirror_to.to_string()));
+        }
+        let uri: hyper::Uri = mirror_to
+            .parse()
+            .context(format!("Invalid mirror_to '{}' of {}", mirror_to, key))?;
+        if !matches!(uri.scheme_str(), Some("http" | "https")) || uri.host().is_none() {
+            bail!("Invalid mirror_to '{}' of {}. Expected a forward or an http:// or https:// URL", mirror_to, key);
+        }
+        Ok(MirrorTarget::Url(mirror_to.trim_end_matches('/').to_string()))
+    }
+
+    /// The URL a request of the forward `key` to `path_and_query` is mirrored
+    /// to, and the credentials the target forward requires.
+    fn resolve(&self, key: &str, path_and_query: &str) -> Result<(String, Option<String>)> {
+        match self {
+            MirrorTarget::Url(base) => Ok((format!("{}{}", base, path_and_query), None)),
+            MirrorTarget::Forward(name) => {
+                let target = crate::forwarder::resolve_mirror_target(key, name)?;
+                let statuses = crate::forwarder::FORWARD_STATUSES.lock().unwrap();
+                let Some(status) = statuses.get(&target) else {
+                    bail!("{} is not running", target);
+                };
+                let mut url = crate::http::local_url(status.local_port, path_and_query);
+                if status.tls {
+                    url = url.replacen("http://", "https://", 1);
+                }
+                Ok((url, crate::auth::credentials(&status.resource)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for MirrorTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MirrorTarget::Forward(name) | MirrorTarget::Url(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A forward's mirroring, with the state of its target.
+pub struct Mirror {
+    target: MirrorTarget,
+    client: MirrorClient,
+    in_flight: Arc<Semaphore>,
+    /// Set while mirrored requests fail, so failures are logged once
+    failing: AtomicBool,
+}
+
+/// Registers where a forward mirrors its requests to, replacing any earlier
+/// target. `resolve` overrides DNS for the hosts of a URL target, and `tls`
+/// how its certificate is checked.
+pub fn configure(
+    key: &str,
+    mirror_to: Option<&str>,
+    resolve: Option<&HashMap<String, Vec<IpAddr>>>,
+    tls: Option<&MirrorTlsConfig>,
+) -> Result<()> {
+    let mirror = match mirror_to {
+        Some(mirror_to) => {
+            let target = MirrorTarget::parse(key, mirror_to)?;
+            let resolver = Resolver {
+                overrides: Arc::new(resolve.cloned().unwrap_or_default()),
+            };
+            let tls = match target {
+                // Another forward's proxy, on this machine, presents the
+                // certificate kpf was given for it
+                MirrorTarget::Forward(_) => crate::tls::client_config(None, true, None)?,
+                MirrorTarget::Url(_) => {
+                    let tls = tls.cloned().unwrap_or_default();
+                    let options = crate::forwarder::forward_options();
+                    let ca = tls.ca.or(options.upstream_ca);
+                    crate::tls::client_config(
+                        ca.as_deref(),
+                        tls.skip_verify.unwrap_or(options.upstream_tls_skip_verify),
+                        None,
+                    )?
+                }
+            };
+            Some(Mirror {
+                target,
+                client: client(resolver, tls),
+                in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+                failing: AtomicBool::new(false),
+            })
+        }
+        None => None,
+    };
+    let mut registry = MIRRORS.lock().unwrap();
+    match mirror {
+        Some(mirror) => registry.insert(key.to_string(), Arc::new(mirror)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+fn client(resolver: Resolver, tls: tokio_rustls::rustls::ClientConfig) -> MirrorClient {
+    let mut http = HttpConnector::new_with_resolver(resolver);
+    http.enforce_http(false);
+    http.set_happy_eyeballs_timeout(Some(HAPPY_EYEBALLS_TIMEOUT));
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http);
+    Client::builder().build(connector)
+}
+
+/// Resolves the hosts of mirror URLs: the forward's `mirror_resolve` first,
+/// then DNS, through `DNS`.
+#[derive(Clone)]
+struct Resolver {
+    overrides: Arc<HashMap<String, Vec<IpAddr>>>,
+}
+
+impl hyper::service::Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = std::io::Error;
+    type Future = Pin<Box<dyn Future<Output = std::io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let overrides = self.overrides.clone();
+        Box::pin(async move {
+            let addresses = match overrides.get(name.as_str()) {
+                Some(addresses) => addresses.clone(),
+                None => lookup(name.as_str()).await?,
+            };
+            // The connector fills in the port of the URL
+            let addresses: Vec<SocketAddr> = addresses.into_iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+            Ok(addresses.into_iter())
+        })
+    }
+}
+
+/// Addresses of `host`, from the resolver's cache while they are fresh.
+async fn lookup(host: &str) -> std::io::Result<Vec<IpAddr>> {
+    let resolver = DNS
+        .as_ref()
+        .map_err(|e| std::io::Error::other(e.clone()))?;
+    let addresses: Vec<IpAddr> = resolver
+        .lookup_ip(host)
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, format!("{}: {}", host, e)))?
+        .iter()
+        .collect();
+    if addresses.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} has no addresses", host),
+        ));
+    }
+    Ok(addresses)
+}
+
+/// The mirroring of a forward, unless `headers` mark the request as a
+/// mirrored copy itself.
+pub fn mirror(key: &str, headers: &HeaderMap) -> Option<Arc<Mirror>> {
+    if headers.contains_key(MIRROR_HEADER) {
+        return None;
+    }
+    MIRRORS.lock().unwrap().get(key).cloned()
+}
+
+/// A request to copy, taken before its body has been read.
+pub struct MirroredRequest {
+    pub key: String,
+    pub method: Method,
+    pub path_and_query: String,
+    pub headers: HeaderMap,
+    pub verbose: u8,
+}
+
+impl Mirror {
+    /// Sends a copy of `request` with `body` in the background.
+    pub fn send(self: &Arc<Self>, request: MirroredRequest, body: Bytes) {
+        // A target that cannot keep up loses copies rather than piling them up
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            return;
+        };
+        let mirror = self.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = mirror.request(&request, body).await;
+            drop(permit);
+            match result {
+                Ok(status) => {
+                    if mirror.failing.swap(false, Ordering::Relaxed) {
+                        crate::logger::log_info(format!(
+                            "{} {} mirroring to {} works again",
+                            "🪞", request.key, mirror.target
+                        ));
+                    }
+                    if request.verbose >= 2 {
+                        crate::logger::log_info(format!(
+                            "{} {} - {} {} mirrored to {} → {} ({}ms)",
+                            "🪞",
+                            request.key,
+                            request.method,
+                            request.path_and_query,
+                            mirror.target,
+                            status,
+                            started.elapsed().as_millis()
+                        ));
+                    }
+                }
+                Err(e) => {
+                    if !mirror.failing.swap(true, Ordering::Relaxed) {
+                        crate::logger::log_warning(format!(
+                            "{} {} failed to mirror {} {} to {}: {:#}",
+                            "🪞", request.key, request.method, request.path_and_query, mirror.target, e
+                        ));
+                    }
+                }
+            }
+        });
+    }
+
+    async fn request(&self, request: &MirroredRequest, body: Bytes) -> Result<hyper::StatusCode> {
+        let (url, credentials) = self.target.resolve(&request.key, &request.path_and_query)?;
+        let mut copy = Request::builder().method(request.method.clone()).uri(&url);
+        for (name, value) in &request.headers {
+            if !matches!(
+                name.as_str(),
+                "host" | "content-length" | "transfer-encoding" | "connection" | "proxy-authorization"
+            ) {
+                copy = copy.header(name, value);
+            }
+        }
+        copy = copy.header(MIRROR_HEADER, HeaderValue::from_str(&request.key)?);
+        if let Some(credentials) = credentials {
+            copy = copy.header(hyper::header::PROXY_AUTHORIZATION, credentials);
+        }
+        let copy = copy.body(Body::from(body))?;
+
+        let exchange = async {
+            let response = self.client.request(copy).await?;
+            let status = response.status();
+            hyper::body::to_bytes(response.into_body()).await?;
+            anyhow::Ok(status)
+        };
+        tokio::time::timeout(MIRROR_TIMEOUT, exchange).await.context("timed out")?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TlsConfig;
+    use std::path::PathBuf;
+
+    fn testdata(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/testdata").join(name)
+    }
+
+    /// Serves one HTTPS request at a time on localhost with the test
+    /// certificate, signed by `testdata/ca.pem`. Returns its port.
+    async fn https_server() -> u16 {
+        let tls = crate::tls::server_config(&TlsConfig {
+            cert: testdata("localhost.pem"),
+            key: testdata("localhost.key"),
+            client_ca: None,
+        })
+        .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(tls);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(stream) = acceptor.accept(stream).await else {
+                    continue;
+                };
+                let service = hyper::service::service_fn(|_| async {
+                    Ok::<_, std::convert::Infallible>(hyper::Response::new(Body::empty()))
+                });
+                let _ = hyper::server::conn::Http::new().serve_connection(stream, service).await;
+            }
+        });
+        port
+    }
+
+    async fn mirror_status(tls: tokio_rustls::rustls::ClientConfig, port: u16) -> Result<u16> {
+        let resolver = Resolver {
+            overrides: Arc::new(HashMap::from([("localhost".to_string(), vec!["127.0.0.1".parse()?])])),
+        };
+        let uri: hyper::Uri = format!("https://localhost:{}/", port).parse()?;
+        Ok(client(resolver, tls).get(uri).await?.status().as_u16())
+    }
+
+    #[tokio::test]
+    async fn untrusted_certificate_is_rejected() {
+        let port = https_server().await;
+        let tls = crate::tls::client_config(None, false, None).unwrap();
+        assert!(mirror_status(tls, port).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn certificate_signed_by_the_ca_is_accepted() {
+        let port = https_server().await;
+        let tls = crate::tls::client_config(Some(&testdata("ca.pem")), false, None).unwrap();
+        assert_eq!(mirror_status(tls, port).await.unwrap(), 200);
+    }
+
+    #[tokio::test]
+    async fn skip_verify_accepts_any_certificate() {
+        let port = https_server().await;
+        let tls = crate::tls::client_config(None, true, None).unwrap();
+        assert_eq!(mirror_status(tls, port).await.unwrap(), 200);
+    }
+
+    #[tokio::test]
+    async fn hosts_resolve_through_the_system_settings() {
+        let addresses = lookup("localhost").await.unwrap();
+        assert!(addresses.iter().all(IpAddr::is_loopback), "{:?}", addresses);
+    }
+
+    #[test]
+    fn targets_are_parsed() {
+        assert!(matches!(MirrorTarget::parse("service/api:80", "service/canary:80"), Ok(MirrorTarget::Forward(_))));
+        assert!(matches!(
+            MirrorTarget::parse("service/api:80", "https://canary.example.com/"),
+            Ok(MirrorTarget::Url(url)) if url == "https://canary.example.com"
+        ));
+        assert!(MirrorTarget::parse("service/api:80", "service/api:80").is_err());
+        assert!(MirrorTarget::parse("service/api:80", "ftp://canary").is_err());
+        assert!(MirrorTarget::parse("service/api:80", " ").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Record-and-mock mode. `--record <DIR>` writes every response the proxy
+//! passes on to disk; `--mock <DIR>` answers requests from those recordings
+//! without connecting to the cluster.
+//!
+//! Each forward gets a directory named after its key, holding one recording
+//! per method, path and query: `<hash>.json` with the status and headers, and
+//! `<hash>.body` with the body as received. A later response to the same
+//! request replaces the earlier one.
+
+use anyhow::{bail, Context, Result};
+use hyper::body::Bytes;
+use hyper::{Body, HeaderMap, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock};
+
+/// Largest response body recorded; bigger responses are not.
+pub const MAX_RECORDED_BODY: usize = 16 * 1024 * 1024;
+
+static RECORD_DIR: OnceLock<PathBuf> = OnceLock::new();
+static MOCK: OnceLock<MockSettings> = OnceLock::new();
+
+/// Recordings served in mock mode, by forward directory and request key.
+static RECORDINGS: LazyLock<Mutex<HashMap<String, HashMap<String, Recording>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct MockSettings {
+    dir: PathBuf,
+    /// Send requests without a recording to the cluster instead of answering 404
+    passthrough: bool,
+}
+
+/// A recorded response, as stored in `<hash>.json`.
+#[derive(Serialize, Deserialize)]
+struct RecordingMeta {
+    method: String,
+    /// Path and query of the request
+    path: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    recorded_at: chrono::DateTime<chrono::Utc>,
+}
+
+struct Recording {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Sets the directory of `--record`.
+pub fn set_record_dir(dir: PathBuf) -> Result<()> {
+    std::fs::create_dir_all(&dir)
+        .context(format!("Failed to create the recordings directory {}", dir.display()))?;
+    let _ = RECORD_DIR.set(dir);
+    Ok(())
+}
+
+/// Turns on mock mode (`--mock`), loading the recordings under `dir`.
+pub fn set_mock(dir: PathBuf, passthrough: bool) -> Result<()> {
+    if !dir.is_dir() {
+        bail!(
+            "Recordings directory {} does not exist; create it with --record {}",
+            dir.display(),
+            dir.display()
+        );
+    }
+    let mut recordings = RECORDINGS.lock().unwrap();
+    let mut count = 0;
+    for forward_dir in std::fs::read_dir(&dir)
+        .context(format!("Failed to read {}", dir.display()))?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+    {
+        let forward = forward_dir.file_name().to_string_lossy().into_owned();
+        for file in std::fs::read_dir(forward_dir.path())?.flatten() {
+            let path = file.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                let (key, recording) = load_recording(&path)
+                    .context(format!("Failed to load recording {}", path.display()))?;
+                recordings.entry(forward.clone()).or_default().insert(key, recording);
+                count += 1;
+            }
+        }
+    }
+    crate::logger::log_info(format!(
+        "{} Mock mode: serving {} recorded responses from {}{}",
+        "🎭",
+        count,
+        dir.display(),
+        if passthrough { ", passing other requests through" } else { "" }
+    ));
+    let _ = MOCK.set(MockSettings { dir, passthrough });
+    Ok(())
+}
+
+fn load_recording(path: &Path) -> Result<(String, Recording)> {
+    let meta: RecordingMeta = serde_json::from_slice(&std::fs::read(path)?)?;
+    let body = std::fs::read(path.with_extension("body")).unwrap_or_default();
+    let key = format!("{} {}", meta.method, meta.path);
+    let recording = Recording {
+        status: StatusCode::from_u16(meta.status)?,
+        headers: meta
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.parse().ok()?, value.parse().ok()?)))
+            .collect(),
+        body: Bytes::from(body),
+    };
+    Ok((key, recording))
+}
+
+/// Whether forwards serve recordings instead of connecting (`--mock`).
+pub fn is_mocking() -> bool {
+    MOCK.get().is_some()
+}
+
+/// Whether requests without a recording go to the cluster (`--mock-passthrough`).
+pub fn passthrough() -> bool {
+    MOCK.get().is_some_and(|mock| mock.passthrough)
+}
+
+/// Directory of a forward's recordings, named after its key.
+fn forward_dir(resource: &str) -> String {
+    resource
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') { c } else { '_' })
+        .collect()
+}
+
+/// The recorded response to `key` (`METHOD path?query`), with an
+/// `x-kpf-mock: hit` header.
+pub fn response(resource: &str, key: &str) -> Option<Response<Body>> {
+    let recordings = RECORDINGS.lock().unwrap();
+    let recording = recordings.get(&forward_dir(resource))?.get(key)?;
+    let mut response = Response::new(Body::from(recording.body.clone()));
+    *response.status_mut() = recording.status;
+    *response.headers_mut() = recording.headers.clone();
+    response
+        .headers_mut()
+        .insert("x-kpf-mock", hyper::header::HeaderValue::from_static("hit"));
+    Some(response)
+}
+
+/// Whether responses are written to disk (`--record`).
+pub fn is_recording() -> bool {
+    RECORD_DIR.get().is_some()
+}
+
+/// Writes a response the client has read in full to the recordings, and
+/// serves it from then on in mock mode.
+pub fn record(resource: &str, key: &str, status: StatusCode, headers: &HeaderMap, body: &[u8]) {
+    let Some(dir) = RECORD_DIR.get() else {
+        return;
+    };
+    let (method, path) = key.split_once(' ').unwrap_or((key, ""));
+    let meta = RecordingMeta {
+        method: method.to_string(),
+        path: path.to_string(),
+        status: status.as_u16(),
+        headers: headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        recorded_at: chrono::Utc::now(),
+    };
+    let forward = forward_dir(resource);
+    let file = dir
+        .join(&forward)
+        .join(format!("{:016x}", crate::config::stable_hash(key)));
+    let written = std::fs::create_dir_all(dir.join(&forward))
+        .and_then(|_| std::fs::write(file.with_extension("body"), body))
+        .and_then(|_| std::fs::write(file.with_extension("json"), serde_json::to_vec_pretty(&meta)?));
+    if let Err(e) = written {
+        crate::logger::log_warning(format!(
+            "{} Failed to record {} for {}: {}",
+            "🎭", key, resource, e
+        ));
+        return;
+    }
+
+    if MOCK.get().is_some_and(|mock| mock.dir == *dir) {
+        if let Ok((key, recording)) = load_recording(&file.with_extension("json")) {
+            RECORDINGS.lock().unwrap().entry(forward).or_default().insert(key, recording);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! `notifications`: telling a webhook, Slack or a shell command when a
+//! forward goes down, fails its liveness probe, gives up reconnecting or
+//! comes back, so a long-running forward that dies overnight does not go
+//! unnoticed.
+//!
+//! Notifications are delivered one at a time by a background task and never
+//! delay the forwards. An outage is reported once: a forward that failed its
+//! probe is not reported down again when the port-forward is then restarted.
+//!
+//! `--notify` also shows failures as desktop notifications, through the
+//! platform's notification service: the freedesktop D-Bus service on Linux,
+//! the notification center on macOS and toast notifications on Windows.
+
+use crate::config::{NotificationEvent, NotificationsConfig};
+use anyhow::{bail, Context, Result};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use hyper_rustls::HttpsConnector;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Longest a webhook or command may take before it is given up on.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Title of desktop notifications.
+const DESKTOP_TITLE: &str = "kpf";
+
+static NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+
+/// Set by `--notify`.
+static DESKTOP: AtomicBool = AtomicBool::new(false);
+/// Set once a desktop notification failed, so the failure is logged once.
+static DESKTOP_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Forwards reported down and not yet back up.
+static DOWN: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+struct Notifier {
+    events: Option<Vec<NotificationEvent>>,
+    sender: mpsc::UnboundedSender<Notification>,
+}
+
+/// A state change of a forward.
+#[derive(Clone, serde::Serialize)]
+pub struct Notification {
+    pub event: NotificationEvent,
+    /// The forward's key, e.g. `service/api:80`
+    pub forward: String,
+    pub namespace: String,
+    pub context: String,
+    pub message: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl Notification {
+    /// e.g. `🔴 service/api:80 is down: liveness probe failed 3 times in a row`
+    fn summary(&self) -> String {
+        let (icon, what) = match self.event {
+            NotificationEvent::Down => ("🔴", "is down"),
+            NotificationEvent::Up => ("🟢", "is back up"),
+            NotificationEvent::ProbeFailed => ("🟠", "failed its liveness probe"),
+            NotificationEvent::GaveUp => ("⛔", "gave up reconnecting"),
+        };
+        match self.message.is_empty() {
+            true => format!("{} {} {}", icon, self.forward, what),
+            false => format!("{} {} {}: {}", icon, self.forward, what, self.message),
+        }
+    }
+}
+
+/// Checks a `notifications` block.
+pub fn validate(config: &NotificationsConfig) -> Result<()> {
+    for (name, url) in [("webhook", &config.webhook), ("slack_webhook", &config.slack_webhook)] {
+        if let Some(url) = url {
+            let uri: hyper::Uri = url.parse().context(format!("Invalid notifications {} '{}'", name, url))?;
+            if !matches!(uri.scheme_str(), Some("http" | "https")) || uri.host().is_none() {
+                bail!("Invalid notifications {} '{}'. Expected an http:// or https:// URL", name, url);
+            }
+        }
+    }
+    if config.command.as_ref().is_some_and(|command| command.trim().is_empty()) {
+        bail!("notifications command is empty");
+    }
+    if config.webhook.is_none() && config.slack_webhook.is_none() && config.command.is_none() {
+        bail!("notifications needs a webhook, slack_webhook or command");
+    }
+    Ok(())
+}
+
+/// Starts delivering notifications to the targets of `config`.
+pub fn start(config: &NotificationsConfig) -> Result<()> {
+    validate(config)?;
+    let tls = crate::tls::client_config(None, false, None)?;
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls)
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = Client::builder().build(connector);
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let notifier = Notifier {
+        events: config.events.clone(),
+        sender,
+    };
+    if NOTIFIER.set(notifier).is_ok() {
+        tokio::spawn(deliver_all(client, config.clone(), receiver));
+    }
+    Ok(())
+}
+
+/// Shows forwards going down, failing their probe or giving up as desktop
+/// notifications (`--notify`).
+pub fn enable_desktop() {
+    DESKTOP.store(true, Ordering::Relaxed);
+}
+
+/// Reports that a connected forward lost its port-forward.
+pub fn down(key: &str, message: String) {
+    if DOWN.lock().unwrap().insert(key.to_string()) {
+        send(NotificationEvent::Down, key, message);
+    }
+}
+
+/// Reports a forward connected again after it was reported down.
+pub fn up(key: &str, message: String) {
+    if DOWN.lock().unwrap().remove(key) {
+        send(NotificationEvent::Up, key, message);
+    }
+}
+
+/// Reports a forward whose liveness probe failed too often; it counts as down.
+pub fn probe_failed(key: &str, message: String) {
+    if DOWN.lock().unwrap().insert(key.to_string()) {
+        send(NotificationEvent::ProbeFailed, key, message);
+    }
+}
+
+/// Reports a forward that stopped reconnecting.
+pub fn gave_up(key: &str, message: String) {
+    DOWN.lock().unwrap().insert(key.to_string());
+    send(NotificationEvent::GaveUp, key, message);
+}
+
+fn send(event: NotificationEvent, key: &str, message: String) {
+    let desktop = DESKTOP.load(Ordering::Relaxed) && event != NotificationEvent::Up;
+    let notifier = NOTIFIER
+        .get()
+        .filter(|notifier| notifier.events.as_ref().is_none_or(|events| events.contains(&event)));
+    if !desktop && notifier.is_none() {
+        return;
+    }
+    let (namespace, context) = crate::forwarder::FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .get(key)
+        .map(|status| (status.namespace.clone(), status.context.clone()))
+        .unwrap_or_default();
+    let notification = Notification {
+        event,
+        forward: key.to_string(),
+        namespace,
+        context,
+        message,
+        timestamp: chrono::Utc::now(),
+    };
+    if desktop {
+        let summary = notification.summary();
+        tokio::spawn(async move {
+            if let Err(e) = show_desktop(&summary).await {
+                if !DESKTOP_FAILED.swap(true, Ordering::Relaxed) {
+                    report_failure("desktop", &e);
+                }
+            }
+        });
+    }
+    if let Some(notifier) = notifier {
+        let _ = notifier.sender.send(notification);
+    }
+}
+
+async fn deliver_all(
+    client: Client<HttpsConnector<HttpConnector>>,
+    config: NotificationsConfig,
+    mut notifications: mpsc::UnboundedReceiver<Notification>,
+) {
+    while let Some(notification) = notifications.recv().await {
+        if let Some(url) = &config.webhook {
+            let body = serde_json::to_vec(&notification).unwrap_or_default();
+            if let Err(e) = post(&client, url, body).await {
+                report_failure("webhook", &e);
+            }
+        }
+        if let Some(url) = &config.slack_webhook {
+            let body = serde_json::json!({ "text": notification.summary() }).to_string();
+            if let Err(e) = post(&client, url, body.into_bytes()).await {
+                report_failure("Slack webhook", &e);
+            }
+        }
+        if let Some(command) = &config.command {
+            if let Err(e) = run(command, &notification).await {
+                report_failure("command", &e);
+            }
+        }
+    }
+}
+
+fn report_failure(target: &str, e: &anyhow::Error) {
+    crate::logger::log_warning(format!("{} Failed to send a notification to the {}: {:#}", "🔔", target, e));
+}
+
+async fn post(client: &Client<HttpsConnector<HttpConnector>>, url: &str, body: Vec<u8>) -> Result<()> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))?;
+    let response = tokio::time::timeout(DELIVERY_TIMEOUT, client.request(request))
+        .await
+        .context("timed out")??;
+    if !response.status().is_success() {
+        bail!("answered {}", response.status());
+    }
+    Ok(())
+}
+
+/// Runs `command` through the shell with the notification in `KPF_EVENT`,
+/// `KPF_FORWARD`, `KPF_NAMESPACE`, `KPF_CONTEXT` and `KPF_MESSAGE`.
+async fn run(command: &str, notification: &Notification) -> Result<()> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let event = serde_json::to_value(notification.event)?;
+    cmd.env("KPF_EVENT", event.as_str().unwrap_or_default())
+        .env("KPF_FORWARD", &notification.forward)
+        .env("KPF_NAMESPACE", &notification.namespace)
+        .env("KPF_CONTEXT", &notification.context)
+        .env("KPF_MESSAGE", notification.summary())
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true);
+    let status = tokio::time::timeout(DELIVERY_TIMEOUT, cmd.status())
+        .await
+        .context("timed out")??;
+    if !status.success() {
+        bail!("exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Shows `message` as a desktop notification. The platform calls block, on
+/// D-Bus or the OS notification center, so they run off the runtime.
+async fn show_desktop(message: &str) -> Result<()> {
+    let mut notification = notify_rust::Notification::new();
+    notification.appname(DESKTOP_TITLE).summary(DESKTOP_TITLE).body(message);
+    let shown = tokio::task::spawn_blocking(move || notification.show().map(|_| ()));
+    tokio::time::timeout(DELIVERY_TIMEOUT, shown)
+        .await
+        .context("timed out")??
+        .context("the desktop notification could not be shown")?;
+    Ok(())
+}
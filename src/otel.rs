@@ -0,0 +1,313 @@
+//! W3C trace context propagation and OTLP span export.
+//!
+//! Every proxied request carries a `traceparent` to the upstream: the client's
+//! trace is continued when it sent one, otherwise a new trace is started. With
+//! `--otlp-endpoint`, each request is also exported as two spans: a server span
+//! for the whole proxy hop and a client span for the upstream call, so proxy
+//! overhead (chaos delays, queuing) shows up separately from upstream latency.
+//! Spans are sent as OTLP/HTTP JSON, which every OTLP collector accepts.
+
+use anyhow::{Context, Result};
+use hyper::{Body, Client, HeaderMap, Method, Request};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// Spans sent in one export request at most.
+const MAX_BATCH: usize = 512;
+/// How long spans are collected before a batch is sent.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+static EXPORTER: OnceLock<mpsc::UnboundedSender<Span>> = OnceLock::new();
+
+/// The trace a proxied request belongs to and the ids of the spans kpf adds.
+#[derive(Clone, Debug)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    /// Span id from the client's `traceparent`, if it sent one
+    parent_span_id: Option<[u8; 8]>,
+    /// The proxy hop, child of the client's span
+    server_span_id: [u8; 8],
+    /// The upstream call, propagated as the parent of the upstream's spans
+    client_span_id: [u8; 8],
+    sampled: bool,
+}
+
+impl TraceContext {
+    /// Continues the trace in the request's `traceparent`, or starts a new one
+    /// when it is missing or malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let parent = headers
+            .get("traceparent")
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_traceparent);
+        let (trace_id, parent_span_id, sampled) = match parent {
+            Some((trace_id, span_id, flags)) => (trace_id, Some(span_id), flags & 1 == 1),
+            None => (random_id(), None, true),
+        };
+        TraceContext {
+            trace_id,
+            parent_span_id,
+            server_span_id: random_id(),
+            client_span_id: random_id(),
+            sampled,
+        }
+    }
+
+    /// `traceparent` value sent to the upstream.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            hex(&self.trace_id),
+            hex(&self.client_span_id),
+            u8::from(self.sampled)
+        )
+    }
+
+    /// Queues the spans of a finished request for export. Does nothing unless
+    /// `--otlp-endpoint` is set or when the client's trace is not sampled.
+    pub fn export(&self, request: &RequestSpans) {
+        let Some(exporter) = EXPORTER.get() else {
+            return;
+        };
+        if !self.sampled {
+            return;
+        }
+
+        let mut attributes = vec![
+            ("kpf.forward", serde_json::json!({ "stringValue": request.resource })),
+            ("http.request.method", serde_json::json!({ "stringValue": request.method.as_str() })),
+            ("url.path", serde_json::json!({ "stringValue": request.path })),
+        ];
+        if let Some(status) = request.status {
+            // OTLP JSON encodes 64-bit integers as strings
+            attributes.push((
+                "http.response.status_code",
+                serde_json::json!({ "intValue": status.to_string() }),
+            ));
+        }
+        let error = request.status.is_none_or(|status| status >= 500);
+
+        let _ = exporter.send(Span {
+            trace_id: self.trace_id,
+            span_id: self.server_span_id,
+            parent_span_id: self.parent_span_id,
+            name: format!("kpf {}", request.method),
+            kind: SpanKind::Server,
+            start: request.started_at,
+            end: request.finished_at,
+            attributes: attributes.clone(),
+            error,
+        });
+        let _ = exporter.send(Span {
+            trace_id: self.trace_id,
+            span_id: self.client_span_id,
+            parent_span_id: Some(self.server_span_id),
+            name: format!("upstream {}", request.method),
+            kind: SpanKind::Client,
+            start: request.upstream_started_at,
+            end: request.finished_at,
+            attributes,
+            error,
+        });
+    }
+}
+
+/// Timing and outcome of one proxied request. Both spans end when the
+/// upstream's response headers arrive, or when the upstream call fails.
+pub struct RequestSpans {
+    pub resource: String,
+    pub method: Method,
+    pub path: String,
+    /// `None` when the upstream could not be reached
+    pub status: Option<u16>,
+    pub started_at: SystemTime,
+    pub upstream_started_at: SystemTime,
+    pub finished_at: SystemTime,
+}
+
+/// `00-<trace id>-<span id>-<flags>`; other versions are read the same way,
+/// as the spec asks.
+fn parse_traceparent(value: &str) -> Option<([u8; 16], [u8; 8], u8)> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let span_id = parts.next()?;
+    let flags = parts.next()?;
+    if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+    let trace_id: [u8; 16] = unhex(trace_id)?.try_into().ok()?;
+    let span_id: [u8; 8] = unhex(span_id)?.try_into().ok()?;
+    let flags = u8::from_str_radix(flags, 16).ok().filter(|_| flags.len() == 2)?;
+    if trace_id == [0; 16] || span_id == [0; 8] {
+        return None;
+    }
+    Some((trace_id, span_id, flags))
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    loop {
+        let id: [u8; N] = std::array::from_fn(|_| rand::random());
+        // All-zero ids are invalid
+        if id.iter().any(|byte| *byte != 0) {
+            return id;
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    let lowercase_hex = text.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'));
+    if !text.len().is_multiple_of(2) || !lowercase_hex {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum SpanKind {
+    Server = 2,
+    Client = 3,
+}
+
+struct Span {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    name: String,
+    kind: SpanKind,
+    start: SystemTime,
+    end: SystemTime,
+    /// Keys with their OTLP `AnyValue`
+    attributes: Vec<(&'static str, serde_json::Value)>,
+    error: bool,
+}
+
+impl Span {
+    fn to_json(&self) -> serde_json::Value {
+        let nanos = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+                .to_string()
+        };
+        let attributes: Vec<_> = self
+            .attributes
+            .iter()
+            .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+            .collect();
+        let mut span = serde_json::json!({
+            "traceId": hex(&self.trace_id),
+            "spanId": hex(&self.span_id),
+            "name": self.name,
+            "kind": self.kind as u8,
+            "startTimeUnixNano": nanos(self.start),
+            "endTimeUnixNano": nanos(self.end),
+            "attributes": attributes,
+            // UNSET lets the backend derive the outcome; ERROR marks failures
+            "status": { "code": if self.error { 2 } else { 0 } },
+        });
+        if let Some(parent) = &self.parent_span_id {
+            span["parentSpanId"] = hex(parent).into();
+        }
+        span
+    }
+}
+
+/// Starts exporting spans to the OTLP/HTTP collector at `endpoint`, e.g.
+/// `http://localhost:4318`. `/v1/traces` is appended unless already present.
+pub fn start_exporter(endpoint: &str) -> Result<()> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let url: hyper::Uri = if endpoint.ends_with("/v1/traces") {
+        endpoint.parse()
+    } else {
+        format!("{}/v1/traces", endpoint).parse()
+    }
+    .context(format!("Invalid OTLP endpoint '{}'", endpoint))?;
+
+    let tls = crate::tls::client_config(None, false, None)?;
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls)
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = Client::builder().build(connector);
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if EXPORTER.set(sender).is_ok() {
+        tokio::spawn(run_exporter(client, url, receiver));
+    }
+    Ok(())
+}
+
+async fn run_exporter(
+    client: Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>,
+    url: hyper::Uri,
+    mut spans: mpsc::UnboundedReceiver<Span>,
+) {
+    // Failures are reported once until an export succeeds again
+    let mut failing = false;
+
+    while let Some(span) = spans.recv().await {
+        let mut batch = vec![span];
+        let deadline = tokio::time::sleep(EXPORT_INTERVAL);
+        tokio::pin!(deadline);
+        while batch.len() < MAX_BATCH {
+            tokio::select! {
+                span = spans.recv() => match span {
+                    Some(span) => batch.push(span),
+                    None => break,
+                },
+                _ = &mut deadline => break,
+            }
+        }
+
+        match export(&client, &url, &batch).await {
+            Ok(()) => failing = false,
+            Err(e) if !failing => {
+                failing = true;
+                crate::logger::log_warning(format!(
+                    "{} Failed to export {} spans to {}: {}",
+                    "🔭",
+                    batch.len(),
+                    url,
+                    e
+                ));
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+async fn export(
+    client: &Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>,
+    url: &hyper::Uri,
+    batch: &[Span],
+) -> Result<()> {
+    let body = serde_json::json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": "kpf" } }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "kpf" },
+                "spans": batch.iter().map(Span::to_json).collect::<Vec<_>>(),
+            }],
+        }],
+    });
+    let request = Request::post(url.clone())
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(&body)?))?;
+    let response = client.request(request).await?;
+    if !response.status().is_success() {
+        anyhow::bail!("collector returned {}", response.status());
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! `--output json`: machine-readable output for scripts and CI. Instead of
+//! the TUI or emoji-decorated log lines, kpf prints one JSON object per line
+//! (NDJSON) on stdout for each forward event and log entry. JSON output is
+//! also used when stdout is not a terminal.
+
+use crate::tui::{LogEntry, LogLevel};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by `--output json`, or when stdout is not a terminal.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// The TUI, or log lines with `start`
+    #[default]
+    Text,
+    /// One JSON event per line on stdout
+    Json,
+}
+
+/// A line of JSON output.
+#[derive(Serialize)]
+struct Line<'a> {
+    timestamp: chrono::DateTime<chrono::Utc>,
+    #[serde(flatten)]
+    event: Event<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    /// A forward was added and is connecting
+    ForwardStarted {
+        forward: &'a str,
+        namespace: &'a str,
+        context: &'a str,
+        local_port: u16,
+    },
+    /// A forward is connected and, with a liveness probe, passed it
+    ForwardReady {
+        forward: &'a str,
+        local_port: u16,
+        pod: Option<&'a str>,
+    },
+    /// A proxied request got its response
+    Request {
+        forward: &'a str,
+        method: &'a str,
+        path: &'a str,
+        status: u16,
+        latency_ms: u128,
+    },
+    /// Connecting failed or an established port-forward ended
+    ForwardFailed {
+        forward: &'a str,
+        error: &'a str,
+        /// Whether the forward tries again
+        retrying: bool,
+    },
+    Log {
+        level: LogLevel,
+        message: &'a str,
+    },
+}
+
+/// Switches to JSON output.
+pub fn set_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+fn emit(event: Event) {
+    emit_at(chrono::Utc::now(), event);
+}
+
+fn emit_at(timestamp: chrono::DateTime<chrono::Utc>, event: Event) {
+    if !is_json() {
+        return;
+    }
+    let line = Line { timestamp, event };
+    let Ok(line) = serde_json::to_string(&line) else {
+        return;
+    };
+    // Written whole under the lock, so lines from different tasks never interleave
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+pub fn forward_started(forward: &str, namespace: &str, context: &str, local_port: u16) {
+    emit(Event::ForwardStarted {
+        forward,
+        namespace,
+        context,
+        local_port,
+    });
+}
+
+pub fn forward_ready(forward: &str, local_port: u16, pod: Option<&str>) {
+    emit(Event::ForwardReady { forward, local_port, pod });
+}
+
+pub fn request(forward: &str, method: &str, path: &str, status: u16, latency_ms: u128) {
+    emit(Event::Request {
+        forward,
+        method,
+        path,
+        status,
+        latency_ms,
+    });
+}
+
+pub fn forward_failed(forward: &str, error: &str, retrying: bool) {
+    emit(Event::ForwardFailed { forward, error, retrying });
+}
+
+/// Prints a log entry as a `log` event.
+pub fn log(entry: &LogEntry) {
+    emit_at(
+        entry.timestamp,
+        Event::Log {
+            level: entry.level,
+            message: &entry.message,
+        },
+    );
+}
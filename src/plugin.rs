@@ -0,0 +1,43 @@
+//! Support for running as a kubectl plugin (`kubectl kpf svc/foo:80`).
+//!
+//! kubectl runs any `kubectl-<name>` executable on the PATH for `kubectl <name>`,
+//! so installing (or symlinking) the binary as `kubectl-kpf` is enough. In that
+//! mode the context and namespace default to kubectl's, like built-in commands.
+
+use std::path::Path;
+
+/// Name kubectl shows for the plugin in usage and help output.
+pub const PLUGIN_BIN_NAME: &str = "kubectl kpf";
+
+/// Whether the binary was started as a kubectl plugin: either through a
+/// `kubectl-*` executable name or by a caller following the
+/// `KUBECTL_PLUGINS_*` environment conventions.
+pub fn is_plugin() -> bool {
+    let invoked_as_plugin = std::env::args_os()
+        .next()
+        .and_then(|arg0| {
+            Path::new(&arg0)
+                .file_name()
+                .map(|name| name.to_string_lossy().starts_with("kubectl-"))
+        })
+        .unwrap_or(false);
+    invoked_as_plugin || std::env::var_os("KUBECTL_PLUGINS_CALLER").is_some()
+}
+
+/// Context passed down by kubectl's global `--context` flag, if any.
+pub fn context() -> Option<String> {
+    non_empty_env("KUBECTL_PLUGINS_GLOBAL_FLAG_CONTEXT")
+}
+
+/// Namespace the plugin should use when none is given on the command line:
+/// kubectl's `--namespace`, then its current namespace, then the namespace set
+/// on the kubeconfig context.
+pub fn namespace(context: Option<&str>) -> Option<String> {
+    non_empty_env("KUBECTL_PLUGINS_GLOBAL_FLAG_NAMESPACE")
+        .or_else(|| non_empty_env("KUBECTL_PLUGINS_CURRENT_NAMESPACE"))
+        .or_else(|| crate::k8s::context_namespace(context))
+}
+
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
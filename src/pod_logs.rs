@@ -0,0 +1,145 @@
+//! `tail_logs`: the container logs of a forward's target pod, streamed into
+//! a buffer the TUI shows next to the request log.
+//!
+//! The stream follows the forward: when it lands on another pod, e.g. after
+//! a rollout, the logs of the new pod are tailed instead. Logs come from the
+//! pod's default container (`kubectl.kubernetes.io/default-container`), or
+//! its first one.
+
+use crate::forwarder::FORWARD_STATUSES;
+use futures::{AsyncBufReadExt, TryStreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::LogParams;
+use kube::Api;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Lines kept per forward; the oldest are dropped first.
+const MAX_LINES: usize = 1000;
+/// Lines of earlier output shown when a pod's stream starts.
+const TAIL_LINES: i64 = 50;
+/// How often the target pod is checked while waiting for one, or for a change.
+const TARGET_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+static POD_LOGS: LazyLock<Mutex<HashMap<String, VecDeque<PodLogLine>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A line of container output.
+#[derive(Clone)]
+pub struct PodLogLine {
+    pub pod: String,
+    pub line: String,
+}
+
+/// Starts tailing the logs of the forward's target pod. Abort the task when
+/// the forward stops.
+pub fn spawn(key: String, namespace: String, context: Option<String>) -> JoinHandle<()> {
+    POD_LOGS.lock().unwrap().entry(key.clone()).or_default();
+    tokio::spawn(async move {
+        let pods: Api<Pod> = match crate::k8s::client_for_context(context.as_deref()).await {
+            Ok(client) => Api::namespaced(client, &namespace),
+            Err(e) => {
+                crate::logger::log_warning(format!("{} Not tailing pod logs for {}: {}", "📜", key, e));
+                return;
+            }
+        };
+        let mut streamed: Option<String> = None;
+        loop {
+            let Some(pod) = target_pod(&key) else {
+                tokio::time::sleep(TARGET_CHECK_INTERVAL).await;
+                continue;
+            };
+            // Set when the stream ended or failed, e.g. because the container restarted
+            let resumed = streamed.as_ref() == Some(&pod);
+            if resumed {
+                tokio::time::sleep(TARGET_CHECK_INTERVAL).await;
+            }
+            tokio::select! {
+                result = tail(&pods, &key, &pod, resumed) => {
+                    // Retried quietly until the pod moves
+                    if let (Err(e), false) = (result, resumed) {
+                        push(&key, &pod, format!("[log stream failed: {:#}]", e));
+                    }
+                }
+                _ = moved(&key, &pod) => {}
+            }
+            streamed = Some(pod);
+        }
+    })
+}
+
+/// The lines of a forward, oldest first, if it tails logs.
+pub fn lines(key: &str) -> Option<Vec<PodLogLine>> {
+    POD_LOGS
+        .lock()
+        .unwrap()
+        .get(key)
+        .map(|lines| lines.iter().cloned().collect())
+}
+
+/// Stops keeping lines for a removed forward.
+pub fn forget(key: &str) {
+    POD_LOGS.lock().unwrap().remove(key);
+}
+
+fn target_pod(key: &str) -> Option<String> {
+    FORWARD_STATUSES
+        .lock()
+        .unwrap()
+        .get(key)
+        .and_then(|status| status.target_pod.clone())
+}
+
+/// Resolves once the forward lands on a pod other than `pod`.
+async fn moved(key: &str, pod: &str) {
+    loop {
+        tokio::time::sleep(TARGET_CHECK_INTERVAL).await;
+        if target_pod(key).is_some_and(|current| current != pod) {
+            return;
+        }
+    }
+}
+
+/// Streams the logs of `pod` until the stream ends. A resumed stream only
+/// picks up new output.
+async fn tail(pods: &Api<Pod>, key: &str, pod: &str, resumed: bool) -> anyhow::Result<()> {
+    let container = default_container(pods, pod).await?;
+    let params = LogParams {
+        container,
+        follow: true,
+        tail_lines: (!resumed).then_some(TAIL_LINES),
+        since_seconds: resumed.then_some(TARGET_CHECK_INTERVAL.as_secs() as i64 + 1),
+        ..Default::default()
+    };
+    let mut lines = pods.log_stream(pod, &params).await?.lines();
+    while let Some(line) = lines.try_next().await? {
+        push(key, pod, line);
+    }
+    Ok(())
+}
+
+/// The container `kubectl logs` would pick.
+async fn default_container(pods: &Api<Pod>, pod: &str) -> anyhow::Result<Option<String>> {
+    let pod = pods.get(pod).await?;
+    let annotated = pod
+        .metadata
+        .annotations
+        .and_then(|annotations| annotations.get("kubectl.kubernetes.io/default-container").cloned());
+    Ok(annotated.or_else(|| pod.spec?.containers.into_iter().next().map(|container| container.name)))
+}
+
+fn push(key: &str, pod: &str, line: String) {
+    let mut logs = POD_LOGS.lock().unwrap();
+    let Some(lines) = logs.get_mut(key) else {
+        return;
+    };
+    if lines.len() >= MAX_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(PodLogLine {
+        pod: pod.to_string(),
+        line,
+    });
+}
@@ -0,0 +1,228 @@
+use crate::config::{ForwardConfig, ProbeKind};
+use crate::forwarder::{ForwardState, FORWARD_STATUSES};
+use hyper::{Body, Request};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const DEFAULT_INTERVAL_SECS: u64 = 2;
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+/// The first probe must succeed within this time (or a few intervals, if longer).
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A forward's effective liveness probe.
+#[derive(Clone, Debug)]
+pub struct Probe {
+    pub kind: ProbeKind,
+    pub path: String,
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub success_statuses: Vec<u16>,
+    pub command: Vec<String>,
+    pub failure_threshold: u32,
+}
+
+/// Where probes are sent for one port-forward connection.
+pub struct ProbeTarget {
+    pub internal_port: u16,
+    pub namespace: String,
+    pub context: Option<String>,
+    /// Pod (or `type/name`) that exec probes run in
+    pub exec_target: String,
+    /// Client for HTTP probes, which speak the forward's upstream scheme
+    pub upstream: crate::http::Upstream,
+}
+
+impl Probe {
+    /// Builds the probe from a forward's `probe` block, falling back to the
+    /// plain `liveness_probe` path. Forwards with neither are not probed.
+    pub fn from_forward(forward: &ForwardConfig) -> Option<Probe> {
+        let config = forward.probe.clone().unwrap_or_default();
+        let kind = config.kind.or_else(|| {
+            forward
+                .liveness_probe
+                .as_ref()
+                .or(config.path.as_ref())
+                .map(|_| ProbeKind::Http)
+        })?;
+
+        Some(Probe {
+            kind,
+            path: config
+                .path
+                .or_else(|| forward.liveness_probe.clone())
+                .unwrap_or_else(|| "/".to_string()),
+            interval: Duration::from_secs(config.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS).max(1)),
+            timeout: Duration::from_secs(config.timeout_secs.or(forward.timeout).unwrap_or(1)),
+            success_statuses: config.success_statuses.unwrap_or_else(|| vec![200]),
+            command: config.command.unwrap_or_default(),
+            failure_threshold: config
+                .failure_threshold
+                .unwrap_or(DEFAULT_FAILURE_THRESHOLD)
+                .max(1),
+        })
+    }
+
+    /// Runs the probe once. The error describes why it failed.
+    pub async fn check(&self, target: &ProbeTarget) -> Result<(), String> {
+        let result = tokio::time::timeout(self.timeout, async {
+            match self.kind {
+                ProbeKind::Http => self.check_http(target).await,
+                ProbeKind::Tcp => tokio::net::TcpStream::connect(("127.0.0.1", target.internal_port))
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| format!("TCP connect failed: {}", e)),
+                ProbeKind::Exec => self.check_exec(target).await,
+            }
+        })
+        .await;
+        result.unwrap_or_else(|_| Err(format!("timed out after {}s", self.timeout.as_secs())))
+    }
+
+    async fn check_http(&self, target: &ProbeTarget) -> Result<(), String> {
+        let url = target.upstream.url(target.internal_port, &self.path);
+        let req = Request::get(url)
+            .header("x-internal-probe", "true")
+            .body(Body::empty())
+            .unwrap();
+        let response = target.upstream.request(req).await.map_err(|e| e.to_string())?;
+        let status = response.status().as_u16();
+        if self.success_statuses.contains(&status) {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", status))
+        }
+    }
+
+    async fn check_exec(&self, target: &ProbeTarget) -> Result<(), String> {
+        if self.command.is_empty() {
+            return Err("exec probe has no command".to_string());
+        }
+        let mut cmd = crate::k8s::kubectl(target.context.as_deref())
+            .await
+            .map_err(|e| e.to_string())?;
+        cmd.arg("exec").arg("-n").arg(&target.namespace);
+        let mut child = cmd
+            .arg(&target.exec_target)
+            .arg("--")
+            .args(&self.command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to run kubectl exec: {}", e))?;
+        crate::process::adopt_child(&child);
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| format!("Failed to run kubectl exec: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("command exited with {}", status))
+        }
+    }
+
+    /// Probes until the first success, marking the forward ACTIVE. Returns
+    /// false once `failure_threshold` probes failed or startup takes too long.
+    pub async fn wait_until_live(&self, key: &str, target: &ProbeTarget) -> bool {
+        let startup_timeout = STARTUP_TIMEOUT.max(self.interval * (self.failure_threshold + 1));
+        let live = tokio::time::timeout(startup_timeout, async {
+            let mut failures = 0;
+            loop {
+                sleep(self.interval).await;
+                let started = std::time::Instant::now();
+                match self.check(target).await {
+                    Ok(()) => {
+                        crate::logger::log_info("Successful probe received.".to_string());
+                        record_success(key, started.elapsed());
+                        return true;
+                    }
+                    Err(reason) => {
+                        failures += 1;
+                        crate::logger::log_warning(format!("Probe failed: {}", reason));
+                        set_unavailable(key);
+                        if failures >= self.failure_threshold {
+                            crate::logger::log_error(format!(
+                                "Probe failed {} times. Restarting port-forward.",
+                                failures
+                            ));
+                            crate::notify::probe_failed(
+                                key,
+                                format!("{} probes failed before it became ready, the last with: {}", failures, reason),
+                            );
+                            return false;
+                        }
+                    }
+                }
+            }
+        })
+        .await;
+
+        live.unwrap_or_else(|_| {
+            set_unavailable(key);
+            crate::logger::log_error("Probe overall timeout reached. Restarting port-forward.".to_string());
+            crate::notify::probe_failed(
+                key,
+                format!("no probe passed within {}s", startup_timeout.as_secs()),
+            );
+            false
+        })
+    }
+
+    /// Keeps probing a live forward and returns once `failure_threshold`
+    /// consecutive probes failed, so the caller can restart the port-forward.
+    pub async fn monitor(&self, key: &str, target: &ProbeTarget) {
+        let show_liveness = crate::forwarder::forward_options().show_liveness;
+        let mut failures = 0;
+        loop {
+            sleep(self.interval).await;
+            let started = std::time::Instant::now();
+            match self.check(target).await {
+                Ok(()) => {
+                    if failures > 0 {
+                        crate::logger::log_success(format!("{} Probe recovered for {}", "💚", key));
+                    } else if show_liveness {
+                        crate::logger::log_info(format!("Probe OK for {}", key));
+                    }
+                    failures = 0;
+                    record_success(key, started.elapsed());
+                }
+                Err(reason) => {
+                    failures += 1;
+                    crate::logger::log_warning(format!(
+                        "Probe failed for {} ({}/{}): {}",
+                        key, failures, self.failure_threshold, reason
+                    ));
+                    set_unavailable(key);
+                    if failures >= self.failure_threshold {
+                        crate::logger::log_error(format!(
+                            "Probe failed {} times in a row for {}. Restarting port-forward.",
+                            failures, key
+                        ));
+                        crate::notify::probe_failed(
+                            key,
+                            format!("{} probes failed in a row, the last with: {}", failures, reason),
+                        );
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn record_success(key: &str, latency: Duration) {
+    crate::metrics::record_probe_latency(key, latency);
+    if let Some(entry) = FORWARD_STATUSES.lock().unwrap().get_mut(key) {
+        entry.last_probe = Some(chrono::Utc::now().to_rfc3339());
+        crate::forwarder::transition(entry, ForwardState::Active);
+    }
+}
+
+fn set_unavailable(key: &str) {
+    if let Some(entry) = FORWARD_STATUSES.lock().unwrap().get_mut(key) {
+        crate::forwarder::transition(entry, ForwardState::Unavailable);
+    }
+}
@@ -0,0 +1,136 @@
+//! Platform specifics of child processes and termination signals.
+//!
+//! On unix, kubectl children die with the runtime through `kill_on_drop` and
+//! the daemon detaches into its own process group. Windows has neither
+//! process groups nor SIGTERM, so children are put in a Job Object that kills
+//! them once kpf exits, however it exits, and the console's close, logoff and
+//! shutdown events stand in for the signals.
+
+use anyhow::Result;
+
+/// Ties a kubectl child to kpf's lifetime, so it does not outlive a crash or
+/// a closed console window.
+pub fn adopt_child(child: &tokio::process::Child) {
+    #[cfg(windows)]
+    if let (Some(job), Some(handle)) = (KILL_ON_EXIT_JOB.as_ref(), child.raw_handle()) {
+        // SAFETY: both handles are open; a failure leaves the child outside the job
+        unsafe {
+            windows_sys::Win32::System::JobObjects::AssignProcessToJobObject(job.0, handle as _);
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = child;
+}
+
+/// A Job Object set to kill its processes when its last handle closes, which
+/// Windows does for kpf's handles when kpf exits.
+#[cfg(windows)]
+struct Job(windows_sys::Win32::Foundation::HANDLE);
+
+#[cfg(windows)]
+static KILL_ON_EXIT_JOB: std::sync::LazyLock<Option<Job>> = std::sync::LazyLock::new(|| {
+    use windows_sys::Win32::System::JobObjects::{
+        CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+        JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+    // SAFETY: plain Win32 calls on a handle this function owns
+    unsafe {
+        let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+        if job == 0 {
+            return None;
+        }
+        let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        let set = SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &limits as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        );
+        if set == 0 {
+            windows_sys::Win32::Foundation::CloseHandle(job);
+            return None;
+        }
+        Some(Job(job))
+    }
+});
+
+/// Starts `command` detached from the terminal: Ctrl+C there and closing it
+/// do not reach the new process.
+pub fn spawn_detached(command: &mut std::process::Command) -> std::io::Result<std::process::Child> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0).spawn()
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        use windows_sys::Win32::System::Threading::{
+            CREATE_BREAKAWAY_FROM_JOB, CREATE_NEW_PROCESS_GROUP, CREATE_NO_WINDOW,
+        };
+        // A hidden console of its own, which the kubectl children share
+        // instead of each opening a window
+        let flags = CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP;
+        // Terminals often run their shells in a job of their own; leave it so
+        // closing the window does not end the session, where the job allows
+        match command.creation_flags(flags | CREATE_BREAKAWAY_FROM_JOB).spawn() {
+            Ok(child) => Ok(child),
+            Err(_) => command.creation_flags(flags).spawn(),
+        }
+    }
+}
+
+/// Resolves when the system asks kpf to terminate: SIGTERM on unix, the
+/// console being closed, the user logging off or a shutdown on Windows.
+pub async fn terminate_requested() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        signal(SignalKind::terminate())?.recv().await;
+    }
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows::{ctrl_break, ctrl_close, ctrl_logoff, ctrl_shutdown};
+        let (mut close, mut logoff, mut shutdown, mut brk) =
+            (ctrl_close()?, ctrl_logoff()?, ctrl_shutdown()?, ctrl_break()?);
+        tokio::select! {
+            _ = close.recv() => {}
+            _ = logoff.recv() => {}
+            _ = shutdown.recv() => {}
+            _ = brk.recv() => {}
+        }
+    }
+    Ok(())
+}
+
+/// Keeps a hangup (the terminal closing) from ending the process.
+pub fn ignore_hangup() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::spawn(async move { while hangup.recv().await.is_some() {} });
+    }
+    // The hidden console of a detached Windows process cannot be closed
+    Ok(())
+}
+
+/// Asks process `pid` to terminate (forcibly on Windows, which has no
+/// SIGTERM for console-less processes). Whether the process was there.
+pub fn terminate(pid: &str) -> bool {
+    let mut command = if cfg!(windows) {
+        let mut command = std::process::Command::new("taskkill");
+        command.args(["/PID", pid, "/F"]);
+        command
+    } else {
+        let mut command = std::process::Command::new("kill");
+        command.args(["-TERM", pid]);
+        command
+    };
+    command
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
@@ -0,0 +1,70 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Minimum gap between two scheduled reconnects, across all forwards.
+const MIN_SPACING: Duration = Duration::from_millis(200);
+/// Upper bound of the random delay added on top of each slot.
+const MAX_JITTER_MS: u64 = 500;
+
+/// Hands out reconnect slots so forwards that lose their pods at the same time
+/// (e.g. a node going away) reconnect one after another instead of all hitting
+/// the API server in the same second.
+static SCHEDULER: LazyLock<Mutex<Scheduler>> = LazyLock::new(|| Mutex::new(Scheduler::default()));
+
+#[derive(Default)]
+struct Scheduler {
+    next_slot: Option<Instant>,
+    waiting: HashMap<String, Instant>,
+}
+
+/// Where a forward stands in the reconnect queue.
+pub struct QueuePosition {
+    /// 1-based position among the waiting forwards
+    pub position: usize,
+    pub queued: usize,
+    pub remaining: Duration,
+}
+
+/// Waits for this forward's turn to reconnect.
+pub async fn wait_turn(key: &str) {
+    let slot = {
+        let mut scheduler = SCHEDULER.lock().unwrap();
+        let now = Instant::now();
+        let earliest = scheduler.next_slot.map_or(now, |next| next.max(now));
+        let slot = earliest + jitter(key);
+        scheduler.next_slot = Some(slot + MIN_SPACING);
+        scheduler.waiting.insert(key.to_string(), slot);
+        slot
+    };
+
+    // Leaves the queue even when the forward is stopped while waiting
+    let _guard = QueueGuard(key);
+    tokio::time::sleep_until(slot.into()).await;
+}
+
+pub fn queue_position(key: &str) -> Option<QueuePosition> {
+    let scheduler = SCHEDULER.lock().unwrap();
+    let slot = *scheduler.waiting.get(key)?;
+    Some(QueuePosition {
+        position: scheduler.waiting.values().filter(|other| **other <= slot).count(),
+        queued: scheduler.waiting.len(),
+        remaining: slot.saturating_duration_since(Instant::now()),
+    })
+}
+
+struct QueueGuard<'a>(&'a str);
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        SCHEDULER.lock().unwrap().waiting.remove(self.0);
+    }
+}
+
+fn jitter(key: &str) -> Duration {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write(key.as_bytes());
+    Duration::from_millis(hasher.finish() % MAX_JITTER_MS)
+}
@@ -0,0 +1,320 @@
+//! Redaction of secrets before requests are logged or captured: the values
+//! of sensitive headers, and of JSON and form fields in bodies.
+//!
+//! Headers named in `DEFAULT_HEADERS`, a forward's `redact_headers` and
+//! `--redact-header` are masked in the `curl` commands kept for the request
+//! inspector and logged at verbosity 2. Fields named in a forward's
+//! `redact_fields` and `--redact-field` are masked in logged and captured
+//! bodies. `--no-redact` turns all of it off.
+
+use anyhow::{bail, Context, Result};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::HeaderMap;
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+
+/// What a redacted value is replaced with.
+const MASK: &str = "[REDACTED]";
+
+/// Headers masked on every forward unless `--no-redact` is given.
+pub const DEFAULT_HEADERS: [&str; 5] = ["authorization", "proxy-authorization", "cookie", "set-cookie", "x-api-key"];
+
+static REDACTIONS: LazyLock<Mutex<HashMap<String, Arc<Redaction>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Headers and fields of `--redact-header` and `--redact-field`, masked on
+/// every forward.
+static GLOBAL: OnceLock<Arc<Redaction>> = OnceLock::new();
+
+/// Set by `--no-redact`.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Compiled `redact_headers` and `redact_fields`.
+#[derive(Default)]
+pub struct Redaction {
+    /// Header names, lowercase
+    headers: Vec<String>,
+    /// Field names matched at any depth
+    names: Vec<String>,
+    /// Dotted field paths from the top of a JSON body; `*` matches any key
+    /// or array element
+    paths: Vec<Vec<String>>,
+    /// Matches the names, and the last segment of the paths, as JSON members
+    /// (`"password": "..."`) and form parameters (`password=...`). Used for
+    /// bodies that do not parse as JSON, e.g. truncated ones.
+    json: Option<Regex>,
+    form: Option<Regex>,
+}
+
+impl Redaction {
+    pub fn new(headers: &[String], fields: &[String]) -> Result<Redaction> {
+        let mut redaction = Redaction::default();
+        for header in headers {
+            let name = HeaderName::from_bytes(header.as_bytes())
+                .context(format!("Invalid redact header '{}'", header))?;
+            redaction.headers.push(name.as_str().to_string());
+        }
+        for field in fields {
+            if field.is_empty() || field.split('.').any(str::is_empty) {
+                bail!("Invalid redact field '{}'. Expected a name or a dotted path like user.password", field);
+            }
+            match field.contains('.') {
+                true => redaction.paths.push(field.split('.').map(str::to_string).collect()),
+                false => redaction.names.push(field.clone()),
+            }
+        }
+
+        let mut fallback: Vec<String> = redaction.names.iter().map(|name| regex::escape(name)).collect();
+        fallback.extend(
+            redaction
+                .paths
+                .iter()
+                .filter_map(|path| path.last())
+                .filter(|name| *name != "*")
+                .map(|name| regex::escape(name)),
+        );
+        if !fallback.is_empty() {
+            let names = fallback.join("|");
+            redaction.json = Some(
+                Regex::new(&format!(r#"(?i)("(?:{})"\s*:\s*)("(?:[^"\\]|\\.)*"?|[^,}}\]\s]+)"#, names))
+                    .context("Invalid redact_fields")?,
+            );
+            redaction.form = Some(
+                Regex::new(&format!(r"(?i)((?:^|&)(?:{})=)[^&\s]*", names)).context("Invalid redact_fields")?,
+            );
+        }
+        Ok(redaction)
+    }
+
+    fn masks_header(&self, name: &str) -> bool {
+        self.headers.iter().any(|header| header == name)
+    }
+
+    /// Masks matching members of a parsed JSON body. Returns whether any was.
+    fn apply_json(&self, value: &mut Value, path: &mut Vec<String>) -> bool {
+        let mut changed = false;
+        match value {
+            Value::Object(members) => {
+                for (name, member) in members.iter_mut() {
+                    path.push(name.clone());
+                    if self.masks_field(path) {
+                        if *member != Value::String(MASK.to_string()) {
+                            *member = Value::String(MASK.to_string());
+                            changed = true;
+                        }
+                    } else {
+                        changed |= self.apply_json(member, path);
+                    }
+                    path.pop();
+                }
+            }
+            Value::Array(elements) => {
+                for (index, element) in elements.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    changed |= self.apply_json(element, path);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+        changed
+    }
+
+    /// Whether the member at `path` is masked: its name is one of `names`, or
+    /// the whole path matches one of `paths`.
+    fn masks_field(&self, path: &[String]) -> bool {
+        let name = path.last().map(String::as_str).unwrap_or_default();
+        self.names.iter().any(|field| field.eq_ignore_ascii_case(name))
+            || self.paths.iter().any(|pattern| {
+                pattern.len() == path.len()
+                    && pattern
+                        .iter()
+                        .zip(path)
+                        .all(|(segment, key)| segment == "*" || segment.eq_ignore_ascii_case(key))
+            })
+    }
+
+    fn apply_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let (Some(json), Some(form)) = (&self.json, &self.form) else {
+            return Cow::Borrowed(text);
+        };
+        match json.replace_all(text, format!("${{1}}\"{}\"", MASK).as_str()) {
+            Cow::Borrowed(text) => form.replace_all(text, format!("${{1}}{}", MASK).as_str()),
+            Cow::Owned(text) => Cow::Owned(text),
+        }
+    }
+}
+
+/// Registers the redacted headers and fields of a forward, replacing any
+/// earlier ones.
+pub fn configure(key: &str, headers: Option<&[String]>, fields: Option<&[String]>) -> Result<()> {
+    let redaction = match (headers, fields) {
+        (None, None) => None,
+        _ => Some(Redaction::new(headers.unwrap_or_default(), fields.unwrap_or_default())?),
+    };
+    let mut registry = REDACTIONS.lock().unwrap();
+    match redaction {
+        Some(redaction) => registry.insert(key.to_string(), Arc::new(redaction)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// Sets `--redact-header` and `--redact-field`, on top of the default headers.
+pub fn set_global(headers: &[String], fields: &[String]) -> Result<()> {
+    let mut headers = headers.to_vec();
+    headers.extend(DEFAULT_HEADERS.iter().map(|name| name.to_string()));
+    let _ = GLOBAL.set(Arc::new(Redaction::new(&headers, fields)?));
+    Ok(())
+}
+
+/// Turns redaction off (`--no-redact`).
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// The redactions that apply to a forward: the global one, then its own.
+fn redactions(key: &str) -> Vec<Arc<Redaction>> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Vec::new();
+    }
+    let global = GLOBAL.get_or_init(|| {
+        let headers: Vec<String> = DEFAULT_HEADERS.iter().map(|name| name.to_string()).collect();
+        Arc::new(Redaction::new(&headers, &[]).unwrap())
+    });
+    std::iter::once(global.clone())
+        .chain(REDACTIONS.lock().unwrap().get(key).cloned())
+        .collect()
+}
+
+/// A copy of `headers` with the values of sensitive headers masked.
+pub fn headers(key: &str, headers: &HeaderMap) -> HeaderMap {
+    let redactions = redactions(key);
+    let mut masked = headers.clone();
+    for (name, value) in masked.iter_mut() {
+        if redactions.iter().any(|redaction| redaction.masks_header(name.as_str())) {
+            *value = HeaderValue::from_static(MASK);
+        }
+    }
+    masked
+}
+
+/// A body of a forward as it may be logged or captured, with the values of
+/// redacted fields masked. Binary bodies are returned as they are.
+pub fn body<'a>(key: &str, body: &'a [u8]) -> Cow<'a, [u8]> {
+    let redactions = redactions(key);
+    if redactions.iter().all(|redaction| redaction.json.is_none()) {
+        return Cow::Borrowed(body);
+    }
+
+    if let Ok(mut value) = serde_json::from_slice::<Value>(body) {
+        let mut changed = false;
+        for redaction in &redactions {
+            changed |= redaction.apply_json(&mut value, &mut Vec::new());
+        }
+        return match changed {
+            true => Cow::Owned(serde_json::to_vec(&value).unwrap_or_default()),
+            false => Cow::Borrowed(body),
+        };
+    }
+
+    // A truncated body may end inside a multi-byte character
+    let text = match std::str::from_utf8(body) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&body[..e.valid_up_to()]).unwrap(),
+        Err(_) => return Cow::Borrowed(body),
+    };
+    let mut redacted = Cow::Borrowed(text);
+    for redaction in &redactions {
+        if let Cow::Owned(text) = redaction.apply_text(&redacted) {
+            redacted = Cow::Owned(text);
+        }
+    }
+    match redacted {
+        Cow::Borrowed(_) => Cow::Borrowed(body),
+        Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redaction(fields: &[&str]) -> Redaction {
+        let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+        Redaction::new(&[], &fields).unwrap()
+    }
+
+    fn json(redaction: &Redaction, body: &str) -> Value {
+        let mut value: Value = serde_json::from_str(body).unwrap();
+        redaction.apply_json(&mut value, &mut Vec::new());
+        value
+    }
+
+    #[test]
+    fn names_match_at_any_depth() {
+        let value = json(&redaction(&["password"]), r#"{"password":"a","user":{"Password":"b","name":"c"}}"#);
+        assert_eq!(value["password"], MASK);
+        assert_eq!(value["user"]["Password"], MASK);
+        assert_eq!(value["user"]["name"], "c");
+    }
+
+    #[test]
+    fn paths_match_from_the_top() {
+        let redaction = redaction(&["user.token", "items.*.secret"]);
+        let value = json(&redaction, r#"{"token":"a","user":{"token":"b"},"items":[{"secret":"c"},{"secret":"d"}]}"#);
+        assert_eq!(value["token"], "a");
+        assert_eq!(value["user"]["token"], MASK);
+        assert_eq!(value["items"][0]["secret"], MASK);
+        assert_eq!(value["items"][1]["secret"], MASK);
+    }
+
+    #[test]
+    fn truncated_json_is_masked_as_text() {
+        let redaction = redaction(&["password"]);
+        assert_eq!(
+            redaction.apply_text(r#"{"password": "hunter2", "retries": 3, "PASSWORD":12"#),
+            r#"{"password": "[REDACTED]", "retries": 3, "PASSWORD":"[REDACTED]""#
+        );
+        assert_eq!(
+            redaction.apply_text(r#"{"name":"x","password":"hun\"ter"#),
+            r#"{"name":"x","password":"[REDACTED]""#
+        );
+    }
+
+    #[test]
+    fn form_parameters_are_masked() {
+        let redaction = redaction(&["user.password"]);
+        assert_eq!(
+            redaction.apply_text("user=alice&password=hunter2&remember=1"),
+            "user=alice&password=[REDACTED]&remember=1"
+        );
+        assert_eq!(redaction.apply_text("password=hunter2"), "password=[REDACTED]");
+        assert_eq!(redaction.apply_text("old_password=hunter2"), "old_password=hunter2");
+    }
+
+    #[test]
+    fn wildcard_segments_add_no_text_pattern() {
+        let redaction = redaction(&["items.*"]);
+        assert!(redaction.json.is_none());
+        assert_eq!(redaction.apply_text(r#"{"items":"x""#), r#"{"items":"x""#);
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        let redaction = redaction(&["a+b"]);
+        assert_eq!(redaction.apply_text(r#"{"aab":"x","a+b":"y"}"#), r#"{"aab":"x","a+b":"[REDACTED]"}"#);
+    }
+
+    #[test]
+    fn invalid_fields_are_rejected() {
+        for field in ["", "user.", ".password", "a..b"] {
+            assert!(Redaction::new(&[], &[field.to_string()]).is_err(), "{}", field);
+        }
+        assert!(Redaction::new(&["bad header".to_string()], &[]).is_err());
+    }
+}
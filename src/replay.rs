@@ -0,0 +1,182 @@
+//! `--record-session`: the log entries and forward status changes of a run,
+//! written with their timestamps to a JSON Lines file, and `kpf replay`,
+//! which plays such a file back in the TUI at real or accelerated speed, e.g.
+//! to show teammates exactly what happened during an outage.
+//!
+//! Each line is a log entry (`{"type": "log", ...}`) or the state of every
+//! forward after a change (`{"type": "forwards", ...}`). Lines are flushed as
+//! they are written, so the recording of a run that crashed is still usable.
+
+use crate::logger::LogSink;
+use crate::tui::{ForwardStatus, LogEntry};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the forward statuses are checked for changes while recording.
+const STATUS_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A line of a recording.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Log(LogEntry),
+    Forwards {
+        timestamp: chrono::DateTime<chrono::Utc>,
+        /// Every forward by key, as the status table shows them
+        forwards: BTreeMap<String, ForwardStatus>,
+    },
+}
+
+impl Record {
+    fn timestamp(&self) -> chrono::DateTime<chrono::Utc> {
+        match self {
+            Record::Log(entry) => entry.timestamp,
+            Record::Forwards { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+/// Appends the records of this run to the recording file.
+struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    fn write(&self, record: &Record) {
+        let Ok(line) = serde_json::to_string(record) else {
+            return;
+        };
+        let mut file = self.file.lock().unwrap();
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Writes log entries to the recording.
+struct RecordingSink(Arc<Recorder>);
+
+impl LogSink for RecordingSink {
+    fn write(&self, entry: &LogEntry) {
+        self.0.write(&Record::Log(entry.clone()));
+    }
+}
+
+/// Starts recording the log entries and forward status changes of this run
+/// to `path`, replacing any existing file.
+pub fn start_recording(path: &Path) -> Result<()> {
+    let file = File::create(path).context(format!("Failed to create session recording {}", path.display()))?;
+    let recorder = Arc::new(Recorder { file: Mutex::new(file) });
+    crate::logger::add_sink(Box::new(RecordingSink(recorder.clone())));
+
+    tokio::spawn(async move {
+        let mut last = String::new();
+        loop {
+            let forwards: BTreeMap<String, ForwardStatus> = crate::forwarder::FORWARD_STATUSES
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(key, status)| (key.clone(), status.clone()))
+                .collect();
+            let current = serde_json::to_string(&forwards).unwrap_or_default();
+            if current != last {
+                recorder.write(&Record::Forwards {
+                    timestamp: chrono::Utc::now(),
+                    forwards,
+                });
+                last = current;
+            }
+            tokio::time::sleep(STATUS_CHECK_INTERVAL).await;
+        }
+    });
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<Vec<Record>> {
+    let file = File::open(path).context(format!("Failed to open session recording {}", path.display()))?;
+    let mut lines = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.context(format!("Failed to read {}", path.display()))?;
+        if !line.trim().is_empty() {
+            lines.push((index + 1, line));
+        }
+    }
+    let mut records = Vec::new();
+    for (position, (number, line)) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            // The last line of a recording cut off by a crash may be incomplete
+            Err(_) if position > 0 && position == lines.len() - 1 => break,
+            Err(e) => bail!("{} line {} is not a session recording: {}", path.display(), number, e),
+        }
+    }
+    if records.is_empty() {
+        bail!("{} holds no recorded session", path.display());
+    }
+    Ok(records)
+}
+
+/// Plays the recording at `path` back in the TUI, `speed` times faster than
+/// it was recorded. No forwards are started.
+pub async fn run(path: PathBuf, speed: f64, max_log_lines: usize) -> Result<()> {
+    if !speed.is_finite() || speed <= 0.0 {
+        bail!("Invalid replay speed {}. Expected a number above 0, e.g. 10 for ten times faster", speed);
+    }
+    let records = load(&path)?;
+
+    let mut terminal = crate::tui::setup_terminal()?;
+    let (log_sender, log_receiver) = crate::tui::create_log_channel();
+    crate::logger::set_log_sender(log_sender);
+    let mut app = crate::tui::App::new(log_receiver, max_log_lines);
+
+    tokio::spawn(play(path, records, speed));
+
+    let res = crate::tui::run_app(&mut terminal, &mut app, Duration::from_millis(100));
+    crate::tui::restore_terminal(&mut terminal)?;
+    if let Err(err) = res {
+        crate::logger::log_error(format!("TUI error: {}", err));
+    }
+    Ok(())
+}
+
+/// Feeds the records to the TUI, waiting between them as long as they were
+/// apart when recorded, divided by `speed`.
+async fn play(path: PathBuf, records: Vec<Record>, speed: f64) {
+    let started = records[0].timestamp();
+    let length = records[records.len() - 1].timestamp() - started;
+    crate::logger::log_info(format!(
+        "{} Replaying {} ({} recorded, {}x speed)",
+        "⏯",
+        path.display(),
+        format_duration(length),
+        speed
+    ));
+
+    let mut previous = started;
+    for record in records {
+        // Entries logged while the statuses were read may be a little out of order
+        let gap = (record.timestamp() - previous).to_std().unwrap_or_default();
+        previous = previous.max(record.timestamp());
+        tokio::time::sleep(gap.div_f64(speed)).await;
+        match record {
+            Record::Log(entry) => crate::logger::write(&entry),
+            Record::Forwards { forwards, .. } => {
+                *crate::forwarder::FORWARD_STATUSES.lock().unwrap() = forwards.into_iter().collect();
+            }
+        }
+    }
+    crate::logger::log_info(format!("{} Replay finished; press q to quit", "⏹"));
+}
+
+/// e.g. `1h 02m 05s`
+fn format_duration(duration: chrono::Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    match (seconds / 3600, seconds % 3600 / 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+
+/// When the requests log file is rotated. Rotated files are renamed to
+/// `<file>.1` (newest) up to `<file>.<max_files>`.
+#[derive(Clone, Debug)]
+pub struct RotationPolicy {
+    /// Rotate before the file would grow past this many bytes
+    pub max_bytes: Option<u64>,
+    /// Rotated files kept next to the current one
+    pub max_files: usize,
+    /// Rotate when the local date changes
+    pub daily: bool,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: None,
+            max_files: 5,
+            daily: false,
+        }
+    }
+}
+
+static POLICY: OnceLock<RotationPolicy> = OnceLock::new();
+
+/// One background writer per log file, fed through a channel so requests
+/// never wait on disk I/O.
+static WRITERS: LazyLock<Mutex<HashMap<PathBuf, mpsc::UnboundedSender<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn configure(policy: RotationPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+/// Queues `line` (newline included) for `path`.
+pub fn append(path: &Path, line: String) {
+    let mut writers = WRITERS.lock().unwrap();
+    let line = match writers.get(path) {
+        Some(sender) => match sender.send(line) {
+            Ok(()) => return,
+            Err(mpsc::error::SendError(line)) => line,
+        },
+        None => line,
+    };
+
+    // Bodies can finish while the runtime shuts down; their lines are lost
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let _ = sender.send(line);
+    runtime.spawn(run_writer(path.to_path_buf(), receiver));
+    writers.insert(path.to_path_buf(), sender);
+}
+
+/// Parses a size such as `500000`, `512K`, `100M` or `1G`.
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let (digits, multiplier) = match size.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&size[..index], 1024),
+        Some((index, 'm' | 'M')) => (&size[..index], 1024 * 1024),
+        Some((index, 'g' | 'G')) => (&size[..index], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .map(|value| value * multiplier)
+        .map_err(|_| format!("invalid size '{}', expected e.g. 500000, 512K, 100M or 1G", size))
+}
+
+async fn run_writer(path: PathBuf, mut lines: mpsc::UnboundedReceiver<String>) {
+    let mut writer = Writer {
+        path,
+        policy: POLICY.get().cloned().unwrap_or_default(),
+        file: None,
+        size: 0,
+        opened_on: chrono::Local::now().date_naive(),
+    };
+
+    while let Some(line) = lines.recv().await {
+        writer.write(&line).await;
+        // Flush once the backlog is drained rather than after every line
+        while let Ok(line) = lines.try_recv() {
+            writer.write(&line).await;
+        }
+        writer.flush().await;
+    }
+}
+
+struct Writer {
+    path: PathBuf,
+    policy: RotationPolicy,
+    file: Option<BufWriter<File>>,
+    size: u64,
+    opened_on: chrono::NaiveDate,
+}
+
+impl Writer {
+    async fn write(&mut self, line: &str) {
+        if self.file.is_none() {
+            self.open().await;
+        }
+        if self.file.is_some() && self.should_rotate(line.len() as u64) {
+            self.rotate().await;
+            self.open().await;
+        }
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        match file.write_all(line.as_bytes()).await {
+            Ok(()) => self.size += line.len() as u64,
+            Err(e) => {
+                crate::logger::log_error(format!(
+                    "Failed to write to log file {}: {}",
+                    self.path.display(),
+                    e
+                ));
+                self.file = None;
+            }
+        }
+    }
+
+    async fn flush(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.flush().await;
+        }
+    }
+
+    fn should_rotate(&self, incoming: u64) -> bool {
+        let too_big = self
+            .policy
+            .max_bytes
+            .is_some_and(|max_bytes| self.size > 0 && self.size + incoming > max_bytes);
+        let new_day = self.policy.daily && chrono::Local::now().date_naive() != self.opened_on;
+        too_big || new_day
+    }
+
+    async fn open(&mut self) {
+        match OpenOptions::new().append(true).create(true).open(&self.path).await {
+            Ok(file) => {
+                let metadata = file.metadata().await.ok();
+                self.size = metadata.as_ref().map_or(0, |metadata| metadata.len());
+                // A file left over from an earlier run dates from its last write
+                self.opened_on = metadata
+                    .and_then(|metadata| metadata.modified().ok())
+                    .map(|modified| chrono::DateTime::<chrono::Local>::from(modified).date_naive())
+                    .unwrap_or_else(|| chrono::Local::now().date_naive());
+                self.file = Some(BufWriter::new(file));
+            }
+            Err(e) => crate::logger::log_error(format!(
+                "Failed to write to log file {}: {}",
+                self.path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Shifts `<file>.N` to `<file>.N+1`, dropping the oldest, and moves the
+    /// current file to `<file>.1`.
+    async fn rotate(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            let _ = file.flush().await;
+        }
+
+        let rotated = |index: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", index));
+            PathBuf::from(name)
+        };
+        if self.policy.max_files == 0 {
+            let _ = tokio::fs::remove_file(&self.path).await;
+            return;
+        }
+        let _ = tokio::fs::remove_file(rotated(self.policy.max_files)).await;
+        for index in (1..self.policy.max_files).rev() {
+            let _ = tokio::fs::rename(rotated(index), rotated(index + 1)).await;
+        }
+        if let Err(e) = tokio::fs::rename(&self.path, rotated(1)).await {
+            crate::logger::log_warning(format!(
+                "Failed to rotate log file {}: {}",
+                self.path.display(),
+                e
+            ));
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::config::RetryConfig;
+use hyper::Method;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Defaults for fields left out of a `retry` block.
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_BACKOFF_MS: u64 = 100;
+/// Backoff never grows past this, however many attempts are allowed.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+/// Largest request body buffered so it can be sent again.
+pub const MAX_RETRY_BODY: u64 = 1024 * 1024;
+
+static RETRY_POLICIES: LazyLock<Mutex<HashMap<String, RetryPolicy>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// How a forward's proxy retries requests the upstream failed to answer.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled for every later one
+    pub backoff: Duration,
+    pub idempotent_only: bool,
+}
+
+impl From<&RetryConfig> for RetryPolicy {
+    fn from(config: &RetryConfig) -> Self {
+        RetryPolicy {
+            max_attempts: config.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+            backoff: Duration::from_millis(config.backoff_ms.unwrap_or(DEFAULT_BACKOFF_MS)),
+            idempotent_only: config.idempotent_only.unwrap_or(true),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a request may be retried. Bodies must be small enough to
+    /// buffer, and of known size so buffering does not stall a stream.
+    pub fn allows(&self, method: &Method, body_size: Option<u64>) -> bool {
+        let idempotent = matches!(
+            *method,
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+        );
+        self.max_attempts > 1
+            && (idempotent || !self.idempotent_only)
+            && body_size.is_some_and(|size| size <= MAX_RETRY_BODY)
+    }
+
+    /// Wait before retry number `retry` (1 for the first retry).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Registers the retry policy of a forward, replacing any earlier one.
+pub fn configure(key: &str, config: Option<&RetryConfig>) {
+    let mut policies = RETRY_POLICIES.lock().unwrap();
+    match config {
+        Some(config) => policies.insert(key.to_string(), RetryPolicy::from(config)),
+        None => policies.remove(key),
+    };
+}
+
+/// Retry policy of a forward, if it has one.
+pub fn policy(key: &str) -> Option<RetryPolicy> {
+    RETRY_POLICIES.lock().unwrap().get(key).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retry_policy(max_attempts: Option<u32>, backoff_ms: Option<u64>, idempotent_only: Option<bool>) -> RetryPolicy {
+        RetryPolicy::from(&RetryConfig {
+            max_attempts,
+            backoff_ms,
+            idempotent_only,
+        })
+    }
+
+    #[test]
+    fn defaults_fill_in_missing_fields() {
+        let policy = retry_policy(None, None, None);
+        assert_eq!(policy.max_attempts, DEFAULT_MAX_ATTEMPTS);
+        assert_eq!(policy.backoff, Duration::from_millis(DEFAULT_BACKOFF_MS));
+        assert!(policy.idempotent_only);
+    }
+
+    #[test]
+    fn a_single_attempt_never_retries() {
+        assert_eq!(retry_policy(Some(0), None, None).max_attempts, 1);
+        assert!(!retry_policy(Some(0), None, None).allows(&Method::GET, Some(0)));
+        assert!(!retry_policy(Some(1), None, None).allows(&Method::GET, Some(0)));
+        assert!(retry_policy(Some(2), None, None).allows(&Method::GET, Some(0)));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = retry_policy(None, Some(100), None);
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(10), MAX_BACKOFF);
+        assert_eq!(policy.backoff(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn only_idempotent_methods_retry_by_default() {
+        let idempotent_only = retry_policy(None, None, None);
+        for method in [Method::GET, Method::HEAD, Method::OPTIONS, Method::TRACE, Method::PUT, Method::DELETE] {
+            assert!(idempotent_only.allows(&method, Some(0)), "{}", method);
+        }
+        assert!(!idempotent_only.allows(&Method::POST, Some(0)));
+        assert!(!idempotent_only.allows(&Method::PATCH, Some(0)));
+
+        let any = retry_policy(None, None, Some(false));
+        assert!(any.allows(&Method::POST, Some(0)));
+        assert!(any.allows(&Method::PATCH, Some(0)));
+    }
+
+    #[test]
+    fn bodies_must_be_small_and_of_known_size() {
+        let policy = retry_policy(None, None, None);
+        assert!(policy.allows(&Method::PUT, Some(MAX_RETRY_BODY)));
+        assert!(!policy.allows(&Method::PUT, Some(MAX_RETRY_BODY + 1)));
+        assert!(!policy.allows(&Method::PUT, None));
+    }
+
+    #[test]
+    fn policies_are_registered_per_forward() {
+        configure("service/retry-test:80", Some(&RetryConfig::default()));
+        assert!(policy("service/retry-test:80").is_some());
+        configure("service/retry-test:80", None);
+        assert!(policy("service/retry-test:80").is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::config::PathRewriteConfig;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+static PATH_REWRITES: LazyLock<Mutex<HashMap<String, Arc<PathRewrite>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A forward's `path_rewrite`, with its pattern compiled.
+#[derive(Debug)]
+pub struct PathRewrite {
+    strip_prefix: Option<String>,
+    pattern: Option<(Regex, String)>,
+}
+
+impl PathRewrite {
+    pub fn new(config: &PathRewriteConfig) -> Result<PathRewrite> {
+        let strip_prefix = config
+            .strip_prefix
+            .as_ref()
+            .map(|prefix| format!("/{}", prefix.trim_matches('/')))
+            .filter(|prefix| prefix != "/");
+        let pattern = match (&config.pattern, &config.replacement) {
+            (Some(pattern), replacement) => Some((
+                Regex::new(pattern).context(format!("Invalid path_rewrite pattern '{}'", pattern))?,
+                replacement.clone().unwrap_or_default(),
+            )),
+            (None, Some(_)) => bail!("path_rewrite has a replacement but no pattern"),
+            (None, None) => None,
+        };
+        Ok(PathRewrite {
+            strip_prefix,
+            pattern,
+        })
+    }
+
+    /// Rewrites the path of `path_and_query`, leaving the query untouched. The
+    /// prefix is stripped first, then the pattern is applied.
+    pub fn apply(&self, path_and_query: &str) -> String {
+        let (path, query) = match path_and_query.find('?') {
+            Some(index) => path_and_query.split_at(index),
+            None => (path_and_query, ""),
+        };
+
+        let mut path = path.to_string();
+        if let Some(prefix) = &self.strip_prefix {
+            // Only whole segments: `/api` strips `/api/users` but not `/apiary`
+            if let Some(rest) = path.strip_prefix(prefix.as_str()) {
+                if rest.is_empty() || rest.starts_with('/') {
+                    path = rest.to_string();
+                }
+            }
+        }
+        if let Some((pattern, replacement)) = &self.pattern {
+            path = pattern.replace(&path, replacement.as_str()).into_owned();
+        }
+        if !path.starts_with('/') {
+            path.insert(0, '/');
+        }
+        path + query
+    }
+}
+
+/// Registers the path rewrite of a forward, replacing any earlier one.
+pub fn configure(key: &str, config: Option<&PathRewriteConfig>) -> Result<()> {
+    let rewrite = config.map(PathRewrite::new).transpose()?;
+    let mut registry = PATH_REWRITES.lock().unwrap();
+    match rewrite {
+        Some(rewrite) => registry.insert(key.to_string(), Arc::new(rewrite)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// Path rewrite of a forward, if it has one.
+pub fn path_rewrite(key: &str) -> Option<Arc<PathRewrite>> {
+    PATH_REWRITES.lock().unwrap().get(key).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(strip_prefix: Option<&str>, pattern: Option<&str>, replacement: Option<&str>) -> PathRewrite {
+        PathRewrite::new(&PathRewriteConfig {
+            strip_prefix: strip_prefix.map(String::from),
+            pattern: pattern.map(String::from),
+            replacement: replacement.map(String::from),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn prefix_is_stripped_by_whole_segments() {
+        let api = rewrite(Some("/api/"), None, None);
+        assert_eq!(api.apply("/api/users"), "/users");
+        assert_eq!(api.apply("/api"), "/");
+        assert_eq!(api.apply("/apiary"), "/apiary");
+        assert_eq!(api.apply("/v1/api/users"), "/v1/api/users");
+    }
+
+    #[test]
+    fn query_is_kept() {
+        assert_eq!(rewrite(Some("api"), None, None).apply("/api/users?page=2&q=/api"), "/users?page=2&q=/api");
+        assert_eq!(rewrite(None, Some("^/v1"), Some("/v2")).apply("/v1/users?v1=true"), "/v2/users?v1=true");
+        assert_eq!(rewrite(Some("api"), None, None).apply("/api?page=2"), "/?page=2");
+    }
+
+    #[test]
+    fn pattern_applies_after_the_prefix_is_stripped() {
+        let users = rewrite(Some("/api"), Some("^/users/(\\d+)"), Some("/people/$1"));
+        assert_eq!(users.apply("/api/users/7/orders"), "/people/7/orders");
+        // The pattern sees the stripped path, so `/api/users` no longer matches `^/api`
+        let anchored = rewrite(Some("/api"), Some("^/api"), Some("/x"));
+        assert_eq!(anchored.apply("/api/api/users"), "/x/users");
+        assert_eq!(anchored.apply("/api/users"), "/users");
+    }
+
+    #[test]
+    fn only_the_first_match_is_replaced() {
+        assert_eq!(rewrite(None, Some("a"), Some("b")).apply("/aaa"), "/baa");
+    }
+
+    #[test]
+    fn rewritten_paths_keep_a_leading_slash() {
+        assert_eq!(rewrite(None, Some("^/"), None).apply("/users"), "/users");
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let invalid_pattern = PathRewriteConfig {
+            pattern: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(PathRewrite::new(&invalid_pattern).is_err());
+        let replacement_only = PathRewriteConfig {
+            replacement: Some("/x".to_string()),
+            ..Default::default()
+        };
+        assert!(PathRewrite::new(&replacement_only).is_err());
+    }
+}
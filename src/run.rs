@@ -0,0 +1,91 @@
+//! `kpf run -- <command>`: runs a command, e.g. an integration test suite,
+//! against forwards that are started for it and torn down once it exits.
+//!
+//! The command starts once every forward is ready. It finds them through
+//! `KPF_<NAME>_PORT` and `KPF_<NAME>_URL` environment variables, or through
+//! `{{port "name"}}` / `{{url "name"}}` placeholders in its arguments. kpf
+//! exits with the command's exit status.
+
+use crate::tui::ForwardStatus;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Longest wait for the forwards to shut down after the command exited.
+const TEARDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Waits for the started forwards, runs `command` and stops the forwards
+/// again. Returns the command's exit code.
+pub async fn run(command: &[String], timeout: Duration) -> Result<i32> {
+    let result = run_command(command, timeout).await;
+    crate::forwarder::stop_all(TEARDOWN_TIMEOUT).await;
+    result
+}
+
+async fn run_command(command: &[String], timeout: Duration) -> Result<i32> {
+    if crate::forwarder::FORWARD_STATUSES.lock().unwrap().is_empty() {
+        bail!("No forwards started; not running {}", command.join(" "));
+    }
+    let ready = tokio::select! {
+        ready = crate::status::wait_until_ready(timeout, || async { Ok(crate::control::forward_list()) }) => ready?,
+        reason = crate::forwarder::failure_exit_requested() => bail!(reason),
+    };
+    crate::logger::log_success(format!("{} {} forwards ready, running {}", "▶", ready, command.join(" ")));
+
+    let forwards: Vec<ForwardStatus> = crate::control::forward_list()
+        .forwards
+        .into_iter()
+        .map(|view| view.status)
+        .collect();
+    let args = command
+        .iter()
+        .map(|arg| crate::template::render(arg, &forwards))
+        .collect::<Result<Vec<_>>>()?;
+    let mut child = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
+        .envs(environment(&forwards))
+        .kill_on_drop(true)
+        .spawn()
+        .context(format!("Failed to run {}", args[0]))?;
+
+    tokio::select! {
+        status = child.wait() => {
+            let status = status.context(format!("Failed to wait for {}", args[0]))?;
+            Ok(exit_code(status))
+        }
+        reason = crate::forwarder::failure_exit_requested() => {
+            let _ = child.kill().await;
+            bail!(reason)
+        }
+        // The command gets Ctrl+C from the terminal as well
+        _ = tokio::signal::ctrl_c() => Ok(exit_code(child.wait().await?)),
+        result = crate::process::terminate_requested() => {
+            let _ = child.kill().await;
+            result?;
+            bail!("Terminated while running {}", args[0])
+        }
+    }
+}
+
+/// `KPF_<NAME>_PORT` and `KPF_<NAME>_URL` of every forward, e.g.
+/// `KPF_MY_API_PORT` for `svc/my-api:80`.
+fn environment(forwards: &[ForwardStatus]) -> HashMap<String, String> {
+    let mut env = HashMap::new();
+    for (forward, name) in forwards.iter().zip(crate::template::variable_names(forwards)) {
+        let scheme = if forward.tls { "https" } else { "http" };
+        env.insert(format!("KPF_{}_PORT", name), forward.local_port.to_string());
+        env.insert(format!("KPF_{}_URL", name), format!("{}://localhost:{}", scheme, forward.local_port));
+    }
+    env
+}
+
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
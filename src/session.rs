@@ -0,0 +1,89 @@
+//! Named sessions: the set of forwards of a run, saved to
+//! `~/.config/kpf/sessions/<name>.json` with `--save-session` and started again
+//! with `--session`. Session files use the config file format, so they can
+//! also be edited by hand or passed to `--config`.
+
+use crate::config::Config;
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::PathBuf;
+
+/// Directory holding the saved sessions.
+pub fn sessions_dir() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .ok_or_else(|| anyhow!("Cannot locate the sessions directory: HOME is not set"))?;
+    Ok(PathBuf::from(home).join(".config").join("kpf").join("sessions"))
+}
+
+/// File backing the session `name`.
+pub fn path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.starts_with('.');
+    if !valid {
+        bail!(
+            "Invalid session name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        );
+    }
+    Ok(sessions_dir()?.join(format!("{}.json", name)))
+}
+
+/// File of a previously saved session, for `--session`.
+pub fn existing_path(name: &str) -> Result<PathBuf> {
+    let path = path(name)?;
+    if !path.is_file() {
+        bail!(
+            "No saved session named '{}' (looked for {}); create it with --save-session {}",
+            name,
+            path.display(),
+            name
+        );
+    }
+    Ok(path)
+}
+
+/// Writes every forward known to this run, including the ones added through
+/// the control API, to the session `name`.
+pub fn save(name: &str, verbose: u8) -> Result<PathBuf> {
+    let path = path(name)?;
+    let config = Config {
+        forwards: crate::forwarder::forwards(),
+        verbose: Some(verbose),
+        protected_contexts: None,
+        addons: None,
+        profiles: None,
+        auto_port_range: None,
+        jump_host: None,
+        notifications: None,
+    };
+    if config.forwards.is_empty() {
+        bail!("No forwards to save in session '{}'", name);
+    }
+
+    let mut value = serde_json::to_value(&config)?;
+    strip_nulls(&mut value);
+    let dir = path.parent().unwrap_or(&path);
+    std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    std::fs::write(&path, serde_json::to_string_pretty(&value)? + "\n")
+        .context(format!("Failed to write session file {}", path.display()))?;
+    Ok(path)
+}
+
+/// Drops unset options so saved sessions read like hand-written configs.
+/// Only the config and forward fields are touched: inside header maps a
+/// `null` value means "remove the header".
+fn strip_nulls(value: &mut serde_json::Value) {
+    let Some(config) = value.as_object_mut() else {
+        return;
+    };
+    config.retain(|_, value| !value.is_null());
+    let forwards = config.get_mut("forwards").and_then(|forwards| forwards.as_array_mut());
+    for forward in forwards.into_iter().flatten() {
+        if let Some(forward) = forward.as_object_mut() {
+            forward.retain(|_, value| !value.is_null());
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! `kpf proxy`: a local SOCKS5 and HTTP CONNECT proxy into the cluster.
+//!
+//! Connections to `<service>.<namespace>.svc[.cluster.local]` are tunneled
+//! through a `kubectl port-forward` started the first time the service port is
+//! used and kept for the rest of the session. Every other host is connected to
+//! directly, so a browser can use the proxy for all of its traffic.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How long a new port-forward gets to start listening.
+const TUNNEL_READY_TIMEOUT: Duration = Duration::from_secs(15);
+/// Largest HTTP CONNECT request head accepted.
+const MAX_CONNECT_HEAD: usize = 8 * 1024;
+
+/// A service port reachable through the cluster.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ServiceTarget {
+    namespace: String,
+    service: String,
+    port: u16,
+}
+
+impl std::fmt::Display for ServiceTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "service/{}:{} ({})", self.service, self.port, self.namespace)
+    }
+}
+
+/// Local ports of the on-demand port-forwards, by target. A port is set once
+/// its port-forward started, so connections to one target wait for it while
+/// others go ahead. An entry is removed when its kubectl process exits, so
+/// the next connection starts a new one.
+static TUNNELS: LazyLock<Mutex<HashMap<ServiceTarget, Tunnel>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+type Tunnel = Arc<tokio::sync::OnceCell<u16>>;
+
+/// Serves the proxy on `localhost:<port>` until Ctrl+C.
+pub async fn run(port: u16, context: Option<String>) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .context(format!("Failed to bind proxy port {}", port))?;
+    crate::logger::log_info(format!(
+        "{} SOCKS5 / HTTP CONNECT proxy listening on localhost:{}",
+        "🧦", port
+    ));
+    crate::logger::log_info(format!(
+        "{} Cluster services are reached as <service>.<namespace>.svc[.cluster.local]",
+        "🧦"
+    ));
+
+    let context = Arc::new(context);
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, context.as_deref()).await {
+                crate::logger::log_warning(format!("{} {}", "🧦", e));
+            }
+        });
+    }
+
+    // Dropping the runtime kills the kubectl processes
+    crate::logger::log_info(format!("{} Proxy stopped", "🛑"));
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream, context: Option<&str>) -> Result<()> {
+    let mut client = BufReader::new(stream);
+    let first = client.fill_buf().await?.first().copied();
+    match first {
+        None => Ok(()),
+        Some(0x05) => handle_socks5(client, context).await,
+        Some(_) => handle_http_connect(client, context).await,
+    }
+}
+
+/// SOCKS5 without authentication, CONNECT only (RFC 1928). Cluster hostnames
+/// only work when the client sends them unresolved (`socks5h://` in curl).
+async fn handle_socks5(mut client: BufReader<TcpStream>, context: Option<&str>) -> Result<()> {
+    let mut header = [0u8; 2];
+    client.read_exact(&mut header).await?;
+    let mut methods = vec![0u8; header[1] as usize];
+    client.read_exact(&mut methods).await?;
+    if !methods.contains(&0x00) {
+        client.write_all(&[0x05, 0xff]).await?;
+        bail!("SOCKS5 client offered no supported authentication method");
+    }
+    client.write_all(&[0x05, 0x00]).await?;
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request).await?;
+    if request[1] != 0x01 {
+        socks5_reply(&mut client, 0x07).await?;
+        bail!("Unsupported SOCKS5 command {}", request[1]);
+    }
+    let host = match request[3] {
+        0x01 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip).await?;
+            std::net::Ipv4Addr::from(ip).to_string()
+        }
+        0x03 => {
+            let mut name = vec![0u8; client.read_u8().await? as usize];
+            client.read_exact(&mut name).await?;
+            String::from_utf8(name).context("SOCKS5 hostname is not UTF-8")?
+        }
+        0x04 => {
+            let mut ip = [0u8; 16];
+            client.read_exact(&mut ip).await?;
+            std::net::Ipv6Addr::from(ip).to_string()
+        }
+        other => {
+            socks5_reply(&mut client, 0x08).await?;
+            bail!("Unsupported SOCKS5 address type {}", other);
+        }
+    };
+    let port = client.read_u16().await?;
+
+    match connect(&host, port, context).await {
+        Ok(mut upstream) => {
+            socks5_reply(&mut client, 0x00).await?;
+            tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+            Ok(())
+        }
+        Err(e) => {
+            socks5_reply(&mut client, 0x05).await?;
+            Err(e)
+        }
+    }
+}
+
+async fn socks5_reply(client: &mut BufReader<TcpStream>, status: u8) -> Result<()> {
+    // The bound address is not meaningful for a local proxy
+    client
+        .write_all(&[0x05, status, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .await?;
+    Ok(())
+}
+
+/// `CONNECT host:port HTTP/1.1`. Plain proxied requests (`GET http://...`)
+/// are refused; clients use CONNECT for them once told to tunnel.
+async fn handle_http_connect(mut client: BufReader<TcpStream>, context: Option<&str>) -> Result<()> {
+    let mut request_line = String::new();
+    client.read_line(&mut request_line).await?;
+    let mut head_size = request_line.len();
+    loop {
+        let mut line = String::new();
+        let read = client.read_line(&mut line).await?;
+        head_size += read;
+        if read == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if head_size > MAX_CONNECT_HEAD {
+            bail!("HTTP proxy request head too large");
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, authority) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    if method != "CONNECT" {
+        client
+            .write_all(b"HTTP/1.1 405 Method Not Allowed\r\nAllow: CONNECT\r\nContent-Length: 0\r\n\r\n")
+            .await?;
+        bail!("Refused {} {}: only CONNECT is supported", method, authority);
+    }
+    let (host, port) = authority
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host.trim_matches(['[', ']']), port.parse::<u16>().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid CONNECT target '{}'", authority))?;
+
+    match connect(host, port, context).await {
+        Ok(mut upstream) => {
+            client
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await?;
+            tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+            Ok(())
+        }
+        Err(e) => {
+            let body = e.to_string();
+            client
+                .write_all(
+                    format!(
+                        "HTTP/1.1 502 Bad Gateway\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+            Err(e)
+        }
+    }
+}
+
+/// Connects through a port-forward for cluster services, directly otherwise.
+async fn connect(host: &str, port: u16, context: Option<&str>) -> Result<TcpStream> {
+    let Some(target) = parse_service_host(host, port) else {
+        return TcpStream::connect((host, port))
+            .await
+            .context(format!("Failed to connect to {}:{}", host, port));
+    };
+    let local_port = tunnel(&target, context).await?;
+    connect_tunnel(&target, local_port).await
+}
+
+/// `<service>.<namespace>.svc` or `<service>.<namespace>.svc.cluster.local`.
+fn parse_service_host(host: &str, port: u16) -> Option<ServiceTarget> {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let name = host
+        .strip_suffix(".svc.cluster.local")
+        .or_else(|| host.strip_suffix(".svc"))?;
+    let (service, namespace) = name.split_once('.')?;
+    if service.is_empty() || namespace.is_empty() || namespace.contains('.') {
+        return None;
+    }
+    Some(ServiceTarget {
+        namespace: namespace.to_string(),
+        service: service.to_string(),
+        port,
+    })
+}
+
+/// Local port of the port-forward to `target`, starting it on first use.
+async fn tunnel(target: &ServiceTarget, context: Option<&str>) -> Result<u16> {
+    let tunnel = TUNNELS.lock().unwrap().entry(target.clone()).or_default().clone();
+    // Left unset on failure, so the next connection tries again
+    let local_port = tunnel.get_or_try_init(|| start_tunnel(target, context, &tunnel)).await?;
+    Ok(*local_port)
+}
+
+/// Starts the port-forward of `tunnel`, dropping the tunnel once the
+/// kubectl process exits.
+async fn start_tunnel(target: &ServiceTarget, context: Option<&str>, tunnel: &Tunnel) -> Result<u16> {
+    let permissions = crate::k8s::required_permissions("service", false);
+    crate::k8s::check_access(&permissions, &target.namespace, context).await?;
+    let local_port = crate::forwarder::find_available_port()?;
+    let child_handle = Arc::new(tokio::sync::Mutex::new(None));
+    let exited = crate::k8s::create_port_forward(
+        "service",
+        &target.service,
+        target.port,
+        local_port,
+        &target.namespace,
+        context,
+        child_handle,
+    )
+    .await?;
+    crate::logger::log_info(format!(
+        "{} Forwarding {} on localhost:{}",
+        "🔌", target, local_port
+    ));
+
+    let finished = target.clone();
+    let tunnel = tunnel.clone();
+    tokio::spawn(async move {
+        let result = exited.await;
+        let mut tunnels = TUNNELS.lock().unwrap();
+        if tunnels.get(&finished).is_some_and(|current| Arc::ptr_eq(current, &tunnel)) {
+            tunnels.remove(&finished);
+        }
+        drop(tunnels);
+        crate::logger::log_warning(format!(
+            "{} Port-forward to {} exited{}",
+            "🔌",
+            finished,
+            result.err().map(|e| format!(": {}", e)).unwrap_or_default()
+        ));
+    });
+
+    Ok(local_port)
+}
+
+/// Local port of the started port-forward to `target`, without waiting for
+/// one being started.
+fn tunnel_port(target: &ServiceTarget) -> Option<u16> {
+    TUNNELS.lock().unwrap().get(target).and_then(|tunnel| tunnel.get().copied())
+}
+
+/// Connects to a tunnel's local port, waiting for a new kubectl process to
+/// start listening. Gives up early if the port-forward exits.
+async fn connect_tunnel(target: &ServiceTarget, local_port: u16) -> Result<TcpStream> {
+    let deadline = tokio::time::Instant::now() + TUNNEL_READY_TIMEOUT;
+    loop {
+        match TcpStream::connect(("127.0.0.1", local_port)).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) if tokio::time::Instant::now() >= deadline => {
+                return Err(e).context(format!("Port-forward to {} did not start listening", target));
+            }
+            Err(_) if tunnel_port(target) != Some(local_port) => {
+                bail!("Port-forward to {} exited", target);
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(service: &str) -> ServiceTarget {
+        ServiceTarget {
+            namespace: "socks-test".to_string(),
+            service: service.to_string(),
+            port: 80,
+        }
+    }
+
+    #[tokio::test]
+    async fn started_tunnels_are_found_while_another_one_starts() {
+        let starting = TUNNELS.lock().unwrap().entry(target("slow")).or_default().clone();
+        let _start = tokio::spawn(async move {
+            starting.get_or_init(std::future::pending::<u16>).await;
+        });
+        tokio::task::yield_now().await;
+        TUNNELS
+            .lock()
+            .unwrap()
+            .insert(target("api"), Arc::new(tokio::sync::OnceCell::new_with(Some(18080))));
+
+        let port = tokio::time::timeout(Duration::from_secs(1), tunnel(&target("api"), None)).await;
+        assert_eq!(port.unwrap().unwrap(), 18080);
+        assert_eq!(tunnel_port(&target("api")), Some(18080));
+        assert_eq!(tunnel_port(&target("slow")), None);
+    }
+
+    #[test]
+    fn service_hosts_are_parsed() {
+        let api = parse_service_host("API.socks-test.svc.cluster.local.", 80);
+        assert_eq!(api, Some(target("api")));
+        assert!(parse_service_host("api.shop.svc", 80).is_some());
+        assert!(parse_service_host("api.svc", 80).is_none());
+        assert!(parse_service_host("api.shop.other.svc", 80).is_none());
+        assert!(parse_service_host("example.com", 80).is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! A forward's `split`: some of its requests go to another forward's
+//! port-forward instead, e.g. to try a canary (`service/api-v2`) from a local
+//! client while everything else keeps hitting `service/api`, without touching
+//! the cluster's ingress.
+//!
+//! Requests carrying the configured headers are always split off; of the
+//! rest, `percent` are picked at random. Either way the request goes through
+//! the splitting forward's own proxy settings, and the response tells which
+//! forward answered in `x-kpf-upstream`.
+
+use crate::config::SplitConfig;
+use anyhow::{bail, Context, Result};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Names the forward that answered a request of a splitting forward.
+pub const UPSTREAM_HEADER: &str = "x-kpf-upstream";
+
+static SPLITS: LazyLock<Mutex<HashMap<String, Arc<Split>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A forward's split, parsed once from its config.
+#[derive(Debug)]
+pub struct Split {
+    /// Forward requests are split off to: its key once the forward started
+    /// (see `forwarder::spawn_forward`), a reference to it before
+    to: String,
+    percent: u8,
+    /// `None` values match any value
+    headers: Vec<(HeaderName, Option<HeaderValue>)>,
+}
+
+impl Split {
+    pub fn new(config: &SplitConfig) -> Result<Split> {
+        let to = crate::config::parse_reference(&config.to).context(format!("Invalid split target '{}'", config.to))?;
+        let percent = config.percent.unwrap_or(0);
+        if percent > 100 {
+            bail!("split percent must be between 0 and 100, not {}", percent);
+        }
+        let mut headers = Vec::new();
+        for (name, value) in config.headers.iter().flatten() {
+            let name = HeaderName::from_bytes(name.as_bytes()).context(format!("Invalid split header '{}'", name))?;
+            let value = match value.as_str() {
+                "*" => None,
+                value => Some(HeaderValue::from_str(value).context(format!("Invalid value of split header {}", name))?),
+            };
+            headers.push((name, value));
+        }
+        if percent == 0 && headers.is_empty() {
+            bail!("split to {} needs a percent or headers", config.to);
+        }
+        Ok(Split {
+            to,
+            percent,
+            headers,
+        })
+    }
+
+    /// Key of the forward a request with `headers` goes to, when it is split off.
+    pub fn route(&self, headers: &HeaderMap) -> Option<&str> {
+        let matches = !self.headers.is_empty()
+            && self.headers.iter().all(|(name, value)| match value {
+                Some(value) => headers.get_all(name).iter().any(|sent| sent == value),
+                None => headers.contains_key(name),
+            });
+        let picked = matches || (self.percent > 0 && rand::thread_rng().gen_range(0..100) < self.percent);
+        picked.then_some(self.to.as_str())
+    }
+}
+
+/// Registers the split of a forward, replacing any earlier one.
+pub fn configure(key: &str, config: Option<&SplitConfig>) -> Result<()> {
+    let split = config.map(Split::new).transpose()?;
+    let mut registry = SPLITS.lock().unwrap();
+    match split {
+        Some(split) => registry.insert(key.to_string(), Arc::new(split)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// Split of a forward, if it has one.
+pub fn policy(key: &str) -> Option<Arc<Split>> {
+    SPLITS.lock().unwrap().get(key).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(percent: Option<u8>, headers: &[(&str, &str)]) -> Result<Split> {
+        Split::new(&SplitConfig {
+            to: "service/api-v2:80".to_string(),
+            percent,
+            headers: (!headers.is_empty()).then(|| {
+                headers
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect()
+            }),
+        })
+    }
+
+    fn headers(sent: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in sent {
+            headers.append(HeaderName::from_bytes(name.as_bytes()).unwrap(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn all_headers_must_match() {
+        let split = split(None, &[("x-canary", "1"), ("x-team", "*")]).unwrap();
+        assert_eq!(split.route(&headers(&[("x-canary", "1"), ("x-team", "web")])), Some("service/api-v2:80"));
+        assert_eq!(split.route(&headers(&[("X-Canary", "1"), ("x-team", "")])), Some("service/api-v2:80"));
+        assert_eq!(split.route(&headers(&[("x-canary", "1")])), None);
+        assert_eq!(split.route(&headers(&[("x-canary", "2"), ("x-team", "web")])), None);
+        assert_eq!(split.route(&headers(&[])), None);
+    }
+
+    #[test]
+    fn any_sent_value_can_match() {
+        let split = split(None, &[("x-canary", "1")]).unwrap();
+        assert_eq!(split.route(&headers(&[("x-canary", "0"), ("x-canary", "1")])), Some("service/api-v2:80"));
+    }
+
+    #[test]
+    fn percent_bounds_pick_none_or_all() {
+        let all = split(Some(100), &[]).unwrap();
+        let none = split(Some(0), &[("x-canary", "1")]).unwrap();
+        for _ in 0..200 {
+            assert_eq!(all.route(&headers(&[])), Some("service/api-v2:80"));
+            assert_eq!(none.route(&headers(&[])), None);
+        }
+    }
+
+    #[test]
+    fn percent_splits_some_requests() {
+        let half = split(Some(50), &[]).unwrap();
+        let picked = (0..1000).filter(|_| half.route(&headers(&[])).is_some()).count();
+        assert!((300..700).contains(&picked), "{}", picked);
+    }
+
+    #[test]
+    fn invalid_splits_are_rejected() {
+        assert!(split(Some(101), &[]).is_err());
+        assert!(split(None, &[]).is_err());
+        assert!(split(Some(0), &[]).is_err());
+        assert!(split(None, &[("bad header", "1")]).is_err());
+        assert!(split(None, &[("x-canary", "bad\nvalue")]).is_err());
+    }
+}
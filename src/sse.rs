@@ -0,0 +1,146 @@
+//! Server-sent events (`text/event-stream`) through the local proxy. Events
+//! reach the client the moment the upstream sends them. At verbosity 3 each
+//! one is logged as it passes, and the end of a stream is logged with the
+//! number of events it carried.
+//!
+//! Open streams end when their forward stops, so stopping or restarting a
+//! forward does not wait for a browser tab to close its dashboard.
+
+use crate::http::{BodyFormat, ProxyBody};
+use http_body::Body as HttpBody;
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Longest incomplete event kept while waiting for its end; a stream that
+/// never sends a blank line is not parsed further.
+const MAX_PENDING_EVENT: usize = 1024 * 1024;
+
+pub fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.trim().to_ascii_lowercase().starts_with("text/event-stream"))
+}
+
+/// Where and how much to log about a stream.
+pub struct EventLog {
+    pub resource: String,
+    pub path: String,
+    pub verbose: u8,
+    pub format: BodyFormat,
+}
+
+/// An event stream response body that logs its events and ends once
+/// `closing` resolves.
+pub struct EventStream {
+    inner: ProxyBody,
+    closing: Pin<Box<dyn Future<Output = ()> + Send>>,
+    log: EventLog,
+    pending: Vec<u8>,
+    events: usize,
+    started: Instant,
+}
+
+impl EventStream {
+    pub fn new(inner: ProxyBody, closing: impl Future<Output = ()> + Send + 'static, log: EventLog) -> EventStream {
+        EventStream {
+            inner,
+            closing: Box::pin(closing),
+            log,
+            pending: Vec::new(),
+            events: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Collects `chunk` and handles every event it completes.
+    fn feed(&mut self, chunk: &[u8]) {
+        // CRLF line endings are read as LF
+        self.pending.extend(chunk.iter().filter(|byte| **byte != b'\r'));
+        while let Some(end) = self.pending.windows(2).position(|window| window == b"\n\n") {
+            let block: Vec<u8> = self.pending.drain(..end + 2).collect();
+            self.handle(&String::from_utf8_lossy(&block));
+        }
+        if self.pending.len() > MAX_PENDING_EVENT {
+            self.pending.clear();
+        }
+    }
+
+    fn handle(&mut self, block: &str) {
+        let mut name = None;
+        let mut data: Vec<&str> = Vec::new();
+        for line in block.lines() {
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => name = Some(value),
+                "data" => data.push(value),
+                _ => {}
+            }
+        }
+        // Comment-only blocks are keep-alives, not events
+        if data.is_empty() {
+            return;
+        }
+        self.events += 1;
+        if self.log.verbose >= 3 {
+            let data = data.join("\n");
+            let data = crate::redact::body(&self.log.resource, data.as_bytes());
+            crate::logger::log_info(format!(
+                "{} {} {} event {}: {}",
+                "📡",
+                self.log.resource,
+                self.log.path,
+                name.unwrap_or("message"),
+                crate::http::describe_body(&data, data.len(), self.log.format, true)
+            ));
+        }
+    }
+}
+
+impl HttpBody for EventStream {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, hyper::Error>>> {
+        let this = &mut *self;
+        if this.closing.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        let polled = Pin::new(&mut this.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &polled {
+            this.feed(chunk);
+        }
+        polled
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, hyper::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        if self.log.verbose > 0 {
+            crate::logger::log_info(format!(
+                "{} {} {} event stream closed after {} events ({}s)",
+                "📡",
+                self.log.resource,
+                self.log.path,
+                self.events,
+                self.started.elapsed().as_secs()
+            ));
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::control::{ForwardList, ForwardView};
+use crate::forwarder::ForwardState;
+use anyhow::{anyhow, bail, Context, Result};
+use std::time::Duration;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable table
+    #[default]
+    Table,
+    /// Table with pod, node, uptime and traffic counters
+    Wide,
+    Json,
+    Yaml,
+    /// Tab-separated values with a header row, for scripts
+    Tsv,
+}
+
+/// Fetches the forwards of a running session from its control API and prints
+/// them in the requested format. Without a port, the session started with
+/// `start` is asked over its socket.
+pub async fn print_status(control_port: Option<u16>, output: OutputFormat) -> Result<()> {
+    let forwards = fetch_forwards(control_port).await?;
+
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&forwards)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(&forwards)?),
+        OutputFormat::Table => print_table(&forwards.forwards, false),
+        OutputFormat::Wide => print_table(&forwards.forwards, true),
+        OutputFormat::Tsv => {
+            println!("{}", columns(true).join("\t"));
+            for forward in &forwards.forwards {
+                println!("{}", row(forward, true).join("\t"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn fetch_forwards(control_port: Option<u16>) -> Result<ForwardList> {
+    let Some(control_port) = control_port else {
+        let body = crate::daemon::request(hyper::Method::GET, "/forwards").await?;
+        return serde_json::from_slice(&body).context("Failed to parse control API response");
+    };
+    let token = crate::control::read_token(control_port)?;
+    let request = hyper::Request::get(format!("http://127.0.0.1:{}/forwards", control_port))
+        .header(hyper::header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(hyper::Body::empty())?;
+    let response = hyper::Client::new()
+        .request(request)
+        .await
+        .context(format!(
+            "Failed to reach the control API on port {}. Is kpf running with --control-port?",
+            control_port
+        ))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Control API returned {}", response.status()));
+    }
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    serde_json::from_slice(&body).context("Failed to parse control API response")
+}
+
+/// How often `wait` asks the running session about its forwards.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Returns once every forward of a running session is ready, i.e. connected
+/// and past its first liveness probe. Paused and idle forwards are not waited
+/// for. Fails when a forward gave up, or after `timeout`.
+pub async fn wait_ready(control_port: Option<u16>, timeout: Duration) -> Result<()> {
+    let count = wait_until_ready(timeout, || fetch_forwards(control_port)).await?;
+    println!("✅ {} forwards ready", count);
+    Ok(())
+}
+
+/// Polls `fetch` until every forward it lists is ready, see `wait_ready`.
+/// Returns how many forwards there are.
+pub async fn wait_until_ready<F, Fut>(timeout: Duration, mut fetch: F) -> Result<usize>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<ForwardList>>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let pending = match fetch().await {
+            Ok(list) => {
+                if let Some(stopped) = list.forwards.iter().find(|view| view.status.state == ForwardState::Stopped) {
+                    bail!("{} stopped before it became ready", stopped.status.resource);
+                }
+                let pending: Vec<String> = list
+                    .forwards
+                    .iter()
+                    .filter(|view| !view.ready && !matches!(view.status.state, ForwardState::Paused | ForwardState::Idle))
+                    .map(|view| format!("{} ({})", view.status.resource, view.status.state))
+                    .collect();
+                if pending.is_empty() && !list.forwards.is_empty() {
+                    return Ok(list.forwards.len());
+                }
+                match pending.is_empty() {
+                    true => "no forwards have started yet".to_string(),
+                    false => format!("still waiting for {}", pending.join(", ")),
+                }
+            }
+            // The session may still be starting
+            Err(e) => format!("{:#}", e),
+        };
+        if tokio::time::Instant::now() >= deadline {
+            bail!("Forwards not ready after {}s: {}", timeout.as_secs(), pending);
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+fn columns(wide: bool) -> Vec<&'static str> {
+    let mut columns = vec!["RESOURCE", "NAMESPACE", "LOCAL PORT", "STATUS", "LAST PROBE"];
+    if wide {
+        columns.extend(["POD", "NODE", "UPTIME", "RESTARTS", "REQUESTS", "ERRORS", "REQ/S"]);
+    }
+    columns
+}
+
+fn row(forward: &ForwardView, wide: bool) -> Vec<String> {
+    let status = &forward.status;
+    let mut row = vec![
+        status.resource.clone(),
+        status.namespace.clone(),
+        status.local_port.to_string(),
+        if status.restarting {
+            format!("{} (restarting)", status.state)
+        } else {
+            status.state.to_string()
+        },
+        status.last_probe.clone().unwrap_or_else(|| "<none>".to_string()),
+    ];
+    if wide {
+        row.extend([
+            status.target_pod.clone().unwrap_or_else(|| "<none>".to_string()),
+            status.target_node.clone().unwrap_or_else(|| "<none>".to_string()),
+            forward
+                .uptime_secs
+                .map(format_duration)
+                .unwrap_or_else(|| "<none>".to_string()),
+            status.restarts.to_string(),
+            forward.metrics.total_requests.to_string(),
+            forward.metrics.error_responses.to_string(),
+            format!("{:.1}", forward.metrics.requests_per_sec),
+        ]);
+    }
+    row
+}
+
+/// Prints space-aligned columns, like `kubectl get`.
+fn print_table(forwards: &[ForwardView], wide: bool) {
+    let header: Vec<String> = columns(wide).into_iter().map(str::to_string).collect();
+    let rows: Vec<Vec<String>> = forwards.iter().map(|forward| row(forward, wide)).collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("   ").trim_end());
+    }
+}
+
+/// Formats seconds the way kubectl prints ages, e.g. `45s`, `12m`, `3h4m`, `2d5h`.
+fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h{}m", secs / 3600, (secs % 3600) / 60),
+        _ => format!("{}d{}h", secs / 86400, (secs % 86400) / 3600),
+    }
+}
@@ -0,0 +1,114 @@
+use crate::config::SyntheticCheck;
+use crate::forwarder::FORWARD_STATUSES;
+use hyper::{Body, Client, Method, Request};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of the latest run of a synthetic check.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SyntheticResult {
+    pub method: String,
+    pub path: String,
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// Response status, or `None` when no response arrived
+    pub status: Option<u16>,
+    pub latency_ms: u128,
+    pub error: Option<String>,
+}
+
+impl SyntheticResult {
+    pub fn is_ok(&self) -> bool {
+        self.status.is_some_and(|status| (200..400).contains(&status))
+    }
+}
+
+/// Issues each configured check through the forward's local proxy on its own
+/// interval, so the target is monitored even when no client is connected.
+/// Requests go through the proxy like any other and show up in the request
+/// inspector and metrics; the latest result per check is kept on the forward's
+/// status.
+pub async fn run_checks(key: String, local_port: u16, checks: Vec<SyntheticCheck>) {
+    // Polled in this task so aborting it stops every check
+    futures::future::join_all(
+        checks
+            .into_iter()
+            .map(|check| run_check(key.clone(), local_port, check)),
+    )
+    .await;
+}
+
+async fn run_check(key: String, local_port: u16, check: SyntheticCheck) {
+    let client = Client::new();
+    let method = check.method.clone().unwrap_or_else(|| "GET".to_string());
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        check.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS).max(1),
+    ));
+    let mut was_ok = true;
+
+    loop {
+        interval.tick().await;
+        // An idle forward is left alone until a client request wakes it
+        if crate::idle::is_idle(&key) {
+            continue;
+        }
+
+        let start = Instant::now();
+        let mut request = Request::builder()
+            .method(Method::from_bytes(method.as_bytes()).unwrap_or(Method::GET))
+            .uri(crate::http::local_url(local_port, &check.path))
+            .header("x-kpf-synthetic", "true");
+        if let Some(credentials) = crate::auth::credentials(&key) {
+            request = request.header(hyper::header::PROXY_AUTHORIZATION, credentials);
+        }
+        let request = request.body(Body::empty()).unwrap();
+        let (status, error) = match tokio::time::timeout(REQUEST_TIMEOUT, client.request(request)).await {
+            Ok(Ok(response)) => (Some(response.status().as_u16()), None),
+            Ok(Err(e)) => (None, Some(e.to_string())),
+            Err(_) => (None, Some("timed out".to_string())),
+        };
+        let result = SyntheticResult {
+            method: method.clone(),
+            path: check.path.clone(),
+            at: chrono::Utc::now(),
+            status,
+            latency_ms: start.elapsed().as_millis(),
+            error,
+        };
+
+        // Only log transitions so a steady monitor stays quiet
+        if result.is_ok() != was_ok {
+            was_ok = result.is_ok();
+            let outcome = match (&result.status, &result.error) {
+                (Some(status), _) => status.to_string(),
+                (None, Some(error)) => error.clone(),
+                (None, None) => "no response".to_string(),
+            };
+            if was_ok {
+                crate::logger::log_success(format!(
+                    "{} Synthetic {} {} on {} recovered ({})",
+                    "🩺", method, check.path, key, outcome
+                ));
+            } else {
+                crate::logger::log_warning(format!(
+                    "{} Synthetic {} {} on {} failing ({})",
+                    "🩺", method, check.path, key, outcome
+                ));
+            }
+        }
+
+        let mut statuses = FORWARD_STATUSES.lock().unwrap();
+        if let Some(entry) = statuses.get_mut(&key) {
+            match entry
+                .synthetic
+                .iter_mut()
+                .find(|existing| existing.method == result.method && existing.path == result.path)
+            {
+                Some(existing) => *existing = result,
+                None => entry.synthetic.push(result),
+            }
+        }
+    }
+}
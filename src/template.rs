@@ -0,0 +1,121 @@
+use crate::tui::ForwardStatus;
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+/// Expands `{{port "name"}}` and `{{url "name"}}` placeholders against live
+/// forward state, e.g. `{{url "api"}}/healthz` → `http://localhost:8080/healthz`.
+///
+/// `name` is either a forward key (`svc/api:80`) or a resource name (`api`).
+/// A resource name must match exactly one forward.
+pub fn render(template: &str, forwards: &[ForwardStatus]) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| anyhow!("Unterminated placeholder in '{}'", template))?;
+        rendered.push_str(&expand(after[..end].trim(), forwards)?);
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+fn expand(placeholder: &str, forwards: &[ForwardStatus]) -> Result<String> {
+    let (function, argument) = placeholder
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| anyhow!("Placeholder '{{{{{}}}}}' needs a forward name", placeholder))?;
+    let name = argument.trim().trim_matches('"');
+    let forward = lookup(name, forwards)?;
+
+    match function {
+        "port" => Ok(forward.local_port.to_string()),
+        "url" => Ok(format!(
+            "{}://localhost:{}",
+            if forward.tls { "https" } else { "http" },
+            forward.local_port
+        )),
+        _ => bail!("Unknown template function '{}' (expected port or url)", function),
+    }
+}
+
+fn lookup<'a>(name: &str, forwards: &'a [ForwardStatus]) -> Result<&'a ForwardStatus> {
+    if let Some(forward) = forwards.iter().find(|forward| forward.resource == name) {
+        return Ok(forward);
+    }
+
+    let matches: Vec<_> = forwards
+        .iter()
+        .filter(|forward| resource_name(&forward.resource) == name)
+        .collect();
+    match matches.as_slice() {
+        [forward] => Ok(forward),
+        [] => bail!("No forward named '{}'", name),
+        _ => bail!(
+            "'{}' matches several forwards ({}); use the full key",
+            name,
+            matches
+                .iter()
+                .map(|forward| forward.resource.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// `svc/api:80@staging` → `svc/api:80`: a key without the qualification of
+/// forwards of the same resource in several namespaces
+pub(crate) fn base_key(key: &str) -> &str {
+    key.split_once('@').map_or(key, |(key, _)| key)
+}
+
+/// `svc/api:80` → `api`, also for keys qualified with a namespace
+/// (`svc/api:80@staging`)
+pub(crate) fn resource_name(key: &str) -> &str {
+    let key = base_key(key);
+    let name = key.split_once('/').map_or(key, |(_, name)| name);
+    name.rsplit_once(':').map_or(name, |(name, _)| name)
+}
+
+/// Environment variable name of each forward, in order: its resource name in
+/// upper case, e.g. `MY_API` for `svc/my-api:80`. Names shared by several
+/// forwards get the remote port too, and the namespace for forwards of the
+/// same resource in several namespaces: `MY_API_80`, `MY_API_80_STAGING`.
+pub(crate) fn variable_names(forwards: &[ForwardStatus]) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for forward in forwards {
+        *counts.entry(resource_name(&forward.resource)).or_default() += 1;
+    }
+    forwards
+        .iter()
+        .map(|forward| {
+            let name = resource_name(&forward.resource);
+            match counts[name] > 1 {
+                true => {
+                    // Everything after `type/name:`: the port and any qualifier
+                    let port = forward
+                        .resource
+                        .split_once('/')
+                        .and_then(|(_, rest)| rest.strip_prefix(name)?.strip_prefix(':'))
+                        .unwrap_or("");
+                    format!("{}_{}", variable_name(name), variable_name(port))
+                }
+                false => variable_name(name),
+            }
+        })
+        .collect()
+}
+
+/// `my-api.v2` → `MY_API_V2`
+fn variable_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,452 @@
+use crate::config::TlsConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use futures::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use std::time::SystemTime;
+use tokio_rustls::rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{
+    Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig, ServerName,
+};
+use tokio_rustls::TlsAcceptor;
+
+/// TLS handshakes allowed to run at the same time per local proxy.
+const MAX_PENDING_HANDSHAKES: usize = 64;
+
+/// A connection accepted by the local proxy, plain or TLS.
+pub trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+/// Builds the server side of the local proxy's TLS from PEM files.
+pub fn server_config(tls: &TlsConfig) -> Result<Arc<ServerConfig>> {
+    let certs = load_certs(&tls.cert)?;
+    let key = load_key(&tls.key)?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match &tls.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(client_ca)? {
+                roots
+                    .add(&cert)
+                    .context(format!("Invalid CA certificate in {}", client_ca.display()))?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .context(format!("Certificate {} does not match key {}", tls.cert.display(), tls.key.display()))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Builds the client side used to reach an HTTPS upstream. Without `ca` the
+/// system roots are trusted. With `pin`, the certificate is also checked
+/// against the one seen the first time, see `CertificatePin`.
+pub fn client_config(ca: Option<&Path>, skip_verify: bool, pin: Option<CertificatePin>) -> Result<ClientConfig> {
+    let verifier: Arc<dyn ServerCertVerifier> = if skip_verify {
+        Arc::new(SkipVerification)
+    } else {
+        Arc::new(WebPkiVerifier::new(root_store(ca)?, None))
+    };
+    // A broken file must not take the forward down; it is then only not pinned
+    let pin = pin.filter(|pin| match load_known_upstreams() {
+        Ok(()) => true,
+        Err(e) => {
+            crate::logger::log_warning(format!(
+                "{} Cannot check the upstream certificate of {}: {:#}",
+                "🔏", pin.key, e
+            ));
+            false
+        }
+    });
+    let verifier = match pin {
+        Some(pin) => Arc::new(PinnedVerification {
+            inner: verifier,
+            pin,
+            warned: Mutex::new(HashSet::new()),
+        }),
+        None => verifier,
+    };
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth())
+}
+
+fn root_store(ca: Option<&Path>) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    match ca {
+        Some(ca) => {
+            for cert in load_certs(ca)? {
+                roots
+                    .add(&cert)
+                    .context(format!("Invalid CA certificate in {}", ca.display()))?;
+            }
+        }
+        None => {
+            let native = rustls_native_certs::load_native_certs()
+                .context("Failed to load system root certificates")?;
+            let native: Vec<_> = native.into_iter().map(|cert| cert.0).collect();
+            roots.add_parsable_certificates(&native);
+        }
+    }
+    Ok(roots)
+}
+
+/// Accepts every upstream certificate, for `--upstream-tls-skip-verify`.
+struct SkipVerification;
+
+impl ServerCertVerifier for SkipVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Trust on first use for an HTTPS upstream: the fingerprint of the first
+/// certificate seen for a forward is recorded in `known_upstreams_path()`, and
+/// a different one later means the forward reaches another service or cluster
+/// than it used to. That is logged as a warning, or refused.
+pub struct CertificatePin {
+    /// Where the upstream is, see `pin_key`; the fingerprint is recorded under it
+    pub key: String,
+    /// Fail the handshake on a changed certificate instead of only warning
+    pub refuse_changed: bool,
+}
+
+/// What a pinned fingerprint is recorded under: the upstream's kube context,
+/// namespace and resource, e.g. `prod/shop/service/api:443`, so the same
+/// resource in another cluster or namespace is pinned separately. Unlike the
+/// forward's key it does not depend on what else runs in the session.
+pub fn pin_key(context: Option<&str>, namespace: &str, resource_type: &str, resource_name: &str, port: u16) -> String {
+    format!(
+        "{}/{}/{}/{}:{}",
+        context.unwrap_or("-"),
+        namespace,
+        resource_type,
+        resource_name,
+        port
+    )
+}
+
+/// Fingerprints recorded so far, with the file they are kept in.
+struct KnownUpstreams {
+    path: PathBuf,
+    fingerprints: HashMap<String, String>,
+}
+
+/// Loaded by `load_known_upstreams` before the first pinned handshake, which
+/// then only looks fingerprints up in memory.
+static KNOWN_UPSTREAMS: LazyLock<Mutex<Option<KnownUpstreams>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Held while the file is written, so writes do not interleave.
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// File mapping upstreams (see `pin_key`) to the SHA-256 fingerprint of their
+/// certificate, next to the sessions directory.
+pub fn known_upstreams_path() -> Result<PathBuf> {
+    Ok(crate::session::sessions_dir()?.with_file_name("known_upstreams.json"))
+}
+
+/// Reads the recorded fingerprints, unless that was done already.
+fn load_known_upstreams() -> Result<()> {
+    let mut known = KNOWN_UPSTREAMS.lock().unwrap();
+    if known.is_some() {
+        return Ok(());
+    }
+    let path = known_upstreams_path()?;
+    let fingerprints = match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).context(format!("Failed to parse {}", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+    };
+    *known = Some(KnownUpstreams { path, fingerprints });
+    Ok(())
+}
+
+/// Writes the recorded fingerprints as they are when it runs.
+fn write_known_upstreams() -> Result<()> {
+    let _writing = WRITING.lock().unwrap();
+    let (path, content) = {
+        let known = KNOWN_UPSTREAMS.lock().unwrap();
+        let Some(known) = known.as_ref() else {
+            return Ok(());
+        };
+        (known.path.clone(), serde_json::to_string_pretty(&known.fingerprints)? + "\n")
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(&path, content).context(format!("Failed to write {}", path.display()))
+}
+
+/// Colon-separated SHA-256 of a DER certificate, as browsers show it.
+fn fingerprint(cert: &Certificate) -> String {
+    Sha256::digest(&cert.0)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// The fingerprint recorded for `key`, recording `fingerprint` if there is
+/// none. New ones are written to disk on the blocking pool, off the handshake.
+fn known_fingerprint(key: &str, fingerprint: &str) -> String {
+    let mut known = KNOWN_UPSTREAMS.lock().unwrap();
+    let Some(known) = known.as_mut() else {
+        return fingerprint.to_string();
+    };
+    if let Some(recorded) = known.fingerprints.get(key) {
+        return recorded.clone();
+    }
+    known.fingerprints.insert(key.to_string(), fingerprint.to_string());
+    crate::logger::log_info(format!(
+        "{} Recorded the upstream certificate of {} ({})",
+        "🔏", key, fingerprint
+    ));
+    let write = || {
+        if let Err(e) = write_known_upstreams() {
+            crate::logger::log_warning(format!("{} Cannot record upstream certificates: {:#}", "🔏", e));
+        }
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => drop(runtime.spawn_blocking(write)),
+        Err(_) => write(),
+    }
+    fingerprint.to_string()
+}
+
+/// Checks the certificate with `inner`, then against the recorded one.
+struct PinnedVerification {
+    inner: Arc<dyn ServerCertVerifier>,
+    pin: CertificatePin,
+    /// Changed fingerprints already warned about, so each is logged once
+    warned: Mutex<HashSet<String>>,
+}
+
+impl ServerCertVerifier for PinnedVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        let verified = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        self.check(fingerprint(end_entity))?;
+        Ok(verified)
+    }
+}
+
+impl PinnedVerification {
+    /// Compares `fingerprint` with the recorded one, warning about or
+    /// refusing a change.
+    fn check(&self, fingerprint: String) -> std::result::Result<(), tokio_rustls::rustls::Error> {
+        let recorded = known_fingerprint(&self.pin.key, &fingerprint);
+        if recorded == fingerprint {
+            return Ok(());
+        }
+        let message = format!(
+            "The upstream certificate of {} changed since it was first seen: {} instead of {}. \
+             kpf may be reaching another service or cluster than before; if the change is expected, \
+             remove {} from {}",
+            self.pin.key,
+            fingerprint,
+            recorded,
+            self.pin.key,
+            known_upstreams_path().map(|path| path.display().to_string()).unwrap_or_default()
+        );
+        if self.pin.refuse_changed {
+            if self.warned.lock().unwrap().insert(fingerprint) {
+                crate::logger::log_error(format!("{} {}; refusing to connect", "🚨", message));
+            }
+            return Err(tokio_rustls::rustls::Error::General(format!(
+                "upstream certificate of {} changed",
+                self.pin.key
+            )));
+        }
+        if self.warned.lock().unwrap().insert(fingerprint) {
+            crate::logger::log_warning(format!("{} {}", "🚨", message));
+        }
+        Ok(())
+    }
+}
+
+/// Accepts connections on `listener`, completing the TLS handshake first when
+/// `tls` is set. Failed handshakes are logged and skipped so one bad client
+/// does not take the proxy down.
+pub fn incoming(
+    listeners: Vec<TcpListener>,
+    tls: Option<Arc<ServerConfig>>,
+    resource: String,
+) -> impl Stream<Item = std::io::Result<Box<dyn Io>>> {
+    let acceptor = tls.map(TlsAcceptor::from);
+
+    let accepted = listeners.into_iter().map(|listener| {
+        futures::stream::unfold(listener, |listener| async move {
+            let accepted = listener.accept().await.map(|(stream, _)| stream);
+            Some((accepted, listener))
+        })
+        .boxed()
+    });
+    futures::stream::select_all(accepted)
+    .map(move |accepted| {
+        let acceptor = acceptor.clone();
+        async move {
+            let stream = accepted?;
+            let io: Box<dyn Io> = match acceptor {
+                Some(acceptor) => Box::new(acceptor.accept(stream).await?),
+                None => Box::new(stream),
+            };
+            Ok::<_, std::io::Error>(io)
+        }
+    })
+    .buffer_unordered(MAX_PENDING_HANDSHAKES)
+    .filter_map(move |result| {
+        let resource = resource.clone();
+        async move {
+            match result {
+                Ok(io) => Some(Ok(io)),
+                Err(e) => {
+                    crate::logger::log_warning(format!(
+                        "{} Rejected connection to {}: {}",
+                        "🔒", resource, e
+                    ));
+                    None
+                }
+            }
+        }
+    })
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .context(format!("Failed to parse {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("No PEM certificates found in {}", path.display());
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &Path) -> Result<PrivateKey> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .context(format!("Failed to parse {}", path.display()))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("No PEM private key found in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points the fingerprint store at a file in the temp directory, unless a
+    /// test did already, and returns that file.
+    fn test_store() -> PathBuf {
+        let mut known = KNOWN_UPSTREAMS.lock().unwrap();
+        let known = known.get_or_insert_with(|| KnownUpstreams {
+            path: std::env::temp_dir()
+                .join(format!("kpf-test-{}", std::process::id()))
+                .join("known_upstreams.json"),
+            fingerprints: HashMap::new(),
+        });
+        known.path.clone()
+    }
+
+    fn verification(key: &str, refuse_changed: bool) -> PinnedVerification {
+        test_store();
+        PinnedVerification {
+            inner: Arc::new(SkipVerification),
+            pin: CertificatePin { key: key.to_string(), refuse_changed },
+            warned: Mutex::new(HashSet::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn first_fingerprint_is_recorded() {
+        let path = test_store();
+        let key = pin_key(Some("staging"), "shop", "service", "api", 443);
+        assert_eq!(key, "staging/shop/service/api:443");
+        assert!(verification(&key, true).check("AA:01".to_string()).is_ok());
+        assert_eq!(known_fingerprint(&key, "BB:02"), "AA:01");
+
+        // Written on the blocking pool
+        for _ in 0..100 {
+            let content = std::fs::read_to_string(&path).unwrap_or_default();
+            if content.contains(&key) {
+                let written: HashMap<String, String> = serde_json::from_str(&content).unwrap();
+                assert_eq!(written[&key], "AA:01");
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("{} was not written to {}", key, path.display());
+    }
+
+    #[tokio::test]
+    async fn same_fingerprint_passes() {
+        let key = pin_key(Some("staging"), "shop", "service", "same", 443);
+        let verification = verification(&key, true);
+        assert!(verification.check("AA:01".to_string()).is_ok());
+        assert!(verification.check("AA:01".to_string()).is_ok());
+        assert!(verification.warned.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn changed_fingerprint_warns() {
+        let key = pin_key(Some("staging"), "shop", "service", "warn", 443);
+        let verification = verification(&key, false);
+        assert!(verification.check("AA:01".to_string()).is_ok());
+        assert!(verification.check("BB:02".to_string()).is_ok());
+        assert!(verification.warned.lock().unwrap().contains("BB:02"));
+        assert_eq!(known_fingerprint(&key, "CC:03"), "AA:01");
+    }
+
+    #[tokio::test]
+    async fn changed_fingerprint_is_refused() {
+        let key = pin_key(Some("staging"), "shop", "service", "refuse", 443);
+        let verification = verification(&key, true);
+        assert!(verification.check("AA:01".to_string()).is_ok());
+        assert!(verification.check("BB:02".to_string()).is_err());
+        assert!(verification.check("AA:01".to_string()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn clusters_are_pinned_separately() {
+        let staging = pin_key(Some("staging"), "shop", "service", "cluster", 443);
+        let prod = pin_key(Some("prod"), "shop", "service", "cluster", 443);
+        assert!(verification(&staging, true).check("AA:01".to_string()).is_ok());
+        assert!(verification(&prod, true).check("BB:02".to_string()).is_ok());
+    }
+}
@@ -0,0 +1,278 @@
+//! A forward's `request_body_transforms` and `response_body_transforms`:
+//! regex replacements and JSON field overrides applied to bodies as they pass
+//! the proxy, e.g. to turn cluster-internal URLs in API responses into
+//! `localhost` ones.
+//!
+//! Only uncompressed text, JSON, XML, JavaScript and form bodies are
+//! rewritten; they are read whole first. Event streams, gRPC and other
+//! binary bodies pass through untouched. Forwards with response transforms
+//! ask the upstream for uncompressed responses.
+
+use crate::config::{BodyTransformConfig, ForwardConfig};
+use anyhow::{bail, Context, Result};
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, CONTENT_ENCODING, CONTENT_TYPE};
+use regex::bytes::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+static TRANSFORMS: LazyLock<Mutex<HashMap<String, Arc<Transforms>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A forward's body transforms, compiled once from its config.
+#[derive(Debug, Default)]
+pub struct Transforms {
+    pub request: BodyTransforms,
+    pub response: BodyTransforms,
+}
+
+impl Transforms {
+    /// `None` when the forward transforms no bodies.
+    pub fn from_forward(forward: &ForwardConfig) -> Result<Option<Transforms>> {
+        let transforms = Transforms {
+            request: BodyTransforms::new(forward.request_body_transforms.as_deref(), "request_body_transforms")?,
+            response: BodyTransforms::new(forward.response_body_transforms.as_deref(), "response_body_transforms")?,
+        };
+        let empty = transforms.request.is_empty() && transforms.response.is_empty();
+        Ok((!empty).then_some(transforms))
+    }
+}
+
+/// The steps applied to one direction's bodies, in order.
+#[derive(Debug, Default)]
+pub struct BodyTransforms {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug)]
+enum Step {
+    Replace(Regex, String),
+    /// Dotted paths split into segments, with the value to set or `null`
+    SetFields(Vec<(Vec<String>, Value)>),
+}
+
+impl BodyTransforms {
+    fn new(configs: Option<&[BodyTransformConfig]>, field: &str) -> Result<BodyTransforms> {
+        let mut steps = Vec::new();
+        for config in configs.into_iter().flatten() {
+            match (&config.pattern, &config.replacement) {
+                (Some(pattern), replacement) => steps.push(Step::Replace(
+                    Regex::new(pattern).context(format!("Invalid {} pattern '{}'", field, pattern))?,
+                    replacement.clone().unwrap_or_default(),
+                )),
+                (None, Some(_)) => bail!("{} has a replacement but no pattern", field),
+                (None, None) => {}
+            }
+            if let Some(fields) = &config.set_fields {
+                let mut paths = Vec::new();
+                for (path, value) in fields {
+                    let segments: Vec<String> = path.split('.').map(str::to_string).collect();
+                    if segments.iter().any(String::is_empty) {
+                        bail!("Invalid {} field '{}'", field, path);
+                    }
+                    paths.push((segments, value.clone()));
+                }
+                // Shorter paths first, so a parent is set before fields below it
+                paths.sort_by_key(|(segments, _)| segments.len());
+                steps.push(Step::SetFields(paths));
+            }
+            if config.pattern.is_none() && config.set_fields.is_none() {
+                bail!("{} entries need a pattern or set_fields", field);
+            }
+        }
+        Ok(BodyTransforms { steps })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Whether a body with these headers is rewritten.
+    pub fn applies(&self, headers: &HeaderMap) -> bool {
+        if self.is_empty() || is_encoded(headers) {
+            return false;
+        }
+        let Some(content_type) = headers.get(CONTENT_TYPE).and_then(|value| value.to_str().ok()) else {
+            return false;
+        };
+        let content_type = content_type.to_ascii_lowercase();
+        if content_type.starts_with("text/event-stream") {
+            return false;
+        }
+        content_type.starts_with("text/")
+            || ["json", "xml", "javascript", "x-www-form-urlencoded"]
+                .iter()
+                .any(|kind| content_type.contains(kind))
+    }
+
+    pub fn apply(&self, body: Bytes) -> Bytes {
+        let mut body = body.to_vec();
+        for step in &self.steps {
+            match step {
+                Step::Replace(pattern, replacement) => {
+                    body = pattern.replace_all(&body, replacement.as_bytes()).into_owned();
+                }
+                Step::SetFields(fields) => {
+                    // Bodies that are not JSON are left as they are
+                    let Ok(mut json) = serde_json::from_slice::<Value>(&body) else {
+                        continue;
+                    };
+                    for (path, value) in fields {
+                        set_field(&mut json, path, value);
+                    }
+                    body = serde_json::to_vec(&json).unwrap_or(body);
+                }
+            }
+        }
+        Bytes::from(body)
+    }
+}
+
+fn is_encoded(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|encoding| !encoding.trim().eq_ignore_ascii_case("identity"))
+}
+
+/// Sets the field at `path` to `value`, creating missing objects on the way,
+/// or removes it when `value` is `null`. `*` walks every element of an array.
+fn set_field(json: &mut Value, path: &[String], value: &Value) {
+    let Some((segment, rest)) = path.split_first() else {
+        return;
+    };
+    match json {
+        Value::Array(elements) if segment == "*" => {
+            for element in elements {
+                set_field(element, rest, value);
+            }
+        }
+        Value::Array(elements) => {
+            if let Some(element) = segment.parse::<usize>().ok().and_then(|index| elements.get_mut(index)) {
+                match rest.is_empty() {
+                    true if !value.is_null() => *element = value.clone(),
+                    true => {}
+                    false => set_field(element, rest, value),
+                }
+            }
+        }
+        Value::Object(fields) if rest.is_empty() => {
+            match value.is_null() {
+                true => fields.remove(segment),
+                false => fields.insert(segment.clone(), value.clone()),
+            };
+        }
+        Value::Object(fields) => {
+            // Nothing to create when the field is removed anyway
+            if value.is_null() && !fields.contains_key(segment) {
+                return;
+            }
+            let child = fields
+                .entry(segment.clone())
+                .or_insert_with(|| Value::Object(Default::default()));
+            set_field(child, rest, value);
+        }
+        _ => {}
+    }
+}
+
+/// Registers the body transforms of a forward, replacing any earlier ones.
+pub fn configure(key: &str, forward: &ForwardConfig) -> Result<()> {
+    let mut registry = TRANSFORMS.lock().unwrap();
+    match Transforms::from_forward(forward)? {
+        Some(transforms) => registry.insert(key.to_string(), Arc::new(transforms)),
+        None => registry.remove(key),
+    };
+    Ok(())
+}
+
+/// Drops the body transforms of a forward that did not start.
+pub fn forget(key: &str) {
+    TRANSFORMS.lock().unwrap().remove(key);
+}
+
+/// Body transforms of a forward, if it has any.
+pub fn rules(key: &str) -> Option<Arc<Transforms>> {
+    TRANSFORMS.lock().unwrap().get(key).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn transforms(configs: serde_json::Value) -> Result<BodyTransforms> {
+        let configs: Vec<BodyTransformConfig> = serde_json::from_value(configs).unwrap();
+        BodyTransforms::new(Some(&configs), "response_body_transforms")
+    }
+
+    fn apply(transforms: &BodyTransforms, body: &str) -> String {
+        String::from_utf8(transforms.apply(Bytes::from(body.to_string())).to_vec()).unwrap()
+    }
+
+    fn headers(content_type: &str, encoding: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+        if let Some(encoding) = encoding {
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_str(encoding).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn replacements_apply_in_order() {
+        let transforms = transforms(serde_json::json!([
+            { "pattern": r"http://(\w+)\.default\.svc", "replacement": "http://localhost/$1" },
+            { "pattern": "localhost/api" , "replacement": "localhost:8080" },
+            { "pattern": "secret" }
+        ]))
+        .unwrap();
+        assert_eq!(
+            apply(&transforms, "see http://api.default.svc/x and http://web.default.svc secret"),
+            "see http://localhost:8080/x and http://localhost/web "
+        );
+    }
+
+    #[test]
+    fn fields_are_set_and_removed() {
+        let transforms = transforms(serde_json::json!([
+            { "set_fields": { "user.role": "admin", "items.*.price": 0, "debug": null, "items.1.name": "b" } }
+        ]))
+        .unwrap();
+        let body = apply(&transforms, r#"{"debug":true,"items":[{"price":3},{"price":4}]}"#);
+        let value: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "user": { "role": "admin" }, "items": [{ "price": 0 }, { "price": 0, "name": "b" }] })
+        );
+    }
+
+    #[test]
+    fn set_fields_leave_other_bodies_alone() {
+        let transforms = transforms(serde_json::json!([{ "set_fields": { "a": 1 } }])).unwrap();
+        assert_eq!(apply(&transforms, "not json"), "not json");
+    }
+
+    #[test]
+    fn only_plain_text_bodies_apply() {
+        let transforms = transforms(serde_json::json!([{ "pattern": "a" }])).unwrap();
+        assert!(transforms.applies(&headers("application/json; charset=utf-8", None)));
+        assert!(transforms.applies(&headers("text/html", Some("identity"))));
+        assert!(transforms.applies(&headers("application/x-www-form-urlencoded", None)));
+        assert!(!transforms.applies(&headers("application/json", Some("gzip"))));
+        assert!(!transforms.applies(&headers("text/event-stream", None)));
+        assert!(!transforms.applies(&headers("application/grpc", None)));
+        assert!(!transforms.applies(&headers("image/png", None)));
+        assert!(!transforms.applies(&HeaderMap::new()));
+        assert!(!BodyTransforms::default().applies(&headers("text/plain", None)));
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        assert!(transforms(serde_json::json!([{ "pattern": "(" }])).is_err());
+        assert!(transforms(serde_json::json!([{ "replacement": "x" }])).is_err());
+        assert!(transforms(serde_json::json!([{}])).is_err());
+        assert!(transforms(serde_json::json!([{ "set_fields": { "a..b": 1 } }])).is_err());
+    }
+}
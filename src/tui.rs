@@ -1,6 +1,9 @@
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -8,91 +11,424 @@ use ratatui::{
     prelude::*,
     text::{Line, Span},
     widgets::{
-        Block, Borders, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap,
+        Block, Borders, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+        TableState, Wrap,
     },
     Frame,
 };
+use regex::{Regex, RegexBuilder};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use std::collections::{HashSet, VecDeque};
 use std::io;
-use std::sync::mpsc;
-use std::thread;
+use std::sync::{mpsc, LazyLock, Mutex};
 use std::time::{Duration, Instant};
 
+/// Color of responses over `--slow-threshold-ms`.
+const SLOW_COLOR: Color = Color::LightMagenta;
+/// How long the status table marks a forward whose target moved to another pod.
+const RECENT_POD_CHANGE_MINUTES: i64 = 5;
+
 // New struct: ForwardStatus holds the state for a port-forward
-#[derive(Clone)]
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct ForwardStatus {
     pub resource: String,
+    /// Namespace the forward runs in, after falling back to the kubeconfig context's
+    pub namespace: String,
     pub local_port: u16,
     pub state: crate::forwarder::ForwardState,
     pub last_probe: Option<String>,
+    /// Whether the forward targets a context marked as protected
+    pub protected: bool,
+    /// Pod the port-forward currently lands on
+    pub target_pod: Option<String>,
+    /// Node the target pod runs on
+    pub target_node: Option<String>,
+    /// When the target moved to another pod, oldest first
+    #[serde(default)]
+    pub pod_changes: Vec<crate::forwarder::PodChange>,
+    /// When the current port-forward connection was established
+    pub connected_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the forward had to reconnect, oldest first
+    pub reconnects: Vec<chrono::DateTime<chrono::Utc>>,
+    /// Latest result of each configured synthetic check
+    pub synthetic: Vec<crate::synthetic::SyntheticResult>,
+    /// Set while the forward waits to retry a failed connection
+    pub retry: Option<crate::forwarder::RetryState>,
+    /// Whether the local proxy serves HTTPS
+    pub tls: bool,
+    /// Dependencies (`depends_on`) the forward still waits for before starting
+    pub waiting_for: Vec<String>,
+    /// Restarts requested with `r` or the control API
+    #[serde(default)]
+    pub restarts: u32,
+    /// Set from a requested restart until the forward connects again
+    #[serde(default)]
+    pub restarting: bool,
+    /// Kubeconfig context the forward talks to; empty without a kubeconfig context
+    #[serde(default)]
+    pub context: String,
+    /// Changes of `state`, oldest first
+    #[serde(default)]
+    pub transitions: Vec<crate::forwarder::StateTransition>,
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct LogEntry {
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub message: String,
     pub level: LogLevel,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LogLevel {
     Info,
     Success,
     Warning,
+    /// A response over `--slow-threshold-ms`: a warning with its own color
+    Slow,
     Error,
 }
 
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Success => "SUCCESS",
+            LogLevel::Warning | LogLevel::Slow => "WARNING",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
 pub struct App {
-    logs: Vec<LogEntry>,
+    /// Newest last; the oldest entries are dropped past `max_log_lines`
+    logs: VecDeque<LogEntry>,
+    max_log_lines: usize,
     log_receiver: mpsc::Receiver<LogEntry>,
     should_quit: bool,
     scroll: usize,
     auto_scroll: bool,
     log_scroll_state: ScrollbarState,
     awaiting_verbosity_input: bool,
+    verbosity_forward: Option<String>, // Forward the pending verbosity change applies to; None = global
     pub forward_statuses: Vec<ForwardStatus>,
+    /// Rows of the status table; `status_state` selects among these
+    status_rows: Vec<StatusRow>,
+    /// Contexts whose forwards are hidden under their heading
+    collapsed_contexts: HashSet<String>,
+    status_state: TableState,
+    /// Whether ↑/↓ move the status table selection instead of scrolling the logs
+    status_focused: bool,
+    show_detail: bool,
+    /// Forward whose pod logs (`tail_logs`) are shown next to the request log
+    pod_logs_forward: Option<String>,
+    /// Whether the metrics view of the selected forward replaces the logs
+    show_metrics: bool,
     // Search state
     search_mode: bool,
     search_query: String,
     search_results: Vec<usize>, // Stores indices of matching log lines
     current_search_result_index: Option<usize>, // Index into search_results
+    search_case_insensitive: bool,
+    search_regex: bool,
+    search_pattern: Option<Regex>, // Compiled from search_query and the toggles above
+    search_error: Option<String>,  // Set when search_query is not a valid regex
+    // Request inspector state
+    show_inspector: bool,
+    captured_requests: Vec<crate::capture::CapturedRequest>,
+    inspector_state: TableState,
+    note_input: Option<String>, // Some while editing a note for the selected request
+    new_forward: Option<NewForwardForm>, // Some while the `o` form is open
+    port_prompt: Option<PortPrompt>, // Some while asking which port to forward
+    port_choice: usize,              // Index into the prompt's ports
+}
+
+/// A row of the status table. Forwards are grouped under a heading per
+/// context when they span more than one.
+#[derive(Clone, PartialEq)]
+enum StatusRow {
+    Context(String),
+    /// Index into `forward_statuses`
+    Forward(usize),
+}
+
+/// Forwards given without a port whose resource declares several, waiting
+/// for the user to pick one.
+static PORT_PROMPTS: LazyLock<Mutex<VecDeque<PortPrompt>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+/// A forward given as `type/name`, without a port.
+#[derive(Clone, Default)]
+pub struct PortPrompt {
+    pub resource: String,
+    pub namespace: Option<String>,
+    pub context: Option<String>,
+    pub local_port: Option<u16>,
+    /// Ports the resource declares, as (port, name)
+    pub ports: Vec<(u16, Option<String>)>,
+    /// Protected context waiting for a y/N answer before starting
+    pub protected: Option<String>,
+}
+
+impl PortPrompt {
+    /// Starts the forward on `port`.
+    fn start(&self, port: u16) {
+        let mut forward = crate::config::ForwardConfig::builder()
+            .resource(format!("{}:{}", self.resource, port));
+        if let Some(namespace) = &self.namespace {
+            forward = forward.namespace(namespace.clone());
+        }
+        if let Some(context) = &self.context {
+            forward = forward.context(context.clone());
+        }
+        if let Some(local_port) = self.local_port {
+            forward = forward.local_port(local_port);
+        }
+        let forward = match forward.build() {
+            Ok(forward) => forward,
+            Err(e) => {
+                crate::logger::log_error(format!("Cannot start {}: {}", self.resource, e));
+                return;
+            }
+        };
+        crate::logger::log_info(format!("{} Starting {}", "➕", forward.resource));
+        tokio::spawn(async move {
+            let resource = forward.resource.clone();
+            if let Err(e) = crate::forwarder::add_forwards(vec![forward]).await {
+                crate::logger::log_error(format!("Cannot start {}: {}", resource, e));
+            }
+        });
+    }
+}
+
+/// Looks up the ports of a forward given without one, and starts it on the
+/// only one it declares or asks in the TUI which one to use.
+pub async fn resolve_port(mut prompt: PortPrompt) {
+    let namespace = prompt
+        .namespace
+        .clone()
+        .or_else(|| crate::k8s::context_namespace(prompt.context.as_deref()))
+        .unwrap_or_else(|| "default".to_string());
+    let ports = match crate::k8s::declared_ports(&prompt.resource, &namespace, prompt.context.as_deref()).await {
+        Ok(ports) => ports,
+        Err(e) => {
+            crate::logger::log_error(format!("Cannot start {}: {:#}", prompt.resource, e));
+            return;
+        }
+    };
+    match ports.as_slice() {
+        [] => crate::logger::log_error(format!(
+            "{} declares no TCP ports; give one as {}:<port>",
+            prompt.resource, prompt.resource
+        )),
+        [(port, _)] => {
+            crate::logger::log_info(format!(
+                "{} Using port {}, the only one {} declares",
+                "🔎", port, prompt.resource
+            ));
+            prompt.start(*port);
+        }
+        _ => {
+            prompt.ports = ports;
+            PORT_PROMPTS.lock().unwrap().push_back(prompt);
+        }
+    }
+}
+
+/// The form opened with `o` to start a forward at runtime.
+#[derive(Default)]
+struct NewForwardForm {
+    resource: String,
+    namespace: String,
+    local_port: String,
+    /// Index of the field being edited, see `NewForwardForm::LABELS`
+    field: usize,
+    /// Why the last submission was rejected
+    error: Option<String>,
+    /// Protected context waiting for a y/N answer before starting
+    protected: Option<String>,
+}
+
+impl NewForwardForm {
+    const LABELS: [&'static str; 3] = ["Resource", "Namespace", "Local port"];
+    const HINTS: [&'static str; 3] = [
+        "e.g. service/api:80, or service/api to pick a port",
+        "default: the kubeconfig context's",
+        "default: the resource port",
+    ];
+
+    fn input(&mut self) -> &mut String {
+        match self.field {
+            0 => &mut self.resource,
+            1 => &mut self.namespace,
+            _ => &mut self.local_port,
+        }
+    }
+
+    fn values(&self) -> [&str; 3] {
+        [&self.resource, &self.namespace, &self.local_port]
+    }
+
+    fn next_field(&mut self) {
+        self.field = (self.field + 1) % Self::LABELS.len();
+    }
+
+    fn previous_field(&mut self) {
+        self.field = (self.field + Self::LABELS.len() - 1) % Self::LABELS.len();
+    }
+
+    /// Validates the fields into a forward the same way a config entry is.
+    fn build(&self) -> Result<crate::config::ForwardConfig> {
+        let mut forward = crate::config::ForwardConfig::builder().resource(self.resource.trim());
+        if !self.namespace.trim().is_empty() {
+            forward = forward.namespace(self.namespace.trim());
+        }
+        if let Some(port) = self.parse_local_port()? {
+            forward = forward.local_port(port);
+        }
+        forward.build()
+    }
+
+    /// The fields of a forward given without a port, to look its ports up.
+    fn port_prompt(&self) -> Result<PortPrompt> {
+        Ok(PortPrompt {
+            resource: self.resource.trim().to_string(),
+            namespace: Some(self.namespace.trim().to_string()).filter(|namespace| !namespace.is_empty()),
+            local_port: self.parse_local_port()?,
+            ..PortPrompt::default()
+        })
+    }
+
+    fn parse_local_port(&self) -> Result<Option<u16>> {
+        let local_port = self.local_port.trim();
+        if local_port.is_empty() {
+            return Ok(None);
+        }
+        local_port
+            .parse::<u16>()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("Invalid local port '{}'", local_port))
+    }
 }
 
 impl App {
-    pub fn new(log_receiver: mpsc::Receiver<LogEntry>) -> Self {
+    pub fn new(log_receiver: mpsc::Receiver<LogEntry>, max_log_lines: usize) -> Self {
         Self {
-            logs: Vec::new(),
+            logs: VecDeque::new(),
+            max_log_lines: max_log_lines.max(1),
             log_receiver,
             should_quit: false,
             scroll: 0,
             auto_scroll: true,
             log_scroll_state: ScrollbarState::default(),
             awaiting_verbosity_input: false,
+            verbosity_forward: None,
             forward_statuses: Vec::new(),
+            status_rows: Vec::new(),
+            collapsed_contexts: HashSet::new(),
+            status_state: TableState::default(),
+            status_focused: false,
+            show_detail: false,
+            pod_logs_forward: None,
+            show_metrics: false,
             // Search state init
             search_mode: false,
             search_query: String::new(),
             search_results: Vec::new(),
             current_search_result_index: None,
+            search_case_insensitive: false,
+            search_regex: false,
+            search_pattern: None,
+            search_error: None,
+            show_inspector: false,
+            captured_requests: Vec::new(),
+            inspector_state: TableState::default(),
+            note_input: None,
+            new_forward: None,
+            port_prompt: None,
+            port_choice: 0,
         }
     }
 
     pub fn on_tick(&mut self) {
+        // A forward's `on_failure` policy ends the run
+        if crate::forwarder::failure_exit().is_some() {
+            self.should_quit = true;
+        }
+
         // Process any new log messages
         let mut received_logs = false;
 
         // Try to receive all pending log messages
         while let Ok(log) = self.log_receiver.try_recv() {
-            self.logs.push(log);
+            self.logs.push_back(log);
             received_logs = true;
         }
+        let overflow = self.logs.len().saturating_sub(self.max_log_lines);
+        if overflow > 0 {
+            self.evict_logs(overflow);
+        }
 
         // Auto-scroll to bottom if enabled and we received new logs
         if received_logs && self.auto_scroll {
             self.scroll_to_bottom();
         }
 
+        if self.port_prompt.is_none() {
+            self.port_prompt = PORT_PROMPTS.lock().unwrap().pop_front();
+            self.port_choice = 0;
+        }
+
+        let selected = self.status_state.selected().and_then(|i| self.status_row_key(i));
         if let Ok(statuses) = crate::forwarder::FORWARD_STATUSES.lock() {
             self.forward_statuses = statuses.values().cloned().collect();
         }
+        self.forward_statuses
+            .sort_by(|a, b| (&a.context, &a.resource).cmp(&(&b.context, &b.resource)));
+        self.update_status_rows();
+        self.reselect_status_row(selected);
+        match self.status_state.selected() {
+            None if !self.status_rows.is_empty() => self.status_state.select(Some(0)),
+            Some(i) if i >= self.status_rows.len() => self
+                .status_state
+                .select(self.status_rows.len().checked_sub(1)),
+            _ => {}
+        }
+
+        if self.show_inspector {
+            self.captured_requests = crate::capture::snapshot();
+            if self.inspector_state.selected().is_none() && !self.captured_requests.is_empty() {
+                self.inspector_state.select(Some(self.captured_requests.len() - 1));
+            }
+        }
+    }
+
+    /// Drops the `count` oldest log entries, keeping the manual scroll
+    /// position and the search hits on the same entries.
+    fn evict_logs(&mut self, count: usize) {
+        let evicted_lines: usize = self
+            .logs
+            .drain(..count)
+            .map(|entry| entry.message.split('\n').count())
+            .sum();
+        if !self.auto_scroll {
+            self.scroll = self.scroll.saturating_sub(evicted_lines);
+        }
+
+        let evicted_results = self
+            .search_results
+            .iter()
+            .take_while(|&&index| index < count)
+            .count();
+        self.search_results.drain(..evicted_results);
+        for index in &mut self.search_results {
+            *index -= count;
+        }
+        self.current_search_result_index = self
+            .current_search_result_index
+            .and_then(|current| current.checked_sub(evicted_results));
     }
 
     pub fn quit(&mut self) {
@@ -152,6 +488,341 @@ impl App {
         }
     }
 
+    // --- Status Table Methods ---
+
+    /// Lays out the status table: the forwards as they are with a single
+    /// context, otherwise a heading per context followed by its forwards
+    /// unless it is collapsed.
+    fn update_status_rows(&mut self) {
+        let mut contexts: Vec<&str> = self.forward_statuses.iter().map(|st| st.context.as_str()).collect();
+        contexts.dedup();
+
+        self.status_rows = if contexts.len() > 1 {
+            let mut rows = Vec::new();
+            for (index, st) in self.forward_statuses.iter().enumerate() {
+                if index == 0 || self.forward_statuses[index - 1].context != st.context {
+                    rows.push(StatusRow::Context(st.context.clone()));
+                }
+                if !self.collapsed_contexts.contains(&st.context) {
+                    rows.push(StatusRow::Forward(index));
+                }
+            }
+            rows
+        } else {
+            (0..self.forward_statuses.len()).map(StatusRow::Forward).collect()
+        };
+    }
+
+    /// Moves the selection to the row with `key`, if it is still shown.
+    fn reselect_status_row(&mut self, key: Option<(String, Option<String>)>) {
+        let Some(key) = key else {
+            return;
+        };
+        if let Some(i) = (0..self.status_rows.len()).find(|&i| self.status_row_key(i).as_ref() == Some(&key)) {
+            self.status_state.select(Some(i));
+        }
+    }
+
+    /// What a row shows, stable across re-sorting: the context of a heading,
+    /// or the context and resource of a forward.
+    fn status_row_key(&self, row: usize) -> Option<(String, Option<String>)> {
+        match self.status_rows.get(row)? {
+            StatusRow::Context(context) => Some((context.clone(), None)),
+            StatusRow::Forward(i) => {
+                let st = self.forward_statuses.get(*i)?;
+                Some((st.context.clone(), Some(st.resource.clone())))
+            }
+        }
+    }
+
+    fn select_next_forward(&mut self) {
+        if self.status_rows.is_empty() {
+            return;
+        }
+        let next = self
+            .status_state
+            .selected()
+            .map_or(0, |i| (i + 1) % self.status_rows.len());
+        self.status_state.select(Some(next));
+    }
+
+    fn select_previous_forward(&mut self) {
+        if self.status_rows.is_empty() {
+            return;
+        }
+        let len = self.status_rows.len();
+        let previous = self.status_state.selected().map_or(0, |i| (i + len - 1) % len);
+        self.status_state.select(Some(previous));
+    }
+
+    /// Rows for every forward plus header and borders, within bounds so the
+    /// logs keep most of the screen.
+    fn status_panel_height(&self) -> u16 {
+        (self.status_rows.len() as u16 + 3).clamp(5, 12)
+    }
+
+    fn selected_forward(&self) -> Option<&ForwardStatus> {
+        match self.status_rows.get(self.status_state.selected()?)? {
+            StatusRow::Forward(i) => self.forward_statuses.get(*i),
+            StatusRow::Context(_) => None,
+        }
+    }
+
+    /// The context whose heading is selected, if any.
+    fn selected_context(&self) -> Option<&str> {
+        match self.status_rows.get(self.status_state.selected()?)? {
+            StatusRow::Context(context) => Some(context),
+            StatusRow::Forward(_) => None,
+        }
+    }
+
+    /// Collapses or expands the forwards under the selected context heading.
+    fn toggle_selected_context(&mut self) {
+        let Some(context) = self.selected_context().map(str::to_string) else {
+            return;
+        };
+        if !self.collapsed_contexts.remove(&context) {
+            self.collapsed_contexts.insert(context.clone());
+        }
+        self.update_status_rows();
+        self.reselect_status_row(Some((context, None)));
+    }
+
+    fn toggle_metrics(&mut self) {
+        self.show_metrics = !self.show_metrics;
+        self.show_inspector = false;
+    }
+
+    // --- Inspector Methods ---
+
+    fn toggle_inspector(&mut self) {
+        self.show_inspector = !self.show_inspector;
+        self.show_metrics = false;
+        if self.show_inspector {
+            self.captured_requests = crate::capture::snapshot();
+            self.inspector_state
+                .select(self.captured_requests.len().checked_sub(1));
+        }
+    }
+
+    fn select_previous_request(&mut self) {
+        let selected = self.inspector_state.selected().unwrap_or(0);
+        self.inspector_state.select(Some(selected.saturating_sub(1)));
+    }
+
+    fn select_next_request(&mut self) {
+        if self.captured_requests.is_empty() {
+            return;
+        }
+        let selected = self.inspector_state.selected().map_or(0, |i| i + 1);
+        self.inspector_state
+            .select(Some(selected.min(self.captured_requests.len() - 1)));
+    }
+
+    fn selected_request(&self) -> Option<&crate::capture::CapturedRequest> {
+        self.inspector_state
+            .selected()
+            .and_then(|i| self.captured_requests.get(i))
+    }
+
+    fn start_note_input(&mut self) {
+        if let Some(request) = self.selected_request() {
+            self.note_input = Some(request.note.clone().unwrap_or_default());
+        }
+    }
+
+    fn save_note(&mut self) {
+        let Some(note) = self.note_input.take() else {
+            return;
+        };
+        if let Some(id) = self.selected_request().map(|request| request.id) {
+            crate::capture::annotate(id, note);
+            self.captured_requests = crate::capture::snapshot();
+        }
+    }
+
+    /// Turns a chaos-mode fault on or off for the selected forward.
+    fn toggle_chaos(&mut self, fault: crate::chaos::Fault) {
+        let Some(key) = self.selected_forward().map(|st| st.resource.clone()) else {
+            return;
+        };
+        let enabled = crate::chaos::toggle(&key, fault);
+        crate::logger::log_warning(format!(
+            "{} Chaos {:?} {} for {}",
+            "🐒",
+            fault,
+            if enabled { "enabled" } else { "disabled" },
+            key
+        ));
+    }
+
+    /// Pauses the selected forward, or resumes it if it is paused.
+    fn toggle_pause(&mut self) {
+        let Some(st) = self.selected_forward() else {
+            return;
+        };
+        let command = if st.state == crate::forwarder::ForwardState::Paused {
+            crate::forwarder::ForwardCommand::Resume
+        } else {
+            crate::forwarder::ForwardCommand::Pause
+        };
+        if let Err(e) = crate::forwarder::send_command(&st.resource, command) {
+            crate::logger::log_error(format!("Cannot pause/resume {}: {}", st.resource, e));
+        }
+    }
+
+    fn submit_new_forward(&mut self) {
+        let Some(form) = self.new_forward.as_mut() else {
+            return;
+        };
+        // The form has no context field, so its forwards go to the current context
+        if let Err(e) = crate::k8s::check_protected_context(None) {
+            form.protected = Some(e.0);
+            return;
+        }
+        let resource = form.resource.trim();
+        if !resource.is_empty() && !crate::k8s::has_port(resource) {
+            let prompt = match form.port_prompt() {
+                Ok(prompt) => prompt,
+                Err(e) => {
+                    form.error = Some(e.to_string());
+                    return;
+                }
+            };
+            self.new_forward = None;
+            crate::logger::log_info(format!("{} Looking up the ports of {}", "🔎", prompt.resource));
+            tokio::spawn(resolve_port(prompt));
+            return;
+        }
+        let forward = match form.build() {
+            Ok(forward) => forward,
+            Err(e) => {
+                form.error = Some(e.to_string());
+                return;
+            }
+        };
+        self.new_forward = None;
+        crate::logger::log_info(format!("{} Starting {}", "➕", forward.resource));
+        tokio::spawn(async move {
+            let resource = forward.resource.clone();
+            if let Err(e) = crate::forwarder::add_forwards(vec![forward]).await {
+                crate::logger::log_error(format!("Cannot start {}: {}", resource, e));
+            }
+        });
+    }
+
+    fn remove_selected(&mut self) {
+        let Some(key) = self.selected_forward().map(|st| st.resource.clone()) else {
+            return;
+        };
+        match crate::forwarder::remove_forward(&key) {
+            Ok(()) => crate::logger::log_info(format!("{} Removed {}", "🗑", key)),
+            Err(e) => crate::logger::log_error(format!("Cannot remove {}: {}", key, e)),
+        }
+    }
+
+    /// Dumps the whole log buffer, which is lost with the alternate screen on
+    /// quit, to a timestamped file in the working directory.
+    fn save_logs(&self) {
+        let path = format!("kpf-logs-{}.log", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        match crate::logger::write_entries(&path, &self.logs) {
+            Ok(()) => crate::logger::log_success(format!(
+                "{} Saved {} log lines to {}",
+                "💾",
+                self.logs.len(),
+                path
+            )),
+            Err(e) => crate::logger::log_error(format!("Cannot save logs: {}", e)),
+        }
+    }
+
+    /// Copies the selected request as a `curl` command in the inspector, and
+    /// the selected log entry otherwise.
+    fn copy_selected(&self) {
+        let (text, what) = if self.show_inspector {
+            let Some(request) = self.selected_request() else {
+                return;
+            };
+            let body_note = match &request.request_body {
+                Some(body) if !crate::capture::is_replayable(body) => {
+                    " (request body left out: truncated or binary)"
+                }
+                _ => "",
+            };
+            (request.curl.clone(), format!("curl command{}", body_note))
+        } else {
+            let Some(entry) = self.selected_log_entry() else {
+                return;
+            };
+            (crate::logger::format_entry(entry), "log line".to_string())
+        };
+        match crate::clipboard::copy(&text) {
+            Ok(()) => crate::logger::log_success(format!("{} Copied {} to the clipboard", "📋", what)),
+            Err(e) => crate::logger::log_error(format!("Cannot copy {}: {:#}", what, e)),
+        }
+    }
+
+    /// The current search match, or else the newest entry while auto-scrolling
+    /// and the entry at the top of the logs panel when scrolled.
+    fn selected_log_entry(&self) -> Option<&LogEntry> {
+        if let Some(index) = self
+            .current_search_result_index
+            .and_then(|current| self.search_results.get(current))
+        {
+            return self.logs.get(*index);
+        }
+        if self.auto_scroll {
+            return self.logs.back();
+        }
+        let mut first_line = 0;
+        self.logs.iter().find(|entry| {
+            first_line += entry.message.split('\n').count();
+            first_line > self.scroll
+        })
+    }
+
+    fn restart_selected(&mut self) {
+        let Some(st) = self.selected_forward() else {
+            return;
+        };
+        match crate::forwarder::send_command(&st.resource, crate::forwarder::ForwardCommand::Restart) {
+            Ok(()) => crate::logger::log_info(format!("{} Restarting {}", "🔄", st.resource)),
+            Err(e) => crate::logger::log_error(format!("Cannot restart {}: {}", st.resource, e)),
+        }
+    }
+
+    /// Switches the selected forward between pretty and compact body logging.
+    fn toggle_pretty_print(&mut self) {
+        let Some(key) = self.selected_forward().map(|st| st.resource.clone()) else {
+            return;
+        };
+        let pretty = crate::http::toggle_pretty_print(&key);
+        crate::logger::log_info(format!(
+            "{} Body logging for {}: {}",
+            "📄",
+            key,
+            if pretty { "pretty" } else { "compact" }
+        ));
+    }
+
+    /// Shows or hides the pod logs of the selected forward, or switches the
+    /// pane to it from another forward.
+    fn toggle_pod_logs(&mut self) {
+        let Some(key) = self.selected_forward().map(|st| st.resource.clone()) else {
+            return;
+        };
+        if self.pod_logs_forward.as_ref() == Some(&key) {
+            self.pod_logs_forward = None;
+        } else if crate::pod_logs::lines(&key).is_some() {
+            self.pod_logs_forward = Some(key);
+        } else {
+            crate::logger::log_warning(format!(
+                "{} {} does not tail pod logs; set \"tail_logs\": true on it",
+                "📜", key
+            ));
+        }
+    }
+
     // --- Search Methods ---
 
     fn enter_search_mode(&mut self) {
@@ -159,6 +830,8 @@ impl App {
         self.search_query.clear();
         self.search_results.clear();
         self.current_search_result_index = None;
+        self.search_pattern = None;
+        self.search_error = None;
     }
 
     fn exit_search_mode(&mut self) {
@@ -171,22 +844,65 @@ impl App {
         self.search_query.clear();
         self.search_results.clear();
         self.current_search_result_index = None;
+        self.search_pattern = None;
+        self.search_error = None;
+    }
+
+    fn toggle_search_case(&mut self) {
+        self.search_case_insensitive = !self.search_case_insensitive;
+        self.update_search_results();
+    }
+
+    fn toggle_search_regex(&mut self) {
+        self.search_regex = !self.search_regex;
+        self.update_search_results();
     }
 
     fn update_search_results(&mut self) {
         self.search_results.clear();
         self.current_search_result_index = None;
+        self.search_pattern = None;
+        self.search_error = None;
         if self.search_query.is_empty() {
             return;
         }
-        for (index, log_entry) in self.logs.iter().enumerate() {
-            // Simple case-sensitive search for now
-            if log_entry.message.contains(&self.search_query) {
-                self.search_results.push(index);
+
+        // Plain searches are compiled as escaped regexes so matching and
+        // highlighting share one code path
+        let pattern = if self.search_regex {
+            self.search_query.clone()
+        } else {
+            regex::escape(&self.search_query)
+        };
+        match RegexBuilder::new(&pattern)
+            .case_insensitive(self.search_case_insensitive)
+            .build()
+        {
+            Ok(regex) => self.search_pattern = Some(regex),
+            Err(e) => {
+                self.search_error = Some(e.to_string().lines().last().unwrap_or_default().to_string());
+                return;
+            }
+        }
+
+        if let Some(pattern) = &self.search_pattern {
+            for (index, log_entry) in self.logs.iter().enumerate() {
+                if pattern.is_match(&log_entry.message) {
+                    self.search_results.push(index);
+                }
             }
         }
     }
 
+    /// Short description of the active search toggles, e.g. `[Aa regex]`.
+    fn search_flags(&self) -> String {
+        format!(
+            "[{} {}]",
+            if self.search_case_insensitive { "aa" } else { "Aa" },
+            if self.search_regex { "regex" } else { "text" }
+        )
+    }
+
     fn jump_to_result(&mut self, result_index: usize, viewport_height: usize) {
         if self.search_results.is_empty() {
             self.current_search_result_index = None;
@@ -243,8 +959,9 @@ pub fn run_app(
     loop {
         // Calculate viewport height for scrolling/jumping logic BEFORE drawing
         let size = terminal.size()?;
-        // Assuming status panel (5), command panel (1), and borders (2) for logs panel
-        let log_viewport_height = size.height.saturating_sub(5 + 1 + 2);
+        // Status panel, command panel (1), and borders (2) for logs panel
+        let log_viewport_height =
+            size.height.saturating_sub(app.status_panel_height() + 1 + 2);
 
         terminal.draw(|f| ui(f, app))?;
 
@@ -253,8 +970,88 @@ pub fn run_app(
             .unwrap_or_else(|| Duration::from_secs(0));
 
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if app.search_mode {
+            let event = event::read()?;
+            // Pasted text (and IME commits some terminals deliver as a paste)
+            // goes into whichever text input is open
+            if let Event::Paste(text) = &event {
+                if let Some(form) = app.new_forward.as_mut() {
+                    push_input(form.input(), text);
+                } else if let Some(note) = app.note_input.as_mut() {
+                    push_input(note, text);
+                } else if app.search_mode {
+                    push_input(&mut app.search_query, text);
+                    app.update_search_results();
+                }
+            }
+            // Windows also reports key releases, which would type every character twice
+            let key = match event {
+                Event::Key(key) if key.kind != KeyEventKind::Release => Some(key),
+                _ => None,
+            };
+            if let Some(key) = key {
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    // Raw mode delivers Ctrl+C as a key press rather than a signal
+                    app.quit();
+                } else if let Some(prompt) = app.port_prompt.as_mut() {
+                    // --- Port Prompt Handling ---
+                    if let Some(context) = prompt.protected.take() {
+                        // Anything but `y` goes back to the list of ports
+                        if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                            crate::k8s::confirm_protected_context(&context);
+                            prompt.start(prompt.ports[app.port_choice].0);
+                            app.port_prompt = None;
+                        }
+                    } else {
+                        match key.code {
+                            KeyCode::Enter => {
+                                match crate::k8s::check_protected_context(prompt.context.as_deref()) {
+                                    Ok(()) => {
+                                        prompt.start(prompt.ports[app.port_choice].0);
+                                        app.port_prompt = None;
+                                    }
+                                    Err(e) => prompt.protected = Some(e.0),
+                                }
+                            }
+                            KeyCode::Esc => {
+                                crate::logger::log_info(format!("{} Not starting {}", "➖", prompt.resource));
+                                app.port_prompt = None;
+                            }
+                            KeyCode::Up => app.port_choice = app.port_choice.saturating_sub(1),
+                            KeyCode::Down => {
+                                app.port_choice = (app.port_choice + 1).min(prompt.ports.len() - 1)
+                            }
+                            _ => {}
+                        }
+                    }
+                } else if let Some(form) = app.new_forward.as_mut() {
+                    // --- New Forward Form Handling ---
+                    if let Some(context) = form.protected.take() {
+                        // Anything but `y` goes back to editing the form
+                        if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                            crate::k8s::confirm_protected_context(&context);
+                            app.submit_new_forward();
+                        }
+                    } else {
+                        match key.code {
+                            KeyCode::Enter => app.submit_new_forward(),
+                            KeyCode::Esc => app.new_forward = None,
+                            KeyCode::Tab | KeyCode::Down => form.next_field(),
+                            KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+                            KeyCode::Backspace => pop_grapheme(form.input()),
+                            KeyCode::Char(c) => form.input().push(c),
+                            _ => {}
+                        }
+                    }
+                } else if let Some(note) = app.note_input.as_mut() {
+                    // --- Note Input Handling ---
+                    match key.code {
+                        KeyCode::Enter => app.save_note(),
+                        KeyCode::Esc => app.note_input = None,
+                        KeyCode::Backspace => pop_grapheme(note),
+                        KeyCode::Char(c) => note.push(c),
+                        _ => {}
+                    }
+                } else if app.search_mode {
                     // --- Search Mode Input Handling ---
                     match key.code {
                         KeyCode::Enter => {
@@ -266,9 +1063,13 @@ pub fn run_app(
                             app.cancel_search();
                         }
                         KeyCode::Backspace => {
-                            app.search_query.pop();
+                            pop_grapheme(&mut app.search_query);
                             app.update_search_results();
                         }
+                        KeyCode::Tab => app.toggle_search_case(),
+                        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.toggle_search_regex();
+                        }
                         KeyCode::Char(c) => {
                             app.search_query.push(c);
                             app.update_search_results();
@@ -278,15 +1079,37 @@ pub fn run_app(
                 } else if app.awaiting_verbosity_input {
                     // --- Verbosity Input Handling ---
                     match key.code {
-                        KeyCode::Char(c) if c >= '0' && c <= '3' => {
+                        KeyCode::Char(c) if ('0'..='3').contains(&c) => {
                             let new_level = c.to_digit(10).unwrap() as u8;
                             app.awaiting_verbosity_input = false;
-                            crate::http::set_verbose(new_level);
-                            crate::logger::log_info(format!("Verbosity updated to {}", new_level));
+                            match app.verbosity_forward.take() {
+                                Some(key) => {
+                                    crate::http::set_forward_verbose(&key, Some(new_level));
+                                    crate::logger::log_info(format!(
+                                        "Verbosity for {} updated to {}",
+                                        key, new_level
+                                    ));
+                                }
+                                None => {
+                                    crate::http::set_verbose(new_level);
+                                    crate::logger::log_info(format!("Verbosity updated to {}", new_level));
+                                }
+                            }
+                        }
+                        KeyCode::Char('g') if app.verbosity_forward.is_some() => {
+                            app.awaiting_verbosity_input = false;
+                            if let Some(key) = app.verbosity_forward.take() {
+                                crate::http::set_forward_verbose(&key, None);
+                                crate::logger::log_info(format!(
+                                    "Verbosity for {} follows the global level again",
+                                    key
+                                ));
+                            }
                         }
                         KeyCode::Esc => {
                             // Allow Esc to cancel verbosity change
                             app.awaiting_verbosity_input = false;
+                            app.verbosity_forward = None;
                             crate::logger::log_info("Verbosity change cancelled".to_string());
                         }
                         _ => {
@@ -304,6 +1127,30 @@ pub fn run_app(
                             // Esc can also quit in normal mode
                             app.quit();
                         }
+                        KeyCode::Char('R') => app.toggle_inspector(),
+                        KeyCode::Tab => app.select_next_forward(),
+                        KeyCode::BackTab => app.select_previous_forward(),
+                        KeyCode::Enter if app.selected_context().is_some() => app.toggle_selected_context(),
+                        KeyCode::Enter => app.show_detail = !app.show_detail,
+                        KeyCode::Char('f') => app.status_focused = !app.status_focused,
+                        KeyCode::Up | KeyCode::Char('k') if app.status_focused => {
+                            app.select_previous_forward()
+                        }
+                        KeyCode::Down | KeyCode::Char('j') if app.status_focused => {
+                            app.select_next_forward()
+                        }
+                        KeyCode::Char('r') => app.restart_selected(),
+                        KeyCode::Char('s') => app.save_logs(),
+                        KeyCode::Char('o') => app.new_forward = Some(NewForwardForm::default()),
+                        KeyCode::Char('d') => app.remove_selected(),
+                        KeyCode::Char('i') if app.show_inspector => app.start_note_input(),
+                        KeyCode::Char('c') => app.copy_selected(),
+                        KeyCode::Up | KeyCode::Char('k') if app.show_inspector => {
+                            app.select_previous_request()
+                        }
+                        KeyCode::Down | KeyCode::Char('j') if app.show_inspector => {
+                            app.select_next_request()
+                        }
                         KeyCode::Up | KeyCode::Char('k') => app.scroll_up(),
                         KeyCode::Down | KeyCode::Char('j') => app.scroll_down(),
                         KeyCode::PageUp => {
@@ -315,11 +1162,21 @@ pub fn run_app(
                         KeyCode::Home => app.scroll_to_top(),
                         KeyCode::End => app.scroll_to_bottom(),
                         KeyCode::Char('a') => app.toggle_auto_scroll(),
+                        KeyCode::Char('L') => app.toggle_chaos(crate::chaos::Fault::Latency),
+                        KeyCode::Char('E') => app.toggle_chaos(crate::chaos::Fault::Errors),
+                        KeyCode::Char('D') => app.toggle_chaos(crate::chaos::Fault::Drops),
+                        KeyCode::Char('P') => app.toggle_pretty_print(),
+                        KeyCode::Char('T') => app.toggle_pod_logs(),
+                        KeyCode::Char('M') => app.toggle_metrics(),
+                        KeyCode::Char('p') => app.toggle_pause(),
                         KeyCode::Char('v') => {
+                            // Applies to the selected forward; `V` changes the global level
                             app.awaiting_verbosity_input = true;
-                            crate::logger::log_info(
-                                "Enter new verbosity level (0-3) or Esc to cancel:".to_string(),
-                            );
+                            app.verbosity_forward = app.selected_forward().map(|st| st.resource.clone());
+                        }
+                        KeyCode::Char('V') => {
+                            app.awaiting_verbosity_input = true;
+                            app.verbosity_forward = None;
                         }
                         KeyCode::Char('/') => {
                             app.enter_search_mode();
@@ -357,7 +1214,7 @@ fn ui(f: &mut Frame, app: &mut App) {
     f.render_widget(Clear, f.area());
     let area = f.area();
     let chunks = Layout::vertical([
-        Constraint::Length(5), // fixed height for Status panel
+        Constraint::Length(app.status_panel_height()),
         Constraint::Min(0),    // remaining area for Logs
         Constraint::Length(1), // fixed height for Command panel
     ])
@@ -366,58 +1223,721 @@ fn ui(f: &mut Frame, app: &mut App) {
     // Pass viewport height to render_logs_panel for highlighting logic if needed
     // (though jump logic now handles scroll calculation)
     let log_viewport_height = chunks[1].height.saturating_sub(2); // Account for borders
-    render_logs_panel(f, app, chunks[1], log_viewport_height);
+    let main_area = if app.show_detail {
+        let columns = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(chunks[1]);
+        render_detail_panel(f, app, columns[1]);
+        columns[0]
+    } else {
+        chunks[1]
+    };
+    let main_area = match app.pod_logs_forward.clone() {
+        Some(key) => {
+            let columns = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(main_area);
+            render_pod_logs_panel(f, &key, columns[1]);
+            columns[0]
+        }
+        None => main_area,
+    };
+    if app.show_metrics {
+        render_metrics_panel(f, app, main_area);
+    } else if app.show_inspector {
+        render_inspector_panel(f, app, main_area);
+    } else {
+        render_logs_panel(f, app, main_area, log_viewport_height);
+    }
     render_command_panel(f, app, chunks[2]);
+    if let Some(form) = &app.new_forward {
+        render_new_forward_form(f, form, area);
+    }
+    if let Some(prompt) = &app.port_prompt {
+        render_port_prompt(f, prompt, app.port_choice, area);
+    }
+}
+
+/// Draws the list of ports to pick from for a forward given without one.
+fn render_port_prompt(f: &mut Frame, prompt: &PortPrompt, choice: usize, area: Rect) {
+    let width = area.width.min(60);
+    let height = (prompt.ports.len() as u16 + 2 + prompt.protected.is_some() as u16).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let mut lines: Vec<Line> = prompt
+        .ports
+        .iter()
+        .enumerate()
+        .map(|(index, port)| {
+            let text = crate::k8s::describe_ports(std::slice::from_ref(port));
+            if index == choice {
+                Line::styled(format!("▶ {}", text), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+            } else {
+                Line::from(format!("  {}", text))
+            }
+        })
+        .collect();
+    if let Some(context) = &prompt.protected {
+        lines.push(protected_confirmation(context));
+    }
+    let block = Block::default()
+        .title(format!("Port of {} (↑/↓, Enter: start, Esc: cancel)", prompt.resource))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// Asks before starting a forward against a protected context.
+fn protected_confirmation(context: &str) -> Line<'static> {
+    Line::from(Span::styled(
+        format!("⚠ {} is a protected context. Start anyway? [y/N]", context),
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+    ))
+}
+
+/// Draws the `o` form as a box in the middle of the screen.
+fn render_new_forward_form(f: &mut Frame, form: &NewForwardForm, area: Rect) {
+    let width = area.width.min(72);
+    let height = 6.min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let label_width = 12;
+    let mut lines: Vec<Line> = NewForwardForm::LABELS
+        .iter()
+        .zip(form.values())
+        .zip(NewForwardForm::HINTS)
+        .enumerate()
+        .map(|(index, ((label, value), hint))| {
+            let label_style = if index == form.field {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            let value = if value.is_empty() && index != form.field {
+                Span::styled(hint, Style::default().fg(Color::DarkGray))
+            } else {
+                Span::raw(value.to_string())
+            };
+            Line::from(vec![
+                Span::styled(format!("{:<width$}", label, width = label_width), label_style),
+                value,
+            ])
+        })
+        .collect();
+    if let Some(context) = &form.protected {
+        lines.push(protected_confirmation(context));
+    } else if let Some(error) = &form.error {
+        lines.push(Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))));
+    }
+
+    let block = Block::default()
+        .title("New forward (Tab: next field, Enter: start, Esc: cancel)")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+    let value_width = form.values()[form.field].width() as u16;
+    f.set_cursor_position((
+        popup.x + 1 + label_width as u16 + value_width,
+        popup.y + 1 + form.field as u16,
+    ));
 }
 
 fn render_status_panel(f: &mut Frame, app: &mut App, area: Rect) {
     use ratatui::widgets::{Cell, Row, Table};
     let header = Row::new(vec![
         Cell::from("Resource"),
+        Cell::from("Namespace"),
         Cell::from("Local Port"),
+        Cell::from("Pod"),
         Cell::from("Status"),
         Cell::from("Last Probe"),
+        Cell::from("Traffic"),
     ])
     .style(Style::default().bg(Color::Blue).fg(Color::White))
     .bottom_margin(0);
+    let grouped = matches!(app.status_rows.first(), Some(StatusRow::Context(_)));
     let rows: Vec<Row> = app
+        .status_rows
+        .iter()
+        .map(|row| match row {
+            StatusRow::Context(context) => context_row(app, context),
+            StatusRow::Forward(i) => forward_row(&app.forward_statuses[*i], grouped),
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        &[
+            Constraint::Percentage(18),
+            Constraint::Percentage(9),
+            Constraint::Percentage(6),
+            Constraint::Percentage(16),
+            Constraint::Percentage(22),
+            Constraint::Percentage(11),
+            Constraint::Percentage(18),
+        ],
+    )
+    .header(header)
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .block(
+        Block::default()
+            .title(if app.status_focused { "Status (↑/↓ select, f: back to logs)" } else { "Status" })
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(if app.status_focused {
+                Color::Yellow
+            } else {
+                Color::Magenta
+            })),
+    );
+    f.render_stateful_widget(table, area, &mut app.status_state);
+}
+
+/// A forward's row, indented under its context heading when `grouped`.
+fn forward_row(st: &ForwardStatus, grouped: bool) -> ratatui::widgets::Row<'static> {
+    use ratatui::widgets::{Cell, Row};
+    let status = st.state.label();
+    let mut notes = Vec::new();
+    if st.restarting {
+        notes.push(format!("restart #{}", st.restarts));
+    }
+    match &st.retry {
+        Some(retry) => notes.push(retry.describe()),
+        None if !st.waiting_for.is_empty() => {
+            notes.push(format!("waiting for {}", st.waiting_for.join(", ")))
+        }
+        None => {}
+    }
+    let status = std::iter::once(status).chain(notes).collect::<Vec<_>>().join(" · ");
+    let resource = format!(
+        "{}{}{}",
+        if grouped { "  " } else { "" },
+        if st.protected { "⚠ " } else { "" },
+        st.resource
+    );
+    let row = Row::new(vec![
+        Cell::from(resource),
+        Cell::from(st.namespace.clone()),
+        Cell::from(st.local_port.to_string()),
+        pod_cell(st),
+        Cell::from(status),
+        Cell::from(st.last_probe.clone().unwrap_or_else(|| "N/A".to_string())),
+        Cell::from(traffic_summary(&st.resource)),
+    ]);
+    if st.protected {
+        row.style(
+            Style::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        row
+    }
+}
+
+/// The pod a forward lands on, marked for a while after it moved to another.
+fn pod_cell(st: &ForwardStatus) -> ratatui::widgets::Cell<'static> {
+    use ratatui::widgets::Cell;
+    let Some(pod) = &st.target_pod else {
+        return Cell::from("-");
+    };
+    match st.pod_changes.last() {
+        Some(change) if chrono::Utc::now() - change.at < chrono::Duration::minutes(RECENT_POD_CHANGE_MINUTES) => {
+            Cell::from(format!("🔀 {}", pod)).style(Style::default().fg(Color::Yellow))
+        }
+        _ => Cell::from(pod.clone()),
+    }
+}
+
+/// Heading of a context's forwards: whether they are collapsed, how many of
+/// them are connected or failing, and their combined request rate.
+fn context_row(app: &App, context: &str) -> ratatui::widgets::Row<'static> {
+    use crate::forwarder::ForwardState;
+    use ratatui::widgets::{Cell, Row};
+    let forwards: Vec<&ForwardStatus> = app
         .forward_statuses
         .iter()
-        .map(|st| {
-            let status_string = st.state.to_string();
-            let status = match status_string.as_str() {
-                "INITIALIZING" => "🔄 INITIALIZING",
-                "OPEN" => "☕ OPEN",
-                "ACTIVE" => "🚀 ACTIVE",
-                "UNAVAILABLE" => "🚫 UNAVAILABLE",
-                _ => status_string.as_str(),
+        .filter(|st| st.context == context)
+        .collect();
+    let connected = forwards
+        .iter()
+        .filter(|st| matches!(st.state, ForwardState::Open | ForwardState::Active))
+        .count();
+    let failing = forwards
+        .iter()
+        .filter(|st| st.state == ForwardState::Unavailable || st.retry.is_some())
+        .count();
+    let health = if connected == forwards.len() {
+        Color::Green
+    } else if connected == 0 && failing > 0 {
+        Color::Red
+    } else {
+        Color::Yellow
+    };
+    let mut summary = format!("● {}/{} connected", connected, forwards.len());
+    if failing > 0 {
+        summary.push_str(&format!(" · {} failing", failing));
+    }
+    let requests_per_sec: f64 = forwards
+        .iter()
+        .map(|st| crate::metrics::snapshot(&st.resource).requests_per_sec)
+        .sum();
+
+    let protected = forwards.iter().any(|st| st.protected);
+    let heading = format!(
+        "{} {}{} ({})",
+        if app.collapsed_contexts.contains(context) { "▸" } else { "▾" },
+        if protected { "⚠ " } else { "" },
+        if context.is_empty() { "(no context)" } else { context },
+        forwards.len()
+    );
+    Row::new(vec![
+        Cell::from(heading),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(summary).style(Style::default().fg(health)),
+        Cell::from(""),
+        Cell::from(format!("{:.1}/s", requests_per_sec)),
+    ])
+    .style(
+        Style::default()
+            .fg(if protected { Color::Red } else { Color::Cyan })
+            .add_modifier(Modifier::BOLD),
+    )
+}
+
+/// Seconds of requests shown in a status row's sparkline.
+const SPARKLINE_SECS: u64 = 10;
+
+/// Requests per second over the last seconds as a sparkline, followed by the
+/// request and byte rates, e.g. `▁▁▃█▅▂▁▁▁▁ 2.4/s 18.2 KB/s`.
+fn traffic_summary(resource: &str) -> String {
+    let history = crate::metrics::traffic_history(resource, SPARKLINE_SECS);
+    let requests: Vec<u64> = history.iter().map(|sample| sample.requests).collect();
+    let metrics = crate::metrics::snapshot(resource);
+    format!(
+        "{} {:.1}/s {}/s",
+        sparkline(&requests),
+        metrics.requests_per_sec,
+        format_bytes(metrics.bytes_per_sec)
+    )
+}
+
+fn sparkline(values: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|value| match max {
+            0 => BARS[0],
+            _ => BARS[(value * 7).div_ceil(max) as usize],
+        })
+        .collect()
+}
+
+fn format_bytes(bytes: f64) -> String {
+    match bytes {
+        b if b >= 1024.0 * 1024.0 => format!("{:.1} MB", b / (1024.0 * 1024.0)),
+        b if b >= 1024.0 => format!("{:.1} KB", b / 1024.0),
+        b => format!("{:.0} B", b),
+    }
+}
+
+fn render_detail_panel(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .title("Forward Detail (Enter: close)")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta));
+    let Some(st) = app.selected_forward() else {
+        f.render_widget(Paragraph::new("No forward selected").block(block), area);
+        return;
+    };
+
+    let label = Style::default().fg(Color::DarkGray);
+    let field = |name: &str, value: String| {
+        Line::from(vec![Span::styled(format!("{:<13}", name), label), Span::raw(value)])
+    };
+    let metrics = crate::metrics::snapshot(&st.resource);
+
+    let mut lines = vec![
+        field("Resource", st.resource.clone()),
+        field("Namespace", st.namespace.clone()),
+        field("Context", if st.context.is_empty() { "-".to_string() } else { st.context.clone() }),
+        field("Local port", st.local_port.to_string()),
+        field(
+            "Verbosity",
+            match crate::http::forward_verbose(&st.resource) {
+                Some(level) => format!("{} (override)", level),
+                None => format!("{} (global)", crate::http::verbose()),
+            },
+        ),
+        field(
+            "State",
+            match &st.retry {
+                Some(retry) => format!("{} ({})", st.state, retry.describe()),
+                None if !st.waiting_for.is_empty() => {
+                    format!("{} (waiting for {})", st.state, st.waiting_for.join(", "))
+                }
+                None => st.state.to_string(),
+            },
+        ),
+        field(
+            "Target pod",
+            match (&st.target_pod, &st.target_node) {
+                (Some(pod), Some(node)) => format!("{} (node {})", pod, node),
+                (Some(pod), None) => pod.clone(),
+                _ => "unknown".to_string(),
+            },
+        ),
+        field("Requests/sec", format!("{:.1}", metrics.requests_per_sec)),
+        field("Throughput", format!("{}/s", format_bytes(metrics.bytes_per_sec))),
+        field(
+            "Error rate",
+            format!(
+                "{:.1}% ({} of {})",
+                metrics.error_rate() * 100.0,
+                metrics.error_responses,
+                metrics.total_requests
+            ),
+        ),
+    ];
+    let mut latency = vec![Span::styled(format!("{:<13}", "Latency"), label)];
+    match (metrics.latency_p50_ms, metrics.latency_p95_ms, metrics.latency_p99_ms) {
+        (Some(p50), Some(p95), Some(p99)) => {
+            for (name, ms) in [("p50", p50), ("p95", p95), ("p99", p99)] {
+                latency.push(Span::raw(format!("{} ", name)));
+                latency.push(Span::styled(format!("{}ms  ", ms), latency_style(ms as u128)));
             }
-            .to_string();
+        }
+        _ => latency.push(Span::raw("no requests yet")),
+    }
+    lines.push(Line::from(latency));
+    if let Some(chaos) = crate::chaos::active(&st.resource) {
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<13}", "Chaos"), label),
+            Span::styled(crate::chaos::describe(&chaos), Style::default().fg(Color::Yellow)),
+        ]));
+    }
+    if let Some(queue) = crate::reconnect::queue_position(&st.resource) {
+        lines.push(field(
+            "Reconnect",
+            format!(
+                "queued {} of {} (in {:.1}s)",
+                queue.position,
+                queue.queued,
+                queue.remaining.as_secs_f64()
+            ),
+        ));
+    }
+    if !st.synthetic.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::styled("Synthetic checks", label));
+        for result in &st.synthetic {
+            let (outcome, color) = match (result.status, &result.error) {
+                (Some(status), _) if result.is_ok() => (status.to_string(), Color::Green),
+                (Some(status), _) => (status.to_string(), Color::Red),
+                (None, error) => (error.clone().unwrap_or_default(), Color::Red),
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {} ", outcome), Style::default().fg(color)),
+                Span::raw(format!(
+                    "{:>6}ms {} {} @ {}",
+                    result.latency_ms,
+                    result.method,
+                    result.path,
+                    result.at.with_timezone(&chrono::Local).format("%H:%M:%S")
+                )),
+            ]));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(field("Restarts", st.restarts.to_string()));
+    if let Some(entries) = crate::cache::len(&st.resource) {
+        let hits = crate::metrics::snapshot(&st.resource).cache_hits;
+        lines.push(field("Cache", format!("{} entries, {} hits", entries, hits)));
+    }
+    if let Some(usage) = crate::limit::usage(&st.resource) {
+        lines.push(field(
+            "In flight",
+            format!("{}/{}, {} queued", usage.in_flight, usage.max_in_flight, usage.queued),
+        ));
+    }
+    if !st.pod_changes.is_empty() {
+        lines.push(Line::styled(format!("Pod changes ({})", st.pod_changes.len()), label));
+        lines.extend(st.pod_changes.iter().rev().map(|change| {
+            Line::from(format!(
+                "  {} {} → {}",
+                change.at.with_timezone(&chrono::Local).format("%H:%M:%S"),
+                change.from,
+                change.to
+            ))
+        }));
+    }
+    if !st.transitions.is_empty() {
+        lines.push(Line::styled(format!("State changes ({})", st.transitions.len()), label));
+        lines.extend(st.transitions.iter().rev().map(|change| {
+            Line::from(format!(
+                "  {} {} → {}",
+                change.at.with_timezone(&chrono::Local).format("%H:%M:%S"),
+                change.from,
+                change.to
+            ))
+        }));
+    }
+    lines.push(Line::styled(format!("Reconnects ({})", st.reconnects.len()), label));
+    lines.extend(st.reconnects.iter().rev().map(|at| {
+        Line::from(format!("  {}", at.with_timezone(&chrono::Local).format("%H:%M:%S")))
+    }));
+
+    lines.push(Line::from(""));
+    lines.push(Line::styled("Last requests", label));
+    let recent = crate::capture::snapshot_forward(&st.resource);
+    for request in recent.iter().rev().take(20) {
+        let color = match request.status {
+            200..=399 => Color::Green,
+            400..=499 => Color::Yellow,
+            _ => Color::Red,
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {} ", cache_marked(request)), Style::default().fg(color)),
+            Span::styled(format!("{:>6}ms ", request.latency_ms), latency_style(request.latency_ms)),
+            Span::raw(format!("{} {}", request.method, request.path)),
+        ]));
+    }
+
+    f.render_widget(
+        Paragraph::new(lines).block(block).wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+/// A request's status, with 💾 when it was answered from the forward's cache.
+fn cache_marked(request: &crate::capture::CapturedRequest) -> String {
+    if request.cached {
+        format!("{} 💾", request.status)
+    } else {
+        request.status.to_string()
+    }
+}
+
+/// Highlights latencies over `--slow-threshold-ms`.
+fn latency_style(latency_ms: u128) -> Style {
+    match crate::http::slow_threshold() {
+        Some(threshold) if latency_ms > threshold as u128 => Style::default().fg(SLOW_COLOR),
+        _ => Style::default(),
+    }
+}
+
+/// Latency histogram and responses per second by status class of the
+/// selected forward.
+fn render_metrics_panel(f: &mut Frame, app: &App, area: Rect) {
+    use crate::metrics::LATENCY_BUCKETS_MS;
+    use ratatui::widgets::{Bar, BarChart, BarGroup};
+    let Some(st) = app.selected_forward() else {
+        let block = Block::default()
+            .title("Metrics (M: close)")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Magenta));
+        f.render_widget(Paragraph::new("No forward selected").block(block), area);
+        return;
+    };
+    let rows = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).split(area);
+
+    let histogram = crate::metrics::latency_histogram(&st.resource);
+    let responses: u64 = histogram.iter().sum();
+    let labels = LATENCY_BUCKETS_MS
+        .iter()
+        .map(|&bound| format!("≤{}", format_latency(bound)))
+        .chain(std::iter::once(format!(">{}", format_latency(LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1]))));
+    let bars: Vec<Bar> = histogram
+        .iter()
+        .zip(labels)
+        .enumerate()
+        .map(|(i, (&count, label))| {
+            let slow = crate::http::slow_threshold()
+                .is_some_and(|threshold| i > 0 && LATENCY_BUCKETS_MS[i - 1] >= threshold);
+            Bar::default()
+                .value(count)
+                .label(Line::from(label))
+                .style(Style::default().fg(if slow { SLOW_COLOR } else { Color::Cyan }))
+        })
+        .collect();
+    let inner_width = rows[0].width.saturating_sub(2);
+    let bar_width = (inner_width / bars.len() as u16).saturating_sub(1).max(3);
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .title(format!(
+                    "Latency of the last {} responses: {} (M: close)",
+                    responses, st.resource
+                ))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Magenta)),
+        )
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(bar_width)
+        .bar_gap(1);
+    f.render_widget(chart, rows[0]);
+
+    render_status_bars(f, &st.resource, rows[1]);
+}
+
+/// e.g. `250ms`, `2.5s`
+fn format_latency(ms: u64) -> String {
+    match ms {
+        ms if ms >= 1000 => format!("{}s", ms as f64 / 1000.0),
+        ms => format!("{}ms", ms),
+    }
+}
+
+/// Responses per second over the kept traffic history, one column per second
+/// (newest right), stacked by status class.
+fn render_status_bars(f: &mut Frame, resource: &str, area: Rect) {
+    const CLASSES: [(&str, Color); 4] = [
+        ("2xx", Color::Green),
+        ("3xx", Color::Cyan),
+        ("4xx", Color::Yellow),
+        ("5xx", Color::Red),
+    ];
+    let width = area.width.saturating_sub(2) as u64;
+    let height = area.height.saturating_sub(3) as usize;
+    let history = crate::metrics::traffic_history(resource, width.min(crate::metrics::TRAFFIC_HISTORY_SECS));
+    let max = history.iter().map(|sample| sample.requests).max().unwrap_or(0);
+
+    let mut lines: Vec<Line> = Vec::new();
+    if max > 0 {
+        let scale = height as f64 / max as f64;
+        for row in (0..height).rev() {
+            let level = row as f64 + 0.5;
+            // Right-aligned while the history is shorter than the panel
+            let padding = Span::raw(" ".repeat((width as usize).saturating_sub(history.len())));
+            let spans: Vec<Span> = std::iter::once(padding)
+                .chain(history.iter().map(|sample| {
+                    let mut top = 0.0;
+                    for (count, (_, color)) in sample.statuses.iter().zip(CLASSES) {
+                        let bottom = top;
+                        top += *count as f64 * scale;
+                        if (bottom..top).contains(&level) {
+                            return Span::styled("█", Style::default().fg(color));
+                        }
+                    }
+                    Span::raw(" ")
+                }))
+                .collect();
+            lines.push(Line::from(spans));
+        }
+    } else {
+        lines.extend((0..height.saturating_sub(1)).map(|_| Line::from("")));
+        lines.push(Line::styled("no requests yet", Style::default().fg(Color::DarkGray)));
+    }
+    let mut legend: Vec<Span> = Vec::new();
+    for (name, color) in CLASSES {
+        legend.push(Span::styled("█ ", Style::default().fg(color)));
+        legend.push(Span::raw(format!("{}  ", name)));
+    }
+    lines.push(Line::from(legend));
+
+    let block = Block::default()
+        .title(format!(
+            "Responses/s by status, last {}s (peak {}/s)",
+            history.len(),
+            max
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta));
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// The newest container output of a forward's target pod, with a heading
+/// wherever the forward moved to another pod.
+fn render_pod_logs_panel(f: &mut Frame, key: &str, area: Rect) {
+    let lines = crate::pod_logs::lines(key).unwrap_or_default();
+    let current = lines.last().map(|line| line.pod.as_str()).unwrap_or("waiting for a pod");
+    let block = Block::default()
+        .title(format!("Pod logs: {} · {} (T: close)", key, current))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let height = area.height.saturating_sub(2) as usize;
+    let mut rendered: Vec<Line> = Vec::new();
+    let mut pod: Option<&str> = None;
+    for line in &lines {
+        if pod.is_some_and(|pod| pod != line.pod) {
+            rendered.push(Line::styled(
+                format!("── {} ──", line.pod),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        pod = Some(&line.pod);
+        rendered.push(Line::raw(line.line.clone()));
+    }
+    let skip = rendered.len().saturating_sub(height);
+    f.render_widget(
+        Paragraph::new(rendered.split_off(skip)).block(block),
+        area,
+    );
+}
+
+fn render_inspector_panel(f: &mut Frame, app: &mut App, area: Rect) {
+    use ratatui::widgets::{Cell, Row, Table};
+    let header = Row::new(vec![
+        Cell::from("Time"),
+        Cell::from("Resource"),
+        Cell::from("Request"),
+        Cell::from("Status"),
+        Cell::from("Latency"),
+        Cell::from("Note"),
+    ])
+    .style(Style::default().bg(Color::Blue).fg(Color::White));
+    let rows: Vec<Row> = app
+        .captured_requests
+        .iter()
+        .map(|request| {
+            let color = match request.status {
+                200..=399 => Color::Green,
+                400..=499 => Color::Yellow,
+                _ => Color::Red,
+            };
             Row::new(vec![
-                Cell::from(st.resource.clone()),
-                Cell::from(st.local_port.to_string()),
-                Cell::from(status),
-                Cell::from(st.last_probe.clone().unwrap_or_else(|| "N/A".to_string())),
+                Cell::from(request.timestamp.format("%H:%M:%S").to_string()),
+                Cell::from(request.resource.clone()),
+                Cell::from(format!("{} {}", request.method, request.path)),
+                Cell::from(cache_marked(request)).style(Style::default().fg(color)),
+                Cell::from(format!("{}ms", request.latency_ms))
+                    .style(latency_style(request.latency_ms)),
+                Cell::from(request.note.clone().unwrap_or_default())
+                    .style(Style::default().fg(Color::Magenta)),
             ])
         })
         .collect();
     let table = Table::new(
         rows,
         &[
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
+            Constraint::Length(8),
+            Constraint::Percentage(20),
+            Constraint::Percentage(35),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Percentage(30),
         ],
     )
     .header(header)
+    .row_highlight_style(Style::default().bg(Color::DarkGray))
     .block(
         Block::default()
-            .title("Status")
+            .title("Requests (i: annotate, c: copy as curl, R: back to logs)")
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Magenta)),
+            .border_style(Style::default().fg(Color::Cyan)),
     );
-    f.render_widget(table, area);
+    f.render_stateful_widget(table, area, &mut app.inspector_state);
 }
 
 fn render_logs_panel(f: &mut Frame, app: &mut App, area: Rect, _viewport_height: u16) {
@@ -442,6 +1962,7 @@ fn render_logs_panel(f: &mut Frame, app: &mut App, area: Rect, _viewport_height:
             LogLevel::Info => Color::Cyan,
             LogLevel::Success => Color::Green,
             LogLevel::Warning => Color::Yellow,
+            LogLevel::Slow => SLOW_COLOR,
             LogLevel::Error => Color::Red,
         };
         let base_style = Style::default().fg(color);
@@ -457,22 +1978,26 @@ fn render_logs_panel(f: &mut Frame, app: &mut App, area: Rect, _viewport_height:
             app: &App,
         ) -> Vec<Span<'a>> {
             let mut spans = Vec::new();
-            if app.search_query.is_empty() || !line_content.contains(&app.search_query) {
-                // No search query or no match in this line, return single span with owned string.
-                spans.push(Span::styled(line_content.to_string(), style));
-            } else {
-                // Highlight matches
+            let pattern = app
+                .search_pattern
+                .as_ref()
+                .filter(|pattern| pattern.is_match(line_content));
+            if let Some(pattern) = pattern {
+                // Highlight matches, skipping empty ones a regex like `a*` can produce
                 let mut last_index = 0;
-                for (start, part) in line_content.match_indices(&app.search_query) {
-                    if start > last_index {
-                        spans.push(Span::styled(&line_content[last_index..start], style));
+                for m in pattern.find_iter(line_content).filter(|m| !m.is_empty()) {
+                    if m.start() > last_index {
+                        spans.push(Span::styled(&line_content[last_index..m.start()], style));
                     }
-                    spans.push(Span::styled(part, highlight_style));
-                    last_index = start + part.len();
+                    spans.push(Span::styled(m.as_str(), highlight_style));
+                    last_index = m.end();
                 }
                 if last_index < line_content.len() {
                     spans.push(Span::styled(&line_content[last_index..], style));
                 }
+            } else {
+                // No search pattern or no match in this line, return single span with owned string.
+                spans.push(Span::styled(line_content.to_string(), style));
             }
             spans
         }
@@ -567,30 +2092,48 @@ fn render_logs_panel(f: &mut Frame, app: &mut App, area: Rect, _viewport_height:
 }
 
 fn render_command_panel(f: &mut Frame, app: &mut App, area: Rect) {
-    let command_text = if app.search_mode {
-        // Display search prompt
-        format!("/{}", app.search_query)
+    let command_text = if let Some(note) = &app.note_input {
+        format!("Note: {}", note)
+    } else if app.search_mode {
+        // Display search prompt with the active toggles and any pattern error
+        match &app.search_error {
+            Some(error) => format!("/{} {} | Invalid regex: {}", app.search_query, app.search_flags(), error),
+            None => format!(
+                "/{} {} | Case: Tab | Regex: Ctrl+R",
+                app.search_query,
+                app.search_flags()
+            ),
+        }
     } else if app.awaiting_verbosity_input {
         // Display verbosity prompt
-        "Enter verbosity (0-3) or Esc:".to_string()
+        match &app.verbosity_forward {
+            Some(key) => format!(
+                "Verbosity for {} (0-3, g: use global {}) or Esc:",
+                key,
+                crate::http::verbose()
+            ),
+            None => format!("Global verbosity (0-3, now {}) or Esc:", crate::http::verbose()),
+        }
     } else if !app.search_query.is_empty() && !app.search_results.is_empty() {
         // Display search status if there are results
         let current_num = app.current_search_result_index.map_or(0, |i| i + 1);
         format!(
-            "Search '{}': {}/{} | Quit: q | Verbosity: v | Auto-scroll: a | Search: / | Next: n | Prev: N",
+            "Search '{}' {}: {}/{} | Quit: q | Verbosity: v/V | Auto-scroll: a | Search: / | Next: n | Prev: N",
             app.search_query,
+            app.search_flags(),
             current_num,
             app.search_results.len()
         )
     } else if !app.search_query.is_empty() {
         // Display search status if query exists but no results
         format!(
-            "Search '{}': Not found | Quit: q | Verbosity: v | Auto-scroll: a | Search: /",
-            app.search_query
+            "Search '{}' {}: Not found | Quit: q | Verbosity: v/V | Auto-scroll: a | Search: /",
+            app.search_query,
+            app.search_flags()
         )
     } else {
         // Default commands
-        "Quit: q | Verbosity: v | Auto-scroll: a | Search: / | Scroll: ↑/↓/PgUp/PgDn/Home/End"
+        "Quit: q | Verbosity: v/V | Auto-scroll: a | Search: / | Requests: R | Metrics: M | Select: Tab or f+↑/↓ | Detail: Enter | Pause: p | Restart: r | Add: o | Remove: d | Save logs: s | Copy: c | Chaos: L/E/D | Pretty: P | Pod logs: T | Scroll: ↑/↓/PgUp/PgDn/Home/End"
             .to_string()
     };
 
@@ -601,17 +2144,46 @@ fn render_command_panel(f: &mut Frame, app: &mut App, area: Rect) {
     .block(Block::default().style(Style::default().bg(Color::Blue)))
     .alignment(Alignment::Left);
 
-    // Render cursor in search mode
+    // Render cursor in search mode. Wide characters (CJK, most emoji) take two columns.
     if app.search_mode {
-        f.set_cursor_position((area.x + 1 + app.search_query.chars().count() as u16, area.y))
+        f.set_cursor_position((area.x + 1 + app.search_query.width() as u16, area.y))
+    } else if let Some(note) = &app.note_input {
+        f.set_cursor_position((area.x + 6 + note.width() as u16, area.y))
     }
     f.render_widget(paragraph, area);
 }
 
+/// Appends typed or pasted text to a single-line input, dropping newlines and
+/// other control characters.
+fn push_input(buffer: &mut String, text: &str) {
+    let text = text.replace(['\r', '\n', '\t'], " ");
+    buffer.extend(text.chars().filter(|c| !c.is_control()));
+}
+
+/// Deletes the last user-perceived character, so an accented letter or an
+/// emoji made of several code points goes away with one Backspace.
+fn pop_grapheme(buffer: &mut String) {
+    if let Some((index, _)) = buffer.grapheme_indices(true).next_back() {
+        buffer.truncate(index);
+    }
+}
+
 pub fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    // A panic must not leave the shell in raw mode on the alternate screen
+    let panic_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableBracketedPaste
+        );
+        panic_hook(info);
+    }));
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout);
     let terminal = Terminal::new(backend)?;
     Ok(terminal)
@@ -622,7 +2194,8 @@ pub fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
     Ok(())
@@ -631,13 +2204,3 @@ pub fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -
 pub fn create_log_channel() -> (mpsc::Sender<LogEntry>, mpsc::Receiver<LogEntry>) {
     mpsc::channel()
 }
-
-pub fn spawn_log_collector(_log_sender: mpsc::Sender<LogEntry>) -> thread::JoinHandle<()> {
-    thread::spawn(move || {
-        // This thread will run until the program exits
-        loop {
-            // Sleep to avoid busy waiting
-            thread::sleep(Duration::from_millis(100));
-        }
-    })
-}
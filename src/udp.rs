@@ -0,0 +1,236 @@
+use crate::config::ForwardConfig;
+use crate::forwarder::{set_state, ForwardCommand, ForwardState, FORWARD_STATUSES};
+use crate::k8s::{parse_resource, resolve_container_port, TargetPod};
+use anyhow::{Context, Result};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UdpSocket;
+use tokio::process::{Child, ChildStdin};
+use tokio::sync::mpsc;
+
+/// Relay sessions without traffic for this long are torn down.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+/// One local UDP peer, relayed through its own `kubectl exec` + `socat` process.
+struct UdpSession {
+    child: Child,
+    stdin: ChildStdin,
+    last_activity: Instant,
+}
+
+/// Forwards UDP datagrams from `127.0.0.1:<local_port>` to the target pod.
+///
+/// kubectl port-forward only carries TCP, so every local peer gets a
+/// `kubectl exec -i <pod> -- socat STDIO UDP:127.0.0.1:<port>` process that
+/// turns stdin chunks into datagrams inside the pod's network namespace and
+/// writes replies back to stdout. The target container must ship `socat`.
+pub async fn start_udp_forward(
+    forward: ForwardConfig,
+    mut commands: mpsc::UnboundedReceiver<ForwardCommand>,
+) -> Result<()> {
+    let (resource_type, resource_name, resource_port) = parse_resource(&forward.resource)?;
+    let key = crate::forwarder::forward_key(&forward)?;
+    let namespace = forward.namespace.clone().unwrap_or_else(|| "default".to_string());
+    let local_port = forward.local_port().unwrap_or(resource_port);
+
+    let socket = Arc::new(
+        UdpSocket::bind(("127.0.0.1", local_port))
+            .await
+            .context(format!("Failed to bind UDP port {}", local_port))?,
+    );
+    crate::logger::log_info(format!(
+        "{} UDP relay listening on 127.0.0.1:{} for {}",
+        "📨", local_port, key
+    ));
+
+    let resolve = || {
+        resolve_container_port(
+            &resource_type,
+            &resource_name,
+            resource_port,
+            "udp",
+            forward.selector.as_deref(),
+            &namespace,
+            forward.context.as_deref(),
+        )
+    };
+    let mut target = resolve().await?;
+    mark_open(&key, &target.0);
+    let events_handle = crate::events::spawn(crate::events::EventTarget {
+        key: key.clone(),
+        resource_type: resource_type.clone(),
+        resource_name: resource_name.clone(),
+        namespace: namespace.clone(),
+        context: forward.context.clone(),
+    });
+    let pod_logs_handle = (forward.tail_logs == Some(true))
+        .then(|| crate::pod_logs::spawn(key.clone(), namespace.clone(), forward.context.clone()));
+
+    let mut sessions: HashMap<SocketAddr, UdpSession> = HashMap::new();
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    let mut cleanup = tokio::time::interval(Duration::from_secs(10));
+    // Datagrams received while paused are dropped
+    let mut paused = false;
+
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        crate::logger::log_error(format!("UDP receive failed on {}: {}", key, e));
+                        continue;
+                    }
+                };
+                if paused {
+                    continue;
+                }
+
+                let session = match sessions.entry(peer) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        match spawn_session(&target.0.name, target.1, &namespace, forward.context.as_deref(), peer, socket.clone()).await {
+                            Ok(session) => {
+                                crate::logger::log_info(format!(
+                                    "{} New UDP session {} → {}:{}",
+                                    "📨", peer, target.0.name, target.1
+                                ));
+                                entry.insert(session)
+                            }
+                            Err(e) => {
+                                crate::logger::log_error(format!("Failed to start UDP relay for {}: {}", key, e));
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                session.last_activity = Instant::now();
+                if let Err(e) = session.stdin.write_all(&buf[..len]).await {
+                    crate::logger::log_warning(format!("UDP session {} closed: {}", peer, e));
+                    sessions.remove(&peer);
+                }
+            }
+            _ = cleanup.tick() => {
+                sessions.retain(|_, session| {
+                    session.last_activity.elapsed() < SESSION_IDLE_TIMEOUT
+                        && matches!(session.child.try_wait(), Ok(None))
+                });
+            }
+            command = commands.recv() => {
+                if command == Some(ForwardCommand::Pause) && paused {
+                    continue;
+                }
+                if command == Some(ForwardCommand::Resume) && !paused {
+                    continue;
+                }
+                // UDP sessions already time out on their own
+                if command == Some(ForwardCommand::Idle) {
+                    continue;
+                }
+                sessions.clear();
+                match command {
+                    Some(ForwardCommand::Pause) => {
+                        crate::logger::log_warning(format!("{} Paused {}", "⏸", key));
+                        set_state(&key, ForwardState::Paused);
+                        paused = true;
+                    }
+                    Some(command @ (ForwardCommand::Restart | ForwardCommand::Resume)) => {
+                        paused = false;
+                        crate::logger::log_warning(format!("{} Restarting {}", "🔁", key));
+                        if command == ForwardCommand::Restart {
+                            crate::forwarder::mark_restart(&key);
+                        }
+                        match resolve().await {
+                            Ok(resolved) => {
+                                target = resolved;
+                                mark_open(&key, &target.0);
+                            }
+                            Err(e) => {
+                                crate::logger::log_error(format!("Failed to resolve {}: {}", key, e));
+                                set_state(&key, ForwardState::Unavailable);
+                            }
+                        }
+                    }
+                    Some(ForwardCommand::Idle) => unreachable!(),
+                    Some(ForwardCommand::Stop) | None => break,
+                }
+            }
+        }
+    }
+
+    crate::logger::log_warning(format!("{} Shutting down {}...", "🛑", key));
+    set_state(&key, ForwardState::Stopped);
+    if let Some(events_handle) = events_handle {
+        events_handle.abort();
+    }
+    if let Some(pod_logs_handle) = pod_logs_handle {
+        pod_logs_handle.abort();
+    }
+    Ok(())
+}
+
+async fn spawn_session(
+    pod: &str,
+    port: u16,
+    namespace: &str,
+    context: Option<&str>,
+    peer: SocketAddr,
+    socket: Arc<UdpSocket>,
+) -> Result<UdpSession> {
+    let mut cmd = crate::k8s::kubectl(context).await?;
+    cmd.arg("exec").arg("-i").arg("-n").arg(namespace);
+    cmd.arg(pod)
+        .arg("--")
+        .arg("socat")
+        .arg("STDIO")
+        .arg(format!("UDP:127.0.0.1:{}", port))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    let mut child = cmd.spawn().context("Failed to start kubectl exec")?;
+    crate::process::adopt_child(&child);
+    let stdin = child.stdin.take().context("kubectl exec has no stdin")?;
+    let mut stdout = child.stdout.take().context("kubectl exec has no stdout")?;
+
+    // Relay replies from the pod back to the local peer
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        while let Ok(len) = stdout.read(&mut buf).await {
+            if len == 0 || socket.send_to(&buf[..len], peer).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(UdpSession {
+        child,
+        stdin,
+        last_activity: Instant::now(),
+    })
+}
+
+fn mark_open(key: &str, pod: &TargetPod) {
+    let landed = {
+        let mut statuses = FORWARD_STATUSES.lock().unwrap();
+        let Some(entry) = statuses.get_mut(key) else {
+            return;
+        };
+        if crate::forwarder::transition(entry, ForwardState::Open) {
+            crate::output::forward_ready(key, entry.local_port, Some(&pod.name));
+        }
+        entry.connected_at = Some(chrono::Utc::now());
+        crate::forwarder::land_on_pod(entry, pod)
+    };
+    if let Some((level, message)) = landed {
+        crate::logger::log(level, message);
+    }
+}
@@ -0,0 +1,110 @@
+//! `kpf validate`: checks a config file without starting anything, reporting
+//! every problem with its location in the file.
+
+use crate::config::Config;
+use anyhow::{bail, Context, Result};
+use k8s_openapi::api::core::v1::Namespace;
+use kube::api::Api;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long the API server gets to answer a namespace lookup.
+const NAMESPACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Validates `path`, and unless `offline`, that the namespaces its forwards
+/// use exist. Fails if anything is wrong.
+pub async fn run(path: &Path, context: Option<&str>, offline: bool) -> Result<()> {
+    let text = std::fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    let config = match crate::config::parse_config(&text, path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("❌ {}", e);
+            bail!("{} is not a valid config", path.display());
+        }
+    };
+
+    let location = |forward: Option<usize>| {
+        match forward.and_then(|index| crate::config::forward_location(&text, index)) {
+            Some((line, column)) => format!("{}:{}:{}", path.display(), line, column),
+            None => path.display().to_string(),
+        }
+    };
+
+    let mut failures = 0;
+    for problem in config.problems() {
+        failures += 1;
+        println!("❌ {}: {:#}", location(problem.forward), problem.error);
+    }
+    if !offline {
+        for (forward, message) in check_namespaces(&config, context).await {
+            failures += 1;
+            println!("❌ {}: {}", location(Some(forward)), message);
+        }
+    }
+
+    if failures > 0 {
+        bail!("{} problem(s) found in {}", failures, path.display());
+    }
+    println!(
+        "✅ {} is valid ({} forward{})",
+        path.display(),
+        config.forwards.len(),
+        if config.forwards.len() == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
+
+/// Namespaces that do not exist or cannot be looked up, each with the first
+/// forward using it. Namespaces the credentials may not read are skipped.
+async fn check_namespaces(config: &Config, context: Option<&str>) -> Vec<(usize, String)> {
+    let mut targets: Vec<((Option<String>, String), usize)> = Vec::new();
+    for (index, forward) in config.forwards.iter().enumerate() {
+        let target = crate::doctor::forward_target(forward, context, None);
+        if !targets.iter().any(|(known, _)| *known == target) {
+            targets.push((target, index));
+        }
+    }
+
+    let mut problems = Vec::new();
+    // Contexts without a client are reported once
+    let mut unusable: Vec<Option<String>> = Vec::new();
+    for ((context, namespace), forward) in targets {
+        if unusable.contains(&context) {
+            continue;
+        }
+        let name = crate::k8s::effective_context(context.as_deref())
+            .unwrap_or_else(|| "current context".to_string());
+        let client = match crate::k8s::client_for_context(context.as_deref()).await {
+            Ok(client) => client,
+            Err(e) => {
+                problems.push((forward, format!("{}: {:#} (use --offline to skip cluster checks)", name, e)));
+                unusable.push(context);
+                continue;
+            }
+        };
+        let namespaces: Api<Namespace> = Api::all(client);
+        match tokio::time::timeout(NAMESPACE_TIMEOUT, namespaces.get_opt(&namespace)).await {
+            Ok(Ok(Some(_))) => {}
+            Ok(Ok(None)) => problems.push((
+                forward,
+                format!("namespace {} does not exist in {}", namespace, name),
+            )),
+            Ok(Err(kube::Error::Api(response))) if response.code == 403 => {
+                println!("⚠️  {}: not allowed to read namespace {}, skipping its check", name, namespace);
+            }
+            Ok(Err(e)) => problems.push((
+                forward,
+                format!("{}: namespace {} could not be checked: {} (use --offline to skip cluster checks)", name, namespace, e),
+            )),
+            Err(_) => problems.push((
+                forward,
+                format!(
+                    "{}: cluster did not answer within {}s (use --offline to skip cluster checks)",
+                    name,
+                    NAMESPACE_TIMEOUT.as_secs()
+                ),
+            )),
+        }
+    }
+    problems
+}